@@ -1,14 +1,34 @@
 use crate::agent::{
     AgentConfig, AgentContent, AgentEvent, AgentMessage, ContentBlock, MessageBuilder,
-    PlanStepInfo, ToolExecutor, ToolUse,
+    PlanStepInfo, ToolDefinition, ToolExecutor, ToolMetrics, ToolUse,
 };
 use crate::llm_client::{ApiFormat, ProviderConfig};
 use crate::mcp::MCPManager;
+use crate::tools::ToolRegistry;
 use regex::Regex;
 use reqwest::Client;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+/// Upper bound on reconnects after a dropped response stream
+const MAX_STREAM_RECONNECT_ATTEMPTS: u32 = 2;
+/// Pause before re-sending a request whose stream dropped
+const STREAM_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Why a model request failed
+enum RequestError {
+    Failed(String),
+    /// The response stream broke mid-way; carries the text received so far
+    StreamDropped { message: String, received_text: String },
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        RequestError::Failed(message)
+    }
+}
+
 #[allow(dead_code)]
 pub struct AgentLoop {
     client: Client,
@@ -49,15 +69,6 @@ impl AgentLoop {
         mcp_manager: Arc<MCPManager>,
         provider_id: Option<&str>,
     ) -> Self {
-        let tool_executor = ToolExecutor::new(config.project_path.clone())
-            .with_mcp_manager(mcp_manager.clone());
-        let message_builder = MessageBuilder::new(
-            config.clone(),
-            model.clone(),
-            max_tokens,
-            temperature,
-        ).with_mcp_manager(mcp_manager);
-
         // Infer config from provider_id or model
         let mut provider_config = if let Some(pid) = provider_id {
             ProviderConfig::from_preset(pid)
@@ -65,10 +76,27 @@ impl AgentLoop {
             ProviderConfig::from_model(&model)
         };
 
+        let mut tool_executor = ToolExecutor::new(config.project_path.clone())
+            .with_mcp_manager(mcp_manager.clone())
+            .with_result_limits(config.max_tool_result_chars, config.tool_result_limits.clone());
+        if let Some(allowed) = &config.allowed_tools {
+            tool_executor = tool_executor.with_allowed_tools(allowed.clone());
+        }
+        let message_builder = MessageBuilder::new(
+            config.clone(),
+            model.clone(),
+            max_tokens,
+            temperature,
+        )
+        .with_mcp_manager(mcp_manager)
+        .with_structured_tool_results(provider_config.api_format == ApiFormat::Google)
+        .with_api_format(provider_config.api_format.clone());
+
         // Use custom base_url
         if !base_url.is_empty() {
             provider_config.base_url = base_url.clone();
         }
+        provider_config.apply_model_headers(&model);
 
         Self {
             client: Client::new(),
@@ -84,6 +112,43 @@ impl AgentLoop {
         }
     }
 
+    /// Use a shared tool registry for both tool definitions and execution
+    pub fn with_tool_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.tool_executor = self.tool_executor.with_registry(registry.clone());
+        self.message_builder = self.message_builder.with_tool_registry(registry);
+        self
+    }
+
+    /// Request complete JSON responses instead of SSE streams when `enabled` is false
+    pub fn with_streaming(mut self, enabled: bool) -> Self {
+        self.provider_config.streaming &= enabled;
+        self
+    }
+
+    /// Send requests with `client`, e.g. one routed through the configured proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Describe tool calls in their results instead of executing them
+    /// Let read tools open files outside the mounted folder(s)
+    pub fn with_outside_workspace_reads(mut self, allowed: bool) -> Self {
+        self.tool_executor = self.tool_executor.with_outside_workspace_reads(allowed);
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.tool_executor = self.tool_executor.with_dry_run(dry_run);
+        self
+    }
+
+    /// Record tool calls into shared metrics
+    pub fn with_tool_metrics(mut self, metrics: Arc<std::sync::Mutex<ToolMetrics>>) -> Self {
+        self.tool_executor = self.tool_executor.with_metrics(metrics);
+        self
+    }
+
     pub async fn run(
         &self,
         initial_message: String,
@@ -97,8 +162,32 @@ impl AgentLoop {
         self.run_with_history(messages, event_tx).await
     }
 
-    /// Run agent with existing conversation history
+    /// Run agent with existing conversation history.
+    /// Fails with `"timeout"` once the run takes longer than `max_run_duration_secs`.
     pub async fn run_with_history(
+        &self,
+        messages: Vec<AgentMessage>,
+        event_tx: mpsc::Sender<AgentEvent>,
+    ) -> Result<Vec<AgentMessage>, String> {
+        let Some(max_secs) = self.config.max_run_duration_secs else {
+            return self.run_turns(messages, event_tx).await;
+        };
+
+        let timeout_tx = event_tx.clone();
+        match tokio::time::timeout(Duration::from_secs(max_secs), self.run_turns(messages, event_tx)).await {
+            Ok(result) => result,
+            Err(_) => {
+                let _ = timeout_tx
+                    .send(AgentEvent::Error {
+                        message: format!("Agent run exceeded maximum duration of {} seconds", max_secs),
+                    })
+                    .await;
+                Err("timeout".to_string())
+            }
+        }
+    }
+
+    async fn run_turns(
         &self,
         mut messages: Vec<AgentMessage>,
         event_tx: mpsc::Sender<AgentEvent>,
@@ -121,9 +210,20 @@ impl AgentLoop {
             let request = self.message_builder.build_request(&messages).await;
 
             let response = self.send_request(&request, &event_tx).await?;
+            if let Some((input_tokens, output_tokens)) = response.get("usage").and_then(usage_tokens) {
+                let cache_tokens = |key: &str| response["usage"][key].as_u64().map(|v| v as u32);
+                let _ = event_tx
+                    .send(AgentEvent::Usage {
+                        input_tokens,
+                        output_tokens,
+                        cache_creation_input_tokens: cache_tokens("cache_creation_input_tokens"),
+                        cache_read_input_tokens: cache_tokens("cache_read_input_tokens"),
+                    })
+                    .await;
+            }
 
             // Parse response
-            let (text_content, tool_uses) = self.parse_response(&response)?;
+            let (text_content, tool_uses, thinking_blocks) = self.parse_response(&response)?;
 
             // Parse and emit plan if present
             if let Some(plan_steps) = self.parse_plan(&text_content) {
@@ -148,7 +248,8 @@ impl AgentLoop {
             let assistant_content = if tool_uses.is_empty() {
                 AgentContent::Text(text_content)
             } else {
-                let mut blocks = Vec::new();
+                // Anthropic rejects a tool_use turn whose thinking blocks are not passed back
+                let mut blocks = thinking_blocks;
                 if !text_content.is_empty() {
                     blocks.push(ContentBlock::Text { text: text_content });
                 }
@@ -181,22 +282,39 @@ impl AgentLoop {
 
             for tool_use in &tool_uses {
                 // Emit tool start
+                let (tool_type, server_id) = self.tool_executor.tool_source(&tool_use.name).await;
                 let _ = event_tx
                     .send(AgentEvent::ToolStart {
                         tool: tool_use.name.clone(),
                         input: tool_use.input.clone(),
+                        tool_type: tool_type.clone(),
+                        server_id,
                     })
                     .await;
 
                 // Execute tool
-                let result = self.tool_executor.execute(tool_use).await;
+                let started = Instant::now();
+                let (result, truncation) = self.tool_executor.execute_with_truncation(tool_use).await;
+                let duration_ms = started.elapsed().as_millis() as u64;
+
+                if let Some(truncation) = truncation {
+                    let _ = event_tx
+                        .send(AgentEvent::ToolResultTruncated {
+                            tool: tool_use.name.clone(),
+                            original_chars: truncation.original_chars,
+                            truncated_to: truncation.truncated_to,
+                        })
+                        .await;
+                }
 
                 // Emit tool end
                 let _ = event_tx
                     .send(AgentEvent::ToolEnd {
                         tool: tool_use.name.clone(),
-                        result: result.content.clone(),
+                        result: result.content.to_text(),
                         success: result.is_error.is_none(),
+                        tool_type,
+                        duration_ms,
                     })
                     .await;
 
@@ -216,18 +334,52 @@ impl AgentLoop {
         Ok(messages)
     }
 
+    /// Send a request, re-sending it when the response stream drops mid-way.
+    /// Text received before the drop is carried over so it is not emitted twice.
     async fn send_request(
         &self,
         request: &crate::agent::message_builder::ClaudeApiRequest,
         event_tx: &mpsc::Sender<AgentEvent>,
     ) -> Result<serde_json::Value, String> {
-        match self.provider_config.api_format {
-            ApiFormat::Anthropic => self.send_anthropic_request(request, event_tx).await,
-            ApiFormat::OpenAI | ApiFormat::OpenAICompatible => {
-                self.send_openai_request(request, event_tx).await
+        let max_reconnects = self
+            .config
+            .stream_reconnect_attempts
+            .min(MAX_STREAM_RECONNECT_ATTEMPTS);
+        let mut received_text = String::new();
+        let mut reconnects = 0;
+
+        loop {
+            let resume = reconnects > 0;
+            let result = match self.provider_config.api_format {
+                ApiFormat::Anthropic => {
+                    self.send_anthropic_request(request, event_tx, &received_text, resume).await
+                }
+                ApiFormat::OpenAI | ApiFormat::OpenAICompatible => {
+                    self.send_openai_request(request, event_tx, &received_text).await
+                }
+                ApiFormat::Google => self.send_google_request(request, event_tx, &received_text).await,
+                _ => Err(RequestError::Failed(format!(
+                    "Unsupported API format: {:?}",
+                    self.provider_config.api_format
+                ))),
+            };
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(RequestError::StreamDropped { message, received_text: text }) => {
+                    if reconnects >= max_reconnects {
+                        return Err(message);
+                    }
+                    reconnects += 1;
+                    received_text = text;
+                    eprintln!(
+                        "[agent] {}; reconnecting ({}/{})",
+                        message, reconnects, max_reconnects
+                    );
+                    tokio::time::sleep(STREAM_RECONNECT_DELAY).await;
+                }
+                Err(RequestError::Failed(message)) => return Err(message),
             }
-            ApiFormat::Google => self.send_google_request(request, event_tx).await,
-            _ => Err(format!("Unsupported API format: {:?}", self.provider_config.api_format)),
         }
     }
 
@@ -236,7 +388,9 @@ impl AgentLoop {
         &self,
         request: &crate::agent::message_builder::ClaudeApiRequest,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+        resume: bool,
+    ) -> Result<serde_json::Value, RequestError> {
         let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
 
         let mut req = self.client.post(&url)
@@ -248,18 +402,49 @@ impl AgentLoop {
             req = req.header("x-api-key", &self.api_key);
         }
 
+        if resume {
+            req = req.header("Prefer", "resume");
+        }
+        req = self.with_extra_headers(req);
+
+        let streaming = self.provider_config.streaming;
+        let mut request = request.clone();
+        request.stream = request.stream && streaming;
+
         let response = req
-            .json(request)
+            .json(&request)
             .send()
             .await
             .map_err(|e| format!("HTTP error: {}", e))?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("API error: {}", error_text));
+            return Err(format!("API error: {}", error_text).into());
+        }
+
+        if !streaming {
+            // A complete response already carries the `content` array parse_response expects
+            let data: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            for block in data["content"].as_array().into_iter().flatten() {
+                if block["type"] == "thinking" {
+                    let _ = event_tx
+                        .send(AgentEvent::Thinking {
+                            content: block["thinking"].as_str().unwrap_or_default().to_string(),
+                            signature: block["signature"].as_str().map(|s| s.to_string()),
+                        })
+                        .await;
+                }
+            }
+            let mut result = serde_json::json!({ "content": data["content"] });
+            attach_usage(&mut result, usage_tokens(&data["usage"]));
+            attach_cache_usage(&mut result, &data["usage"]);
+            return Ok(result);
         }
 
-        self.handle_stream_response(response, event_tx).await
+        self.handle_stream_response(response, event_tx, resumed_text).await
     }
 
     /// Send OpenAI compatible format request
@@ -267,16 +452,24 @@ impl AgentLoop {
         &self,
         request: &crate::agent::message_builder::ClaudeApiRequest,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+    ) -> Result<serde_json::Value, RequestError> {
         let base = self.base_url.trim_end_matches('/');
-        let url = if base.ends_with("/v1") {
+        let url = if base.ends_with("/v1") || self.provider_config.id == "perplexity" {
             format!("{}/chat/completions", base)
         } else {
             format!("{}/v1/chat/completions", base)
         };
 
         // Convert request format to OpenAI format
-        let openai_request = self.convert_to_openai_format(request);
+        let streaming = self.provider_config.streaming;
+        let mut openai_request = self.convert_to_openai_format(request);
+        if !streaming {
+            openai_request["stream"] = serde_json::json!(false);
+        } else if self.provider_config.id == "openai" {
+            // Other compatible servers may reject the field; many report usage anyway
+            openai_request["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
 
         let mut req = self.client.post(&url)
             .header("Content-Type", "application/json");
@@ -285,6 +478,7 @@ impl AgentLoop {
         if !self.api_key.is_empty() {
             req = req.header("Authorization", format!("Bearer {}", self.api_key));
         }
+        req = self.with_extra_headers(req);
 
         let response = req
             .json(&openai_request)
@@ -294,10 +488,39 @@ impl AgentLoop {
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("API error: {}", error_text));
+            return Err(format!("API error: {}", error_text).into());
         }
 
-        self.handle_openai_stream_response(response, event_tx).await
+        if !streaming {
+            let data: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            // DeepSeek-R1 returns its reasoning next to the answer; show it apart from the text
+            if let Some(reasoning) = data["choices"][0]["message"]["reasoning_content"]
+                .as_str()
+                .filter(|r| !r.is_empty())
+            {
+                let _ = event_tx
+                    .send(AgentEvent::ThinkingContent {
+                        content: reasoning.to_string(),
+                    })
+                    .await;
+            }
+            return Ok(openai_completion_to_claude(&data));
+        }
+
+        self.handle_openai_stream_response(response, event_tx, resumed_text).await
+    }
+
+    /// Append provider-specific headers after the standard ones
+    fn with_extra_headers(&self, mut req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(extra) = &self.provider_config.extra_headers {
+            for (key, value) in extra {
+                req = req.header(key, value);
+            }
+        }
+        req
     }
 
     /// Convert Claude request format to OpenAI format
@@ -311,10 +534,10 @@ impl AgentLoop {
         let mut messages: Vec<serde_json::Value> = Vec::new();
 
         // Add system message
-        if !request.system.is_empty() {
+        if !request.system.text.is_empty() {
             messages.push(serde_json::json!({
                 "role": "system",
-                "content": request.system
+                "content": request.system.text
             }));
         }
 
@@ -391,16 +614,7 @@ impl AgentLoop {
         }
 
         // Convert tools definition
-        let tools: Vec<serde_json::Value> = request.tools.iter().map(|tool| {
-            serde_json::json!({
-                "type": "function",
-                "function": {
-                    "name": tool.name,
-                    "description": tool.description,
-                    "parameters": tool.input_schema
-                }
-            })
-        }).collect();
+        let tools: Vec<serde_json::Value> = request.tools.iter().map(ToolDefinition::to_openai_function).collect();
 
         let mut openai_request = serde_json::json!({
             "model": request.model,
@@ -443,7 +657,8 @@ impl AgentLoop {
         &self,
         request: &crate::agent::message_builder::ClaudeApiRequest,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+    ) -> Result<serde_json::Value, RequestError> {
         let base = self.base_url.trim_end_matches('/');
         let url = format!("{}/v1beta/models/{}:streamGenerateContent?alt=sse", base, request.model);
 
@@ -460,10 +675,10 @@ impl AgentLoop {
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("API error: {}", error_text));
+            return Err(format!("API error: {}", error_text).into());
         }
 
-        self.handle_google_stream_response(response, event_tx).await
+        self.handle_google_stream_response(response, event_tx, resumed_text).await
     }
 
     /// Convert Claude request format to Google Gemini format
@@ -579,9 +794,9 @@ impl AgentLoop {
         });
 
         // Add system instruction if present
-        if !request.system.is_empty() {
+        if !request.system.text.is_empty() {
             google_request["systemInstruction"] = serde_json::json!({
-                "parts": [{"text": request.system}]
+                "parts": [{"text": request.system.text}]
             });
         }
 
@@ -600,16 +815,26 @@ impl AgentLoop {
         &self,
         response: reqwest::Response,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+    ) -> Result<serde_json::Value, RequestError> {
         use futures::StreamExt;
 
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         let mut accumulated_text = String::new();
         let mut tool_calls: Vec<serde_json::Value> = Vec::new();
+        let mut usage = None;
 
         while let Some(chunk) = stream.next().await {
-            let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    return Err(RequestError::StreamDropped {
+                        message: format!("Stream error: {}", e),
+                        received_text: merge_resumed_text(resumed_text, &accumulated_text),
+                    })
+                }
+            };
             buffer.push_str(&String::from_utf8_lossy(&chunk));
 
             while let Some(pos) = buffer.find('\n') {
@@ -628,6 +853,11 @@ impl AgentLoop {
                 };
 
                 if let Ok(event) = serde_json::from_str::<serde_json::Value>(json_str) {
+                    // Each chunk carries the running totals
+                    if let Some(tokens) = event.get("usageMetadata").and_then(usage_tokens) {
+                        usage = Some(tokens);
+                    }
+
                     // Extract text and function calls from candidates
                     if let Some(candidates) = event.get("candidates").and_then(|v| v.as_array()) {
                         for candidate in candidates {
@@ -641,7 +871,7 @@ impl AgentLoop {
                                         if !text.is_empty() {
                                             accumulated_text.push_str(text);
                                             let _ = event_tx.send(AgentEvent::Text {
-                                                content: accumulated_text.clone(),
+                                                content: merge_resumed_text(resumed_text, &accumulated_text),
                                             }).await;
                                         }
                                     }
@@ -677,6 +907,7 @@ impl AgentLoop {
         }
 
         // Build Claude format response
+        let accumulated_text = merge_resumed_text(resumed_text, &accumulated_text);
         let mut content = Vec::new();
         if !accumulated_text.is_empty() {
             content.push(serde_json::json!({
@@ -686,9 +917,11 @@ impl AgentLoop {
         }
         content.extend(tool_calls);
 
-        Ok(serde_json::json!({
+        let mut result = serde_json::json!({
             "content": content
-        }))
+        });
+        attach_usage(&mut result, usage);
+        Ok(result)
     }
 
     /// Handle OpenAI streaming response
@@ -696,17 +929,28 @@ impl AgentLoop {
         &self,
         response: reqwest::Response,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+    ) -> Result<serde_json::Value, RequestError> {
         use futures::StreamExt;
 
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         let mut accumulated_text = String::new();
+        let mut accumulated_reasoning = String::new();
         let mut tool_calls: Vec<serde_json::Value> = Vec::new();
         let mut current_tool_calls: std::collections::HashMap<i64, (String, String, String)> = std::collections::HashMap::new();
+        let mut usage = None;
 
         while let Some(chunk) = stream.next().await {
-            let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    return Err(RequestError::StreamDropped {
+                        message: format!("Stream error: {}", e),
+                        received_text: merge_resumed_text(resumed_text, &accumulated_text),
+                    })
+                }
+            };
             buffer.push_str(&String::from_utf8_lossy(&chunk));
 
             while let Some(pos) = buffer.find('\n') {
@@ -719,14 +963,27 @@ impl AgentLoop {
                     }
 
                     if let Ok(event) = serde_json::from_str::<serde_json::Value>(data) {
+                        // Sent in a final chunk with no choices
+                        if let Some(tokens) = event.get("usage").and_then(usage_tokens) {
+                            usage = Some(tokens);
+                        }
+
                         if let Some(choices) = event.get("choices").and_then(|v| v.as_array()) {
                             for choice in choices {
                                 if let Some(delta) = choice.get("delta") {
+                                    // DeepSeek-R1 streams its reasoning separately; keep it out of the answer
+                                    if let Some(reasoning) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
+                                        accumulated_reasoning.push_str(reasoning);
+                                        let _ = event_tx.send(AgentEvent::ThinkingContent {
+                                            content: accumulated_reasoning.clone(),
+                                        }).await;
+                                    }
+
                                     // Handle text content
                                     if let Some(content) = delta.get("content").and_then(|v| v.as_str()) {
                                         accumulated_text.push_str(content);
                                         let _ = event_tx.send(AgentEvent::Text {
-                                            content: accumulated_text.clone(),
+                                            content: merge_resumed_text(resumed_text, &accumulated_text),
                                         }).await;
                                     }
 
@@ -778,6 +1035,7 @@ impl AgentLoop {
         }
 
         // Build Claude format response
+        let accumulated_text = merge_resumed_text(resumed_text, &accumulated_text);
         let mut content = Vec::new();
         if !accumulated_text.is_empty() {
             content.push(serde_json::json!({
@@ -787,16 +1045,19 @@ impl AgentLoop {
         }
         content.extend(tool_calls);
 
-        Ok(serde_json::json!({
+        let mut result = serde_json::json!({
             "content": content
-        }))
+        });
+        attach_usage(&mut result, usage);
+        Ok(result)
     }
 
     async fn handle_stream_response(
         &self,
         response: reqwest::Response,
         event_tx: &mpsc::Sender<AgentEvent>,
-    ) -> Result<serde_json::Value, String> {
+        resumed_text: &str,
+    ) -> Result<serde_json::Value, RequestError> {
         use futures::StreamExt;
 
         let mut stream = response.bytes_stream();
@@ -807,9 +1068,22 @@ impl AgentLoop {
         let mut current_tool_input = String::new();
         let mut current_tool_id = String::new();
         let mut current_tool_name = String::new();
+        let mut thinking_blocks: Vec<serde_json::Value> = Vec::new();
+        let mut current_thinking: Option<String> = None;
+        let mut current_signature: Option<String> = None;
+        let mut usage: Option<(u32, u32)> = None;
+        let mut start_usage = serde_json::Value::Null;
 
         while let Some(chunk) = stream.next().await {
-            let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    return Err(RequestError::StreamDropped {
+                        message: format!("Stream error: {}", e),
+                        received_text: merge_resumed_text(resumed_text, &accumulated_text),
+                    })
+                }
+            };
             buffer.push_str(&String::from_utf8_lossy(&chunk));
 
             while let Some(pos) = buffer.find('\n') {
@@ -825,9 +1099,27 @@ impl AgentLoop {
                         let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
 
                         match event_type {
+                            "message_start" => {
+                                start_usage = event["message"]["usage"].clone();
+                                usage = usage_tokens(&start_usage);
+                            }
+                            "message_delta" => {
+                                if let Some(output) = event["usage"]["output_tokens"].as_u64() {
+                                    usage = Some((usage.map(|u| u.0).unwrap_or(0), output as u32));
+                                }
+                            }
                             "content_block_start" => {
                                 if let Some(block) = event.get("content_block") {
-                                    if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
+                                    if block.get("type").and_then(|v| v.as_str()) == Some("thinking") {
+                                        current_thinking = Some(
+                                            block.get("thinking").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                                        );
+                                        current_signature = block
+                                            .get("signature")
+                                            .and_then(|v| v.as_str())
+                                            .filter(|s| !s.is_empty())
+                                            .map(|s| s.to_string());
+                                    } else if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
                                         current_tool_id = block
                                             .get("id")
                                             .and_then(|v| v.as_str())
@@ -852,7 +1144,7 @@ impl AgentLoop {
                                             // Emit streaming text
                                             let _ = event_tx
                                                 .send(AgentEvent::Text {
-                                                    content: accumulated_text.clone(),
+                                                    content: merge_resumed_text(resumed_text, &accumulated_text),
                                                 })
                                                 .await;
                                         }
@@ -860,11 +1152,37 @@ impl AgentLoop {
                                         if let Some(partial) = delta.get("partial_json").and_then(|v| v.as_str()) {
                                             current_tool_input.push_str(partial);
                                         }
+                                    } else if delta_type == "thinking_delta" {
+                                        if let (Some(thinking), Some(text)) =
+                                            (current_thinking.as_mut(), delta.get("thinking").and_then(|v| v.as_str()))
+                                        {
+                                            thinking.push_str(text);
+                                        }
+                                    } else if delta_type == "signature_delta" {
+                                        if let Some(signature) = delta.get("signature").and_then(|v| v.as_str()) {
+                                            current_signature = Some(signature.to_string());
+                                        }
                                     }
                                 }
                             }
                             "content_block_stop" => {
-                                if !current_tool_id.is_empty() {
+                                if let Some(thinking) = current_thinking.take() {
+                                    let signature = current_signature.take();
+                                    let _ = event_tx
+                                        .send(AgentEvent::Thinking {
+                                            content: thinking.clone(),
+                                            signature: signature.clone(),
+                                        })
+                                        .await;
+                                    let mut block = serde_json::json!({
+                                        "type": "thinking",
+                                        "thinking": thinking
+                                    });
+                                    if let Some(signature) = signature {
+                                        block["signature"] = serde_json::json!(signature);
+                                    }
+                                    thinking_blocks.push(block);
+                                } else if !current_tool_id.is_empty() {
                                     let input: serde_json::Value = serde_json::from_str(&current_tool_input)
                                         .unwrap_or(serde_json::json!({}));
 
@@ -882,18 +1200,22 @@ impl AgentLoop {
                             }
                             "message_stop" => {
                                 // Build final response
-                                let mut content = Vec::new();
-                                if !accumulated_text.is_empty() {
+                                let text = merge_resumed_text(resumed_text, &accumulated_text);
+                                let mut content = thinking_blocks.clone();
+                                if !text.is_empty() {
                                     content.push(serde_json::json!({
                                         "type": "text",
-                                        "text": accumulated_text
+                                        "text": text
                                     }));
                                 }
                                 content.extend(tool_uses.clone());
 
-                                full_response = Some(serde_json::json!({
+                                let mut result = serde_json::json!({
                                     "content": content
-                                }));
+                                });
+                                attach_usage(&mut result, usage);
+                                attach_cache_usage(&mut result, &start_usage);
+                                full_response = Some(result);
                             }
                             _ => {}
                         }
@@ -902,13 +1224,14 @@ impl AgentLoop {
             }
         }
 
-        full_response.ok_or_else(|| "No response received".to_string())
+        full_response.ok_or_else(|| RequestError::Failed("No response received".to_string()))
     }
 
+    /// Split a response into its text, tool calls and thinking blocks
     fn parse_response(
         &self,
         response: &serde_json::Value,
-    ) -> Result<(String, Vec<ToolUse>), String> {
+    ) -> Result<(String, Vec<ToolUse>, Vec<ContentBlock>), String> {
         let content = response
             .get("content")
             .and_then(|v| v.as_array())
@@ -916,6 +1239,7 @@ impl AgentLoop {
 
         let mut text_parts = Vec::new();
         let mut tool_uses = Vec::new();
+        let mut thinking_blocks = Vec::new();
 
         for block in content {
             let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
@@ -945,11 +1269,17 @@ impl AgentLoop {
 
                     tool_uses.push(ToolUse { id, name, input, thought_signature });
                 }
+                "thinking" => {
+                    thinking_blocks.push(ContentBlock::Thinking {
+                        text: block.get("thinking").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                        signature: block.get("signature").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                    });
+                }
                 _ => {}
             }
         }
 
-        Ok((text_parts.join(""), tool_uses))
+        Ok((text_parts.join(""), tool_uses, thinking_blocks))
     }
 
     /// Parse plan from text content
@@ -1005,6 +1335,96 @@ impl AgentLoop {
     }
 }
 
+/// Convert a complete (non-streaming) OpenAI chat completion to Claude format
+fn openai_completion_to_claude(data: &serde_json::Value) -> serde_json::Value {
+    let message = &data["choices"][0]["message"];
+    let mut content = Vec::new();
+
+    if let Some(text) = message["content"].as_str().filter(|t| !t.is_empty()) {
+        content.push(serde_json::json!({
+            "type": "text",
+            "text": text
+        }));
+    }
+
+    if let Some(tool_calls) = message["tool_calls"].as_array() {
+        for tc in tool_calls {
+            let input: serde_json::Value = tc["function"]["arguments"]
+                .as_str()
+                .and_then(|args| serde_json::from_str(args).ok())
+                .unwrap_or(serde_json::json!({}));
+            content.push(serde_json::json!({
+                "type": "tool_use",
+                "id": tc["id"],
+                "name": tc["function"]["name"],
+                "input": input
+            }));
+        }
+    }
+
+    let mut result = serde_json::json!({
+        "content": content
+    });
+    attach_usage(&mut result, usage_tokens(&data["usage"]));
+    result
+}
+
+/// Input and output token counts from an Anthropic, OpenAI or Gemini usage object
+fn usage_tokens(usage: &serde_json::Value) -> Option<(u32, u32)> {
+    let count = |keys: &[&str]| keys.iter().find_map(|key| usage.get(*key).and_then(|v| v.as_u64()));
+    let input = count(&["input_tokens", "prompt_tokens", "promptTokenCount"]);
+    let output = count(&["output_tokens", "completion_tokens", "candidatesTokenCount"]);
+    if input.is_none() && output.is_none() {
+        return None;
+    }
+    Some((input.unwrap_or(0) as u32, output.unwrap_or(0) as u32))
+}
+
+/// Add token counts to a Claude-format response, in Anthropic's field names
+fn attach_usage(response: &mut serde_json::Value, usage: Option<(u32, u32)>) {
+    if let Some((input_tokens, output_tokens)) = usage {
+        response["usage"] = serde_json::json!({
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens
+        });
+    }
+}
+
+/// Copy Anthropic's prompt cache token counts into a response that already has `usage`
+fn attach_cache_usage(response: &mut serde_json::Value, usage: &serde_json::Value) {
+    if response.get("usage").is_none() {
+        return;
+    }
+    for key in ["cache_creation_input_tokens", "cache_read_input_tokens"] {
+        if let Some(tokens) = usage.get(key).filter(|v| v.is_u64()) {
+            response["usage"][key] = tokens.clone();
+        }
+    }
+}
+
+/// Combine text received before a stream dropped with the text of the re-sent request.
+///
+/// A re-sent request usually regenerates the response from the start, so whatever
+/// overlaps the text already shown is skipped rather than repeated.
+fn merge_resumed_text(previous: &str, fresh: &str) -> String {
+    if fresh.starts_with(previous) {
+        return fresh.to_string();
+    }
+    if previous.starts_with(fresh) {
+        return previous.to_string();
+    }
+
+    // Longest prefix of `fresh` that `previous` already ends with
+    let overlap = fresh
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(fresh.len()))
+        .filter(|&i| previous.ends_with(&fresh[..i]))
+        .max()
+        .unwrap_or(0);
+    format!("{}{}", previous, &fresh[overlap..])
+}
+
 // Make ClaudeApiRequest cloneable for non-stream fallback
 impl Clone for crate::agent::message_builder::ClaudeApiRequest {
     fn clone(&self) -> Self {
@@ -1019,3 +1439,613 @@ impl Clone for crate::agent::message_builder::ClaudeApiRequest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ToolResultContent;
+    use crate::mcp::MCPServerConfig;
+    use serde_json::json;
+    use crate::test_support::{mock_mcp_response, read_http_request, read_json_request, spawn_mock_server};
+    use std::sync::Mutex;
+
+    fn sse(events: &[serde_json::Value]) -> String {
+        let mut body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+        body.push_str("data: [DONE]\n\n");
+        body
+    }
+
+    #[tokio::test]
+    async fn test_agent_turn_dispatches_mcp_tool_by_server_id() {
+        let mcp_url = spawn_mock_server(mock_mcp_response).await;
+
+        let mcp_manager = Arc::new(MCPManager::new());
+        let config = MCPServerConfig::new("mock".to_string(), "Mock".to_string(), mcp_url)
+            .with_enabled(true);
+        mcp_manager.connect_server(&config).await.unwrap();
+
+        // The model calls the tool by `server_id:tool_name`, then answers in text
+        let llm_requests = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let recorded = llm_requests.clone();
+        let llm_url = spawn_mock_server(move |body| {
+            let mut requests = recorded.lock().unwrap();
+            requests.push(body);
+            let events = if requests.len() == 1 {
+                vec![
+                    json!({"choices": [{"delta": {"tool_calls": [{
+                        "index": 0,
+                        "id": "call_1",
+                        "function": { "name": "mock:echo", "arguments": "{\"text\":\"hi\"}" }
+                    }]}}]}),
+                    json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+                ]
+            } else {
+                vec![
+                    json!({"choices": [{"delta": {"content": "Done."}}]}),
+                    json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+                ]
+            };
+            ("text/event-stream", sse(&events))
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            system_prompt: "You are a test agent.".to_string(),
+            max_turns: 3,
+            project_path: None,
+            allowed_tools: Some(vec!["mcp_mock_echo".to_string()]),
+            stream_reconnect_attempts: 2,
+            max_tool_result_chars: Some(8000),
+            tool_result_limits: None,
+            max_run_duration_secs: None,
+            enable_prompt_caching: false,
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            agent_config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            mcp_manager,
+            Some("ollama"),
+        );
+
+        let (event_tx, _event_rx) = mpsc::channel(100);
+        let messages = agent.run("Say hi through the echo tool".to_string(), event_tx).await.unwrap();
+
+        let tool_result = messages
+            .iter()
+            .find_map(|m| match &m.content {
+                AgentContent::ToolResults(results) => results.first().cloned(),
+                _ => None,
+            })
+            .expect("tool results message");
+        assert_eq!(tool_result.tool_use_id, "call_1");
+        assert!(tool_result.is_error.is_none());
+        assert_eq!(
+            tool_result.content,
+            ToolResultContent::Json(json!({ "content": [{ "type": "text", "text": "echo: hi" }] }))
+        );
+
+        let requests = llm_requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+
+        // MCP tools are offered to the model under their function names
+        let offered: Vec<&str> = requests[0]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|t| t["function"]["name"].as_str())
+            .collect();
+        assert_eq!(offered, vec!["mcp_mock_echo"]);
+
+        // The follow-up request carries the tool result back to the model
+        let tool_message = requests[1]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["role"] == "tool")
+            .expect("tool message in follow-up request");
+        assert_eq!(tool_message["tool_call_id"], "call_1");
+        assert!(tool_message["content"].as_str().unwrap().contains("echo: hi"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_events_report_native_and_mcp_tool_types() {
+        let mcp_url = spawn_mock_server(mock_mcp_response).await;
+        let mcp_manager = Arc::new(MCPManager::new());
+        let config = MCPServerConfig::new("mock".to_string(), "Mock".to_string(), mcp_url)
+            .with_enabled(true);
+        mcp_manager.connect_server(&config).await.unwrap();
+
+        let project = std::env::temp_dir().join(format!("kuse-tool-types-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("notes.md"), "hello").unwrap();
+
+        // One native and one MCP tool call in the same turn, then a text answer
+        let turns = Arc::new(Mutex::new(0));
+        let llm_url = spawn_mock_server(move |_body| {
+            let mut turns = turns.lock().unwrap();
+            *turns += 1;
+            let events = if *turns == 1 {
+                vec![
+                    json!({"choices": [{"delta": {"tool_calls": [{
+                        "index": 0,
+                        "id": "call_native",
+                        "function": { "name": "glob", "arguments": "{\"pattern\":\"*.md\"}" }
+                    }]}}]}),
+                    json!({"choices": [{"delta": {"tool_calls": [{
+                        "index": 1,
+                        "id": "call_mcp",
+                        "function": { "name": "mcp_mock_echo", "arguments": "{\"text\":\"hi\"}" }
+                    }]}}]}),
+                    json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+                ]
+            } else {
+                vec![
+                    json!({"choices": [{"delta": {"content": "Done."}}]}),
+                    json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+                ]
+            };
+            ("text/event-stream", sse(&events))
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            system_prompt: "You are a test agent.".to_string(),
+            max_turns: 3,
+            project_path: Some(project.to_string_lossy().to_string()),
+            allowed_tools: None,
+            stream_reconnect_attempts: 2,
+            max_tool_result_chars: Some(8000),
+            tool_result_limits: None,
+            max_run_duration_secs: None,
+            enable_prompt_caching: false,
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            agent_config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            mcp_manager,
+            Some("ollama"),
+        );
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        agent.run("List notes and say hi".to_string(), event_tx).await.unwrap();
+
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                AgentEvent::ToolStart { tool, tool_type, server_id, .. } => starts.push((tool, tool_type, server_id)),
+                AgentEvent::ToolEnd { tool, tool_type, success, .. } => ends.push((tool, tool_type, success)),
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            starts,
+            vec![
+                ("glob".to_string(), "native".to_string(), None),
+                ("mcp_mock_echo".to_string(), "mcp".to_string(), Some("mock".to_string())),
+            ]
+        );
+        assert_eq!(
+            ends,
+            vec![
+                ("glob".to_string(), "native".to_string(), true),
+                ("mcp_mock_echo".to_string(), "mcp".to_string(), true),
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&project);
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_openai_response_emits_single_text() {
+        let llm_requests = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let recorded = llm_requests.clone();
+        // A complete JSON body, as returned by proxies that strip SSE
+        let llm_url = spawn_mock_server(move |body| {
+            recorded.lock().unwrap().push(body);
+            (
+                "application/json",
+                json!({
+                    "choices": [{
+                        "message": { "role": "assistant", "content": "Hello from JSON" },
+                        "finish_reason": "stop"
+                    }]
+                })
+                .to_string(),
+            )
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            allowed_tools: None,
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            agent_config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        )
+        .with_streaming(false);
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        let messages = agent.run("Say hello".to_string(), event_tx).await.unwrap();
+
+        assert_eq!(llm_requests.lock().unwrap()[0]["stream"], json!(false));
+        assert!(matches!(&messages.last().unwrap().content, AgentContent::Text(text) if text == "Hello from JSON"));
+
+        let mut texts = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Text { content } = event {
+                texts.push(content);
+            }
+        }
+        assert_eq!(texts, vec!["Hello from JSON".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_anthropic_response_parses_content_array() {
+        let llm_requests = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let recorded = llm_requests.clone();
+        let llm_url = spawn_mock_server(move |body| {
+            let mut requests = recorded.lock().unwrap();
+            requests.push(body);
+            let content = if requests.len() == 1 {
+                json!([
+                    { "type": "text", "text": "Checking." },
+                    { "type": "tool_use", "id": "toolu_1", "name": "missing_tool", "input": { "x": 1 } }
+                ])
+            } else {
+                json!([{ "type": "text", "text": "All done." }])
+            };
+            (
+                "application/json",
+                json!({ "type": "message", "role": "assistant", "content": content }).to_string(),
+            )
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            allowed_tools: None,
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            "sk-test".to_string(),
+            llm_url,
+            agent_config,
+            "claude-sonnet-4-5".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("anthropic"),
+        )
+        .with_streaming(false);
+
+        let (event_tx, _event_rx) = mpsc::channel(100);
+        let messages = agent.run("Use a tool".to_string(), event_tx).await.unwrap();
+
+        let requests = llm_requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["stream"], json!(false));
+
+        let tool_call = messages
+            .iter()
+            .find_map(|m| match &m.content {
+                AgentContent::Blocks(blocks) => blocks.iter().find_map(|b| match b {
+                    ContentBlock::ToolUse { id, input, .. } => Some((id.clone(), input.clone())),
+                    _ => None,
+                }),
+                _ => None,
+            })
+            .expect("tool use parsed from content array");
+        assert_eq!(tool_call, ("toolu_1".to_string(), json!({ "x": 1 })));
+        assert!(matches!(&messages.last().unwrap().content, AgentContent::Text(text) if text == "All done."));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_thinking_block_emitted_and_sent_back() {
+        let llm_requests = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let recorded = llm_requests.clone();
+        let llm_url = spawn_mock_server(move |body| {
+            let mut requests = recorded.lock().unwrap();
+            requests.push(body);
+            let events = if requests.len() == 1 {
+                vec![
+                    json!({"type": "message_start", "message": {"usage": {"input_tokens": 10, "output_tokens": 0}}}),
+                    json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "Need the "}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "file list."}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "sig-123"}}),
+                    json!({"type": "content_block_stop", "index": 0}),
+                    json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "missing_tool"}}),
+                    json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{}"}}),
+                    json!({"type": "content_block_stop", "index": 1}),
+                    json!({"type": "message_stop"}),
+                ]
+            } else {
+                vec![
+                    json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Done."}}),
+                    json!({"type": "content_block_stop", "index": 0}),
+                    json!({"type": "message_stop"}),
+                ]
+            };
+            ("text/event-stream", sse(&events))
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            allowed_tools: None,
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            "sk-test".to_string(),
+            llm_url,
+            agent_config,
+            "claude-sonnet-4-5".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("anthropic"),
+        );
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        agent.run("List the files".to_string(), event_tx).await.unwrap();
+
+        let mut thinking = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Thinking { content, signature } = event {
+                thinking.push((content, signature));
+            }
+        }
+        assert_eq!(thinking, vec![("Need the file list.".to_string(), Some("sig-123".to_string()))]);
+
+        // The tool_use turn is replayed with its thinking block first
+        let requests = llm_requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let assistant = requests[1]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["role"] == "assistant")
+            .expect("assistant turn in follow-up request");
+        assert_eq!(
+            assistant["content"][0],
+            json!({ "type": "thinking", "thinking": "Need the file list.", "signature": "sig-123" })
+        );
+        assert_eq!(assistant["content"][1]["type"], "tool_use");
+    }
+
+    #[test]
+    fn test_openai_completion_to_claude() {
+        let data = json!({
+            "choices": [{
+                "message": {
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "glob", "arguments": "{\"pattern\":\"*.rs\"}" }
+                    }]
+                }
+            }]
+        });
+        assert_eq!(
+            openai_completion_to_claude(&data),
+            json!({ "content": [{ "type": "tool_use", "id": "call_1", "name": "glob", "input": { "pattern": "*.rs" } }] })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_content_split_from_text_and_extra_headers_sent() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let llm_url = format!("http://{}", listener.local_addr().unwrap());
+        let heads = Arc::new(Mutex::new(Vec::<String>::new()));
+        let recorded = heads.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let Some((head, _)) = read_http_request(&mut socket).await else {
+                    continue;
+                };
+                recorded.lock().unwrap().push(head);
+                let body = sse(&[
+                    json!({"choices": [{"delta": {"reasoning_content": "The user wants "}}]}),
+                    json!({"choices": [{"delta": {"reasoning_content": "a greeting."}}]}),
+                    json!({"choices": [{"delta": {"content": "Hello!"}}]}),
+                    json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+                ]);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        let agent_config = AgentConfig {
+            allowed_tools: None,
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            "sk-test".to_string(),
+            llm_url,
+            agent_config,
+            "deepseek-ai/DeepSeek-R1".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("siliconflow"),
+        );
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        let messages = agent.run("Say hello".to_string(), event_tx).await.unwrap();
+
+        let head = heads.lock().unwrap()[0].to_lowercase();
+        assert!(head.contains("sf-model-id: deepseek-ai/deepseek-r1"), "{}", head);
+        assert!(matches!(&messages.last().unwrap().content, AgentContent::Text(text) if text == "Hello!"));
+
+        let mut thinking = Vec::new();
+        let mut texts = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                AgentEvent::ThinkingContent { content } => thinking.push(content),
+                AgentEvent::Text { content } => texts.push(content),
+                _ => {}
+            }
+        }
+        assert_eq!(thinking.last().map(String::as_str), Some("The user wants a greeting."));
+        assert!(texts.iter().all(|t| !t.contains("greeting")));
+    }
+
+    #[test]
+    fn test_merge_resumed_text() {
+        assert_eq!(merge_resumed_text("", "Hello"), "Hello");
+        // Regenerated from the start: the fresh text already contains what was shown
+        assert_eq!(merge_resumed_text("Hello wo", "Hello world"), "Hello world");
+        // Still replaying the part that was already shown
+        assert_eq!(merge_resumed_text("Hello wo", "Hel"), "Hello wo");
+        // Resumed mid-way with some overlap
+        assert_eq!(merge_resumed_text("Hello wo", "world"), "Hello world");
+        assert_eq!(merge_resumed_text("Hello", " there"), "Hello there");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_reconnects_and_completes_turn() {
+        use tokio::io::AsyncWriteExt;
+
+        // The first response promises more body than it sends, then hangs up mid-stream
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let llm_url = format!("http://{}", listener.local_addr().unwrap());
+        let request_count = Arc::new(Mutex::new(0usize));
+        let served = request_count.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                if read_json_request(&mut socket).await.is_none() {
+                    continue;
+                }
+                let attempt = {
+                    let mut count = served.lock().unwrap();
+                    *count += 1;
+                    *count
+                };
+
+                let response = if attempt == 1 {
+                    let partial = sse(&[json!({"choices": [{"delta": {"content": "Hello "}}]})]);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 100000\r\n\r\n{}",
+                        partial
+                    )
+                } else {
+                    let body = sse(&[
+                        json!({"choices": [{"delta": {"content": "Hello "}}]}),
+                        json!({"choices": [{"delta": {"content": "world"}}]}),
+                        json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+                    ]);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        let agent_config = AgentConfig {
+            allowed_tools: None,
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            agent_config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        );
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        let messages = agent.run("Say hello".to_string(), event_tx).await.unwrap();
+
+        assert_eq!(*request_count.lock().unwrap(), 2);
+        let last = messages.last().unwrap();
+        assert_eq!(last.role, "assistant");
+        assert!(matches!(&last.content, AgentContent::Text(text) if text == "Hello world"));
+
+        // Streamed text never repeats what was shown before the drop
+        let mut texts = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Text { content } = event {
+                texts.push(content);
+            }
+        }
+        assert!(texts.iter().all(|t| t.starts_with("Hello ") && !t.contains("Hello Hello")));
+        assert_eq!(texts.last().map(String::as_str), Some("Hello world"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn test_run_times_out_during_slow_tool() {
+        let llm_url = spawn_mock_server(|_body| {
+            let events = vec![
+                json!({"choices": [{"delta": {"tool_calls": [{
+                    "index": 0,
+                    "id": "call_1",
+                    "function": { "name": "bash", "arguments": "{\"command\":\"sleep 5\"}" }
+                }]}}]}),
+                json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+            ];
+            ("text/event-stream", sse(&events))
+        })
+        .await;
+
+        let agent_config = AgentConfig {
+            max_run_duration_secs: Some(1),
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            agent_config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        );
+
+        let (event_tx, mut event_rx) = mpsc::channel(100);
+        let started = Instant::now();
+        let result = agent.run("Build the project".to_string(), event_tx).await;
+
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert_eq!(result.unwrap_err(), "timeout");
+        let mut errors = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Error { message } = event {
+                errors.push(message);
+            }
+        }
+        assert_eq!(errors, vec!["Agent run exceeded maximum duration of 1 seconds".to_string()]);
+    }
+}
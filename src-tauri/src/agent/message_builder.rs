@@ -1,14 +1,18 @@
-use crate::agent::{AgentConfig, AgentContent, AgentMessage, ToolDefinition};
+use crate::agent::{render_system_prompt, AgentConfig, AgentContent, AgentMessage, SystemPromptVars, ToolDefinition};
+use crate::llm_client::{ApiFormat, DEFAULT_TOKENS_PER_CHAR};
 use crate::mcp::{MCPManager, MCPTool};
-use crate::tools;
-use serde::{Deserialize, Serialize};
+use crate::tools::ToolRegistry;
+use serde::{Deserialize, Serialize, Serializer};
 use std::sync::Arc;
 
+/// Anthropic only caches prompts of at least this many tokens
+const MIN_CACHEABLE_PROMPT_TOKENS: usize = 1024;
+
 #[derive(Debug, Serialize)]
 pub struct ClaudeApiRequest {
     pub model: String,
     pub max_tokens: u32,
-    pub system: String,
+    pub system: SystemPrompt,
     pub messages: Vec<ApiMessage>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<ToolDefinition>,
@@ -17,6 +21,35 @@ pub struct ClaudeApiRequest {
     pub stream: bool,
 }
 
+/// System prompt text; serialized as a plain string, or as a single text block
+/// with `cache_control` when it should be cached
+#[derive(Debug, Clone, Default)]
+pub struct SystemPrompt {
+    pub text: String,
+    pub cache: bool,
+}
+
+impl From<String> for SystemPrompt {
+    fn from(text: String) -> Self {
+        Self { text, cache: false }
+    }
+}
+
+impl Serialize for SystemPrompt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.cache {
+            serde_json::json!([{
+                "type": "text",
+                "text": self.text,
+                "cache_control": { "type": "ephemeral" }
+            }])
+            .serialize(serializer)
+        } else {
+            self.text.serialize(serializer)
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiMessage {
     pub role: String,
@@ -36,6 +69,9 @@ pub struct MessageBuilder {
     max_tokens: u32,
     temperature: Option<f32>,
     mcp_manager: Option<Arc<MCPManager>>,
+    structured_tool_results: bool,
+    tool_registry: Arc<ToolRegistry>,
+    api_format: ApiFormat,
 }
 
 impl MessageBuilder {
@@ -46,29 +82,73 @@ impl MessageBuilder {
             max_tokens,
             temperature,
             mcp_manager: None,
+            structured_tool_results: false,
+            tool_registry: Arc::new(ToolRegistry::new()),
+            api_format: ApiFormat::default(),
         }
     }
 
+    /// API format of the provider the request is sent to
+    pub fn with_api_format(mut self, api_format: ApiFormat) -> Self {
+        self.api_format = api_format;
+        self
+    }
+
     pub fn with_mcp_manager(mut self, mcp_manager: Arc<MCPManager>) -> Self {
         self.mcp_manager = Some(mcp_manager);
         self
     }
 
+    pub fn with_tool_registry(mut self, tool_registry: Arc<ToolRegistry>) -> Self {
+        self.tool_registry = tool_registry;
+        self
+    }
+
+    /// Keep JSON tool results structured (for providers whose function responses accept JSON)
+    pub fn with_structured_tool_results(mut self, structured: bool) -> Self {
+        self.structured_tool_results = structured;
+        self
+    }
+
     pub async fn build_request(&self, messages: &[AgentMessage]) -> ClaudeApiRequest {
-        let mut tools = tools::get_tools(&self.config.allowed_tools);
+        let mut tools = match &self.config.allowed_tools {
+            Some(allowed) => self.tool_registry.definitions_for(allowed),
+            None => self.tool_registry.definitions(),
+        };
 
         // Add MCP tools if available
         if let Some(mcp_manager) = &self.mcp_manager {
-            let mcp_tools = self.get_mcp_tools(mcp_manager).await;
+            let mut mcp_tools = self.get_mcp_tools(mcp_manager).await;
+            if let Some(allowed) = &self.config.allowed_tools {
+                mcp_tools.retain(|t| allowed.contains(&t.name));
+            }
             tools.extend(mcp_tools);
         }
 
         let api_messages = self.convert_messages(messages);
 
+        let mut system = self.config.system_prompt.clone();
+        if system.contains("{{") {
+            let vars = SystemPromptVars::current(self.config.project_path.clone(), &self.model);
+            system = render_system_prompt(&system, &vars);
+        }
+
+        // Providers only accept the system prompt out of band, so system notes are appended to it
+        for message in messages.iter().filter(|m| m.role == "system") {
+            if let AgentContent::Text(note) = &message.content {
+                system.push_str("\n\n");
+                system.push_str(note);
+            }
+        }
+
+        let cache = self.config.enable_prompt_caching
+            && self.api_format == ApiFormat::Anthropic
+            && estimate_tokens(&system) > MIN_CACHEABLE_PROMPT_TOKENS;
+
         ClaudeApiRequest {
             model: self.model.clone(),
             max_tokens: self.max_tokens,
-            system: self.config.system_prompt.clone(),
+            system: SystemPrompt { text: system, cache },
             messages: api_messages,
             tools,
             temperature: self.temperature,
@@ -83,7 +163,7 @@ impl MessageBuilder {
         for status in server_statuses {
             if matches!(status.status, crate::mcp::types::ConnectionStatus::Connected) {
                 for tool in status.tools {
-                    mcp_tools.push(Self::convert_mcp_tool_to_definition(&status.id, &status.name, &tool));
+                    mcp_tools.push(Self::convert_mcp_tool_to_definition(&status.name, &tool));
                 }
             }
         }
@@ -91,20 +171,18 @@ impl MessageBuilder {
         mcp_tools
     }
 
-    fn convert_mcp_tool_to_definition(server_id: &str, server_name: &str, mcp_tool: &MCPTool) -> ToolDefinition {
-        let safe_server_id = server_id.replace("-", "_").replace(":", "_");
-        let safe_tool_name = mcp_tool.name.replace("-", "_").replace(":", "_");
-
+    fn convert_mcp_tool_to_definition(server_name: &str, mcp_tool: &MCPTool) -> ToolDefinition {
         ToolDefinition {
-            name: format!("mcp_{}_{}", safe_server_id, safe_tool_name),
+            name: mcp_tool.function_name(),
             description: format!("{} (MCP tool from server '{}')", mcp_tool.description, server_name),
-            input_schema: mcp_tool.input_schema.clone(),
+            input_schema: crate::mcp::types::clean_input_schema(&mcp_tool.input_schema),
         }
     }
 
     fn convert_messages(&self, messages: &[AgentMessage]) -> Vec<ApiMessage> {
         messages
             .iter()
+            .filter(|m| m.role != "system")
             .map(|m| {
                 let content = match &m.content {
                     AgentContent::Text(text) => ApiContent::Text(text.clone()),
@@ -118,7 +196,7 @@ impl MessageBuilder {
                     AgentContent::ToolResults(results) => {
                         let json_results: Vec<serde_json::Value> = results
                             .iter()
-                            .map(|r| serde_json::to_value(r).unwrap_or_default())
+                            .map(|r| r.to_api_block(self.structured_tool_results))
                             .collect();
                         ApiContent::Blocks(json_results)
                     }
@@ -132,3 +210,47 @@ impl MessageBuilder {
             .collect()
     }
 }
+
+fn estimate_tokens(text: &str) -> usize {
+    (text.chars().count() as f32 * DEFAULT_TOKENS_PER_CHAR).ceil() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request_json(enable_prompt_caching: bool, api_format: ApiFormat, system_prompt: String) -> serde_json::Value {
+        let config = AgentConfig {
+            system_prompt,
+            enable_prompt_caching,
+            ..AgentConfig::default()
+        };
+        let builder = MessageBuilder::new(config, "claude-sonnet-4-5".to_string(), 1024, None).with_api_format(api_format);
+        let messages = vec![AgentMessage {
+            role: "user".to_string(),
+            content: AgentContent::Text("Summarize the handbook".to_string()),
+        }];
+        let request = builder.build_request(&messages).await;
+        serde_json::to_value(&request).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_long_system_prompt_marked_for_caching() {
+        let long_prompt = "Follow the company style guide closely. ".repeat(200);
+
+        let cached = request_json(true, ApiFormat::Anthropic, long_prompt.clone()).await;
+        assert_eq!(cached["system"][0]["type"], "text");
+        assert_eq!(cached["system"][0]["text"], long_prompt.as_str());
+        assert_eq!(cached["system"][0]["cache_control"], serde_json::json!({ "type": "ephemeral" }));
+
+        let disabled = request_json(false, ApiFormat::Anthropic, long_prompt.clone()).await;
+        assert_eq!(disabled["system"], long_prompt.as_str());
+        assert!(!disabled.to_string().contains("cache_control"));
+
+        // Short prompts and other providers are never marked
+        let short = request_json(true, ApiFormat::Anthropic, "Be brief.".to_string()).await;
+        assert_eq!(short["system"], "Be brief.");
+        let openai = request_json(true, ApiFormat::OpenAI, long_prompt).await;
+        assert!(!openai.to_string().contains("cache_control"));
+    }
+}
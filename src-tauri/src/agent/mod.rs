@@ -1,9 +1,240 @@
 pub mod agent_loop;
 pub mod message_builder;
 pub mod tool_executor;
+pub mod tool_metrics;
 pub mod types;
 
 pub use agent_loop::AgentLoop;
 pub use message_builder::MessageBuilder;
-pub use tool_executor::ToolExecutor;
+pub use tool_executor::{tool_risk, ToolExecutor};
+pub use tool_metrics::{ToolMetrics, ToolStats};
 pub use types::*;
+
+/// Plan-and-progress instructions for agent and task runs, whose `<plan>` and
+/// `[STEP n ...]` markers the agent loop turns into plan events
+pub const PLANNING_PROMPT: &str = r#"
+
+## Planning
+Before starting ANY task, you MUST output a plan in this exact format:
+
+<plan>
+1. [First step description]
+2. [Second step description]
+...
+</plan>
+
+Then execute each step, marking progress with `[STEP 1 START]` when beginning a step and
+`[STEP 1 DONE]` when completing it. Verify your changes work and finish with a short summary.
+"#;
+
+/// Default system prompt with `## Role`, `## Capabilities`, `## Tools`, `## Workspace`
+/// (only when `config.project_path` is set) and `## Guidelines` sections, followed by the
+/// MCP server and skills listings
+pub fn build_default_system_prompt(
+    config: &AgentConfig,
+    tools: &[ToolDefinition],
+    mcp_info: &str,
+    skills_info: &str,
+) -> String {
+    let mut prompt = String::from(
+        "## Role\n\
+         You are Kuse Cowork, an AI agent that helps users get work done on their computer: \
+         documents, spreadsheets, files, data and code.\n",
+    );
+
+    prompt.push_str(
+        "\n## Capabilities\n\
+         - Read, create and edit files, including .xlsx spreadsheets\n\
+         - Search files by name or content\n\
+         - Run shell commands and code snippets\n\
+         - Run commands in Docker containers: the workspace is mounted at /workspace and \
+         skills (~/.kuse-cowork/skills) at /skills (read-only). Default image: python:3.11-alpine; \
+         also available: ubuntu:latest, node:20, rust:alpine\n",
+    );
+
+    prompt.push_str("\n## Tools\n");
+    for tool in tools {
+        prompt.push_str(&format!("- `{}` - {}\n", tool.name, one_line(&tool.description)));
+    }
+
+    if let Some(project_path) = &config.project_path {
+        prompt.push_str(&format!(
+            "\n## Workspace\n\
+             Mounted folder(s): {}\n\
+             Always read and write files only inside mounted folder(s). Avoid temporary directories \
+             unless the user explicitly asks.\n",
+            project_path
+        ));
+    }
+
+    prompt.push_str(
+        "\n## Guidelines\n\
+         - Use tools when a request needs files or commands; answer simple questions directly\n\
+         - Always read files before modifying them\n\
+         - Use edit_file for small changes, write_file for new files or complete rewrites\n\
+         - Be careful with bash commands - prefer read-only operations when possible\n\
+         - Search with glob and grep before making assumptions about file locations\n\
+         - After tool execution, keep your response strictly grounded in tool outputs\n\
+         - Do not add unrelated commentary, such as project overviews when the user asked for a direct tool action\n\
+         - If the user explicitly asks to use a specific tool, execute it and return concise, outcome-first results\n",
+    );
+    if cfg!(target_os = "windows") {
+        prompt.push_str(
+            "- `bash` runs PowerShell: use commands like `Get-ChildItem`, `Get-Location`, `Select-String`, \
+             and avoid Unix-only syntax such as `pwd` or `ls | head`\n",
+        );
+    } else {
+        prompt.push_str("- Use `sh`-compatible syntax for `bash` tool commands\n");
+    }
+
+    prompt.push_str(mcp_info);
+    prompt.push_str(skills_info);
+    prompt
+}
+
+/// First line of a tool description, cut at the end of its first sentence
+fn one_line(description: &str) -> &str {
+    let line = description.lines().next().unwrap_or("").trim();
+    match line.find(". ") {
+        Some(end) => &line[..=end],
+        None => line,
+    }
+}
+
+/// `## Available Skills` section listing installed skills and how to use them;
+/// empty when none are installed
+pub fn skills_system_prompt() -> String {
+    let skills = crate::skills::get_available_skills();
+    if skills.is_empty() {
+        return String::new();
+    }
+
+    let skills_path = crate::skills::get_skills_directory_path();
+    let mut prompt = String::from("\n\n## Available Skills\n");
+    prompt.push_str(&format!("Skills are located in {} (auto-mounted at /skills in Docker):\n\n", skills_path));
+    for skill in skills {
+        prompt.push_str(&format!("- **{}**: {}\n", skill.name, skill.description));
+    }
+
+    prompt.push_str("\n### Using Skills\n");
+    prompt.push_str("When a user's request matches a skill:\n");
+    prompt.push_str(&format!("1. Read the skill's SKILL.md file using read_file tool: `{}/{{skill_name}}/SKILL.md`\n", skills_path));
+    prompt.push_str("2. Follow the instructions in SKILL.md\n");
+    prompt.push_str("3. Load additional referenced files progressively as needed:\n");
+    prompt.push_str(&format!("   - `{}/{{skill_name}}/forms.md`\n", skills_path));
+    prompt.push_str(&format!("   - `{}/{{skill_name}}/reference.md`\n", skills_path));
+    prompt.push_str("4. Execute scripts using docker_run tool - skills are auto-mounted at /skills\n");
+    prompt.push_str("5. Example: `python /skills/pdf/scripts/extract_text.py /workspace/document.pdf`\n");
+    prompt.push_str("\nNote: The ~ symbol is supported in read_file paths and will expand to the user's home directory.\n");
+    prompt
+}
+
+/// Values substituted into `{{variable}}` placeholders of a system prompt
+#[derive(Debug, Clone, Default)]
+pub struct SystemPromptVars {
+    pub workspace: Option<String>,
+    pub model: String,
+    pub date: String,
+    pub platform: String,
+    pub skills: Vec<String>,
+}
+
+impl SystemPromptVars {
+    /// Variables for the current date, platform and installed skills
+    pub fn current(workspace: Option<String>, model: &str) -> Self {
+        Self {
+            workspace,
+            model: model.to_string(),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            platform: std::env::consts::OS.to_string(),
+            skills: crate::skills::get_available_skills()
+                .into_iter()
+                .map(|s| s.name)
+                .collect(),
+        }
+    }
+}
+
+/// Fill `{{workspace}}`, `{{model}}`, `{{date}}`, `{{platform}}` and `{{skills}}`
+/// in a system prompt. Unknown placeholders are left as-is.
+pub fn render_system_prompt(template: &str, vars: &SystemPromptVars) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+
+    template
+        .replace("{{workspace}}", vars.workspace.as_deref().unwrap_or("none"))
+        .replace("{{model}}", &vars.model)
+        .replace("{{date}}", &vars.date)
+        .replace("{{platform}}", &vars.platform)
+        .replace("{{skills}}", &vars.skills.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_system_prompt() {
+        let vars = SystemPromptVars {
+            workspace: Some("/home/me/project".to_string()),
+            model: "claude-sonnet-4".to_string(),
+            date: "2025-01-31".to_string(),
+            platform: "linux".to_string(),
+            skills: vec!["pdf".to_string(), "xlsx".to_string()],
+        };
+
+        let rendered = render_system_prompt(
+            "Folder {{workspace}} | {{model}} | {{date}} | {{platform}} | skills: {{skills}} | {{user}}",
+            &vars,
+        );
+        assert_eq!(
+            rendered,
+            "Folder /home/me/project | claude-sonnet-4 | 2025-01-31 | linux | skills: pdf, xlsx | {{user}}"
+        );
+
+        let no_workspace = SystemPromptVars { workspace: None, ..vars };
+        assert_eq!(render_system_prompt("In {{workspace}}", &no_workspace), "In none");
+    }
+
+    #[test]
+    fn test_default_system_prompt_lists_all_tools() {
+        let tools = crate::tools::ToolRegistry::new().definitions();
+        let config = AgentConfig {
+            project_path: Some("/home/me/reports".to_string()),
+            ..Default::default()
+        };
+
+        let prompt = build_default_system_prompt(&config, &tools, "\n\n## MCP Servers\n- docs", "");
+        for section in ["## Role", "## Capabilities", "## Tools", "## Workspace", "## Guidelines"] {
+            assert!(prompt.contains(section), "missing {}", section);
+        }
+        for tool in &tools {
+            let line = prompt
+                .lines()
+                .find(|line| line.starts_with(&format!("- `{}` - ", tool.name)))
+                .unwrap_or_else(|| panic!("{} not listed", tool.name));
+            assert!(line.len() > tool.name.len() + 6, "{} has no description", tool.name);
+        }
+        assert!(prompt.contains("Mounted folder(s): /home/me/reports"));
+        assert!(prompt.ends_with("## MCP Servers\n- docs"));
+    }
+
+    #[test]
+    fn test_default_system_prompt_without_workspace() {
+        let tools = vec![ToolDefinition {
+            name: "read_file".to_string(),
+            description: "Read a file. Supports line ranges.\nSecond line".to_string(),
+            input_schema: serde_json::json!({ "type": "object" }),
+        }];
+        let config = AgentConfig {
+            project_path: None,
+            ..Default::default()
+        };
+
+        let prompt = build_default_system_prompt(&config, &tools, "", "");
+        assert!(!prompt.contains("## Workspace"));
+        assert!(prompt.contains("- `read_file` - Read a file.\n"));
+        assert!(!prompt.contains("Second line"));
+    }
+}
@@ -1,11 +1,29 @@
-use crate::agent::{ToolResult, ToolUse};
-use crate::mcp::{MCPManager, MCPToolCall};
-use crate::tools;
-use std::sync::Arc;
+use crate::agent::{ToolMetrics, ToolResult, ToolResultContent, ToolUse, DEFAULT_MAX_TOOL_RESULT_CHARS};
+use crate::mcp::{MCPManager, MCPTool, MCPToolCall};
+use crate::tools::ToolRegistry;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Size of a tool result before and after it was cut to the result limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncation {
+    pub original_chars: usize,
+    pub truncated_to: usize,
+}
 
 pub struct ToolExecutor {
     project_path: Option<String>,
     mcp_manager: Option<Arc<MCPManager>>,
+    registry: Arc<ToolRegistry>,
+    metrics: Arc<Mutex<ToolMetrics>>,
+    max_result_chars: Option<usize>,
+    result_limits: HashMap<String, usize>,
+    allowed_tools: Option<HashSet<String>>,
+    /// Describe tool calls instead of running them
+    dry_run: bool,
+    /// Let read tools open files outside the mounted folder(s)
+    allow_outside_workspace: bool,
 }
 
 impl ToolExecutor {
@@ -13,87 +31,392 @@ impl ToolExecutor {
         Self {
             project_path,
             mcp_manager: None,
+            registry: Arc::new(ToolRegistry::new()),
+            metrics: Arc::new(Mutex::new(ToolMetrics::new())),
+            max_result_chars: Some(DEFAULT_MAX_TOOL_RESULT_CHARS),
+            result_limits: HashMap::new(),
+            allowed_tools: None,
+            dry_run: false,
+            allow_outside_workspace: false,
         }
     }
 
+    pub fn with_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
     pub fn with_mcp_manager(mut self, mcp_manager: Arc<MCPManager>) -> Self {
         self.mcp_manager = Some(mcp_manager);
         self
     }
 
+    /// Record call counts and timings into shared metrics
+    pub fn with_metrics(mut self, metrics: Arc<Mutex<ToolMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Limit tool output length; `per_tool` overrides `max_chars` for specific tools
+    pub fn with_result_limits(mut self, max_chars: Option<usize>, per_tool: Option<HashMap<String, usize>>) -> Self {
+        self.max_result_chars = max_chars;
+        self.result_limits = per_tool.unwrap_or_default();
+        self
+    }
+
+    /// Refuse any tool not named in `tools`
+    pub fn with_allowed_tools(mut self, tools: Vec<String>) -> Self {
+        self.allowed_tools = Some(tools.into_iter().collect());
+        self
+    }
+
+    /// Return a description of each call instead of executing it
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Let read tools open files outside the mounted folder(s); off by default
+    pub fn with_outside_workspace_reads(mut self, allowed: bool) -> Self {
+        self.allow_outside_workspace = allowed;
+        self
+    }
+
     pub async fn execute(&self, tool_use: &ToolUse) -> ToolResult {
+        self.execute_with_truncation(tool_use).await.0
+    }
+
+    /// Execute a tool, also reporting whether its output was truncated to the result limit
+    pub async fn execute_with_truncation(&self, tool_use: &ToolUse) -> (ToolResult, Option<Truncation>) {
+        if let Some(allowed) = &self.allowed_tools {
+            if !allowed.contains(&tool_use.name) {
+                return (
+                    ToolResult::error(tool_use.id.clone(), "tool not in allowed list".to_string()),
+                    None,
+                );
+            }
+        }
+
+        if self.dry_run {
+            return (
+                ToolResult::success(
+                    tool_use.id.clone(),
+                    format!("[DRY RUN] Would execute: {} with input: {}", tool_use.name, tool_use.input),
+                ),
+                None,
+            );
+        }
+
+        let started = Instant::now();
+        let mut result = self.execute_tool(tool_use).await;
+
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.record(&tool_use.name, started.elapsed(), result.is_error == Some(true));
+        }
+
+        let limit = self
+            .result_limits
+            .get(&tool_use.name)
+            .copied()
+            .or(self.max_result_chars);
+        let truncation = limit.and_then(|limit| truncate_result(&mut result, limit));
+
+        (result, truncation)
+    }
+
+    /// Whether a tool runs locally (`"native"`) or on an MCP server (`"mcp"`), with the server id
+    pub async fn tool_source(&self, name: &str) -> (String, Option<String>) {
+        if !is_mcp_tool_name(name) {
+            return ("native".to_string(), None);
+        }
+
+        let server_id = match &self.mcp_manager {
+            Some(mcp_manager) => Self::resolve_mcp_tool(mcp_manager, name).await.map(|t| t.server_id),
+            None => None,
+        }
+        .or_else(|| name.split_once(':').map(|(server_id, _)| server_id.to_string()));
+        ("mcp".to_string(), server_id)
+    }
+
+    async fn execute_tool(&self, tool_use: &ToolUse) -> ToolResult {
         let project_path = self.project_path.as_deref();
 
-        // Check if this is an MCP tool (format: mcp_server_id_tool_name)
-        if tool_use.name.starts_with("mcp_") {
-            if let Some(mcp_manager) = &self.mcp_manager {
-                // Get all available tools to find the correct mapping
-                let all_tools = mcp_manager.get_all_tools().await;
-                let mut matching_tool = None;
-
-                // Find the tool that matches the current tool_use name
-                for tool in &all_tools {
-                    let safe_server_id = tool.server_id.replace("-", "_").replace(":", "_");
-                    let safe_tool_name = tool.name.replace("-", "_").replace(":", "_");
-                    let expected_name = format!("mcp_{}_{}", safe_server_id, safe_tool_name);
-
-                    if expected_name == tool_use.name {
-                        matching_tool = Some(tool);
-                        break;
-                    }
-                }
-
-                if let Some(tool) = matching_tool {
-                    let mcp_call = MCPToolCall {
-                        server_id: tool.server_id.clone(),
-                        tool_name: tool.name.clone(),
-                        parameters: tool_use.input.clone(),
-                    };
-
-                    let mcp_result = mcp_manager.execute_tool(&mcp_call).await;
-
-                    return if mcp_result.success {
-                        ToolResult::success(tool_use.id.clone(), mcp_result.result.to_string())
-                    } else {
-                        ToolResult::error(
-                            tool_use.id.clone(),
-                            mcp_result.error.unwrap_or("MCP tool execution failed".to_string())
-                        )
-                    };
-                } else {
-                    return ToolResult::error(
-                        tool_use.id.clone(),
-                        format!("MCP tool '{}' not found", tool_use.name)
-                    );
-                }
-            } else {
+        if is_mcp_tool_name(&tool_use.name) {
+            let Some(mcp_manager) = &self.mcp_manager else {
                 return ToolResult::error(
                     tool_use.id.clone(),
                     "MCP manager not available".to_string()
                 );
+            };
+
+            let Some(tool) = Self::resolve_mcp_tool(mcp_manager, &tool_use.name).await else {
+                return ToolResult::error(
+                    tool_use.id.clone(),
+                    format!("MCP tool '{}' not found", tool_use.name)
+                );
+            };
+
+            let mcp_call = MCPToolCall {
+                server_id: tool.server_id,
+                tool_name: tool.name,
+                parameters: tool_use.input.clone(),
+            };
+
+            let mcp_result = mcp_manager.execute_tool(&mcp_call).await;
+
+            return if mcp_result.success {
+                ToolResult::json(tool_use.id.clone(), mcp_result.result)
+            } else {
+                ToolResult::error(
+                    tool_use.id.clone(),
+                    mcp_result.error.unwrap_or("MCP tool execution failed".to_string())
+                )
+            };
+        }
+
+        match self
+            .registry
+            .execute(&tool_use.name, &tool_use.input, project_path, self.allow_outside_workspace)
+            .await {
+            Some(mut result) => {
+                result.tool_use_id = tool_use.id.clone();
+                result
             }
+            None => ToolResult::error(tool_use.id.clone(), format!("Unknown tool: {}", tool_use.name)),
         }
+    }
+
+    /// Find the connected MCP tool for a tool name.
+    ///
+    /// `server_id:tool_name` is split on the first `:`; anything else is matched
+    /// against the `mcp_{server_id}_{tool_name}` function names sent to the model.
+    async fn resolve_mcp_tool(mcp_manager: &MCPManager, name: &str) -> Option<MCPTool> {
+        let all_tools = mcp_manager.get_all_tools().await;
+
+        if let Some((server_id, tool_name)) = name.split_once(':') {
+            return all_tools
+                .into_iter()
+                .find(|tool| tool.server_id == server_id && tool.name == tool_name);
+        }
+
+        all_tools.into_iter().find(|tool| tool.function_name() == name)
+    }
+}
+
+/// How much a tool call can change: `"read_only"`, `"write"` (files or clipboard) or `"exec"`.
+/// MCP, custom and unknown tools count as `"exec"` since their effects can't be known.
+pub fn tool_risk(name: &str) -> &'static str {
+    match name {
+        "read_file" | "glob" | "grep" | "list_dir" | "file_tree" | "list_allowed_directories"
+        | "extract_text_from_image" | "clipboard_read" | "docker_list" | "docker_images"
+        | "docker_container_logs" => "read_only",
+        "write_file" | "edit_file" | "create_xlsx_file" | "clipboard_write" => "write",
+        _ => "exec",
+    }
+}
+
+/// MCP tools are named `mcp_{server_id}_{tool_name}` or `server_id:tool_name`
+fn is_mcp_tool_name(name: &str) -> bool {
+    name.starts_with("mcp_") || name.contains(':')
+}
+
+/// Cut the result's text to `limit` characters and append a notice saying so
+fn truncate_result(result: &mut ToolResult, limit: usize) -> Option<Truncation> {
+    let text = result.content.to_text();
+    let original_chars = text.chars().count();
+    if original_chars <= limit {
+        return None;
+    }
+
+    let mut truncated: String = text.chars().take(limit).collect();
+    truncated.push_str(&format!(
+        "\n[Output truncated to {} chars. Use more specific tool parameters to get less output.]",
+        limit
+    ));
+    result.content = match result.content {
+        ToolResultContent::Error(_) => ToolResultContent::Error(truncated),
+        _ => ToolResultContent::Text(truncated),
+    };
+
+    Some(Truncation {
+        original_chars,
+        truncated_to: limit,
+    })
+}
 
-        // Docker tools have their own result handling
-        if tool_use.name.starts_with("docker_") {
-            return tools::docker::execute_docker_tool(tool_use, &self.project_path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool_use(name: &str, input: serde_json::Value) -> ToolUse {
+        ToolUse {
+            id: format!("call_{}", name),
+            name: name.to_string(),
+            input,
+            thought_signature: None,
         }
+    }
+
+    #[tokio::test]
+    async fn test_executor_records_tool_metrics() {
+        let dir = std::env::temp_dir().join(format!("kuse-metrics-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
 
-        let result = match tool_use.name.as_str() {
-            "read_file" => tools::file_read::execute(&tool_use.input, project_path),
-            "write_file" => tools::file_write::execute(&tool_use.input, project_path),
-            "edit_file" => tools::file_edit::execute(&tool_use.input, project_path),
-            "bash" => tools::bash::execute(&tool_use.input, project_path),
-            "glob" => tools::glob::execute(&tool_use.input, project_path),
-            "grep" => tools::grep::execute(&tool_use.input, project_path),
-            "list_dir" => tools::list_dir::execute(&tool_use.input, project_path),
-            "create_xlsx_file" => tools::xlsx_create::execute(&tool_use.input, project_path),
-            _ => Err(format!("Unknown tool: {}", tool_use.name)),
-        };
-
-        match result {
-            Ok(content) => ToolResult::success(tool_use.id.clone(), content),
-            Err(error) => ToolResult::error(tool_use.id.clone(), error),
+        let metrics = Arc::new(Mutex::new(ToolMetrics::new()));
+        let executor = ToolExecutor::new(Some(dir.to_string_lossy().to_string()))
+            .with_metrics(metrics.clone());
+
+        executor.execute(&tool_use("read_file", json!({ "path": "notes.txt" }))).await;
+        executor.execute(&tool_use("list_dir", json!({ "path": "." }))).await;
+        let failed = executor.execute(&tool_use("read_file", json!({ "path": "missing.txt" }))).await;
+        assert_eq!(failed.is_error, Some(true));
+
+        let stats = metrics.lock().unwrap().snapshot();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["read_file"].call_count, 2);
+        assert_eq!(stats["read_file"].error_count, 1);
+        assert_eq!(stats["list_dir"].call_count, 1);
+        assert_eq!(stats["list_dir"].error_count, 0);
+        for tool in stats.values() {
+            assert!(tool.total_duration_ms < 60_000);
+            assert!(tool.last_called_at.is_some());
         }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_outside_workspace_reads_follow_setting() {
+        let workspace = std::env::temp_dir().join(format!("kuse-workspace-{}", uuid::Uuid::new_v4()));
+        let outside = std::env::temp_dir().join(format!("kuse-outside-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("report.txt"), "quarterly numbers").unwrap();
+        let read = tool_use("read_file", json!({ "path": outside.join("report.txt").to_string_lossy() }));
+        let project_path = Some(workspace.to_string_lossy().to_string());
+
+        let result = ToolExecutor::new(project_path.clone()).execute(&read).await;
+        assert_eq!(result.is_error, Some(true), "{}", result.content.to_text());
+
+        let result = ToolExecutor::new(project_path)
+            .with_outside_workspace_reads(true)
+            .execute(&read)
+            .await;
+        assert_eq!(result.is_error, None);
+        assert!(result.content.to_text().contains("quarterly numbers"));
+
+        let _ = std::fs::remove_dir_all(&workspace);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn test_truncate_result_at_threshold() {
+        let mut at_limit = ToolResult::success("t1".to_string(), "é".repeat(10));
+        assert_eq!(truncate_result(&mut at_limit, 10), None);
+        assert_eq!(at_limit.content.to_text(), "é".repeat(10));
+
+        let mut over = ToolResult::success("t1".to_string(), "é".repeat(11));
+        assert_eq!(
+            truncate_result(&mut over, 10),
+            Some(Truncation { original_chars: 11, truncated_to: 10 })
+        );
+        assert_eq!(
+            over.content.to_text(),
+            format!(
+                "{}\n[Output truncated to 10 chars. Use more specific tool parameters to get less output.]",
+                "é".repeat(10)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_applies_per_tool_result_limits() {
+        let dir = std::env::temp_dir().join(format!("kuse-truncate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("big.txt"), "x".repeat(5000)).unwrap();
+
+        let executor = ToolExecutor::new(Some(dir.to_string_lossy().to_string())).with_result_limits(
+            Some(100),
+            Some(HashMap::from([("list_dir".to_string(), 100_000)])),
+        );
+
+        let (result, truncation) = executor
+            .execute_with_truncation(&tool_use("read_file", json!({ "path": "big.txt" })))
+            .await;
+        let truncation = truncation.expect("read_file output truncated");
+        assert!(truncation.original_chars > 5000);
+        assert_eq!(truncation.truncated_to, 100);
+        assert!(result.content.to_text().ends_with("to get less output.]"));
+
+        let (_, truncation) = executor
+            .execute_with_truncation(&tool_use("list_dir", json!({ "path": "." })))
+            .await;
+        assert_eq!(truncation, None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_tool_is_not_executed() {
+        let dir = std::env::temp_dir().join(format!("kuse-allowed-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+
+        let metrics = Arc::new(Mutex::new(ToolMetrics::new()));
+        let executor = ToolExecutor::new(Some(dir.to_string_lossy().to_string()))
+            .with_metrics(metrics.clone())
+            .with_allowed_tools(vec!["read_file".to_string()]);
+
+        let blocked = executor
+            .execute(&tool_use("write_file", json!({ "path": "out.txt", "content": "nope" })))
+            .await;
+        assert_eq!(blocked.is_error, Some(true));
+        assert_eq!(blocked.tool_use_id, "call_write_file");
+        assert_eq!(blocked.content.to_text(), "tool not in allowed list");
+        assert!(!dir.join("out.txt").exists());
+
+        let allowed = executor.execute(&tool_use("read_file", json!({ "path": "notes.txt" }))).await;
+        assert!(allowed.is_error.is_none());
+
+        let stats = metrics.lock().unwrap().snapshot();
+        assert!(!stats.contains_key("write_file"));
+        assert_eq!(stats["read_file"].call_count, 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_describes_call_without_side_effects() {
+        let dir = std::env::temp_dir().join(format!("kuse-dry-run-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let metrics = Arc::new(Mutex::new(ToolMetrics::new()));
+        let executor = ToolExecutor::new(Some(dir.to_string_lossy().to_string()))
+            .with_metrics(metrics.clone())
+            .with_dry_run(true);
+
+        let input = json!({ "path": "out.txt", "content": "hello" });
+        let result = executor.execute(&tool_use("write_file", input.clone())).await;
+        assert!(result.is_error.is_none());
+        assert_eq!(result.tool_use_id, "call_write_file");
+        assert_eq!(
+            result.content.to_text(),
+            format!("[DRY RUN] Would execute: write_file with input: {}", input)
+        );
+        assert!(!dir.join("out.txt").exists());
+        assert!(metrics.lock().unwrap().snapshot().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tool_risk() {
+        assert_eq!(tool_risk("read_file"), "read_only");
+        assert_eq!(tool_risk("docker_container_logs"), "read_only");
+        assert_eq!(tool_risk("edit_file"), "write");
+        assert_eq!(tool_risk("bash"), "exec");
+        assert_eq!(tool_risk("mcp_github_create_issue"), "exec");
     }
 }
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Accumulated call statistics for one tool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolStats {
+    pub call_count: u64,
+    pub total_duration_ms: u64,
+    pub error_count: u64,
+    /// Unix timestamp (ms) of the most recent call
+    pub last_called_at: Option<i64>,
+}
+
+/// Per-tool call statistics, keyed by tool name
+#[derive(Debug, Clone, Default)]
+pub struct ToolMetrics {
+    stats: HashMap<String, ToolStats>,
+}
+
+impl ToolMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one finished call; failed calls count toward timing like any other
+    pub fn record(&mut self, tool: &str, duration: Duration, is_error: bool) {
+        let stats = self.stats.entry(tool.to_string()).or_default();
+        stats.call_count += 1;
+        stats.total_duration_ms = stats
+            .total_duration_ms
+            .saturating_add(duration.as_millis().min(u64::MAX as u128) as u64);
+        if is_error {
+            stats.error_count += 1;
+        }
+        stats.last_called_at = Some(chrono::Utc::now().timestamp_millis());
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, ToolStats> {
+        self.stats.clone()
+    }
+
+    pub fn reset(&mut self) {
+        self.stats.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_per_tool() {
+        let mut metrics = ToolMetrics::new();
+        metrics.record("bash", Duration::from_millis(120), false);
+        metrics.record("bash", Duration::from_millis(30), true);
+        metrics.record("glob", Duration::from_millis(5), false);
+
+        let stats = metrics.snapshot();
+        let bash = &stats["bash"];
+        assert_eq!(bash.call_count, 2);
+        assert_eq!(bash.total_duration_ms, 150);
+        assert_eq!(bash.error_count, 1);
+        assert!(bash.last_called_at.is_some());
+        assert_eq!(stats["glob"].call_count, 1);
+
+        metrics.reset();
+        assert!(metrics.snapshot().is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use crate::skills::{get_available_skills, get_skills_directory_path};
+use std::collections::HashMap;
 
 /// Tool definition sent to Claude API
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +9,20 @@ pub struct ToolDefinition {
     pub input_schema: serde_json::Value,
 }
 
+impl ToolDefinition {
+    /// Entry for an OpenAI `tools` array
+    pub fn to_openai_function(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.input_schema,
+            }
+        })
+    }
+}
+
 /// Tool use request from Claude
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolUse {
@@ -20,13 +34,46 @@ pub struct ToolUse {
     pub thought_signature: Option<String>,
 }
 
+/// Payload of a tool result.
+///
+/// Structured results (e.g. from MCP servers) are kept as JSON so providers that accept
+/// structured function responses receive them unchanged; text-only APIs get a
+/// pretty-printed rendering via [`ToolResultContent::to_text`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ToolResultContent {
+    Text(String),
+    Json(serde_json::Value),
+    Error(String),
+}
+
+impl ToolResultContent {
+    /// Render the content as plain text (JSON is pretty-printed)
+    pub fn to_text(&self) -> String {
+        match self {
+            ToolResultContent::Text(text) | ToolResultContent::Error(text) => text.clone(),
+            ToolResultContent::Json(value) => {
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+            }
+        }
+    }
+
+    /// Render the content as a JSON value, keeping structured results intact
+    pub fn to_value(&self) -> serde_json::Value {
+        match self {
+            ToolResultContent::Json(value) => value.clone(),
+            other => serde_json::Value::String(other.to_text()),
+        }
+    }
+}
+
 /// Tool result to send back to Claude
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     #[serde(rename = "type")]
     pub result_type: String,
     pub tool_use_id: String,
-    pub content: String,
+    pub content: ToolResultContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_error: Option<bool>,
     /// Thought signature from Google Gemini 3 (required for function response)
@@ -39,7 +86,17 @@ impl ToolResult {
         Self {
             result_type: "tool_result".to_string(),
             tool_use_id,
-            content,
+            content: ToolResultContent::Text(content),
+            is_error: None,
+            thought_signature: None,
+        }
+    }
+
+    pub fn json(tool_use_id: String, value: serde_json::Value) -> Self {
+        Self {
+            result_type: "tool_result".to_string(),
+            tool_use_id,
+            content: ToolResultContent::Json(value),
             is_error: None,
             thought_signature: None,
         }
@@ -49,11 +106,36 @@ impl ToolResult {
         Self {
             result_type: "tool_result".to_string(),
             tool_use_id,
-            content: error,
+            content: ToolResultContent::Error(error),
             is_error: Some(true),
             thought_signature: None,
         }
     }
+
+    /// Build the `tool_result` block sent to the API.
+    ///
+    /// When `structured` is false, JSON content is flattened to a pretty-printed string
+    /// (Anthropic and OpenAI-style tool messages only accept text here).
+    pub fn to_api_block(&self, structured: bool) -> serde_json::Value {
+        let content = if structured {
+            self.content.to_value()
+        } else {
+            serde_json::Value::String(self.content.to_text())
+        };
+
+        let mut block = serde_json::json!({
+            "type": self.result_type,
+            "tool_use_id": self.tool_use_id,
+            "content": content,
+        });
+        if let Some(is_error) = self.is_error {
+            block["is_error"] = serde_json::json!(is_error);
+        }
+        if let Some(sig) = &self.thought_signature {
+            block["thought_signature"] = serde_json::json!(sig);
+        }
+        block
+    }
 }
 
 /// Content block in Claude response
@@ -75,6 +157,14 @@ pub enum ContentBlock {
     Image {
         source: ImageSource,
     },
+    /// Anthropic extended thinking; sent back unchanged (with its signature) in later requests
+    #[serde(rename = "thinking")]
+    Thinking {
+        #[serde(rename = "thinking")]
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,135 +191,70 @@ pub enum AgentContent {
 }
 
 /// Agent configuration
+///
+/// `system_prompt` may use `{{workspace}}`, `{{model}}`, `{{date}}` (YYYY-MM-DD),
+/// `{{platform}}` and `{{skills}}` (comma-separated skill names); see `render_system_prompt`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
     pub system_prompt: String,
     pub max_turns: u32,
     pub project_path: Option<String>,
-    pub allowed_tools: Vec<String>,
+    /// Tools the agent may call; `None` allows every registered tool
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+    /// How many times a dropped response stream is re-requested before the turn fails
+    #[serde(default = "default_stream_reconnect_attempts")]
+    pub stream_reconnect_attempts: u32,
+    /// Tool output longer than this many characters is truncated; `None` disables the limit
+    #[serde(default = "default_max_tool_result_chars")]
+    pub max_tool_result_chars: Option<usize>,
+    /// Per-tool overrides of `max_tool_result_chars`, keyed by tool name
+    #[serde(default)]
+    pub tool_result_limits: Option<HashMap<String, usize>>,
+    /// Wall-clock limit for a whole run, tool calls included; `None` disables it
+    #[serde(default = "default_max_run_duration_secs")]
+    pub max_run_duration_secs: Option<u64>,
+    /// Mark long system prompts as cacheable on Anthropic, so repeated turns reuse them
+    #[serde(default)]
+    pub enable_prompt_caching: bool,
 }
 
-impl Default for AgentConfig {
-    fn default() -> Self {
-        Self {
-            system_prompt: build_system_prompt(),
-            max_turns: 20,
-            project_path: None,
-            allowed_tools: vec![
-                "read_file".to_string(),
-                "write_file".to_string(),
-                "edit_file".to_string(),
-                "bash".to_string(),
-                "glob".to_string(),
-                "grep".to_string(),
-                "list_dir".to_string(),
-                "create_xlsx_file".to_string(),
-                "docker_run".to_string(),
-                "docker_list".to_string(),
-                "docker_images".to_string(),
-            ],
-        }
-    }
+fn default_stream_reconnect_attempts() -> u32 {
+    2
 }
 
-pub const DEFAULT_SYSTEM_PROMPT: &str = r#"You are Kuse Cowork, an AI agent that helps users with software development tasks.
-
-You have access to tools that allow you to read and write files, execute commands, and search through codebases.
-
-## IMPORTANT: Always Create a Plan First
-
-Before starting ANY task, you MUST output a plan in this exact format:
+pub const DEFAULT_MAX_TOOL_RESULT_CHARS: usize = 8000;
 
-<plan>
-1. [First step description]
-2. [Second step description]
-3. [Third step description]
-...
-</plan>
-
-After outputting the plan, immediately begin executing each step. As you work through each step, indicate progress with:
-- `[STEP 1 START]` when beginning a step
-- `[STEP 1 DONE]` when completing a step
-
-## Guidelines
-- Always read files before modifying them to understand the context
-- Use edit_file for small changes, write_file for new files or complete rewrites
-- Be careful with bash commands - prefer read-only operations when possible
-- Search with glob and grep before making assumptions about file locations
-- Explain what you're doing briefly
-- After tool execution, keep your final response strictly grounded in tool outputs
-- Do not add unrelated commentary (for example project overviews when user requested a direct tool action)
-- If user explicitly asks to use a specific tool, execute it and return concise, outcome-first results
-
-## Available Tools
-- `read_file` - Read file contents
-- `write_file` - Create or overwrite a file
-- `edit_file` - Make targeted edits to a file
-- `bash` - Execute shell commands
-- `glob` - Find files by pattern
-- `grep` - Search file contents
-- `list_dir` - List directory contents
-- `create_xlsx_file` - Create valid .xlsx files from structured rows
-- `docker_run` - Run commands in Docker containers
-- `docker_list` - List running containers
-- `docker_images` - List available images
-
-## Docker Integration
-The project_path (if provided) is automatically mounted to /workspace in containers.
-Skills directory (~/.kuse-cowork/skills) is automatically mounted to /skills (read-only).
-Default image: python:3.11-alpine. Also available: ubuntu:latest, node:20, rust:alpine
-
-## Workflow
-1. Output your plan in <plan> tags
-2. Execute step by step, marking progress
-3. Verify your changes work
-4. Summarize what was accomplished
-"#;
-
-/// Build system prompt with dynamic skills information
-pub fn build_system_prompt() -> String {
-    let mut prompt = DEFAULT_SYSTEM_PROMPT.to_string();
-
-    #[cfg(target_os = "windows")]
-    {
-        prompt.push_str("\n\n## Platform Command Guidance (Windows)\n");
-        prompt.push_str("- Use PowerShell-compatible syntax for `bash` tool commands.\n");
-        prompt.push_str("- Prefer commands like `Get-ChildItem`, `Get-Location`, `Select-String`, `Get-Process`.\n");
-        prompt.push_str("- Avoid Unix-only syntax such as `pwd`, `ls | head`, and shell expansions that rely on `sh`.\n");
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    {
-        prompt.push_str("\n\n## Platform Command Guidance (Unix-like)\n");
-        prompt.push_str("- Use `sh`-compatible command syntax for `bash` tool commands.\n");
-    }
-
-    // Get available skills
-    let skills = get_available_skills();
-
-    if !skills.is_empty() {
-        let skills_path = get_skills_directory_path();
-
-        prompt.push_str("\n\n## Available Skills\n");
-        prompt.push_str(&format!("Skills are located in {} (auto-mounted at /skills in Docker):\n\n", skills_path));
+fn default_max_run_duration_secs() -> Option<u64> {
+    Some(300)
+}
 
-        for skill in skills {
-            prompt.push_str(&format!("- **{}**: {}\n", skill.name, skill.description));
-        }
+fn default_max_tool_result_chars() -> Option<usize> {
+    Some(DEFAULT_MAX_TOOL_RESULT_CHARS)
+}
 
-        prompt.push_str("\n### Using Skills\n");
-        prompt.push_str("When a user's request matches a skill:\n");
-        prompt.push_str(&format!("1. Read the skill's SKILL.md file using read_file tool: `{}/{{skill_name}}/SKILL.md`\n", skills_path));
-        prompt.push_str("2. Follow the instructions in SKILL.md\n");
-        prompt.push_str("3. Load additional referenced files progressively as needed:\n");
-        prompt.push_str(&format!("   - `{}/{{skill_name}}/forms.md`\n", skills_path));
-        prompt.push_str(&format!("   - `{}/{{skill_name}}/reference.md`\n", skills_path));
-        prompt.push_str("4. Execute scripts using docker_run tool - skills are auto-mounted at /skills\n");
-        prompt.push_str("5. Example: `python /skills/pdf/scripts/extract_text.py /workspace/document.pdf`\n");
-        prompt.push_str("\nNote: The ~ symbol is supported in read_file paths and will expand to the user's home directory.\n");
+impl Default for AgentConfig {
+    fn default() -> Self {
+        let mut config = Self {
+            system_prompt: String::new(),
+            max_turns: crate::database::DEFAULT_MAX_AGENT_TURNS,
+            project_path: None,
+            allowed_tools: None,
+            stream_reconnect_attempts: default_stream_reconnect_attempts(),
+            max_tool_result_chars: default_max_tool_result_chars(),
+            tool_result_limits: None,
+            max_run_duration_secs: default_max_run_duration_secs(),
+            enable_prompt_caching: false,
+        };
+        config.system_prompt = super::build_default_system_prompt(
+            &config,
+            &crate::tools::ToolRegistry::new().definitions(),
+            "",
+            &super::skills_system_prompt(),
+        );
+        config.system_prompt.push_str(super::PLANNING_PROMPT);
+        config
     }
-
-    prompt
 }
 
 /// Event emitted during agent execution
@@ -238,16 +263,50 @@ pub fn build_system_prompt() -> String {
 pub enum AgentEvent {
     #[serde(rename = "text")]
     Text { content: String },
+    /// Reasoning a model returns separately from its answer (e.g. DeepSeek-R1)
+    #[serde(rename = "thinking_content")]
+    ThinkingContent { content: String },
+    /// A complete Anthropic extended-thinking block
+    #[serde(rename = "thinking")]
+    Thinking { content: String, signature: Option<String> },
     #[serde(rename = "plan")]
     Plan { steps: Vec<PlanStepInfo> },
     #[serde(rename = "step_start")]
     StepStart { step: i32 },
     #[serde(rename = "step_done")]
     StepDone { step: i32 },
+    /// `tool_type` is `"native"` or `"mcp"`; `server_id` is set for MCP tools
     #[serde(rename = "tool_start")]
-    ToolStart { tool: String, input: serde_json::Value },
+    ToolStart {
+        tool: String,
+        input: serde_json::Value,
+        tool_type: String,
+        server_id: Option<String>,
+    },
     #[serde(rename = "tool_end")]
-    ToolEnd { tool: String, result: String, success: bool },
+    ToolEnd {
+        tool: String,
+        result: String,
+        success: bool,
+        tool_type: String,
+        duration_ms: u64,
+    },
+    #[serde(rename = "tool_result_truncated")]
+    ToolResultTruncated { tool: String, original_chars: usize, truncated_to: usize },
+    /// Tokens used by one model response, when the provider reports them.
+    /// The cache counts are only reported by Anthropic with prompt caching.
+    #[serde(rename = "usage")]
+    Usage {
+        input_tokens: u32,
+        output_tokens: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_creation_input_tokens: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_read_input_tokens: Option<u32>,
+    },
+    /// Every agent slot is busy; the run starts once those ahead of it finish
+    #[serde(rename = "queued")]
+    Queued { position: usize },
     #[serde(rename = "turn_complete")]
     TurnComplete { turn: u32 },
     #[serde(rename = "done")]
@@ -261,3 +320,51 @@ pub struct PlanStepInfo {
     pub step: i32,
     pub description: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_result_text_block() {
+        let block = ToolResult::success("t1".to_string(), "hello".to_string()).to_api_block(false);
+        assert_eq!(block["type"], "tool_result");
+        assert_eq!(block["tool_use_id"], "t1");
+        assert_eq!(block["content"], "hello");
+        assert!(block.get("is_error").is_none());
+    }
+
+    #[test]
+    fn test_tool_result_error_block() {
+        let block = ToolResult::error("t1".to_string(), "boom".to_string()).to_api_block(false);
+        assert_eq!(block["content"], "boom");
+        assert_eq!(block["is_error"], true);
+    }
+
+    #[test]
+    fn test_tool_result_json_block() {
+        let value = json!({"items": [1, 2], "ok": true});
+        let result = ToolResult::json("t1".to_string(), value.clone());
+
+        // Text-only APIs receive pretty-printed JSON
+        let text_block = result.to_api_block(false);
+        assert_eq!(
+            text_block["content"],
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+
+        // Structured APIs receive the value unchanged
+        let structured_block = result.to_api_block(true);
+        assert_eq!(structured_block["content"], value);
+    }
+
+    #[test]
+    fn test_tool_result_content_roundtrip() {
+        let content = ToolResultContent::Json(json!({"a": 1}));
+        let serialized = serde_json::to_value(&content).unwrap();
+        assert_eq!(serialized, json!({"kind": "json", "value": {"a": 1}}));
+        let parsed: ToolResultContent = serde_json::from_value(serialized).unwrap();
+        assert_eq!(parsed, content);
+    }
+}
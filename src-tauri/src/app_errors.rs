@@ -0,0 +1,97 @@
+//! Errors from background work, forwarded to the frontend as `app-error` events
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Errors kept for `subscribe_to_errors`
+pub const MAX_RECENT_ERRORS: usize = 100;
+/// Tauri event carrying each reported `AppError`
+pub const APP_ERROR_EVENT: &str = "app-error";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppError {
+    pub code: String,
+    pub message: String,
+    /// Subsystem that failed, e.g. "mcp"
+    pub component: String,
+    /// Whether a retry or user action can clear the error
+    pub recoverable: bool,
+}
+
+impl AppError {
+    pub fn new(component: &str, code: &str, message: impl Into<String>, recoverable: bool) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            component: component.to_string(),
+            recoverable,
+        }
+    }
+}
+
+/// Broadcasts reported errors and keeps the last `MAX_RECENT_ERRORS` of them
+#[derive(Clone)]
+pub struct ErrorReporter {
+    pub error_tx: Arc<broadcast::Sender<AppError>>,
+    recent: Arc<Mutex<VecDeque<AppError>>>,
+}
+
+impl ErrorReporter {
+    pub fn new() -> Self {
+        let (error_tx, _) = broadcast::channel(MAX_RECENT_ERRORS);
+        Self {
+            error_tx: Arc::new(error_tx),
+            recent: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AppError> {
+        self.error_tx.subscribe()
+    }
+
+    pub fn report(&self, error: AppError) {
+        eprintln!("[{}] {}: {}", error.component, error.code, error.message);
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() >= MAX_RECENT_ERRORS {
+                recent.pop_front();
+            }
+            recent.push_back(error.clone());
+        }
+        // No receivers just means nobody is listening yet
+        let _ = self.error_tx.send(error);
+    }
+
+    /// Buffered errors, oldest first
+    pub fn recent(&self) -> Vec<AppError> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_errors_capped() {
+        let reporter = ErrorReporter::new();
+        for i in 0..MAX_RECENT_ERRORS + 5 {
+            reporter.report(AppError::new("test", "failed", format!("error {}", i), true));
+        }
+
+        let recent = reporter.recent();
+        assert_eq!(recent.len(), MAX_RECENT_ERRORS);
+        assert_eq!(recent[0].message, "error 5");
+        assert_eq!(recent.last().unwrap().message, format!("error {}", MAX_RECENT_ERRORS + 4));
+    }
+}
@@ -0,0 +1,338 @@
+//! Export and import of all app data, for moving to a new machine
+
+use crate::database::{attachment_files, remove_attachment_files, write_settings, Conversation, Database, DbError, Message, Settings, Task, TaskMessage};
+use crate::mcp::storage::write_mcp_server;
+use crate::mcp::MCPServerConfig;
+use rusqlite::{params, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Format version written to exports; bump when a change can't be read by older builds
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Everything `export_all` writes. Every collection defaults to empty so
+/// exports from older builds, which may lack newer sections, still load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataExport {
+    pub version: u32,
+    pub exported_at: i64,
+    #[serde(default)]
+    pub settings: Option<Settings>,
+    #[serde(default)]
+    pub conversations: Vec<Conversation>,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub task_messages: Vec<TaskMessage>,
+    #[serde(default)]
+    pub mcp_servers: Vec<MCPServerConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Wipe existing data, then import everything
+    Replace,
+    /// Keep existing data and skip records whose ID already exists
+    Merge,
+}
+
+impl ImportMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "replace" => Ok(ImportMode::Replace),
+            "merge" => Ok(ImportMode::Merge),
+            other => Err(format!(
+                "Invalid merge mode '{}': expected \"replace\" or \"merge\"",
+                other
+            )),
+        }
+    }
+}
+
+/// Records written by an import; skipped records already existed (merge mode only)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub conversations: u32,
+    pub messages: u32,
+    pub tasks: u32,
+    pub task_messages: u32,
+    pub mcp_servers: u32,
+    pub settings: bool,
+    pub skipped: u32,
+}
+
+impl Database {
+    /// Snapshot of all app data, including conversations in the trash
+    pub fn export_all(&self) -> Result<DataExport, DbError> {
+        let mut conversations = self.list_conversations()?;
+        conversations.extend(self.list_deleted_conversations()?);
+        let mut messages = Vec::new();
+        for conversation in &conversations {
+            messages.extend(self.get_messages(&conversation.id)?);
+        }
+
+        let mut tasks = self.list_tasks()?;
+        tasks.extend(self.list_archived_tasks()?);
+        let mut task_messages = Vec::new();
+        for task in &tasks {
+            task_messages.extend(self.get_task_messages(&task.id)?);
+        }
+
+        Ok(DataExport {
+            version: EXPORT_VERSION,
+            exported_at: chrono::Utc::now().timestamp_millis(),
+            settings: Some(self.get_settings()?),
+            conversations,
+            messages,
+            tasks,
+            task_messages,
+            mcp_servers: self.get_mcp_servers()?,
+        })
+    }
+
+    /// Import an export in one transaction; any failure rolls the whole import back
+    pub fn import_all(&self, data: &DataExport, mode: ImportMode) -> Result<ImportSummary, DbError> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+
+        let mut removed_files = Vec::new();
+        if mode == ImportMode::Replace {
+            removed_files = attachment_files(&tx, None)?;
+            // Messages, task messages, attachments and agent events go with their parents via cascade
+            tx.execute_batch(
+                "DELETE FROM conversations;
+                 DELETE FROM tasks;
+                 DELETE FROM settings;
+                 DELETE FROM mcp_servers;",
+            )?;
+        }
+
+        let summary = import_records(&tx, data, mode)?;
+        tx.commit()?;
+        remove_attachment_files(&removed_files);
+        Ok(summary)
+    }
+}
+
+fn import_records(tx: &Transaction, data: &DataExport, mode: ImportMode) -> Result<ImportSummary, DbError> {
+    // In replace mode the tables are empty, so `OR IGNORE` only skips duplicates within the export
+    let mut skipped = 0;
+    let mut count = |inserted: usize, total: &mut u32| {
+        if inserted > 0 {
+            *total += 1;
+        } else {
+            skipped += 1;
+        }
+    };
+
+    let mut conversations = 0;
+    for c in &data.conversations {
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO conversations (id, title, created_at, updated_at, deleted_at, project_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![c.id, c.title, c.created_at, c.updated_at, c.deleted_at, c.project_path],
+        )?;
+        count(inserted, &mut conversations);
+    }
+
+    let mut messages = 0;
+    for m in &data.messages {
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO messages (id, conversation_id, role, content, timestamp, message_type)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![m.id, m.conversation_id, m.role, m.content, m.timestamp, m.message_type],
+        )?;
+        count(inserted, &mut messages);
+    }
+
+    let mut tasks = 0;
+    for t in &data.tasks {
+        let plan_json = t.plan.as_ref().and_then(|plan| serde_json::to_string(plan).ok());
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO tasks
+             (id, title, description, status, plan, current_step, project_path, created_at, updated_at, archived, template_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                t.id,
+                t.title,
+                t.description,
+                t.status,
+                plan_json,
+                t.current_step,
+                t.project_path,
+                t.created_at,
+                t.updated_at,
+                t.archived,
+                t.template_id,
+            ],
+        )?;
+        count(inserted, &mut tasks);
+    }
+
+    let mut task_messages = 0;
+    for m in &data.task_messages {
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO task_messages (id, task_id, role, content, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![m.id, m.task_id, m.role, m.content, m.timestamp],
+        )?;
+        count(inserted, &mut task_messages);
+    }
+
+    let mut mcp_servers = 0;
+    for server in &data.mcp_servers {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM mcp_servers WHERE id = ?1)",
+            [&server.id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            count(0, &mut mcp_servers);
+        } else {
+            write_mcp_server(tx, server)?;
+            count(1, &mut mcp_servers);
+        }
+    }
+
+    // Merging keeps this machine's settings; replacing takes the exported ones
+    let import_settings = mode == ImportMode::Replace && data.settings.is_some();
+    if let Some(settings) = data.settings.as_ref().filter(|_| import_settings) {
+        write_settings(tx, settings)?;
+    }
+
+    Ok(ImportSummary {
+        conversations,
+        messages,
+        tasks,
+        task_messages,
+        mcp_servers,
+        settings: import_settings,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::PlanStep;
+    use std::path::PathBuf;
+
+    fn temp_db() -> (Database, PathBuf) {
+        let (db, path) = crate::test_support::temp_db();
+        db.create_mcp_tables().unwrap();
+        (db, path)
+    }
+
+    fn seed(db: &Database) {
+        db.save_settings(&Settings {
+            api_key: "sk-test".to_string(),
+            model: "gpt-4o".to_string(),
+            provider: "openai".to_string(),
+            base_url: "https://api.openai.com".to_string(),
+            ..Settings::default()
+        })
+        .unwrap();
+        db.create_conversation("c1", "Chat", None).unwrap();
+        db.add_message("m1", "c1", "user", "hello").unwrap();
+        db.add_message("m2", "c1", "assistant", "hi there").unwrap();
+        db.create_conversation("c2", "Trashed", None).unwrap();
+        db.add_message("m3", "c2", "user", "old").unwrap();
+        db.delete_conversation("c2").unwrap();
+        db.create_task("t1", "Report", "Write the report", Some("/tmp/project")).unwrap();
+        db.update_task_plan(
+            "t1",
+            &[PlanStep { step: 1, description: "Outline".to_string(), status: "pending".to_string() }],
+        )
+        .unwrap();
+        db.add_task_message("tm1", "t1", "user", "start").unwrap();
+        db.create_task("t2", "Old", "Archived work", None).unwrap();
+        db.archive_task("t2").unwrap();
+        db.save_mcp_server(
+            &MCPServerConfig::new("srv".to_string(), "Server".to_string(), "http://localhost:9000".to_string())
+                .with_enabled(true),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_export_import_round_trip_replace() {
+        let (source, source_path) = temp_db();
+        seed(&source);
+        let json = serde_json::to_string(&source.export_all().unwrap()).unwrap();
+
+        let (target, target_path) = temp_db();
+        target.create_conversation("stale", "Wiped on replace", None).unwrap();
+
+        let data: DataExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(data.version, EXPORT_VERSION);
+        let summary = target.import_all(&data, ImportMode::Replace).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                conversations: 2,
+                messages: 3,
+                tasks: 2,
+                task_messages: 1,
+                mcp_servers: 1,
+                settings: true,
+                skipped: 0,
+            }
+        );
+
+        let conversations = target.list_conversations().unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].id, "c1");
+        let messages: Vec<String> = target.get_messages("c1").unwrap().into_iter().map(|m| m.content).collect();
+        assert_eq!(messages, vec!["hello".to_string(), "hi there".to_string()]);
+        let trashed = target.list_deleted_conversations().unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].id, "c2");
+        assert!(trashed[0].deleted_at.is_some());
+        assert_eq!(target.get_messages("c2").unwrap()[0].content, "old");
+
+        let task = target.get_task("t1").unwrap().unwrap();
+        assert_eq!(task.project_path.as_deref(), Some("/tmp/project"));
+        assert_eq!(task.plan.unwrap()[0].description, "Outline");
+        assert!(target.get_task("t2").unwrap().unwrap().archived);
+        assert_eq!(target.get_task_messages("t1").unwrap()[0].content, "start");
+
+        let settings = target.get_settings().unwrap();
+        assert_eq!(settings.model, "gpt-4o");
+        assert_eq!(settings.api_key, "sk-test");
+        let servers = target.get_mcp_servers().unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].server_url, "http://localhost:9000");
+
+        // Merging the same export again skips every record
+        let summary = target.import_all(&data, ImportMode::Merge).unwrap();
+        assert_eq!(summary.skipped, 9);
+        assert_eq!(summary.messages, 0);
+
+        drop(source);
+        drop(target);
+        let _ = std::fs::remove_file(&source_path);
+        let _ = std::fs::remove_file(&target_path);
+    }
+
+    #[test]
+    fn test_failed_import_rolls_back() {
+        let (db, path) = temp_db();
+        db.create_conversation("keep", "Existing", None).unwrap();
+
+        // The message points at a conversation that doesn't exist
+        let data: DataExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "exported_at": 0,
+            "messages": [{
+                "id": "orphan", "conversation_id": "missing", "role": "user", "content": "x", "timestamp": 0
+            }]
+        }))
+        .unwrap();
+
+        assert!(db.import_all(&data, ImportMode::Replace).is_err());
+        assert_eq!(db.list_conversations().unwrap()[0].id, "keep");
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+}
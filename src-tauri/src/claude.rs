@@ -20,14 +20,67 @@ pub struct Message {
     pub content: String,
 }
 
+/// A message whose content is a list of Anthropic content blocks
+#[derive(Debug, Clone, Serialize)]
+pub struct RichMessage {
+    pub role: String,
+    pub content: Vec<MessageBlock>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MessageBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source_type: String,
+        media_type: String,
+        data: String,
+    },
+}
+
+impl Serialize for MessageBlock {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match self {
+            MessageBlock::Text { text } => serde_json::json!({ "type": "text", "text": text }),
+            MessageBlock::Image {
+                source_type,
+                media_type,
+                data,
+            } => serde_json::json!({
+                "type": "image",
+                "source": {
+                    "type": source_type,
+                    "media_type": media_type,
+                    "data": data,
+                }
+            }),
+        };
+        value.serialize(serializer)
+    }
+}
+
+impl From<Message> for RichMessage {
+    fn from(message: Message) -> Self {
+        Self {
+            role: message.role,
+            content: vec![MessageBlock::Text {
+                text: message.content,
+            }],
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
-struct ClaudeRequest {
+struct ClaudeRequest<M> {
     model: String,
     max_tokens: u32,
-    messages: Vec<Message>,
+    messages: Vec<M>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,10 +111,12 @@ struct Delta {
     text: Option<String>,
 }
 
+#[derive(Clone)]
 pub struct ClaudeClient {
     client: Client,
     api_key: String,
     base_url: String,
+    system: Option<String>,
 }
 
 impl ClaudeClient {
@@ -70,9 +125,27 @@ impl ClaudeClient {
             client: Client::new(),
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://api.anthropic.com".to_string()),
+            system: None,
         }
     }
 
+    /// Send requests with `client`, e.g. one routed through the configured proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send `system` as the system prompt of every request
+    pub fn with_system_prompt(mut self, system: Option<String>) -> Self {
+        self.system = system;
+        self
+    }
+
+    /// Whether this client was built from the given credentials
+    pub fn matches(&self, api_key: &str, base_url: &str) -> bool {
+        self.api_key == api_key && self.base_url == base_url
+    }
+
     pub async fn send_message(
         &self,
         messages: Vec<Message>,
@@ -86,6 +159,7 @@ impl ClaudeClient {
             messages,
             stream: false,
             temperature,
+            system: self.system.clone(),
         };
 
         let response = self
@@ -129,15 +203,43 @@ impl ClaudeClient {
             messages,
             stream: true,
             temperature,
+            system: self.system.clone(),
+        };
+        self.stream_request(&request, tx).await
+    }
+
+    /// Stream a reply to messages that may carry image blocks
+    pub async fn send_rich_message_stream(
+        &self,
+        messages: Vec<RichMessage>,
+        model: &str,
+        max_tokens: u32,
+        temperature: Option<f32>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<String, ClaudeError> {
+        let request = ClaudeRequest {
+            model: model.to_string(),
+            max_tokens,
+            messages,
+            stream: true,
+            temperature,
+            system: self.system.clone(),
         };
+        self.stream_request(&request, tx).await
+    }
 
+    async fn stream_request<M: Serialize>(
+        &self,
+        request: &ClaudeRequest<M>,
+        tx: mpsc::Sender<String>,
+    ) -> Result<String, ClaudeError> {
         let response = self
             .client
             .post(format!("{}/v1/messages", self.base_url))
             .header("Content-Type", "application/json")
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
-            .json(&request)
+            .json(request)
             .send()
             .await?;
 
@@ -182,3 +284,65 @@ impl ClaudeClient {
         Ok(full_text)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_server;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_rich_message_stream_sends_image_block() {
+        let captured: Arc<Mutex<Option<serde_json::Value>>> = Arc::new(Mutex::new(None));
+        let captured_clone = captured.clone();
+        let url = spawn_mock_server(move |body: serde_json::Value| {
+            *captured_clone.lock().unwrap() = Some(body);
+            (
+                "text/event-stream",
+                [
+                    r#"data: {"type":"content_block_delta","delta":{"type":"text_delta","text":"A "}}"#,
+                    r#"data: {"type":"content_block_delta","delta":{"type":"text_delta","text":"cat"}}"#,
+                    r#"data: {"type":"message_stop"}"#,
+                ]
+                .join("\n\n")
+                    + "\n\n",
+            )
+        })
+        .await;
+
+        let client = ClaudeClient::new("test-key".to_string(), Some(url));
+        let mut history: Vec<RichMessage> = vec![Message {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+        }
+        .into()];
+        history.push(RichMessage {
+            role: "user".to_string(),
+            content: vec![
+                MessageBlock::Text {
+                    text: "What is in this picture?".to_string(),
+                },
+                MessageBlock::Image {
+                    source_type: "base64".to_string(),
+                    media_type: "image/png".to_string(),
+                    data: "iVBORw0KGgo=".to_string(),
+                },
+            ],
+        });
+
+        let (tx, _rx) = mpsc::channel(10);
+        let reply = client
+            .send_rich_message_stream(history, "claude-sonnet-4-5", 256, None, tx)
+            .await
+            .unwrap();
+        assert_eq!(reply, "A cat");
+
+        let body = captured.lock().unwrap().clone().unwrap();
+        assert_eq!(body["messages"][0]["content"][0]["type"], "text");
+        let image = &body["messages"][1]["content"][1];
+        assert_eq!(image["type"], "image");
+        assert_eq!(image["source"]["type"], "base64");
+        assert_eq!(image["source"]["media_type"], "image/png");
+        assert_eq!(image["source"]["data"], "iVBORw0KGgo=");
+    }
+}
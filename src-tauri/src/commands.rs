@@ -1,14 +1,26 @@
-use crate::agent::{AgentConfig, AgentContent, AgentEvent, AgentLoop, AgentMessage};
-use crate::agent::{ContentBlock, ImageSource};
-use crate::claude::{ClaudeClient, Message as ClaudeMessage};
-use crate::database::{Conversation, Database, Message, PlanStep, Settings, Task, TaskMessage};
-use crate::mcp::{MCPManager, MCPServerConfig, MCPServerStatus, MCPToolCall, MCPToolResult};
+use crate::agent::{tool_risk, AgentConfig, AgentContent, AgentEvent, AgentLoop, AgentMessage, ToolMetrics, ToolStats};
+use crate::agent::{
+    build_default_system_prompt, render_system_prompt, skills_system_prompt, ContentBlock, ImageSource, SystemPromptVars,
+    PLANNING_PROMPT,
+};
+use crate::backup::{DataExport, ImportMode, ImportSummary, EXPORT_VERSION};
+use crate::claude::{ClaudeClient, Message as ClaudeMessage, MessageBlock, RichMessage};
+use crate::database::{
+    AgentSessionStats, BulkDeleteResult, CheckpointStats, Conversation, ConversationPage, ConversationStats, Database, GlobalSearchResult, Message, MessageEdit, PlanStep, Settings, StoredAgentEvent, Task,
+    TaskMessage, TaskTemplate, TaskWithStats, DEFAULT_MAX_AGENT_TURNS, DEFAULT_MAX_CONCURRENT_AGENTS,
+    INTERMEDIATE_MESSAGE_TYPE,
+};
+use crate::mcp::{MCPConnectionEvent, MCPManager, MCPPrompt, MCPServerConfig, MCPServerStatus, MCPToolCall, MCPToolResult};
+use crate::app_errors::{AppError, ErrorReporter};
+use crate::run_queue::RunQueue;
 use crate::skills::{SkillMetadata, get_available_skills};
+use crate::startup::StartupEvent;
+use crate::tools::ToolRegistry;
 use base64::{Engine as _, engine::general_purpose};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tauri::{command, Emitter, State, Window};
 use tokio::sync::Mutex;
@@ -17,6 +29,43 @@ pub struct AppState {
     pub db: Arc<Database>,
     pub claude_client: Mutex<Option<ClaudeClient>>,
     pub mcp_manager: Arc<MCPManager>,
+    /// Replaced wholesale when custom tools are reloaded; running agents keep their copy
+    pub tool_registry: std::sync::RwLock<Arc<ToolRegistry>>,
+    /// Directory scanned for `*.tool.json` custom tool descriptors
+    pub custom_tools_dir: Option<PathBuf>,
+    pub tool_metrics: Arc<std::sync::Mutex<ToolMetrics>>,
+    /// Step timings from the last app startup
+    pub startup_timing: std::sync::Mutex<Vec<StartupEvent>>,
+    /// Discovered model lists keyed by provider and base URL, with when they were fetched
+    pub model_cache: std::sync::Mutex<std::collections::HashMap<String, (Vec<String>, std::time::Instant)>>,
+    /// Errors from background work; `error_tx` feeds the `app-error` event
+    pub errors: ErrorReporter,
+    /// Caps concurrent `run_agent` and `run_task_agent` calls
+    pub run_queue: RunQueue,
+}
+
+impl AppState {
+    /// The Claude client for the saved settings, (re)built when it is missing or was made
+    /// from an older API key or base URL. `None` unless Anthropic is configured with a key.
+    pub async fn get_or_init_claude_client(&self) -> Result<Option<ClaudeClient>, crate::database::DbError> {
+        let settings = self.db.get_settings()?;
+        let mut client = self.claude_client.lock().await;
+        let up_to_date = client
+            .as_ref()
+            .is_some_and(|c| c.matches(&settings.api_key, &settings.base_url));
+        if !up_to_date {
+            *client = claude_client_for(&settings);
+        }
+        Ok(client.clone())
+    }
+
+    /// The current tool registry
+    pub fn tool_registry(&self) -> Arc<ToolRegistry> {
+        match self.tool_registry.read() {
+            Ok(registry) => registry.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -24,6 +73,17 @@ pub struct CommandError {
     message: String,
 }
 
+/// Client for Anthropic settings that have an API key
+pub fn claude_client_for(settings: &Settings) -> Option<ClaudeClient> {
+    if settings.api_key.is_empty() || settings.get_provider() != "anthropic" {
+        return None;
+    }
+    Some(
+        ClaudeClient::new(settings.api_key.clone(), Some(settings.base_url.clone()))
+            .with_http_client(crate::llm_client::http_client_for(settings, "anthropic")),
+    )
+}
+
 fn default_workspace_root() -> Option<String> {
     crate::tools::path_utils::default_local_workspace_root()
         .ok()
@@ -90,6 +150,8 @@ pub async fn save_settings(
     state: State<'_, Arc<AppState>>,
     settings: Settings,
 ) -> Result<(), CommandError> {
+    settings.validate().map_err(|message| CommandError { message })?;
+
     println!("[save_settings] model: {}", settings.model);
     println!("[save_settings] base_url: {}", settings.base_url);
     println!("[save_settings] api_key length: {}", settings.api_key.len());
@@ -100,24 +162,64 @@ pub async fn save_settings(
             &settings.api_key[settings.api_key.len()-10..]);
     }
 
+    validate_proxy_settings(&settings)?;
+
     state.db.save_settings(&settings)?;
 
     // Update Claude client with new settings
-    let mut client = state.claude_client.lock().await;
-    if !settings.api_key.is_empty() {
-        *client = Some(ClaudeClient::new(
-            settings.api_key.clone(),
-            Some(settings.base_url.clone()),
-        ));
-    } else {
-        *client = None;
+    *state.claude_client.lock().await = claude_client_for(&settings);
+    state
+        .run_queue
+        .set_limit(settings.max_concurrent_agents.unwrap_or(DEFAULT_MAX_CONCURRENT_AGENTS));
+
+    Ok(())
+}
+
+/// Reject a proxy URL that requests could not be routed through
+fn validate_proxy_settings(settings: &Settings) -> Result<(), CommandError> {
+    let Some(proxy_url) = settings.proxy_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) else {
+        return Ok(());
+    };
+
+    let invalid = |reason: String| CommandError {
+        message: format!("Invalid proxy URL '{}': {}", proxy_url, reason),
+    };
+
+    let parsed = reqwest::Url::parse(proxy_url).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid("expected an http:// or https:// URL".to_string()));
     }
 
+    crate::llm_client::build_http_client(Some(proxy_url), settings.proxy_no_proxy.as_deref())
+        .map_err(|e| invalid(e.to_string()))?;
+
     Ok(())
 }
 
+/// Open a TCP connection to the configured proxy to check it is reachable
+async fn check_proxy_reachable(proxy_url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(proxy_url.trim()).map_err(|e| e.to_string())?;
+    let host = parsed.host_str().ok_or("missing proxy host")?.to_string();
+    let port = parsed.port_or_known_default().unwrap_or(8080);
+
+    match tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        tokio::net::TcpStream::connect((host.as_str(), port)),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("connection timed out".to_string()),
+    }
+}
+
 #[command]
 pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String, CommandError> {
+    check_saved_connection(&state).await
+}
+
+async fn check_saved_connection(state: &AppState) -> Result<String, CommandError> {
     use crate::llm_client::{LLMClient, Message};
 
     let settings = state.db.get_settings()?;
@@ -150,13 +252,29 @@ pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String,
             Err(e) => Ok(format!("Error: {}", e)),
         }
     } else {
+        // Cloud requests go through the proxy, so make sure it can be reached first
+        if let Some(proxy_url) = settings.proxy_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            if let Err(e) = check_proxy_reachable(proxy_url).await {
+                return Ok(format!(
+                    "Error: Cannot reach proxy {} ({}). Check the proxy address in settings or clear it to connect directly",
+                    proxy_url, e
+                ));
+            }
+        }
+
         // Cloud service - check provider type
         let provider = settings.get_provider();
 
         match provider.as_str() {
             "anthropic" => {
-                // Anthropic - use ClaudeClient
-                let client = ClaudeClient::new(settings.api_key, Some(settings.base_url));
+                // Anthropic - use the shared ClaudeClient, which may not exist yet right after startup
+                let client = state
+                    .get_or_init_claude_client()
+                    .await?
+                    .unwrap_or_else(|| {
+                        ClaudeClient::new(settings.api_key.clone(), Some(settings.base_url.clone()))
+                            .with_http_client(crate::llm_client::http_client_for(&settings, "anthropic"))
+                    });
                 let messages = vec![ClaudeMessage {
                     role: "user".to_string(),
                     content: "Hi".to_string(),
@@ -169,14 +287,7 @@ pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String,
             }
             "openai" => {
                 // OpenAI - test with actual API request using LLMClient
-                let llm_client = LLMClient::new_with_openai_headers(
-                    settings.api_key.clone(),
-                    Some(settings.base_url.clone()),
-                    Some("openai"),
-                    Some(&settings.model),
-                    settings.openai_organization.clone(),
-                    settings.openai_project.clone(),
-                );
+                let llm_client = LLMClient::from_settings_for_provider(&settings, "openai");
 
                 let test_messages = vec![Message {
                     role: "user".to_string(),
@@ -189,14 +300,23 @@ pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String,
                     Err(e) => Ok(format!("Error: {}", e)),
                 }
             }
+            "perplexity" => {
+                // Perplexity - OpenAI-compatible chat completions (no models endpoint to fall back on)
+                let llm_client = LLMClient::from_settings_for_provider(&settings, "perplexity");
+
+                let test_messages = vec![Message {
+                    role: "user".to_string(),
+                    content: "Hi".to_string(),
+                }];
+
+                match llm_client.send_message(test_messages, &settings.model, 10, None).await {
+                    Ok(_) => Ok("success".to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            }
             "google" => {
                 // Google Gemini - test with actual API request
-                let llm_client = LLMClient::new(
-                    settings.api_key.clone(),
-                    Some(settings.base_url.clone()),
-                    Some("google"),
-                    Some(&settings.model),
-                );
+                let llm_client = LLMClient::from_settings_for_provider(&settings, "google");
 
                 let test_messages = vec![Message {
                     role: "user".to_string(),
@@ -210,11 +330,15 @@ pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String,
             }
             _ => {
                 // Other cloud services - try sending a test message
-                let llm_client = LLMClient::new(
+                let llm_client = LLMClient::new_with_openai_headers(
                     settings.api_key.clone(),
                     Some(settings.base_url.clone()),
                     None,
                     Some(&settings.model),
+                    None,
+                    None,
+                    settings.proxy_url.clone(),
+                    settings.proxy_no_proxy.clone(),
                 );
 
                 let test_messages = vec![Message {
@@ -240,8 +364,14 @@ pub async fn test_connection(state: State<'_, Arc<AppState>>) -> Result<String,
 }
 
 #[command]
-pub async fn check_local_service_status(base_url: String) -> LocalServiceStatus {
-    let client = reqwest::Client::new();
+pub async fn check_local_service_status(
+    state: State<'_, Arc<AppState>>,
+    base_url: String,
+) -> Result<LocalServiceStatus, CommandError> {
+    let client = match state.db.get_settings() {
+        Ok(settings) => crate::llm_client::http_client_for(&settings, "ollama"),
+        Err(_) => reqwest::Client::new(),
+    };
     let base = base_url.trim_end_matches('/');
     let normalized = base.strip_suffix("/v1").unwrap_or(base);
     let url = format!("{}/api/tags", normalized);
@@ -254,30 +384,30 @@ pub async fn check_local_service_status(base_url: String) -> LocalServiceStatus
     {
         Ok(resp) => resp,
         Err(e) => {
-            return LocalServiceStatus {
+            return Ok(LocalServiceStatus {
                 running: false,
                 models: vec![],
                 error: Some(e.to_string()),
-            }
+            })
         }
     };
 
     if !response.status().is_success() {
-        return LocalServiceStatus {
+        return Ok(LocalServiceStatus {
             running: false,
             models: vec![],
             error: Some(format!("HTTP {}", response.status())),
-        };
+        });
     }
 
     let data: serde_json::Value = match response.json().await {
         Ok(json) => json,
         Err(e) => {
-            return LocalServiceStatus {
+            return Ok(LocalServiceStatus {
                 running: false,
                 models: vec![],
                 error: Some(e.to_string()),
-            }
+            })
         }
     };
 
@@ -295,11 +425,209 @@ pub async fn check_local_service_status(base_url: String) -> LocalServiceStatus
         })
         .unwrap_or_default();
 
-    LocalServiceStatus {
+    Ok(LocalServiceStatus {
         running: true,
         models,
         error: None,
+    })
+}
+
+/// How long a discovered model list is reused before asking the provider again
+const MODEL_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Models offered by a provider, from its models endpoint (a built-in list for Anthropic)
+#[command]
+pub async fn discover_provider_models(
+    state: State<'_, Arc<AppState>>,
+    provider_id: String,
+    base_url: Option<String>,
+) -> Result<Vec<String>, CommandError> {
+    let settings = state.db.get_settings()?;
+    let cache_key = model_cache_key(
+        &provider_id,
+        base_url.as_deref(),
+        &discovery_api_key(&settings, &provider_id),
+    );
+    if let Some(models) = cached_models(&state.model_cache, &cache_key, MODEL_CACHE_TTL) {
+        return Ok(models);
+    }
+
+    let models = discover_models_for(&settings, &provider_id, base_url).await?;
+    if let Ok(mut cache) = state.model_cache.lock() {
+        cache.insert(cache_key, (models.clone(), std::time::Instant::now()));
+    }
+    Ok(models)
+}
+
+/// Keyed on a hash of the API key too, so changing the key fetches the list again
+fn model_cache_key(provider_id: &str, base_url: Option<&str>, api_key: &str) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    api_key.hash(&mut hasher);
+    format!("{}|{}|{:016x}", provider_id, base_url.unwrap_or_default(), hasher.finish())
+}
+
+fn cached_models(
+    cache: &std::sync::Mutex<std::collections::HashMap<String, (Vec<String>, std::time::Instant)>>,
+    key: &str,
+    ttl: std::time::Duration,
+) -> Option<Vec<String>> {
+    let cache = cache.lock().ok()?;
+    let (models, fetched_at) = cache.get(key)?;
+    (fetched_at.elapsed() < ttl).then(|| models.clone())
+}
+
+/// The provider's own key if set, otherwise `api_key` when it is the active provider
+fn discovery_api_key(settings: &Settings, provider_id: &str) -> String {
+    settings
+        .provider_keys
+        .get(provider_id)
+        .filter(|k| !k.is_empty())
+        .cloned()
+        .or_else(|| (settings.get_provider() == provider_id).then(|| settings.api_key.clone()))
+        .unwrap_or_default()
+}
+
+async fn discover_models_for(
+    settings: &Settings,
+    provider_id: &str,
+    base_url: Option<String>,
+) -> Result<Vec<String>, CommandError> {
+    use crate::llm_client::{AuthType, LLMClient, ProviderConfig};
+
+    let api_key = discovery_api_key(settings, provider_id);
+    // Local services are never reached through the proxy
+    let (proxy_url, proxy_no_proxy) = if ProviderConfig::from_preset(provider_id).auth_type != AuthType::None {
+        (settings.proxy_url.clone(), settings.proxy_no_proxy.clone())
+    } else {
+        (None, None)
+    };
+    let (organization, project) = crate::llm_client::openai_headers_for(settings, provider_id);
+
+    let client = LLMClient::new_with_openai_headers(
+        api_key,
+        base_url,
+        Some(provider_id),
+        None,
+        organization,
+        project,
+        proxy_url,
+        proxy_no_proxy,
+    );
+    client.discover_models().await.map_err(|e| CommandError {
+        message: format!("Failed to list {} models: {}", provider_id, e),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderHealthStatus {
+    pub provider_id: String,
+    pub name: String,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+const PROVIDER_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Check every provider preset at once, in preset order
+#[command]
+pub async fn check_all_provider_connections(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<ProviderHealthStatus>, CommandError> {
+    let settings = state.db.get_settings()?;
+    Ok(check_provider_connections(provider_health_targets(&settings), &settings, PROVIDER_CHECK_TIMEOUT).await)
+}
+
+/// Each preset with the API key to check it with. The active provider uses the
+/// configured base URL; providers without a key of their own fall back to `api_key`
+/// only when they are the active provider.
+fn provider_health_targets(settings: &Settings) -> Vec<(crate::llm_client::ProviderConfig, Option<String>)> {
+    use crate::llm_client::{provider_preset_ids, ProviderConfig};
+
+    let active = settings.get_provider();
+    provider_preset_ids()
+        .into_iter()
+        .map(|id| {
+            let mut config = ProviderConfig::from_preset(id);
+            let mut api_key = settings.provider_keys.get(id).filter(|k| !k.is_empty()).cloned();
+            if id == active {
+                if !settings.base_url.is_empty() {
+                    config.base_url = settings.base_url.clone();
+                }
+                if api_key.is_none() && !settings.api_key.is_empty() {
+                    api_key = Some(settings.api_key.clone());
+                }
+            }
+            (config, api_key)
+        })
+        .collect()
+}
+
+async fn check_provider_connections(
+    targets: Vec<(crate::llm_client::ProviderConfig, Option<String>)>,
+    settings: &Settings,
+    timeout: std::time::Duration,
+) -> Vec<ProviderHealthStatus> {
+    use crate::llm_client::{AuthType, LLMClient};
+
+    let mut checks = tokio::task::JoinSet::new();
+    for (index, (config, api_key)) in targets.into_iter().enumerate() {
+        let needs_key = config.auth_type != AuthType::None;
+        // Local services are never reached through the proxy
+        let (proxy_url, proxy_no_proxy) = if needs_key {
+            (settings.proxy_url.clone(), settings.proxy_no_proxy.clone())
+        } else {
+            (None, None)
+        };
+
+        checks.spawn(async move {
+            let mut status = ProviderHealthStatus {
+                provider_id: config.id.clone(),
+                name: config.name.clone(),
+                reachable: false,
+                latency_ms: None,
+                error: None,
+            };
+            if needs_key && api_key.is_none() {
+                status.error = Some("No API key configured".to_string());
+                return (index, status);
+            }
+
+            let client = LLMClient::new_with_openai_headers(
+                api_key.unwrap_or_default(),
+                Some(config.base_url.clone()),
+                Some(&config.id),
+                None,
+                None,
+                None,
+                proxy_url,
+                proxy_no_proxy,
+            );
+            let started = std::time::Instant::now();
+            match tokio::time::timeout(timeout, client.check_connection()).await {
+                Ok(Ok(true)) => {
+                    status.reachable = true;
+                    status.latency_ms = Some(started.elapsed().as_millis() as u64);
+                }
+                Ok(Ok(false)) => status.error = Some(format!("Cannot connect to {}", config.base_url)),
+                Ok(Err(e)) => status.error = Some(e.to_string()),
+                Err(_) => status.error = Some(format!("Timed out after {} seconds", timeout.as_secs())),
+            }
+            (index, status)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = checks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("[provider_health] Check task failed: {}", e),
+        }
     }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, status)| status).collect()
 }
 
 // Conversation commands
@@ -310,13 +638,39 @@ pub fn list_conversations(
     state.db.list_conversations().map_err(Into::into)
 }
 
+#[command]
+pub fn list_conversations_paginated(
+    state: State<'_, Arc<AppState>>,
+    limit: u32,
+    before_id: Option<String>,
+) -> Result<ConversationPage, CommandError> {
+    state
+        .db
+        .list_conversations_paginated(limit, before_id.as_deref())
+        .map_err(Into::into)
+}
+
 #[command]
 pub fn create_conversation(
     state: State<'_, Arc<AppState>>,
     title: String,
+    project_path: Option<String>,
 ) -> Result<Conversation, CommandError> {
     let id = uuid::Uuid::new_v4().to_string();
-    state.db.create_conversation(&id, &title).map_err(Into::into)
+    let project_path = normalize_project_path_csv(project_path);
+    state
+        .db
+        .create_conversation(&id, &title, project_path.as_deref())
+        .map_err(Into::into)
+}
+
+#[command]
+pub fn list_conversations_for_project(
+    state: State<'_, Arc<AppState>>,
+    project_path: String,
+) -> Result<Vec<Conversation>, CommandError> {
+    let project_path = normalize_project_path_csv(Some(project_path)).unwrap_or_default();
+    state.db.list_conversations_for_project(&project_path).map_err(Into::into)
 }
 
 #[command]
@@ -325,7 +679,7 @@ pub fn update_conversation_title(
     id: String,
     title: String,
 ) -> Result<(), CommandError> {
-    state.db.update_conversation_title(&id, &title).map_err(Into::into)
+    state.db.set_custom_conversation_title(&id, &title).map_err(Into::into)
 }
 
 #[command]
@@ -336,13 +690,76 @@ pub fn delete_conversation(
     state.db.delete_conversation(&id).map_err(Into::into)
 }
 
+#[command]
+pub fn list_deleted_conversations(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<Conversation>, CommandError> {
+    state.db.list_deleted_conversations().map_err(Into::into)
+}
+
+#[command]
+pub fn restore_conversation(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), CommandError> {
+    state.db.restore_conversation(&id).map_err(Into::into)
+}
+
+#[command]
+pub fn purge_conversation(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), CommandError> {
+    if !state.db.purge_conversation(&id)? {
+        return Err(CommandError {
+            message: format!("Conversation {} is not in the trash", id),
+        });
+    }
+    Ok(())
+}
+
+#[command]
+pub fn purge_conversations_older_than(
+    state: State<'_, Arc<AppState>>,
+    days: u32,
+) -> Result<u32, CommandError> {
+    state.db.purge_conversations_older_than(days).map_err(Into::into)
+}
+
+#[command]
+pub fn delete_conversations(
+    state: State<'_, Arc<AppState>>,
+    ids: Vec<String>,
+    dry_run: bool,
+) -> Result<BulkDeleteResult, CommandError> {
+    state.db.delete_conversations(&ids, dry_run).map_err(Into::into)
+}
+
+#[command]
+pub fn delete_all_conversations(
+    state: State<'_, Arc<AppState>>,
+    dry_run: bool,
+) -> Result<BulkDeleteResult, CommandError> {
+    state.db.delete_all_conversations(dry_run).map_err(Into::into)
+}
+
 // Message commands
 #[command]
 pub fn get_messages(
     state: State<'_, Arc<AppState>>,
     conversation_id: String,
 ) -> Result<Vec<Message>, CommandError> {
-    state.db.get_messages(&conversation_id).map_err(Into::into)
+    // Tool call steps are only replayed to the model, not shown in the chat
+    let messages = state.db.get_messages(&conversation_id)?;
+    Ok(messages.into_iter().filter(|m| !m.is_intermediate()).collect())
+}
+
+#[command]
+pub fn get_conversation_stats(
+    state: State<'_, Arc<AppState>>,
+    conversation_id: String,
+) -> Result<ConversationStats, CommandError> {
+    state.db.get_conversation_stats(&conversation_id).map_err(Into::into)
 }
 
 #[command]
@@ -359,6 +776,56 @@ pub fn add_message(
         .map_err(Into::into)
 }
 
+const MAX_EDITED_MESSAGE_BYTES: usize = 1024 * 1024;
+
+fn validate_edited_content(content: &str) -> Result<(), CommandError> {
+    if content.trim().is_empty() {
+        return Err(CommandError {
+            message: "Message content must not be empty".to_string(),
+        });
+    }
+    if content.len() >= MAX_EDITED_MESSAGE_BYTES {
+        return Err(CommandError {
+            message: "Message content must be under 1 MB".to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[command]
+pub fn edit_message(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    content: String,
+) -> Result<Message, CommandError> {
+    validate_edited_content(&content)?;
+    state.db.update_message_content(&id, &content)?;
+    state.db.get_message(&id)?.ok_or_else(|| CommandError {
+        message: format!("Message '{}' not found", id),
+    })
+}
+
+#[command]
+pub fn edit_task_message(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    content: String,
+) -> Result<TaskMessage, CommandError> {
+    validate_edited_content(&content)?;
+    state.db.update_task_message_content(&id, &content)?;
+    state.db.get_task_message(&id)?.ok_or_else(|| CommandError {
+        message: format!("Task message '{}' not found", id),
+    })
+}
+
+#[command]
+pub fn get_message_edit_history(
+    state: State<'_, Arc<AppState>>,
+    message_id: String,
+) -> Result<Vec<MessageEdit>, CommandError> {
+    state.db.get_message_edit_history(&message_id).map_err(Into::into)
+}
+
 // Chat command with streaming
 #[derive(Clone, Serialize)]
 struct StreamPayload {
@@ -372,6 +839,7 @@ pub async fn send_chat_message(
     state: State<'_, Arc<AppState>>,
     conversation_id: String,
     content: String,
+    images: Option<Vec<ImageAttachmentInput>>,
 ) -> Result<String, CommandError> {
     use crate::llm_client::{LLMClient, Message as LLMMessage};
 
@@ -383,14 +851,21 @@ pub async fn send_chat_message(
         });
     }
 
+    let settings_for_title = settings.clone();
+
     // Add user message to database
     let user_msg_id = uuid::Uuid::new_v4().to_string();
     state
         .db
         .add_message(&user_msg_id, &conversation_id, "user", &content)?;
 
-    // Get conversation history
-    let db_messages = state.db.get_messages(&conversation_id)?;
+    // Get conversation history; plain chat has no use for tool call steps
+    let db_messages: Vec<Message> = state
+        .db
+        .get_messages(&conversation_id)?
+        .into_iter()
+        .filter(|m| !m.is_intermediate())
+        .collect();
 
     // Create channel for streaming
     let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(100);
@@ -403,6 +878,9 @@ pub async fn send_chat_message(
         }
     });
 
+    // Plain chat has no built-in prompt, so only a custom one from settings is sent
+    let system_prompt = Some(settings.apply_system_prompt("")).filter(|prompt| !prompt.is_empty());
+
     // Choose client based on provider
     let provider = settings.get_provider();
     let response = match provider.as_str() {
@@ -415,34 +893,50 @@ pub async fn send_chat_message(
                     content: m.content.clone(),
                 })
                 .collect();
-            let client = ClaudeClient::new(settings.api_key, Some(settings.base_url));
-            client
-                .send_message_stream(
-                    claude_messages,
-                    &settings.model,
-                    settings.max_tokens,
-                    Some(settings.temperature),
-                    tx,
-                )
-                .await?
+            let http_client = crate::llm_client::http_client_for(&settings, "anthropic");
+            let client = ClaudeClient::new(settings.api_key, Some(settings.base_url))
+                .with_http_client(http_client)
+                .with_system_prompt(system_prompt);
+            match images.as_deref() {
+                Some(images) if !images.is_empty() => {
+                    client
+                        .send_rich_message_stream(
+                            attach_images_to_last_message(claude_messages, images),
+                            &settings.model,
+                            settings.max_tokens,
+                            Some(settings.temperature),
+                            tx,
+                        )
+                        .await?
+                }
+                _ => {
+                    client
+                        .send_message_stream(
+                            claude_messages,
+                            &settings.model,
+                            settings.max_tokens,
+                            Some(settings.temperature),
+                            tx,
+                        )
+                        .await?
+                }
+            }
         }
         _ => {
             // Use LLMClient for OpenAI and other providers
-            let llm_messages: Vec<LLMMessage> = db_messages
-                .iter()
-                .map(|m| LLMMessage {
+            let mut llm_messages: Vec<LLMMessage> = system_prompt
+                .map(|prompt| LLMMessage {
+                    role: "system".to_string(),
+                    content: prompt,
+                })
+                .into_iter()
+                .chain(db_messages.iter().map(|m| LLMMessage {
                     role: m.role.clone(),
                     content: m.content.clone(),
-                })
+                }))
                 .collect();
-            let llm_client = LLMClient::new_with_openai_headers(
-                settings.api_key.clone(),
-                Some(settings.base_url.clone()),
-                Some(&provider),
-                Some(&settings.model),
-                settings.openai_organization.clone(),
-                settings.openai_project.clone(),
-            );
+            let inline_images = inline_images_for_llm(&mut llm_messages, images.as_deref().unwrap_or_default());
+            let llm_client = LLMClient::from_settings(&settings).with_images(inline_images);
             llm_client
                 .send_message_stream(
                     llm_messages,
@@ -475,41 +969,171 @@ pub async fn send_chat_message(
         .add_message(&assistant_msg_id, &conversation_id, "assistant", &response)?;
 
     // Update conversation title if this is the first message
-    if db_messages.len() == 1 {
-        let title = if content.len() > 30 {
-            format!("{}...", &content[..30])
-        } else {
-            content.clone()
-        };
-        state.db.update_conversation_title(&conversation_id, &title)?;
+    if db_messages.len() == 1 && set_first_message_title(&state.db, &conversation_id, &content)? {
+        // Replace the truncated title with a generated one in the background
+        spawn_auto_title(window, state.db.clone(), settings_for_title, conversation_id, content);
     }
 
     Ok(response)
 }
 
-// Chat event for tool-enabled chat
+const AUTO_TITLE_PROMPT: &str =
+    "Summarize this conversation in 5 words or fewer as a title. Reply only with the title, no punctuation.";
+
 #[derive(Debug, Clone, Serialize)]
-#[serde(tag = "type")]
-pub enum ChatEvent {
-    #[serde(rename = "text")]
-    Text { content: String },
-    #[serde(rename = "tool_start")]
-    ToolStart { tool: String, input: serde_json::Value },
-    #[serde(rename = "tool_end")]
-    ToolEnd { tool: String, result: String, success: bool },
-    #[serde(rename = "done")]
-    Done { final_text: String },
+struct ConversationTitlePayload {
+    conversation_id: String,
+    title: String,
 }
 
-#[derive(Debug, Clone)]
-struct ForcedToolPreview {
-    tool: String,
-    input: serde_json::Value,
-    result: String,
-    success: bool,
+/// Title a conversation after its first message, unless the user already named it.
+/// Returns whether the title was set.
+fn set_first_message_title(db: &Database, conversation_id: &str, content: &str) -> Result<bool, crate::database::DbError> {
+    if db.has_custom_title(conversation_id)? {
+        return Ok(false);
+    }
+
+    let title = if content.chars().count() > 30 {
+        format!("{}...", content.chars().take(30).collect::<String>())
+    } else {
+        content.to_string()
+    };
+    db.update_conversation_title(conversation_id, &title)?;
+    Ok(true)
 }
 
-#[derive(Debug, Clone)]
+/// Generate a conversation title without blocking the caller; emits `conversation-title` when done
+fn spawn_auto_title(
+    window: Window,
+    db: Arc<Database>,
+    settings: Settings,
+    conversation_id: String,
+    first_message: String,
+) {
+    tokio::spawn(async move {
+        if let Some(title) = auto_title_conversation(&db, &settings, &conversation_id, &first_message).await {
+            let _ = window.emit(
+                "conversation-title",
+                ConversationTitlePayload { conversation_id, title },
+            );
+        }
+    });
+}
+
+/// Ask the LLM for a short title and store it. Failures leave the existing title untouched.
+async fn auto_title_conversation(
+    db: &Database,
+    settings: &Settings,
+    conversation_id: &str,
+    first_message: &str,
+) -> Option<String> {
+    use crate::llm_client::{LLMClient, Message as LLMMessage};
+
+    let model = settings
+        .auto_title_model
+        .clone()
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| settings.model.clone());
+    let provider = settings.get_provider();
+    let (organization, project) = crate::llm_client::openai_headers_for(settings, &provider);
+
+    let llm_client = LLMClient::new_with_openai_headers(
+        settings.api_key.clone(),
+        Some(settings.base_url.clone()),
+        Some(&provider),
+        Some(&model),
+        organization,
+        project,
+        settings.proxy_url.clone(),
+        settings.proxy_no_proxy.clone(),
+    );
+
+    // Not every client path supports a system prompt, so the instruction leads the user message
+    let messages = vec![LLMMessage {
+        role: "user".to_string(),
+        content: format!("{}\n\n{}", AUTO_TITLE_PROMPT, first_message),
+    }];
+
+    let response = match llm_client.send_message(messages, &model, 20, Some(settings.temperature)).await {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("[auto_title] Failed to generate title: {}", e);
+            return None;
+        }
+    };
+
+    let title = clean_generated_title(&response)?;
+    // The user may have renamed the conversation while the title was generating
+    if db.has_custom_title(conversation_id).unwrap_or(false) {
+        return None;
+    }
+    if let Err(e) = db.update_conversation_title(conversation_id, &title) {
+        eprintln!("[auto_title] Failed to save title: {}", e);
+        return None;
+    }
+    Some(title)
+}
+
+/// Normalize a model-generated title: first line, no quotes or trailing punctuation
+fn clean_generated_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(|l| l.trim()).find(|l| !l.is_empty())?;
+    let title = line
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '`' || c == '*')
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .trim();
+
+    if title.is_empty() {
+        return None;
+    }
+
+    Some(title.chars().take(60).collect())
+}
+
+// Chat event for tool-enabled chat
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum ChatEvent {
+    #[serde(rename = "text")]
+    Text { content: String },
+    #[serde(rename = "tool_start")]
+    ToolStart { tool: String, input: serde_json::Value },
+    #[serde(rename = "tool_end")]
+    ToolEnd { tool: String, result: String, success: bool },
+    #[serde(rename = "done")]
+    Done { final_text: String },
+    #[serde(rename = "context_truncated")]
+    ContextTruncated { removed: usize },
+}
+
+#[derive(Debug, Clone)]
+struct ForcedToolPreview {
+    tool: String,
+    input: serde_json::Value,
+    result: String,
+    success: bool,
+}
+
+/// Replay a forced execution's tool calls as agent events. Forced calls are made by the
+/// app itself rather than the agent, so they are reported as native tools.
+fn emit_forced_previews(window: &Window, previews: &[ForcedToolPreview]) {
+    for preview in previews {
+        let _ = window.emit("agent-event", AgentEvent::ToolStart {
+            tool: preview.tool.clone(),
+            input: preview.input.clone(),
+            tool_type: "native".to_string(),
+            server_id: None,
+        });
+        let _ = window.emit("agent-event", AgentEvent::ToolEnd {
+            tool: preview.tool.clone(),
+            result: preview.result.clone(),
+            success: preview.success,
+            tool_type: "native".to_string(),
+            duration_ms: 0,
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
 struct ForcedExecution {
     final_text: String,
     previews: Vec<ForcedToolPreview>,
@@ -522,6 +1146,54 @@ pub struct AgentRequest {
     pub project_path: Option<String>,
     pub system_prompt: Option<String>,
     pub max_turns: Option<u32>,
+    /// Continue an existing chat conversation: its messages become the agent's history
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+}
+
+/// Apply the global turn and duration limits from settings; request-level values override these
+fn apply_agent_limits(config: &mut AgentConfig, settings: &Settings) {
+    config.max_turns = settings.max_agent_turns.unwrap_or(DEFAULT_MAX_AGENT_TURNS);
+    if let Some(secs) = settings.max_agent_duration_secs {
+        config.max_run_duration_secs = Some(secs);
+    }
+}
+
+/// Agent config for a `run_agent` request: the request's own system prompt, or the built-in
+/// one combined with the custom prompt from settings
+async fn agent_run_config(state: &AppState, settings: &Settings, request: &AgentRequest) -> AgentConfig {
+    let mut config = AgentConfig::default();
+    apply_agent_limits(&mut config, settings);
+    if let Some(turns) = request.max_turns {
+        config.max_turns = turns;
+    }
+    config.project_path = normalize_project_path_csv(request.project_path.clone())
+        .or_else(default_workspace_root);
+    if let Some(prompt) = &request.system_prompt {
+        config.system_prompt = prompt.clone();
+    } else {
+        set_default_system_prompt(state, &mut config).await;
+        config.system_prompt.push_str(PLANNING_PROMPT);
+        config.system_prompt = settings.apply_system_prompt(&config.system_prompt);
+    }
+    render_config_prompt(&mut config, &settings.model);
+    config
+}
+
+/// Wait for an agent run slot, telling the window when the run has to queue
+async fn acquire_run_slot(state: &AppState, window: &Window) -> tokio::sync::OwnedSemaphorePermit {
+    state
+        .run_queue
+        .acquire(|position| {
+            let _ = window.emit("agent-event", AgentEvent::Queued { position });
+        })
+        .await
+}
+
+/// Agent runs waiting for a free slot
+#[command]
+pub fn get_agent_queue_depth(state: State<'_, Arc<AppState>>) -> Result<usize, CommandError> {
+    Ok(state.run_queue.depth())
 }
 
 #[command]
@@ -539,40 +1211,14 @@ pub async fn run_agent(
         });
     }
 
-    // Build agent config
-    let mut config = AgentConfig::default();
-    if let Some(prompt) = request.system_prompt {
-        config.system_prompt = prompt;
-    } else {
-        // Add MCP servers info to default system prompt
-        let mcp_servers = state.mcp_manager.get_server_statuses().await;
-        let mut mcp_info = String::new();
-        if !mcp_servers.is_empty() {
-            mcp_info.push_str("\nMCP (Model Context Protocol) Tools:\n");
-            for server in mcp_servers {
-                if matches!(server.status, crate::mcp::types::ConnectionStatus::Connected) {
-                    mcp_info.push_str(&format!("Server '{}' is connected with tools:\n", server.id));
-                    for tool in server.tools {
-                        mcp_info.push_str(&format!("  - {}: {} (use format: {}:{})\n",
-                            tool.name, tool.description, server.id, tool.name));
-                    }
-                }
-            }
-        }
-        if !mcp_info.is_empty() {
-            config.system_prompt.push_str(&mcp_info);
-        }
-    }
-    if let Some(turns) = request.max_turns {
-        config.max_turns = turns;
-    }
-    config.project_path = normalize_project_path_csv(request.project_path)
-        .or_else(default_workspace_root);
+    let _run_permit = acquire_run_slot(&state, &window).await;
+    let config = agent_run_config(&state, &settings, &request).await;
 
     // Get provider info
     let provider_id = settings.get_provider();
 
     // Create agent loop with provider
+    let http_client = crate::llm_client::http_client_for(&settings, &provider_id);
     let agent = AgentLoop::new_with_provider(
         settings.api_key,
         settings.base_url,
@@ -582,31 +1228,291 @@ pub async fn run_agent(
         Some(settings.temperature),
         state.mcp_manager.clone(),
         Some(&provider_id),
-    );
+    )
+    .with_http_client(http_client)
+    .with_streaming(settings.disable_streaming != Some(true))
+    .with_outside_workspace_reads(settings.allow_reads_outside_workspace == Some(true))
+    .with_tool_registry(state.tool_registry())
+    .with_tool_metrics(state.tool_metrics.clone());
 
     // Create channel for events
     let (tx, mut rx) = tokio::sync::mpsc::channel::<AgentEvent>(100);
 
-    // Spawn event emitter
+    // Spawn event emitter, saving plans when continuing a conversation
     let window_clone = window.clone();
+    let db = state.db.clone();
+    let plan_conversation_id = request.conversation_id.clone();
     let emit_task = tokio::spawn(async move {
+        let mut turn: u32 = 1;
         while let Some(event) = rx.recv().await {
+            if let Some(conversation_id) = &plan_conversation_id {
+                record_conversation_plan(&db, conversation_id, &mut turn, &event);
+            }
             let _ = window_clone.emit("agent-event", &event);
         }
     });
 
-    // Run agent
-    let result = agent.run(request.message, tx).await;
+    // Run agent, continuing the chat conversation when one is given
+    let result = match &request.conversation_id {
+        Some(conversation_id) => {
+            let history = conversation_agent_history(&state.db, conversation_id, &request.message)?;
+            state
+                .db
+                .add_message(&uuid::Uuid::new_v4().to_string(), conversation_id, "user", &request.message)?;
+            agent.run_with_history(history, tx).await
+        }
+        None => agent.run(request.message, tx).await,
+    };
 
     // Wait for emitter to finish
     let _ = emit_task.await;
 
     match result {
-        Ok(_messages) => Ok("Agent completed successfully".to_string()),
+        Ok(messages) => {
+            if let Some(conversation_id) = &request.conversation_id {
+                if let Some(text) = final_assistant_text(&messages) {
+                    state
+                        .db
+                        .add_message(&uuid::Uuid::new_v4().to_string(), conversation_id, "assistant", &text)?;
+                }
+            }
+            Ok("Agent completed successfully".to_string())
+        }
         Err(e) => Err(CommandError { message: e }),
     }
 }
 
+/// A tool call the model would make next; `estimated_risk` is `"read_only"`, `"write"` or `"exec"`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCallPreview {
+    pub tool: String,
+    pub input: serde_json::Value,
+    pub estimated_risk: String,
+}
+
+/// Run one turn of a dry-run `agent` on `messages` and list the tool calls the model asked for
+async fn preview_agent_tool_calls(
+    agent: &AgentLoop,
+    messages: Vec<AgentMessage>,
+) -> Result<Vec<ToolCallPreview>, String> {
+    let history_len = messages.len();
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<AgentEvent>(100);
+    let drain = tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    let result = agent.run_with_history(messages, tx).await;
+    let _ = drain.await;
+
+    let Some(reply) = result?.into_iter().skip(history_len).find(|m| m.role == "assistant") else {
+        return Ok(Vec::new());
+    };
+    let AgentContent::Blocks(blocks) = reply.content else {
+        return Ok(Vec::new());
+    };
+    Ok(blocks
+        .into_iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { name, input, .. } => Some(ToolCallPreview {
+                estimated_risk: tool_risk(&name).to_string(),
+                tool: name,
+                input,
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The tool calls the model would make in reply to `messages`, without running any of them
+#[command]
+pub async fn preview_tool_calls(
+    state: State<'_, Arc<AppState>>,
+    messages: Vec<AgentMessage>,
+) -> Result<Vec<ToolCallPreview>, CommandError> {
+    let settings = state.db.get_settings()?;
+    if settings.api_key.is_empty() && !settings.allows_empty_api_key() {
+        return Err(CommandError {
+            message: "API key not configured".to_string(),
+        });
+    }
+
+    let mut config = AgentConfig {
+        project_path: default_workspace_root(),
+        ..Default::default()
+    };
+    set_default_system_prompt(&state, &mut config).await;
+    config.system_prompt = settings.apply_system_prompt(&config.system_prompt);
+    render_config_prompt(&mut config, &settings.model);
+    config.max_turns = 1;
+
+    let provider_id = settings.get_provider();
+    let http_client = crate::llm_client::http_client_for(&settings, &provider_id);
+    let agent = AgentLoop::new_with_provider(
+        settings.api_key,
+        settings.base_url,
+        config,
+        settings.model,
+        settings.max_tokens,
+        Some(settings.temperature),
+        state.mcp_manager.clone(),
+        Some(&provider_id),
+    )
+    .with_http_client(http_client)
+    .with_streaming(settings.disable_streaming != Some(true))
+    .with_outside_workspace_reads(settings.allow_reads_outside_workspace == Some(true))
+    .with_tool_registry(state.tool_registry())
+    .with_dry_run(true);
+
+    preview_agent_tool_calls(&agent, messages)
+        .await
+        .map_err(|message| CommandError { message })
+}
+
+/// Save plans an agent emits while continuing a conversation and track their step progress;
+/// `turn` follows `TurnComplete` events
+fn record_conversation_plan(db: &Database, conversation_id: &str, turn: &mut u32, event: &AgentEvent) {
+    match event {
+        AgentEvent::Plan { steps } => {
+            let plan: Vec<PlanStep> = steps
+                .iter()
+                .map(|s| PlanStep {
+                    step: s.step,
+                    description: s.description.clone(),
+                    status: "pending".to_string(),
+                })
+                .collect();
+            if let Err(e) = db.save_conversation_plan(conversation_id, *turn, &plan) {
+                eprintln!("[conversation_plan] Failed to save plan: {}", e);
+            }
+        }
+        AgentEvent::StepStart { step } | AgentEvent::StepDone { step } => {
+            let status = if matches!(event, AgentEvent::StepStart { .. }) { "running" } else { "completed" };
+            if let Err(e) = db.update_conversation_plan_step(conversation_id, *step, status) {
+                eprintln!("[conversation_plan] Failed to update step {}: {}", step, e);
+            }
+        }
+        AgentEvent::TurnComplete { turn: completed } => *turn = completed + 1,
+        _ => {}
+    }
+}
+
+#[command]
+pub fn get_conversation_plan(
+    state: State<'_, Arc<AppState>>,
+    conversation_id: String,
+) -> Result<Option<Vec<PlanStep>>, CommandError> {
+    state.db.get_conversation_plan(&conversation_id).map_err(Into::into)
+}
+
+/// Agent history for a chat conversation: its stored messages followed by the new user message
+fn conversation_agent_history(
+    db: &Database,
+    conversation_id: &str,
+    message: &str,
+) -> Result<Vec<AgentMessage>, CommandError> {
+    let mut history: Vec<AgentMessage> = db
+        .get_messages(conversation_id)?
+        .iter()
+        .map(stored_agent_message)
+        .collect();
+
+    history.push(AgentMessage {
+        role: "user".to_string(),
+        content: AgentContent::Text(message.to_string()),
+    });
+
+    Ok(history)
+}
+
+/// Agent message for a stored chat message; intermediate tool steps get their blocks back
+fn stored_agent_message(message: &Message) -> AgentMessage {
+    let content = if message.is_intermediate() {
+        serde_json::from_str(&message.content).unwrap_or_else(|_| AgentContent::Text(message.content.clone()))
+    } else {
+        AgentContent::Text(message.content.clone())
+    };
+    AgentMessage {
+        role: message.role.clone(),
+        content,
+    }
+}
+
+/// Agent history of a tool-enabled chat as of its last saved message
+#[derive(Serialize, Deserialize)]
+struct SavedAgentContext {
+    last_message_id: String,
+    messages: Vec<AgentMessage>,
+}
+
+/// History for the next tool-enabled chat run: the agent context saved by the previous run,
+/// followed by any messages stored after it. Falls back to rebuilding it from stored messages.
+fn chat_tool_history(db: &Database, conversation_id: &str, db_messages: &[Message]) -> Vec<AgentMessage> {
+    let saved = db
+        .get_agent_context(conversation_id)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str::<SavedAgentContext>(&json).ok());
+    if let Some(saved) = saved {
+        if let Some(pos) = db_messages.iter().position(|m| m.id == saved.last_message_id) {
+            let mut history = saved.messages;
+            history.extend(db_messages[pos + 1..].iter().map(stored_agent_message));
+            return history;
+        }
+    }
+    db_messages.iter().map(stored_agent_message).collect()
+}
+
+fn save_chat_agent_context(db: &Database, conversation_id: &str, last_message_id: &str, messages: &[AgentMessage]) {
+    let saved = serde_json::to_string(&SavedAgentContext {
+        last_message_id: last_message_id.to_string(),
+        messages: messages.to_vec(),
+    })
+    .map_err(|e| e.to_string())
+    .and_then(|json| db.save_agent_context(conversation_id, &json).map_err(|e| e.to_string()));
+    if let Err(e) = saved {
+        eprintln!("[chat] Failed to save agent context: {}", e);
+    }
+}
+
+/// Save a tool call or its results as soon as they happen, so the chain survives an early exit
+fn save_intermediate_message(db: &Database, conversation_id: &str, message: &AgentMessage) {
+    let saved = serde_json::to_string(&message.content)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            db.add_message_with_type(
+                &uuid::Uuid::new_v4().to_string(),
+                conversation_id,
+                &message.role,
+                &content,
+                Some(INTERMEDIATE_MESSAGE_TYPE),
+            )
+            .map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        eprintln!("[chat] Failed to save intermediate message: {}", e);
+    }
+}
+
+/// Text of the last assistant message in an agent run, if it has any
+fn final_assistant_text(messages: &[AgentMessage]) -> Option<String> {
+    let message = messages.iter().rev().find(|m| m.role == "assistant")?;
+    let text = match &message.content {
+        AgentContent::Text(text) => text.clone(),
+        AgentContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        AgentContent::ToolResults(_) => String::new(),
+    };
+
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
 // Enhanced chat with tools - integrates agent capabilities into chat
 #[derive(Debug, Deserialize)]
 pub struct EnhancedChatRequest {
@@ -614,6 +1520,74 @@ pub struct EnhancedChatRequest {
     pub content: String,
     pub project_path: Option<String>,
     pub enable_tools: bool,
+    /// Messages kept in the model context each turn (default 40)
+    pub max_context_messages: Option<usize>,
+}
+
+const DEFAULT_MAX_CONTEXT_MESSAGES: usize = 40;
+const CONTEXT_TRUNCATED_NOTE: &str = "(Earlier conversation truncated to fit context window.)";
+
+fn is_context_truncated_note(message: &crate::agent::AgentMessage) -> bool {
+    message.role == "system"
+        && matches!(&message.content, crate::agent::AgentContent::Text(t) if t == CONTEXT_TRUNCATED_NOTE)
+}
+
+/// Drop the oldest messages after the first so at most `max_messages` remain (not counting
+/// the truncation note, which is inserted at position 1). Returns how many were removed.
+fn truncate_context_messages(messages: &mut Vec<crate::agent::AgentMessage>, max_messages: usize) -> usize {
+    use crate::agent::{AgentContent, AgentMessage};
+
+    let has_note = messages.len() > 1 && is_context_truncated_note(&messages[1]);
+    let body_start = if has_note { 2 } else { 1 };
+    let counted = messages.len() - usize::from(has_note);
+    if max_messages < 2 || counted <= max_messages {
+        return 0;
+    }
+
+    let mut cut = messages.len() - (max_messages - 1);
+    // Tool results can't be sent without the tool calls they answer
+    while cut < messages.len() && matches!(messages[cut].content, AgentContent::ToolResults(_)) {
+        cut += 1;
+    }
+    messages.drain(body_start..cut);
+
+    if !has_note {
+        messages.insert(
+            1,
+            AgentMessage {
+                role: "system".to_string(),
+                content: AgentContent::Text(CONTEXT_TRUNCATED_NOTE.to_string()),
+            },
+        );
+    }
+    cut - body_start
+}
+
+/// How many messages (the first one plus the most recent ones, as `truncate_context_messages`
+/// keeps them) fit in `token_budget`, estimating tokens from each message's serialized length
+fn context_messages_within(
+    messages: &[crate::agent::AgentMessage],
+    token_budget: u64,
+    tokens_per_char: f32,
+) -> usize {
+    let estimate = |message: &crate::agent::AgentMessage| {
+        let chars = serde_json::to_string(&message.content).map(|s| s.len()).unwrap_or(0);
+        (chars as f64 * tokens_per_char as f64).ceil() as u64
+    };
+
+    let Some(first) = messages.first() else {
+        return 0;
+    };
+    let mut used = estimate(first);
+    let mut fitting = 1;
+    for message in messages[1..].iter().rev().filter(|m| !is_context_truncated_note(m)) {
+        used += estimate(message);
+        if used > token_budget {
+            break;
+        }
+        fitting += 1;
+    }
+    fitting
 }
 
 #[command]
@@ -640,6 +1614,9 @@ pub async fn send_chat_with_tools(
     state
         .db
         .add_message(&user_msg_id, &request.conversation_id, "user", &request.content)?;
+    if let Some(project_path) = normalize_project_path_csv(request.project_path.clone()) {
+        state.db.set_conversation_project_path(&request.conversation_id, &project_path)?;
+    }
 
     // Get conversation history
     let db_messages = state.db.get_messages(&request.conversation_id)?;
@@ -663,12 +1640,14 @@ pub async fn send_chat_with_tools(
                 // Use ClaudeClient for Anthropic
                 let claude_messages: Vec<ClaudeMessage> = db_messages
                     .iter()
+                    .filter(|m| !m.is_intermediate())
                     .map(|m| ClaudeMessage {
                         role: m.role.clone(),
                         content: m.content.clone(),
                     })
                     .collect();
-                let client = ClaudeClient::new(settings.api_key.clone(), Some(settings.base_url.clone()));
+                let client = ClaudeClient::new(settings.api_key.clone(), Some(settings.base_url.clone()))
+                    .with_http_client(crate::llm_client::http_client_for(&settings, "anthropic"));
                 client
                     .send_message_stream(
                         claude_messages,
@@ -683,19 +1662,13 @@ pub async fn send_chat_with_tools(
                 // Use LLMClient for OpenAI and other providers
                 let llm_messages: Vec<LLMMessage> = db_messages
                     .iter()
+                    .filter(|m| !m.is_intermediate())
                     .map(|m| LLMMessage {
                         role: m.role.clone(),
                         content: m.content.clone(),
                     })
                     .collect();
-                let llm_client = LLMClient::new_with_openai_headers(
-                    settings.api_key.clone(),
-                    Some(settings.base_url.clone()),
-                    Some(&provider),
-                    Some(&settings.model),
-                    settings.openai_organization.clone(),
-                    settings.openai_project.clone(),
-                );
+                let llm_client = LLMClient::from_settings(&settings);
                 llm_client
                     .send_message_stream(
                         llm_messages,
@@ -727,8 +1700,11 @@ pub async fn send_chat_with_tools(
     let effective_project_path = normalize_project_path_csv(request.project_path.clone())
         .or_else(default_workspace_root);
 
-    let tool_executor = ToolExecutor::new(effective_project_path.clone())
-        .with_mcp_manager(state.mcp_manager.clone());
+    let mut tool_executor = ToolExecutor::new(effective_project_path.clone())
+        .with_mcp_manager(state.mcp_manager.clone())
+        .with_registry(state.tool_registry())
+        .with_metrics(state.tool_metrics.clone())
+        .with_outside_workspace_reads(settings.allow_reads_outside_workspace == Some(true));
 
     // Build agent-style config for tools
     let mut config = AgentConfig {
@@ -736,43 +1712,13 @@ pub async fn send_chat_with_tools(
         max_turns: 10, // Limit turns in chat mode
         ..Default::default()
     };
-
-    // System prompt for chat with tools - include MCP servers info
-    let mcp_servers = state.mcp_manager.get_server_statuses().await;
-    let mut mcp_info = String::new();
-    if !mcp_servers.is_empty() {
-        mcp_info.push_str("\nMCP (Model Context Protocol) Tools:\n");
-        for server in mcp_servers {
-            if matches!(server.status, crate::mcp::types::ConnectionStatus::Connected) {
-                mcp_info.push_str(&format!("Server '{}' is connected with tools:\n", server.id));
-                for tool in server.tools {
-                    mcp_info.push_str(&format!("  - {}: {} (use format: {}:{})\n",
-                        tool.name, tool.description, server.id, tool.name));
-                }
-            }
-        }
+    if let Some(allowed) = &config.allowed_tools {
+        tool_executor = tool_executor.with_allowed_tools(allowed.clone());
     }
 
-    config.system_prompt = format!(r#"You are Kuse Cowork, an AI assistant that helps users for non dev work.
-
-You have access to tools that allow you to read and write files, execute commands, and search through codebases.
-
-When the user asks you to do something that requires accessing files or running commands, use the appropriate tools.
-For simple questions or conversations, respond directly without using tools.
-
-Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_info);
-    config.system_prompt.push_str(
-        "\n\n## Response Quality Rules\n\
-        - After any tool call, respond only with results grounded in that tool output.\n\
-        - Do not add unrelated explanations about project structure or technology stacks.\n\
-        - If the user explicitly asks to use a specific tool, execute it and return a short outcome-focused response.\n"
-    );
-    if let Some(project_path) = &effective_project_path {
-        config.system_prompt.push_str(&format!(
-            "\n\n## Workspace Constraints\nMounted folder(s): {}\nAlways read and write files only inside mounted folder(s). Avoid temporary directories unless user explicitly asks.",
-            project_path
-        ));
-    }
+    // Chat replies aren't parsed for plans, so the planning section is left out
+    set_default_system_prompt(&state, &mut config).await;
+    config.system_prompt = settings.apply_system_prompt(&config.system_prompt);
 
     if let Some(forced) = try_force_xlsx_creation(&request.content, effective_project_path.as_deref()) {
         for preview in &forced.previews {
@@ -816,23 +1762,10 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
         return Ok(forced.final_text);
     }
 
-    let message_builder = MessageBuilder::new(
-        config.clone(),
-        settings.model.clone(),
-        settings.max_tokens,
-        Some(settings.temperature),
-    );
-
-    // Convert DB messages to agent messages
-    let mut agent_messages: Vec<AgentMessage> = db_messages
-        .iter()
-        .map(|m| AgentMessage {
-            role: m.role.clone(),
-            content: AgentContent::Text(m.content.clone()),
-        })
-        .collect();
+    // Resume the previous run's agent history, tool call chains included
+    let mut agent_messages = chat_tool_history(&state.db, &request.conversation_id, &db_messages);
 
-    let client = reqwest::Client::new();
+    let client = crate::llm_client::http_client_for(&settings, &settings.get_provider());
     let mut final_text = String::new();
     let mut last_tool_output: Option<String> = None;
     let mut tool_call_count: usize = 0;
@@ -856,15 +1789,45 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
         crate::llm_client::ApiFormat::Google
     );
 
+    let message_builder = MessageBuilder::new(
+        config.clone(),
+        settings.model.clone(),
+        settings.max_tokens,
+        Some(settings.temperature),
+    )
+    .with_tool_registry(state.tool_registry())
+    .with_structured_tool_results(use_google_format)
+    .with_api_format(provider_config.api_format.clone());
+
     // For Google: track thoughtSignature per function call across iterations (required for Gemini 3)
     let mut google_thought_signatures: std::collections::HashMap<String, String> = std::collections::HashMap::new();
 
+    let max_context_messages = request
+        .max_context_messages
+        .unwrap_or(DEFAULT_MAX_CONTEXT_MESSAGES);
+    // For known models, also keep the history within the context window, leaving room for
+    // the system prompt and the reply
+    let context_token_budget = crate::llm_client::LLMClient::model_context_window(&settings.model).map(|window| {
+        let system_tokens = (config.system_prompt.len() as f64 * provider_config.tokens_per_char as f64).ceil() as u64;
+        u64::from(window).saturating_sub(u64::from(settings.max_tokens) + system_tokens)
+    });
+
     loop {
         turn += 1;
         if turn > max_turns {
             break;
         }
 
+        let max_messages = match context_token_budget {
+            Some(budget) => max_context_messages
+                .min(context_messages_within(&agent_messages, budget, provider_config.tokens_per_char)),
+            None => max_context_messages,
+        };
+        let removed = truncate_context_messages(&mut agent_messages, max_messages);
+        if removed > 0 {
+            let _ = window.emit("chat-event", ChatEvent::ContextTruncated { removed });
+        }
+
         // Build and send request
         let api_request = message_builder.build_request(&agent_messages).await;
 
@@ -885,7 +1848,7 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
             // OpenAI format request
             let openai_request = convert_to_openai_format(&api_request, &settings.model);
             let base = provider_config.base_url.trim_end_matches('/');
-            let url = if base.ends_with("/v1") {
+            let url = if base.ends_with("/v1") || provider_config.id == "perplexity" {
                 format!("{}/chat/completions", base)
             } else {
                 format!("{}/v1/chat/completions", base)
@@ -897,16 +1860,13 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
             if !settings.api_key.is_empty() {
                 req = req.header("Authorization", format!("Bearer {}", settings.api_key));
             }
-            // Add optional OpenAI headers
-            if let Some(ref org) = settings.openai_organization {
-                if !org.is_empty() {
-                    req = req.header("OpenAI-Organization", org);
-                }
+            // Add optional OpenAI headers, for OpenAI itself only
+            let (organization, project) = crate::llm_client::openai_headers_for(&settings, &provider_config.id);
+            if let Some(org) = organization.filter(|org| !org.is_empty()) {
+                req = req.header("OpenAI-Organization", org);
             }
-            if let Some(ref proj) = settings.openai_project {
-                if !proj.is_empty() {
-                    req = req.header("OpenAI-Project", proj);
-                }
+            if let Some(proj) = project.filter(|proj| !proj.is_empty()) {
+                req = req.header("OpenAI-Project", proj);
             }
 
             req.json(&openai_request)
@@ -1206,11 +2166,15 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
             AgentContent::Blocks(blocks)
         };
 
-        agent_messages.push(AgentMessage {
+        let assistant_message = AgentMessage {
             role: "assistant".to_string(),
             content: assistant_content,
-        });
-
+        };
+        if !tool_uses.is_empty() {
+            save_intermediate_message(&state.db, &request.conversation_id, &assistant_message);
+        }
+        agent_messages.push(assistant_message);
+
         // If no tool uses, we're done
         if tool_uses.is_empty() {
             break;
@@ -1222,8 +2186,9 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
         for tool_use in &tool_uses {
             let result = tool_executor.execute(tool_use).await;
             tool_call_count += 1;
-            if !result.content.trim().is_empty() {
-                let mut summary = result.content.trim().to_string();
+            let result_text = result.content.to_text();
+            if !result_text.trim().is_empty() {
+                let mut summary = result_text.trim().to_string();
                 if summary.chars().count() > 1200 {
                     summary = summary.chars().take(1200).collect::<String>() + "...";
                 }
@@ -1233,7 +2198,7 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
             // Emit tool end
             let _ = window.emit("chat-event", ChatEvent::ToolEnd {
                 tool: tool_use.name.clone(),
-                result: result.content.clone(),
+                result: result_text,
                 success: result.is_error.is_none(),
             });
 
@@ -1241,10 +2206,12 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
         }
 
         // Add tool results as user message
-        agent_messages.push(AgentMessage {
+        let results_message = AgentMessage {
             role: "user".to_string(),
             content: AgentContent::ToolResults(tool_results),
-        });
+        };
+        save_intermediate_message(&state.db, &request.conversation_id, &results_message);
+        agent_messages.push(results_message);
     }
 
     if final_text.trim().is_empty() {
@@ -1271,15 +2238,17 @@ Be concise and helpful. Explain what you're doing when using tools.{}"#, mcp_inf
     state
         .db
         .add_message(&assistant_msg_id, &request.conversation_id, "assistant", &final_text)?;
+    save_chat_agent_context(&state.db, &request.conversation_id, &assistant_msg_id, &agent_messages);
 
     // Update conversation title if this is the first exchange
-    if db_messages.len() == 1 {
-        let title = if request.content.len() > 30 {
-            format!("{}...", &request.content[..30])
-        } else {
-            request.content.clone()
-        };
-        state.db.update_conversation_title(&request.conversation_id, &title)?;
+    if db_messages.len() == 1 && set_first_message_title(&state.db, &request.conversation_id, &request.content)? {
+        spawn_auto_title(
+            window.clone(),
+            state.db.clone(),
+            settings.clone(),
+            request.conversation_id.clone(),
+            request.content.clone(),
+        );
     }
 
     Ok(final_text)
@@ -1291,9 +2260,10 @@ pub fn list_tasks(state: State<'_, Arc<AppState>>) -> Result<Vec<Task>, CommandE
     state.db.list_tasks().map_err(Into::into)
 }
 
+/// A task with its message count and the token usage, turns and duration of its last agent run
 #[command]
-pub fn get_task(state: State<'_, Arc<AppState>>, id: String) -> Result<Option<Task>, CommandError> {
-    state.db.get_task(&id).map_err(Into::into)
+pub fn get_task(state: State<'_, Arc<AppState>>, id: String) -> Result<Option<TaskWithStats>, CommandError> {
+    state.db.get_task_with_stats(&id).map_err(Into::into)
 }
 
 #[command]
@@ -1303,15 +2273,168 @@ pub fn create_task(
     description: String,
     project_path: Option<String>,
 ) -> Result<Task, CommandError> {
+    let project_path = canonical_project_path(project_path.as_deref())?;
     let id = uuid::Uuid::new_v4().to_string();
     state.db.create_task(&id, &title, &description, project_path.as_deref()).map_err(Into::into)
 }
 
+/// Check that each comma-separated folder of a task's project path is an existing directory,
+/// and return them canonicalized (`..` and symlinks resolved)
+fn canonical_project_path(project_path: Option<&str>) -> Result<Option<String>, CommandError> {
+    let Some(project_path) = project_path else {
+        return Ok(None);
+    };
+
+    let mut roots = Vec::new();
+    for root in project_path.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let path = Path::new(root);
+        if !path.exists() {
+            return Err(CommandError {
+                message: format!("Project path '{}' does not exist", root),
+            });
+        }
+        if !path.is_dir() {
+            return Err(CommandError {
+                message: "Project path must be a directory".to_string(),
+            });
+        }
+        let canonical = std::fs::canonicalize(path).map_err(|e| CommandError {
+            message: format!("Failed to resolve project path '{}': {}", root, e),
+        })?;
+        roots.push(strip_verbatim_prefix(&canonical.to_string_lossy()));
+    }
+
+    Ok(if roots.is_empty() { None } else { Some(roots.join(",")) })
+}
+
+/// Turn a Windows verbatim path from `canonicalize` (`\\?\C:\x`, `\\?\UNC\server\share`)
+/// back into the ordinary form other tools and the frontend expect
+fn strip_verbatim_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Create a task whose agent runs use the template's system prompt, tools and turn limit
+#[command]
+pub fn create_task_from_template(
+    state: State<'_, Arc<AppState>>,
+    template_id: String,
+    title: String,
+    project_path: Option<String>,
+) -> Result<Task, CommandError> {
+    let project_path = canonical_project_path(project_path.as_deref())?;
+    task_from_template(&state.db, &template_id, &title, project_path.as_deref())
+}
+
+fn task_from_template(
+    db: &Database,
+    template_id: &str,
+    title: &str,
+    project_path: Option<&str>,
+) -> Result<Task, CommandError> {
+    let template = db.get_task_template(template_id)?.ok_or_else(|| CommandError {
+        message: format!("Task template not found: {}", template_id),
+    })?;
+    let id = uuid::Uuid::new_v4().to_string();
+    db.create_task_with_template(&id, title, &template.description, project_path, Some(&template.id))
+        .map_err(Into::into)
+}
+
+#[command]
+pub fn list_task_templates(state: State<'_, Arc<AppState>>) -> Result<Vec<TaskTemplate>, CommandError> {
+    state.db.list_task_templates().map_err(Into::into)
+}
+
+/// Create or update a template; an empty id creates a new one
+#[command]
+pub fn save_task_template(
+    state: State<'_, Arc<AppState>>,
+    mut template: TaskTemplate,
+) -> Result<TaskTemplate, CommandError> {
+    if template.name.trim().is_empty() {
+        return Err(CommandError {
+            message: "Template name cannot be empty".to_string(),
+        });
+    }
+    if template.id.is_empty() {
+        template.id = uuid::Uuid::new_v4().to_string();
+    }
+    state.db.save_task_template(&template).map_err(Into::into)
+}
+
+#[command]
+pub fn delete_task_template(state: State<'_, Arc<AppState>>, id: String) -> Result<(), CommandError> {
+    state.db.delete_task_template(&id).map_err(Into::into)
+}
+
+/// Apply a task template's instructions, tool list and turn limit to the agent config
+fn apply_task_template(config: &mut AgentConfig, template: &TaskTemplate) {
+    if !template.system_prompt.trim().is_empty() {
+        config.system_prompt.push_str(&format!(
+            "\n\n## Task Template: {}\n{}",
+            template.name,
+            template.system_prompt.trim()
+        ));
+    }
+    if !template.allowed_tools.is_empty() {
+        config.allowed_tools = Some(template.allowed_tools.clone());
+    }
+    if let Some(turns) = template.max_turns {
+        config.max_turns = turns;
+    }
+}
+
+#[command]
+pub fn list_archived_tasks(state: State<'_, Arc<AppState>>) -> Result<Vec<Task>, CommandError> {
+    state.db.list_archived_tasks().map_err(Into::into)
+}
+
+/// Tasks matching a text query, status and creation date range (millisecond timestamps)
+#[command]
+pub fn search_tasks(
+    state: State<'_, Arc<AppState>>,
+    query: Option<String>,
+    status: Option<String>,
+    created_after: Option<i64>,
+    created_before: Option<i64>,
+) -> Result<Vec<Task>, CommandError> {
+    state
+        .db
+        .search_tasks(query.as_deref(), status.as_deref(), created_after, created_before)
+        .map_err(Into::into)
+}
+
+#[command]
+pub fn archive_task(state: State<'_, Arc<AppState>>, id: String) -> Result<(), CommandError> {
+    state.db.archive_task(&id).map_err(Into::into)
+}
+
 #[command]
-pub fn delete_task(state: State<'_, Arc<AppState>>, id: String) -> Result<(), CommandError> {
+pub fn unarchive_task(state: State<'_, Arc<AppState>>, id: String) -> Result<(), CommandError> {
+    state.db.unarchive_task(&id).map_err(Into::into)
+}
+
+/// Delete a task and its messages. Only archived tasks can be deleted unless `force` is set.
+#[command]
+pub fn delete_task(state: State<'_, Arc<AppState>>, id: String, force: Option<bool>) -> Result<(), CommandError> {
+    check_task_deletable(state.db.get_task(&id)?.as_ref(), force.unwrap_or(false))?;
     state.db.delete_task(&id).map_err(Into::into)
 }
 
+fn check_task_deletable(task: Option<&Task>, force: bool) -> Result<(), CommandError> {
+    match task {
+        Some(task) if !task.archived && !force => Err(CommandError {
+            message: "Task must be archived before it can be deleted".to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
 // Run agent with task tracking
 #[derive(Debug, Deserialize)]
 pub struct TaskAgentRequest {
@@ -1321,6 +2444,13 @@ pub struct TaskAgentRequest {
     pub image_paths: Option<Vec<String>>,
     pub image_data: Option<Vec<ImageAttachmentInput>>,
     pub max_turns: Option<u32>,
+    /// JSON schema the final response must match
+    pub output_schema: Option<serde_json::Value>,
+    /// Files or glob patterns whose content is added to the system prompt up front
+    pub inject_context_files: Option<Vec<String>>,
+    /// Also add the project's README.md or README.rst
+    #[serde(default)]
+    pub auto_inject_readme: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -1336,8 +2466,16 @@ pub async fn run_task_agent(
     state: State<'_, Arc<AppState>>,
     request: TaskAgentRequest,
 ) -> Result<String, CommandError> {
+    if let Some(schema) = &request.output_schema {
+        crate::output_schema::check_schema(schema).map_err(|message| CommandError { message })?;
+    }
+
     let settings = state.db.get_settings()?;
     let task = state.db.get_task(&request.task_id)?;
+    let template = match task.as_ref().and_then(|t| t.template_id.as_deref()) {
+        Some(template_id) => state.db.get_task_template(template_id)?,
+        None => None,
+    };
     let effective_project_path = normalize_project_path_csv(request.project_path.clone())
         .or_else(|| {
             task.as_ref()
@@ -1352,6 +2490,8 @@ pub async fn run_task_agent(
         });
     }
 
+    let _run_permit = acquire_run_slot(&state, &window).await;
+
     // Load existing conversation history
     let existing_messages = state.db.get_task_messages(&request.task_id)?;
 
@@ -1379,22 +2519,24 @@ pub async fn run_task_agent(
         format!("{}\n\n[Attached images: {}]", request.message, attached_names.join(", "))
     };
     state.db.add_task_message(&user_msg_id, &request.task_id, "user", &user_text_for_db)?;
+    if let Some(inline_images) = &request.image_data {
+        for (idx, img) in inline_images.iter().enumerate() {
+            let name = img
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("pasted-image-{}", idx + 1));
+            state
+                .db
+                .add_task_message_attachment(&user_msg_id, &name, &img.media_type, &img.data)?;
+        }
+    }
 
-    // Update task status to running
+    // Update task status to running; output from an earlier run no longer applies
     state.db.update_task_status(&request.task_id, "running")?;
+    state.db.clear_task_output_json(&request.task_id)?;
 
     if let Some(forced) = try_force_xlsx_creation(&request.message, effective_project_path.as_deref()) {
-        for preview in &forced.previews {
-            let _ = window.emit("agent-event", AgentEvent::ToolStart {
-                tool: preview.tool.clone(),
-                input: preview.input.clone(),
-            });
-            let _ = window.emit("agent-event", AgentEvent::ToolEnd {
-                tool: preview.tool.clone(),
-                result: preview.result.clone(),
-                success: preview.success,
-            });
-        }
+        emit_forced_previews(&window, &forced.previews);
         let assistant_msg_id = uuid::Uuid::new_v4().to_string();
         let _ = state.db.add_task_message(&assistant_msg_id, &request.task_id, "assistant", &forced.final_text);
         let _ = state.db.update_task_status(&request.task_id, "completed");
@@ -1404,17 +2546,7 @@ pub async fn run_task_agent(
     }
 
     if let Some(forced) = try_force_directory_listing(&state.mcp_manager, &request.message).await {
-        for preview in &forced.previews {
-            let _ = window.emit("agent-event", AgentEvent::ToolStart {
-                tool: preview.tool.clone(),
-                input: preview.input.clone(),
-            });
-            let _ = window.emit("agent-event", AgentEvent::ToolEnd {
-                tool: preview.tool.clone(),
-                result: preview.result.clone(),
-                success: preview.success,
-            });
-        }
+        emit_forced_previews(&window, &forced.previews);
         let assistant_msg_id = uuid::Uuid::new_v4().to_string();
         let _ = state.db.add_task_message(&assistant_msg_id, &request.task_id, "assistant", &forced.final_text);
         let _ = state.db.update_task_status(&request.task_id, "completed");
@@ -1423,43 +2555,54 @@ pub async fn run_task_agent(
         return Ok("Task completed successfully".to_string());
     }
 
-    // Build agent config with MCP servers info
-    let mut config = AgentConfig::default();
+    // Build agent config; the template's tool list decides which tools the prompt lists
+    let mut config = AgentConfig {
+        project_path: effective_project_path.clone(),
+        allowed_tools: template
+            .as_ref()
+            .filter(|t| !t.allowed_tools.is_empty())
+            .map(|t| t.allowed_tools.clone()),
+        ..Default::default()
+    };
+    set_default_system_prompt(&state, &mut config).await;
+    config.system_prompt.push_str(PLANNING_PROMPT);
+    apply_agent_limits(&mut config, &settings);
 
-    // Add MCP servers info to system prompt
-    let mcp_servers = state.mcp_manager.get_server_statuses().await;
-    let mut mcp_info = String::new();
-    if !mcp_servers.is_empty() {
-        mcp_info.push_str("\nMCP (Model Context Protocol) Tools:\n");
-        for server in mcp_servers {
-            if matches!(server.status, crate::mcp::types::ConnectionStatus::Connected) {
-                mcp_info.push_str(&format!("Server '{}' is connected with tools:\n", server.id));
-                for tool in server.tools {
-                    mcp_info.push_str(&format!("  - {}: {} (use format: {}:{})\n",
-                        tool.name, tool.description, server.id, tool.name));
-                }
-            }
-        }
+    if let Some(template) = &template {
+        apply_task_template(&mut config, template);
     }
-    if !mcp_info.is_empty() {
-        config.system_prompt.push_str(&mcp_info);
-    }
-
     if let Some(turns) = request.max_turns {
         config.max_turns = turns;
     }
-    config.project_path = effective_project_path.clone();
-    if let Some(project_path) = &effective_project_path {
-        config.system_prompt.push_str(&format!(
-            "\n\n## Workspace Constraints\nMounted folder(s): {}\nAlways read and write files only inside mounted folder(s). Avoid temporary directories unless user explicitly asks.",
-            project_path
-        ));
+    if let Some(context) = crate::project_context::project_context_prompt(
+        request.inject_context_files.as_deref().unwrap_or(&[]),
+        request.auto_inject_readme,
+        effective_project_path.as_deref(),
+    ) {
+        config.system_prompt.push_str(&context);
+    }
+
+    // Pull in skills whose keywords appear in the task description
+    let task_description = task
+        .as_ref()
+        .map(|t| t.description.as_str())
+        .unwrap_or(&request.message);
+    if let Some(skills) = crate::skills::skills_prompt_for_task(task_description) {
+        config.system_prompt.push_str(&skills);
+    }
+    if let Some(schema) = &request.output_schema {
+        config.system_prompt.push_str(&crate::output_schema::schema_prompt(schema));
     }
+    render_config_prompt(&mut config, &settings.model);
 
     // Get provider info
     let provider_id = settings.get_provider();
 
+    // Kept for the output correction call after the run
+    let settings_for_output = settings.clone();
+
     // Create agent loop with provider
+    let http_client = crate::llm_client::http_client_for(&settings, &provider_id);
     let agent = AgentLoop::new_with_provider(
         settings.api_key,
         settings.base_url,
@@ -1469,16 +2612,15 @@ pub async fn run_task_agent(
         Some(settings.temperature),
         state.mcp_manager.clone(),
         Some(&provider_id),
-    );
+    )
+    .with_http_client(http_client)
+    .with_streaming(settings.disable_streaming != Some(true))
+    .with_outside_workspace_reads(settings.allow_reads_outside_workspace == Some(true))
+    .with_tool_registry(state.tool_registry())
+    .with_tool_metrics(state.tool_metrics.clone());
 
     // Build conversation history from existing messages
-    let mut agent_messages: Vec<AgentMessage> = existing_messages
-        .iter()
-        .map(|m| AgentMessage {
-            role: m.role.clone(),
-            content: AgentContent::Text(m.content.clone()),
-        })
-        .collect();
+    let mut agent_messages = task_agent_history(&state.db, &existing_messages)?;
 
     // Add the new user message
     agent_messages.push(AgentMessage {
@@ -1507,11 +2649,28 @@ pub async fn run_task_agent(
     let last_tool_output_clone = last_tool_output.clone();
     let tool_call_count = std::sync::Arc::new(std::sync::Mutex::new(0usize));
     let tool_call_count_clone = tool_call_count.clone();
+    let session_stats = std::sync::Arc::new(std::sync::Mutex::new(AgentSessionStats::default()));
+    let session_stats_clone = session_stats.clone();
+
+    // Events from this run are recorded under one session id for later replay
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let session_id_for_stats = session_id.clone();
+    let run_started = std::time::Instant::now();
 
     // Spawn event emitter with task tracking
     let window_clone = window.clone();
     let emit_task = tokio::spawn(async move {
+        let mut turn: u32 = 1;
+        let mut recorder = AgentEventRecorder::new(&db, &task_id, &session_id);
         while let Some(event) = rx.recv().await {
+            recorder.record(turn, &event);
+            if let AgentEvent::TurnComplete { turn: completed } = &event {
+                turn = completed + 1;
+            }
+            if let Ok(mut stats) = session_stats_clone.lock() {
+                record_session_stats(&mut stats, &event);
+            }
+
             // Track plan and step updates in database
             match &event {
                 AgentEvent::Text { content } => {
@@ -1560,6 +2719,7 @@ pub async fn run_task_agent(
             // Emit to frontend
             let _ = window_clone.emit("agent-event", &event);
         }
+        recorder.finish();
     });
 
     // Run agent with conversation history
@@ -1568,6 +2728,12 @@ pub async fn run_task_agent(
     // Wait for emitter to finish
     let _ = emit_task.await;
 
+    let mut stats = session_stats.lock().map(|s| s.clone()).unwrap_or_default();
+    stats.duration_ms = run_started.elapsed().as_millis() as u64;
+    if let Err(e) = state.db.save_agent_session(&request.task_id, &session_id_for_stats, &stats) {
+        eprintln!("[agent_sessions] Failed to save session stats: {}", e);
+    }
+
     // Save assistant message with accumulated text
     let final_text = accumulated_text.lock().map(|t| t.clone()).unwrap_or_default();
     let last_tool_output_text = last_tool_output.lock().ok().and_then(|v| v.clone());
@@ -1593,6 +2759,18 @@ pub async fn run_task_agent(
     let assistant_msg_id = uuid::Uuid::new_v4().to_string();
     let _ = db_for_msg.add_task_message(&assistant_msg_id, &task_id_for_msg, "assistant", &resolved_final_text);
 
+    if let (Ok(_), Some(schema)) = (&result, &request.output_schema) {
+        match crate::output_schema::enforce_output_schema(&settings_for_output, &resolved_final_text, schema).await {
+            Ok(output) => {
+                if let Err(e) = state.db.set_task_output_json(&request.task_id, &output.to_string()) {
+                    let _ = state.db.update_task_status(&request.task_id, "failed");
+                    return Err(e.into());
+                }
+            }
+            Err(e) => eprintln!("[output_schema] Task {} output rejected: {}", request.task_id, e),
+        }
+    }
+
     // Always ensure task status is updated at the end
     match result {
         Ok(_messages) => {
@@ -1607,123 +2785,416 @@ pub async fn run_task_agent(
     }
 }
 
-// Get task messages command
+/// Structured output of a task run with `output_schema`, if it validated
 #[command]
-pub fn get_task_messages(
+pub fn get_task_output(
     state: State<'_, Arc<AppState>>,
     task_id: String,
-) -> Result<Vec<TaskMessage>, CommandError> {
-    state.db.get_task_messages(&task_id).map_err(Into::into)
+) -> Result<Option<serde_json::Value>, CommandError> {
+    let Some(output) = state.db.get_task_output_json(&task_id)? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&output).map(Some).map_err(|e| CommandError {
+        message: format!("Stored task output is not valid JSON: {}", e),
+    })
 }
 
-// Skills commands
-#[command]
-pub fn get_skills_list() -> Vec<SkillMetadata> {
-    get_available_skills()
+/// Add an agent event's turns and token usage to the running session totals
+fn record_session_stats(stats: &mut AgentSessionStats, event: &AgentEvent) {
+    match event {
+        AgentEvent::Usage { input_tokens, output_tokens, .. } => {
+            stats.total_input_tokens += input_tokens;
+            stats.total_output_tokens += output_tokens;
+        }
+        AgentEvent::TurnComplete { turn } => stats.total_turns = *turn,
+        AgentEvent::Done { total_turns } => stats.total_turns = *total_turns,
+        _ => {}
+    }
 }
 
-// MCP commands
-#[command]
-pub fn list_mcp_servers(state: State<'_, Arc<AppState>>) -> Result<Vec<MCPServerConfig>, CommandError> {
-    state.db.get_mcp_servers().map_err(|e| CommandError {
-        message: format!("Failed to get MCP servers: {}", e)
-    })
+/// Persist an emitted agent event; failures are logged so they never interrupt the run
+fn record_agent_event(db: &Database, task_id: &str, session_id: &str, turn: u32, event: &AgentEvent) {
+    let event_json = match serde_json::to_string(event) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("[agent_events] Failed to serialize event: {}", e);
+            return;
+        }
+    };
+    let event_type = serde_json::to_value(event)
+        .ok()
+        .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(|t| t.to_string()))
+        .unwrap_or_default();
+
+    let id = uuid::Uuid::new_v4().to_string();
+    if let Err(e) = db.add_agent_event(&id, task_id, session_id, turn, &event_type, &event_json) {
+        eprintln!("[agent_events] Failed to store event: {}", e);
+    }
 }
 
-#[command]
-pub async fn save_mcp_server(
-    state: State<'_, Arc<AppState>>,
-    config: MCPServerConfig,
-) -> Result<(), CommandError> {
-    state.db.save_mcp_server(&config).map_err(|e| CommandError {
-        message: format!("Failed to save MCP server: {}", e)
-    })?;
+/// Stores one run's agent events. Text events repeat the whole reply so far, so only the
+/// last of each stretch of consecutive text events is stored.
+struct AgentEventRecorder<'a> {
+    db: &'a Database,
+    task_id: &'a str,
+    session_id: &'a str,
+    pending_text: Option<(u32, AgentEvent)>,
+}
 
-    // Auto-restart connected server to apply updated config immediately.
-    let should_restart = state
-        .mcp_manager
-        .get_server_statuses()
-        .await
-        .into_iter()
-        .any(|status| {
-            status.id == config.id
-                && matches!(
-                    status.status,
-                    crate::mcp::types::ConnectionStatus::Connected
-                        | crate::mcp::types::ConnectionStatus::Connecting
-                )
-        });
+impl<'a> AgentEventRecorder<'a> {
+    fn new(db: &'a Database, task_id: &'a str, session_id: &'a str) -> Self {
+        Self { db, task_id, session_id, pending_text: None }
+    }
 
-    if should_restart {
-        state.mcp_manager.disconnect_server(&config.id).await;
-        if config.enabled {
-            state
-                .mcp_manager
-                .connect_server(&config)
-                .await
-                .map_err(|e| CommandError {
-                    message: format!("Server saved, but reconnect failed: {}", e),
-                })?;
+    fn record(&mut self, turn: u32, event: &AgentEvent) {
+        if matches!(event, AgentEvent::Text { .. }) {
+            self.pending_text = Some((turn, event.clone()));
+            return;
         }
+        self.flush_text();
+        record_agent_event(self.db, self.task_id, self.session_id, turn, event);
     }
 
-    Ok(())
+    /// Store the text still held back once the run has ended
+    fn finish(mut self) {
+        self.flush_text();
+    }
+
+    fn flush_text(&mut self) {
+        if let Some((turn, text)) = self.pending_text.take() {
+            record_agent_event(self.db, self.task_id, self.session_id, turn, &text);
+        }
+    }
+}
+
+/// Emit stored events in order, returning how many were emitted
+fn replay_stored_events<F>(events: &[StoredAgentEvent], mut emit: F) -> usize
+where
+    F: FnMut(&StoredAgentEvent) -> bool,
+{
+    events.iter().filter(|event| emit(event)).count()
 }
 
 #[command]
-pub async fn test_mcp_server_config(
+pub fn get_agent_events(
     state: State<'_, Arc<AppState>>,
-    mut config: MCPServerConfig,
-) -> Result<MCPServerStatus, CommandError> {
-    let test_id = format!("test-{}", uuid::Uuid::new_v4());
-    config.id = test_id.clone();
-    config.enabled = true;
-
+    task_id: String,
+    session_id: Option<String>,
+) -> Result<Vec<StoredAgentEvent>, CommandError> {
     state
-        .mcp_manager
-        .connect_server(&config)
-        .await
-        .map_err(|e| CommandError {
-            message: format!("MCP config test failed: {}", e),
-        })?;
-
-    let status = state
-        .mcp_manager
-        .get_server_statuses()
-        .await
-        .into_iter()
-        .find(|s| s.id == test_id)
-        .unwrap_or(MCPServerStatus {
-            id: test_id.clone(),
-            name: config.name.clone(),
-            transport: config.transport.clone(),
-            status: crate::mcp::types::ConnectionStatus::Disconnected,
-            tools: vec![],
-            last_error: Some("No status returned for test connection".to_string()),
-            managed_process: false,
-            pid: None,
-            endpoint: None,
-        });
-
-    state.mcp_manager.disconnect_server(&test_id).await;
-    Ok(status)
+        .db
+        .get_agent_events(&task_id, session_id.as_deref())
+        .map_err(Into::into)
 }
 
+/// Re-emit every stored event of a task as `agent-event-replay` so the frontend can
+/// rebuild the execution timeline
 #[command]
-pub fn delete_mcp_server(
+pub fn replay_agent_events(
+    window: Window,
     state: State<'_, Arc<AppState>>,
-    id: String,
-) -> Result<(), CommandError> {
-    state.db.delete_mcp_server(&id).map_err(|e| CommandError {
-        message: format!("Failed to delete MCP server: {}", e)
-    })
+    task_id: String,
+) -> Result<usize, CommandError> {
+    let events = state.db.get_agent_events(&task_id, None)?;
+    Ok(replay_stored_events(&events, |event| {
+        window.emit("agent-event-replay", event).is_ok()
+    }))
 }
 
+// Get task messages command
 #[command]
-pub async fn connect_mcp_server(
+pub fn get_task_messages(
     state: State<'_, Arc<AppState>>,
-    id: String,
-) -> Result<(), CommandError> {
+    task_id: String,
+) -> Result<Vec<TaskMessage>, CommandError> {
+    state.db.get_task_messages(&task_id).map_err(Into::into)
+}
+
+#[command]
+pub fn get_task_stats(
+    state: State<'_, Arc<AppState>>,
+    task_id: String,
+) -> Result<ConversationStats, CommandError> {
+    state.db.get_task_stats(&task_id).map_err(Into::into)
+}
+
+// Search commands
+#[command]
+pub fn global_search(
+    state: State<'_, Arc<AppState>>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<GlobalSearchResult>, CommandError> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    state.db.global_search(&query, limit).map_err(Into::into)
+}
+
+#[command]
+pub fn estimate_chat_tokens(
+    messages: Vec<crate::llm_client::Message>,
+    provider_id: String,
+) -> Result<u32, CommandError> {
+    let client = crate::llm_client::LLMClient::new(String::new(), None, Some(&provider_id), None);
+    Ok(client.estimate_tokens(&messages))
+}
+
+// Startup timing commands
+#[command]
+pub fn get_startup_timing(state: State<'_, Arc<AppState>>) -> Result<Vec<StartupEvent>, CommandError> {
+    let timing = state.startup_timing.lock().map_err(|_| CommandError {
+        message: "Startup timing lock poisoned".to_string(),
+    })?;
+    Ok(timing.clone())
+}
+
+// Background error commands
+/// The last errors reported by background work, oldest first; new ones arrive as `app-error` events
+#[command]
+pub fn subscribe_to_errors(state: State<'_, Arc<AppState>>) -> Result<Vec<AppError>, CommandError> {
+    Ok(state.errors.recent())
+}
+
+// Tool metrics commands
+#[command]
+pub fn get_tool_metrics(
+    state: State<'_, Arc<AppState>>,
+) -> Result<std::collections::HashMap<String, ToolStats>, CommandError> {
+    let metrics = state.tool_metrics.lock().map_err(|_| CommandError {
+        message: "Tool metrics lock poisoned".to_string(),
+    })?;
+    Ok(metrics.snapshot())
+}
+
+#[command]
+pub fn reset_tool_metrics(state: State<'_, Arc<AppState>>) -> Result<(), CommandError> {
+    let mut metrics = state.tool_metrics.lock().map_err(|_| CommandError {
+        message: "Tool metrics lock poisoned".to_string(),
+    })?;
+    metrics.reset();
+    Ok(())
+}
+
+/// Save a conversation as a standalone HTML page, returning the path written
+#[command]
+pub fn export_conversation_html(
+    state: State<'_, Arc<AppState>>,
+    conversation_id: String,
+    output_path: String,
+) -> Result<String, CommandError> {
+    crate::html_export::export_conversation_html(&state.db, &conversation_id, std::path::Path::new(&output_path))
+        .map_err(|message| CommandError { message })?;
+    Ok(output_path)
+}
+
+// Backup commands
+/// Serialize all app data to a single JSON document
+#[command]
+pub fn export_all(state: State<'_, Arc<AppState>>) -> Result<String, CommandError> {
+    let data = state.db.export_all()?;
+    serde_json::to_string_pretty(&data).map_err(|e| CommandError {
+        message: format!("Failed to serialize export: {}", e),
+    })
+}
+
+/// Import a document produced by `export_all`; `merge_mode` is "replace" or "merge"
+#[command]
+pub async fn import_all(
+    state: State<'_, Arc<AppState>>,
+    json: String,
+    merge_mode: String,
+) -> Result<ImportSummary, CommandError> {
+    let mode = ImportMode::parse(&merge_mode).map_err(|message| CommandError { message })?;
+    let data: DataExport = serde_json::from_str(&json).map_err(|e| CommandError {
+        message: format!("Invalid export file: {}", e),
+    })?;
+    if data.version > EXPORT_VERSION {
+        return Err(CommandError {
+            message: format!(
+                "Export version {} is newer than this app supports ({}); please update the app",
+                data.version, EXPORT_VERSION
+            ),
+        });
+    }
+
+    let summary = state.db.import_all(&data, mode)?;
+
+    // Replacing data also replaces settings, so refresh the client like save_settings does
+    if summary.settings {
+        let settings = state.db.get_settings()?;
+        *state.claude_client.lock().await = claude_client_for(&settings);
+    }
+
+    Ok(summary)
+}
+
+/// Run a WAL checkpoint (PASSIVE, FULL or TRUNCATE) and report how many frames were moved
+#[command]
+pub fn checkpoint_database(
+    state: State<'_, Arc<AppState>>,
+    mode: String,
+) -> Result<CheckpointStats, CommandError> {
+    Ok(state.db.checkpoint(&mode)?)
+}
+
+// Skills commands
+#[command]
+pub fn get_skills_list() -> Vec<SkillMetadata> {
+    get_available_skills()
+}
+
+// Tool commands
+#[command]
+pub fn list_available_tools(state: State<'_, Arc<AppState>>) -> Vec<crate::agent::ToolDefinition> {
+    state.tool_registry().definitions()
+}
+
+/// Re-read the custom tools directory and swap in a registry with the new tools.
+/// Returns the names of the custom tools now registered.
+#[command]
+pub fn reload_custom_tools(state: State<'_, Arc<AppState>>) -> Result<Vec<String>, CommandError> {
+    let dir = state.custom_tools_dir.as_ref().ok_or_else(|| CommandError {
+        message: "No custom tools directory configured".to_string(),
+    })?;
+
+    let builtin: std::collections::HashSet<String> =
+        ToolRegistry::new().definitions().into_iter().map(|d| d.name).collect();
+    let registry = build_tool_registry(Some(dir));
+    let names = registry
+        .definitions()
+        .into_iter()
+        .map(|d| d.name)
+        .filter(|name| !builtin.contains(name))
+        .collect();
+
+    let mut current = state.tool_registry.write().map_err(|_| CommandError {
+        message: "Tool registry lock poisoned".to_string(),
+    })?;
+    *current = Arc::new(registry);
+    Ok(names)
+}
+
+/// Built-in tools plus any custom tools found in `custom_tools_dir`
+pub fn build_tool_registry(custom_tools_dir: Option<&std::path::Path>) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    if let Some(dir) = custom_tools_dir {
+        for error in registry.register_custom_tools(dir) {
+            eprintln!("[custom_tools] Skipped {}", error);
+        }
+    }
+    registry
+}
+
+// MCP commands
+#[command]
+pub fn list_mcp_servers(state: State<'_, Arc<AppState>>) -> Result<Vec<MCPServerConfig>, CommandError> {
+    state.db.get_mcp_servers().map_err(|e| CommandError {
+        message: format!("Failed to get MCP servers: {}", e)
+    })
+}
+
+#[command]
+pub async fn save_mcp_server(
+    state: State<'_, Arc<AppState>>,
+    config: MCPServerConfig,
+) -> Result<(), CommandError> {
+    config.validate().map_err(|e| CommandError { message: e })?;
+
+    state.db.save_mcp_server(&config).map_err(|e| CommandError {
+        message: format!("Failed to save MCP server: {}", e)
+    })?;
+    state.mcp_manager.clear_oauth_session(&config.id).await;
+
+    // Auto-restart connected server to apply updated config immediately.
+    let should_restart = state
+        .mcp_manager
+        .get_server_statuses()
+        .await
+        .into_iter()
+        .any(|status| {
+            status.id == config.id
+                && matches!(
+                    status.status,
+                    crate::mcp::types::ConnectionStatus::Connected
+                        | crate::mcp::types::ConnectionStatus::Connecting
+                )
+        });
+
+    if should_restart {
+        state.mcp_manager.disconnect_server(&config.id).await;
+        if config.enabled {
+            state
+                .mcp_manager
+                .connect_server(&config)
+                .await
+                .map_err(|e| CommandError {
+                    message: format!("Server saved, but reconnect failed: {}", e),
+                })?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+pub async fn test_mcp_server_config(
+    state: State<'_, Arc<AppState>>,
+    mut config: MCPServerConfig,
+) -> Result<MCPServerStatus, CommandError> {
+    config.validate().map_err(|e| CommandError { message: e })?;
+
+    let test_id = format!("test-{}", uuid::Uuid::new_v4());
+    config.id = test_id.clone();
+    config.enabled = true;
+
+    state
+        .mcp_manager
+        .connect_server(&config)
+        .await
+        .map_err(|e| CommandError {
+            message: format!("MCP config test failed: {}", e),
+        })?;
+
+    let status = state
+        .mcp_manager
+        .get_server_statuses()
+        .await
+        .into_iter()
+        .find(|s| s.id == test_id)
+        .unwrap_or(MCPServerStatus {
+            id: test_id.clone(),
+            name: config.name.clone(),
+            transport: config.transport.clone(),
+            status: crate::mcp::types::ConnectionStatus::Disconnected,
+            tools: vec![],
+            prompts: vec![],
+            last_error: Some("No status returned for test connection".to_string()),
+            managed_process: false,
+            pid: None,
+            endpoint: None,
+            tool_call_timeout_secs: None,
+        });
+
+    state.mcp_manager.disconnect_server(&test_id).await;
+    Ok(status)
+}
+
+#[command]
+pub async fn delete_mcp_server(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), CommandError> {
+    state.db.delete_mcp_server(&id).map_err(|e| CommandError {
+        message: format!("Failed to delete MCP server: {}", e)
+    })?;
+    state.mcp_manager.clear_oauth_session(&id).await;
+    Ok(())
+}
+
+#[command]
+pub async fn connect_mcp_server(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), CommandError> {
     // Get server config from database
     let config = match state.db.get_mcp_server(&id).map_err(|e| CommandError {
         message: format!("Failed to get server config: {}", e)
@@ -1750,8 +3221,9 @@ pub async fn disconnect_mcp_server(
     state: State<'_, Arc<AppState>>,
     id: String,
 ) -> Result<(), CommandError> {
-    // Disconnect using MCP manager
+    // Disconnect using MCP manager; signing in again is required after an explicit disconnect
     state.mcp_manager.disconnect_server(&id).await;
+    state.mcp_manager.clear_oauth_session(&id).await;
 
     // Update enabled status in database
     state.db.update_mcp_server_enabled(&id, false).map_err(|e| CommandError {
@@ -1766,6 +3238,38 @@ pub async fn get_mcp_server_statuses(
     Ok(state.mcp_manager.get_server_statuses().await)
 }
 
+/// Recent stderr output of a stdio MCP server, oldest first
+#[command]
+pub fn get_mcp_server_logs(
+    state: State<'_, Arc<AppState>>,
+    server_id: String,
+) -> Result<Vec<String>, CommandError> {
+    Ok(state.mcp_manager.get_server_logs(&server_id))
+}
+
+/// Recent MCP connection status changes, oldest first, optionally for one server
+#[command]
+pub fn get_mcp_connection_history(
+    state: State<'_, Arc<AppState>>,
+    server_id: Option<String>,
+) -> Result<Vec<MCPConnectionEvent>, CommandError> {
+    Ok(state.mcp_manager.get_connection_history(server_id.as_deref()))
+}
+
+/// Override a connected server's tool call timeout for this session
+#[command]
+pub async fn set_mcp_tool_timeout(
+    state: State<'_, Arc<AppState>>,
+    server_id: String,
+    timeout_secs: u64,
+) -> Result<(), CommandError> {
+    state
+        .mcp_manager
+        .set_tool_timeout(&server_id, timeout_secs)
+        .await
+        .map_err(|message| CommandError { message })
+}
+
 #[command]
 pub async fn execute_mcp_tool(
     state: State<'_, Arc<AppState>>,
@@ -1774,6 +3278,44 @@ pub async fn execute_mcp_tool(
     Ok(state.mcp_manager.execute_tool(&call).await)
 }
 
+#[command]
+pub async fn refresh_mcp_server_tools(
+    state: State<'_, Arc<AppState>>,
+    server_id: String,
+) -> Result<(), CommandError> {
+    state
+        .mcp_manager
+        .refresh_server_tools(&server_id)
+        .await
+        .map_err(|message| CommandError { message })
+}
+
+#[command]
+pub async fn list_mcp_prompts(
+    state: State<'_, Arc<AppState>>,
+    server_id: String,
+) -> Result<Vec<MCPPrompt>, CommandError> {
+    state
+        .mcp_manager
+        .list_prompts_for_server(&server_id)
+        .await
+        .map_err(|message| CommandError { message })
+}
+
+#[command]
+pub async fn get_mcp_prompt(
+    state: State<'_, Arc<AppState>>,
+    server_id: String,
+    name: String,
+    args: Option<std::collections::HashMap<String, String>>,
+) -> Result<serde_json::Value, CommandError> {
+    state
+        .mcp_manager
+        .get_prompt(&server_id, &name, args.unwrap_or_default())
+        .await
+        .map_err(|message| CommandError { message })
+}
+
 fn should_force_directory_listing_query(message: &str) -> bool {
     let normalized = message.to_lowercase();
     let targets = ["folders", "folder", "files", "file", "directories", "directory", "contents"];
@@ -2178,6 +3720,79 @@ fn extract_first_windows_path(input: &str) -> Option<String> {
     re.find(input).map(|m| m.as_str().trim().to_string())
 }
 
+/// Agent history for a task, with stored image attachments restored as image blocks
+fn task_agent_history(db: &Database, messages: &[TaskMessage]) -> Result<Vec<AgentMessage>, CommandError> {
+    let mut history = Vec::with_capacity(messages.len());
+    for m in messages {
+        let mut content = m.content.clone();
+        let mut images = Vec::new();
+        for attachment in db.get_task_message_attachments(&m.id)? {
+            if attachment.missing {
+                content.push_str(&format!("\n\n[Image {} is no longer available]", attachment.name));
+                continue;
+            }
+            images.push(ImageAttachmentInput {
+                name: Some(attachment.name),
+                media_type: attachment.media_type,
+                data: attachment.data_base64,
+            });
+        }
+        history.push(AgentMessage {
+            role: m.role.clone(),
+            content: build_user_content_with_images(&content, &[], &images, None),
+        });
+    }
+    Ok(history)
+}
+
+/// Replace `config.system_prompt` with the default prompt for its workspace, the tools it
+/// may call and the connected MCP servers
+async fn set_default_system_prompt(state: &AppState, config: &mut AgentConfig) {
+    let registry = state.tool_registry();
+    let tools = match &config.allowed_tools {
+        Some(allowed) => registry.definitions_for(allowed),
+        None => registry.definitions(),
+    };
+    let mcp_info = state.mcp_manager.format_tools_for_system_prompt().await;
+    config.system_prompt = build_default_system_prompt(config, &tools, &mcp_info, &skills_system_prompt());
+}
+
+/// Fill `{{variable}}` placeholders in a config's system prompt
+fn render_config_prompt(config: &mut AgentConfig, model: &str) {
+    if config.system_prompt.contains("{{") {
+        let vars = SystemPromptVars::current(config.project_path.clone(), model);
+        config.system_prompt = render_system_prompt(&config.system_prompt, &vars);
+    }
+}
+
+const ALLOWED_IMAGE_MEDIA_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/webp", "image/gif"];
+const MAX_IMAGE_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Base64 size of a 10 MB image, with some headroom
+const MAX_INLINE_IMAGE_BASE64_BYTES: usize = 14 * 1024 * 1024;
+
+/// Text block telling the model an attached image was left out
+fn skipped_image_block(text: String) -> ContentBlock {
+    ContentBlock::Text { text }
+}
+
+/// Lowercased media type of a pasted image, or a note for the model saying why it was left out
+fn check_inline_image(image: &ImageAttachmentInput) -> Result<String, String> {
+    let name = image.name.as_deref().unwrap_or("attachment");
+    let media = image.media_type.to_lowercase();
+    if !ALLOWED_IMAGE_MEDIA_TYPES.contains(&media.as_str()) {
+        return Err(format!("[Image {} skipped: unsupported format {}]", name, image.media_type));
+    }
+    if image.data.len() > MAX_INLINE_IMAGE_BASE64_BYTES {
+        return Err(format!(
+            "[Image {} was skipped: file too large ({:.1} MB, max 10 MB)]",
+            name,
+            // Base64 inflates the payload by 4/3
+            (image.data.len() as f64 * 3.0 / 4.0) / (1024.0 * 1024.0)
+        ));
+    }
+    Ok(media)
+}
+
 fn build_user_content_with_images(
     message: &str,
     image_paths: &[String],
@@ -2200,6 +3815,10 @@ fn build_user_content_with_images(
             Ok(p) => p,
             Err(_) => continue,
         };
+        let name = resolved
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| image_path.clone());
 
         let ext = resolved
             .extension()
@@ -2212,22 +3831,39 @@ fn build_user_content_with_images(
             "jpg" | "jpeg" => "image/jpeg",
             "webp" => "image/webp",
             "gif" => "image/gif",
-            _ => continue,
+            _ => {
+                blocks.push(skipped_image_block(format!(
+                    "[Image {} skipped: unsupported format {}]",
+                    name,
+                    if ext.is_empty() { "(no extension)" } else { ext.as_str() }
+                )));
+                continue;
+            }
         };
 
-        let bytes = match fs::read(&resolved) {
-            Ok(b) => b,
+        // Keep request sizes manageable and avoid provider rejections.
+        // Check the size up front so oversized files are never read.
+        let size = match fs::metadata(&resolved) {
+            Ok(m) => m.len(),
             Err(_) => continue,
         };
-
-        // Keep request sizes manageable and avoid provider rejections.
-        if bytes.len() > 10 * 1024 * 1024 {
+        if size > MAX_IMAGE_FILE_BYTES {
+            blocks.push(skipped_image_block(format!(
+                "[Image {} was skipped: file too large ({:.1} MB, max 10 MB)]",
+                name,
+                size as f64 / (1024.0 * 1024.0)
+            )));
             continue;
         }
 
-        let encoded = general_purpose::STANDARD.encode(bytes);
-        blocks.push(ContentBlock::Image {
-            source: ImageSource {
+        let bytes = match fs::read(&resolved) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+
+        let encoded = general_purpose::STANDARD.encode(bytes);
+        blocks.push(ContentBlock::Image {
+            source: ImageSource {
                 source_type: "base64".to_string(),
                 media_type: media_type.to_string(),
                 data: encoded,
@@ -2236,19 +3872,13 @@ fn build_user_content_with_images(
     }
 
     for inline in image_data {
-        let media = inline.media_type.to_lowercase();
-        let allowed = [
-            "image/png",
-            "image/jpeg",
-            "image/webp",
-            "image/gif",
-        ];
-        if !allowed.contains(&media.as_str()) {
-            continue;
-        }
-        if inline.data.len() > 14 * 1024 * 1024 {
-            continue;
-        }
+        let media = match check_inline_image(inline) {
+            Ok(media) => media,
+            Err(note) => {
+                blocks.push(skipped_image_block(note));
+                continue;
+            }
+        };
         blocks.push(ContentBlock::Image {
             source: ImageSource {
                 source_type: "base64".to_string(),
@@ -2261,6 +3891,61 @@ fn build_user_content_with_images(
     AgentContent::Blocks(blocks)
 }
 
+/// Chat history as rich messages, with pasted images appended to the latest user message
+fn attach_images_to_last_message(
+    messages: Vec<ClaudeMessage>,
+    images: &[ImageAttachmentInput],
+) -> Vec<RichMessage> {
+    let mut rich: Vec<RichMessage> = messages.into_iter().map(RichMessage::from).collect();
+    let Some(last) = rich.iter_mut().rev().find(|m| m.role == "user") else {
+        return rich;
+    };
+
+    for image in images {
+        let media = match check_inline_image(image) {
+            Ok(media) => media,
+            Err(note) => {
+                last.content.push(MessageBlock::Text { text: note });
+                continue;
+            }
+        };
+        last.content.push(MessageBlock::Image {
+            source_type: "base64".to_string(),
+            media_type: media,
+            data: image.data.clone(),
+        });
+    }
+
+    rich
+}
+
+/// Pasted images for `LLMClient`; notes about skipped images are appended to the latest
+/// user message instead
+fn inline_images_for_llm(
+    messages: &mut [crate::llm_client::Message],
+    images: &[ImageAttachmentInput],
+) -> Vec<crate::llm_client::InlineImage> {
+    let mut inline = Vec::new();
+    let mut notes = Vec::new();
+    for image in images {
+        match check_inline_image(image) {
+            Ok(media_type) => inline.push(crate::llm_client::InlineImage {
+                media_type,
+                data: image.data.clone(),
+            }),
+            Err(note) => notes.push(note),
+        }
+    }
+
+    if let Some(last) = messages.iter_mut().rev().find(|m| m.role == "user") {
+        for note in notes {
+            last.content.push_str("\n\n");
+            last.content.push_str(&note);
+        }
+    }
+    inline
+}
+
 /// Convert Claude API request format to OpenAI format
 fn convert_to_openai_format(
     request: &crate::agent::message_builder::ClaudeApiRequest,
@@ -2272,10 +3957,10 @@ fn convert_to_openai_format(
     let mut messages: Vec<serde_json::Value> = Vec::new();
 
     // Add system message
-    if !request.system.is_empty() {
+    if !request.system.text.is_empty() {
         messages.push(serde_json::json!({
             "role": "system",
-            "content": request.system
+            "content": request.system.text
         }));
     }
 
@@ -2387,16 +4072,11 @@ fn convert_to_openai_format(
     }
 
     // Convert tools definition
-    let tools: Vec<serde_json::Value> = request.tools.iter().map(|tool| {
-        serde_json::json!({
-            "type": "function",
-            "function": {
-                "name": tool.name,
-                "description": tool.description,
-                "parameters": tool.input_schema
-            }
-        })
-    }).collect();
+    let tools: Vec<serde_json::Value> = request
+        .tools
+        .iter()
+        .map(crate::agent::ToolDefinition::to_openai_function)
+        .collect();
 
     let mut openai_request = serde_json::json!({
         "model": request.model,
@@ -2548,9 +4228,9 @@ fn convert_to_google_format(
     });
 
     // Add system instruction if present
-    if !request.system.is_empty() {
+    if !request.system.text.is_empty() {
         google_request["systemInstruction"] = serde_json::json!({
-            "parts": [{"text": request.system}]
+            "parts": [{"text": request.system.text}]
         });
     }
 
@@ -2563,3 +4243,1175 @@ fn convert_to_google_format(
 
     google_request
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::message_builder::{ApiContent, ApiMessage, ClaudeApiRequest};
+    use crate::agent::ToolResult;
+
+    #[tokio::test]
+    async fn test_provider_health_checks_report_latency() {
+        use crate::test_support::spawn_mock_server;
+
+        let base_url = spawn_mock_server(|_body| ("application/json", r#"{"data": []}"#.to_string())).await;
+        let mut settings = Settings {
+            provider: "openai".to_string(),
+            api_key: "sk-active".to_string(),
+            ..Settings::default()
+        };
+        settings.provider_keys.insert("groq".to_string(), "gsk-test".to_string());
+
+        let mut targets = provider_health_targets(&settings);
+        assert_eq!(targets.len(), crate::llm_client::provider_preset_ids().len());
+        let key_of = |targets: &[(crate::llm_client::ProviderConfig, Option<String>)], id: &str| {
+            targets.iter().find(|(c, _)| c.id == id).and_then(|(_, k)| k.clone())
+        };
+        assert_eq!(key_of(&targets, "openai").as_deref(), Some("sk-active"));
+        assert_eq!(key_of(&targets, "groq").as_deref(), Some("gsk-test"));
+        assert_eq!(key_of(&targets, "anthropic"), None);
+
+        for (config, _) in targets.iter_mut() {
+            config.base_url = base_url.clone();
+        }
+        let statuses = check_provider_connections(targets, &settings, PROVIDER_CHECK_TIMEOUT).await;
+
+        let ids: Vec<&str> = statuses.iter().map(|s| s.provider_id.as_str()).collect();
+        assert_eq!(ids, crate::llm_client::provider_preset_ids());
+        for id in ["openai", "groq", "ollama", "lm-studio"] {
+            let status = statuses.iter().find(|s| s.provider_id == id).unwrap();
+            assert!(status.reachable, "{}: {:?}", id, status.error);
+            assert!(status.latency_ms.is_some(), "{}", id);
+            assert_eq!(status.error, None);
+        }
+        let anthropic = statuses.iter().find(|s| s.provider_id == "anthropic").unwrap();
+        assert!(!anthropic.reachable);
+        assert_eq!(anthropic.latency_ms, None);
+        assert_eq!(anthropic.error.as_deref(), Some("No API key configured"));
+    }
+
+    fn text_message(role: &str, text: &str) -> crate::agent::AgentMessage {
+        crate::agent::AgentMessage {
+            role: role.to_string(),
+            content: crate::agent::AgentContent::Text(text.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_truncated_at_threshold() {
+        use crate::agent::{AgentConfig, AgentContent, AgentMessage, MessageBuilder};
+
+        let mut messages: Vec<AgentMessage> = (0..5)
+            .map(|i| text_message(if i % 2 == 0 { "user" } else { "assistant" }, &format!("message {}", i)))
+            .collect();
+        assert_eq!(truncate_context_messages(&mut messages, 5), 0);
+        assert_eq!(messages.len(), 5);
+
+        messages.push(AgentMessage {
+            role: "user".to_string(),
+            content: AgentContent::ToolResults(vec![ToolResult::success("t1".to_string(), "ok".to_string())]),
+        });
+        messages.push(text_message("assistant", "message 6"));
+        messages.push(text_message("user", "message 7"));
+        messages.push(text_message("assistant", "message 8"));
+        // Keeping the last 4 would start on a tool result without its tool call, so it goes too
+        assert_eq!(truncate_context_messages(&mut messages, 5), 5);
+
+        let texts: Vec<String> = messages
+            .iter()
+            .map(|m| match &m.content {
+                AgentContent::Text(t) => format!("{}: {}", m.role, t),
+                _ => format!("{}: (tool results)", m.role),
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "user: message 0".to_string(),
+                format!("system: {}", CONTEXT_TRUNCATED_NOTE),
+                "assistant: message 6".to_string(),
+                "user: message 7".to_string(),
+                "assistant: message 8".to_string(),
+            ]
+        );
+
+        // The note isn't counted or duplicated on later turns
+        assert_eq!(truncate_context_messages(&mut messages, 5), 0);
+        messages.push(text_message("user", "message 9"));
+        messages.push(text_message("assistant", "message 10"));
+        assert_eq!(truncate_context_messages(&mut messages, 5), 1);
+        assert_eq!(messages.iter().filter(|m| is_context_truncated_note(m)).count(), 1);
+        assert!(is_context_truncated_note(&messages[1]));
+
+        // The note is sent as part of the system prompt rather than as a message
+        let request = MessageBuilder::new(AgentConfig::default(), "llama3.3:latest".to_string(), 1024, None)
+            .build_request(&messages)
+            .await;
+        assert!(request.system.text.ends_with(CONTEXT_TRUNCATED_NOTE));
+        assert!(request.messages.iter().all(|m| m.role != "system"));
+        assert_eq!(request.messages.len(), messages.len() - 1);
+    }
+
+    #[test]
+    fn test_context_messages_within_token_budget() {
+        // Each message serializes to 402 characters, about 101 tokens at 0.25 tokens per char
+        let mut messages: Vec<crate::agent::AgentMessage> = (0..7)
+            .map(|i| text_message(if i % 2 == 0 { "user" } else { "assistant" }, &"x".repeat(400)))
+            .collect();
+
+        assert_eq!(context_messages_within(&messages, 350, 0.25), 3);
+        assert_eq!(context_messages_within(&messages, 1_000_000, 0.25), 7);
+        assert_eq!(context_messages_within(&[], 350, 0.25), 0);
+
+        assert_eq!(truncate_context_messages(&mut messages, 3), 5);
+        // The truncation note isn't counted against the budget
+        assert_eq!(context_messages_within(&messages, 350, 0.25), 3);
+
+        assert_eq!(crate::llm_client::LLMClient::model_context_window("gpt-4"), Some(8_192));
+    }
+
+    fn request_with_results(results: Vec<ToolResult>) -> ClaudeApiRequest {
+        ClaudeApiRequest {
+            model: "gpt-4o".to_string(),
+            max_tokens: 1024,
+            system: String::new().into(),
+            messages: vec![ApiMessage {
+                role: "user".to_string(),
+                content: ApiContent::Blocks(results.iter().map(|r| r.to_api_block(false)).collect()),
+            }],
+            tools: Vec::new(),
+            temperature: None,
+            stream: true,
+        }
+    }
+
+    #[test]
+    fn test_openai_tool_messages_are_text() {
+        let value = serde_json::json!({"rows": 3});
+        let request = request_with_results(vec![
+            ToolResult::success("a".to_string(), "plain".to_string()),
+            ToolResult::json("b".to_string(), value.clone()),
+            ToolResult::error("c".to_string(), "failed".to_string()),
+        ]);
+
+        let openai = convert_to_openai_format(&request, "gpt-4o");
+        let tool_messages: Vec<&serde_json::Value> = openai["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|m| m["role"] == "tool")
+            .collect();
+
+        assert_eq!(tool_messages.len(), 3);
+        assert_eq!(tool_messages[0]["content"], "plain");
+        assert_eq!(
+            tool_messages[1]["content"],
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+        assert_eq!(tool_messages[2]["content"], "failed");
+    }
+
+    #[tokio::test]
+    async fn test_auto_title_updates_conversation_in_background() {
+        let base_url = crate::test_support::spawn_mock_server(|_body| {
+            let reply = serde_json::json!({
+                "choices": [{"message": {"role": "assistant", "content": "\"Bash Backup Script\"."}}]
+            });
+            ("application/json", reply.to_string())
+        })
+        .await;
+
+        let (db, db_path) = crate::test_support::temp_db();
+        let db = Arc::new(db);
+        db.create_conversation("conv-1", "Write me a bash script th...", None).unwrap();
+
+        let settings = Settings {
+            provider: "ollama".to_string(),
+            model: "llama3.3:latest".to_string(),
+            base_url,
+            auto_title_model: Some("qwen2.5:0.5b".to_string()),
+            ..Settings::default()
+        };
+
+        let task_db = db.clone();
+        let handle = tokio::spawn(async move {
+            auto_title_conversation(&task_db, &settings, "conv-1", "Write me a bash script that backs up my home dir").await
+        });
+
+        let title = handle.await.unwrap();
+        assert_eq!(title.as_deref(), Some("Bash Backup Script"));
+
+        let conversations = db.list_conversations().unwrap();
+        assert_eq!(conversations[0].title, "Bash Backup Script");
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_first_message_title_respects_custom_title() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("custom", "New Chat", None).unwrap();
+        db.create_conversation("auto", "New Chat", None).unwrap();
+
+        db.set_custom_conversation_title("custom", "Q3 planning").unwrap();
+        assert!(db.has_custom_title("custom").unwrap());
+        assert!(!db.has_custom_title("auto").unwrap());
+
+        let first_message = "Summarize the attached quarterly report for me";
+        assert!(!set_first_message_title(&db, "custom", first_message).unwrap());
+        assert!(set_first_message_title(&db, "auto", first_message).unwrap());
+
+        let titles: std::collections::HashMap<String, String> = db
+            .list_conversations()
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.id, c.title))
+            .collect();
+        assert_eq!(titles["custom"], "Q3 planning");
+        assert_eq!(titles["auto"], "Summarize the attached quarter...");
+
+        // Multi-byte characters straddling the cut must not panic
+        db.create_conversation("unicode", "New Chat", None).unwrap();
+        assert!(set_first_message_title(&db, "unicode", &"é".repeat(40)).unwrap());
+        let unicode = db.list_conversations().unwrap().into_iter().find(|c| c.id == "unicode").unwrap();
+        assert_eq!(unicode.title, format!("{}...", "é".repeat(30)));
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_clean_generated_title() {
+        assert_eq!(clean_generated_title("  \"Rust CLI Setup\"\n").as_deref(), Some("Rust CLI Setup"));
+        assert_eq!(clean_generated_title("Weekly Sales Report.").as_deref(), Some("Weekly Sales Report"));
+        assert_eq!(clean_generated_title("   \n  "), None);
+    }
+
+    #[test]
+    fn test_invalid_proxy_url_rejected_at_save() {
+        let mut settings = Settings {
+            proxy_url: Some("http://proxy.corp.example:8080".to_string()),
+            proxy_no_proxy: Some("localhost,127.0.0.1".to_string()),
+            ..Settings::default()
+        };
+        assert!(validate_proxy_settings(&settings).is_ok());
+
+        settings.proxy_url = Some("   ".to_string());
+        assert!(validate_proxy_settings(&settings).is_ok());
+
+        settings.proxy_url = Some("ftp://proxy.corp.example".to_string());
+        let err = validate_proxy_settings(&settings).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Invalid proxy URL 'ftp://proxy.corp.example': expected an http:// or https:// URL"
+        );
+
+        settings.proxy_url = Some("http://proxy.corp.example:99999".to_string());
+        let err = validate_proxy_settings(&settings).unwrap_err();
+        assert!(err.message.starts_with("Invalid proxy URL 'http://proxy.corp.example:99999':"));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_proxy_detected() {
+        // Bind then drop a listener so the port is known to be closed
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert!(check_proxy_reachable(&format!("http://{}", addr)).await.is_err());
+    }
+
+    #[test]
+    fn test_agent_events_stored_in_order_and_replayed() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_task("task-1", "Debug", "Investigate failure", None).unwrap();
+
+        let events = [
+            AgentEvent::Text { content: "Looking".to_string() },
+            AgentEvent::ToolStart {
+                tool: "bash".to_string(),
+                input: serde_json::json!({"command": "ls"}),
+                tool_type: "native".to_string(),
+                server_id: None,
+            },
+            AgentEvent::ToolEnd {
+                tool: "bash".to_string(),
+                result: "a.txt".to_string(),
+                success: true,
+                tool_type: "native".to_string(),
+                duration_ms: 3,
+            },
+            AgentEvent::TurnComplete { turn: 1 },
+            AgentEvent::Error { message: "boom".to_string() },
+        ];
+        let mut turn = 1;
+        for event in &events {
+            record_agent_event(&db, "task-1", "session-1", turn, event);
+            if let AgentEvent::TurnComplete { turn: completed } = event {
+                turn = completed + 1;
+            }
+        }
+        record_agent_event(&db, "task-1", "session-2", 1, &AgentEvent::Done { total_turns: 1 });
+
+        let stored = db.get_agent_events("task-1", Some("session-1")).unwrap();
+        let types: Vec<&str> = stored.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, vec!["text", "tool_start", "tool_end", "turn_complete", "error"]);
+        let turns: Vec<u32> = stored.iter().map(|e| e.turn).collect();
+        assert_eq!(turns, vec![1, 1, 1, 1, 2]);
+        assert_eq!(stored[1].event, serde_json::to_value(&events[1]).unwrap());
+
+        let all = db.get_agent_events("task-1", None).unwrap();
+        assert_eq!(all.len(), 6);
+
+        let mut replayed = Vec::new();
+        let count = replay_stored_events(&all, |event| {
+            replayed.push(event.event.clone());
+            true
+        });
+        assert_eq!(count, 6);
+        assert_eq!(replayed.first().unwrap()["type"], "text");
+        assert_eq!(replayed.last().unwrap()["type"], "done");
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_agent_event_recorder_keeps_last_text_of_each_stretch() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_task("task-1", "Debug", "Investigate failure", None).unwrap();
+
+        let text = |content: &str| AgentEvent::Text { content: content.to_string() };
+        let mut recorder = AgentEventRecorder::new(&db, "task-1", "session-1");
+        for event in [text("Look"), text("Looking"), text("Looking now")] {
+            recorder.record(1, &event);
+        }
+        recorder.record(1, &AgentEvent::TurnComplete { turn: 1 });
+        recorder.record(2, &text("Done"));
+        recorder.record(2, &text("Done."));
+        recorder.finish();
+
+        let stored = db.get_agent_events("task-1", Some("session-1")).unwrap();
+        let types: Vec<&str> = stored.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, vec!["text", "turn_complete", "text"]);
+        assert_eq!(stored[0].event["content"], "Looking now");
+        assert_eq!(stored[2].event["content"], "Done.");
+        assert_eq!(stored[2].turn, 2);
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_task_history_restores_image_attachments() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_task("task-1", "Review", "Check the chart", None).unwrap();
+        db.add_task_message("u1", "task-1", "user", "What is wrong here?\n\n[Attached images: chart.png]")
+            .unwrap();
+        db.add_task_message_attachment("u1", "chart.png", "image/png", "aGVsbG8=").unwrap();
+        db.add_task_message("a1", "task-1", "assistant", "The axis is mislabeled.").unwrap();
+
+        let messages = db.get_task_messages("task-1").unwrap();
+        let history = task_agent_history(&db, &messages).unwrap();
+        assert_eq!(history.len(), 2);
+
+        match &history[0].content {
+            AgentContent::Blocks(blocks) => {
+                assert!(matches!(&blocks[0], ContentBlock::Text { text } if text.starts_with("What is wrong here?")));
+                assert!(matches!(
+                    &blocks[1],
+                    ContentBlock::Image { source } if source.media_type == "image/png" && source.data == "aGVsbG8="
+                ));
+            }
+            other => panic!("expected image blocks, got {:?}", other),
+        }
+        assert!(matches!(&history[1].content, AgentContent::Text(text) if text == "The axis is mislabeled."));
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_skipped_images_reported_in_content() {
+        let dir = std::env::temp_dir().join(format!("kuse-cowork-images-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let large = dir.join("huge.png");
+        std::fs::File::create(&large).unwrap().set_len(11 * 1024 * 1024).unwrap();
+        let small = dir.join("ok.png");
+        std::fs::write(&small, b"png").unwrap();
+        let bitmap = dir.join("scan.bmp");
+        std::fs::write(&bitmap, b"bmp").unwrap();
+
+        let paths: Vec<String> = [&large, &small, &bitmap]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let inline = vec![
+            ImageAttachmentInput {
+                name: Some("photo.tiff".to_string()),
+                media_type: "image/tiff".to_string(),
+                data: "aGVsbG8=".to_string(),
+            },
+            ImageAttachmentInput {
+                name: Some("big.png".to_string()),
+                media_type: "image/png".to_string(),
+                data: "A".repeat(14 * 1024 * 1024 + 1),
+            },
+        ];
+
+        let content = build_user_content_with_images("Look", &paths, &inline, dir.to_str());
+        let AgentContent::Blocks(blocks) = content else {
+            panic!("expected content blocks");
+        };
+        let texts: Vec<&str> = blocks
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let images = blocks.iter().filter(|b| matches!(b, ContentBlock::Image { .. })).count();
+
+        assert_eq!(images, 1);
+        assert_eq!(
+            texts,
+            vec![
+                "Look",
+                "[Image huge.png was skipped: file too large (11.0 MB, max 10 MB)]",
+                "[Image scan.bmp skipped: unsupported format bmp]",
+                "[Image photo.tiff skipped: unsupported format image/tiff]",
+                "[Image big.png was skipped: file too large (10.5 MB, max 10 MB)]",
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_task_project_path_validated_and_canonicalized() {
+        let dir = std::env::temp_dir().join(format!("kuse-task-path-{}", uuid::Uuid::new_v4()));
+        let project = dir.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+        let canonical = strip_verbatim_prefix(&std::fs::canonicalize(&project).unwrap().to_string_lossy());
+        assert!(!canonical.starts_with(r"\\?\"));
+
+        let missing = dir.join("missing").to_string_lossy().to_string();
+        let err = canonical_project_path(Some(&missing)).unwrap_err();
+        assert_eq!(err.message, format!("Project path '{}' does not exist", missing));
+
+        let file = dir.join("notes.txt").to_string_lossy().to_string();
+        let err = canonical_project_path(Some(&file)).unwrap_err();
+        assert_eq!(err.message, "Project path must be a directory");
+
+        let dotted = project.join("..").join("project").to_string_lossy().to_string();
+        assert_eq!(canonical_project_path(Some(&dotted)).unwrap(), Some(canonical.clone()));
+        assert_eq!(canonical_project_path(None).unwrap(), None);
+
+        #[cfg(unix)]
+        {
+            let link = dir.join("link");
+            std::os::unix::fs::symlink(&project, &link).unwrap();
+            let link = link.to_string_lossy().to_string();
+            assert_eq!(canonical_project_path(Some(&link)).unwrap(), Some(canonical.clone()));
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_strip_verbatim_prefix() {
+        assert_eq!(strip_verbatim_prefix(r"\\?\C:\Users\me\project"), r"C:\Users\me\project");
+        assert_eq!(strip_verbatim_prefix(r"\\?\UNC\server\share\project"), r"\\server\share\project");
+        assert_eq!(strip_verbatim_prefix("/home/me/project"), "/home/me/project");
+    }
+
+    #[test]
+    fn test_delete_requires_archived_task() {
+        let (db, db_path) = crate::test_support::temp_db();
+        let task = db.create_task("task-1", "Old", "Stale work", None).unwrap();
+
+        let err = check_task_deletable(Some(&task), false).unwrap_err();
+        assert_eq!(err.message, "Task must be archived before it can be deleted");
+        assert!(check_task_deletable(Some(&task), true).is_ok());
+        assert!(check_task_deletable(None, false).is_ok());
+
+        db.archive_task("task-1").unwrap();
+        let archived = db.get_task("task-1").unwrap().unwrap();
+        assert!(check_task_deletable(Some(&archived), false).is_ok());
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_agent_continues_conversation_history() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("conv-1", "Backups", None).unwrap();
+        db.add_message("m1", "conv-1", "user", "Where are my backups stored?").unwrap();
+        db.add_message("m2", "conv-1", "assistant", "In ~/backups, rotated weekly.").unwrap();
+
+        let history = conversation_agent_history(&db, "conv-1", "Now list the oldest one").unwrap();
+        assert_eq!(history.len(), 3);
+
+        let llm_requests = Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+        let recorded = llm_requests.clone();
+        let llm_url = crate::test_support::spawn_mock_server(move |body| {
+            recorded.lock().unwrap().push(body);
+            let events = [
+                serde_json::json!({"choices": [{"delta": {"content": "It is backup-01.tar."}}]}),
+                serde_json::json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+            ];
+            let mut sse: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+            sse.push_str("data: [DONE]\n\n");
+            ("text/event-stream", sse)
+        })
+        .await;
+
+        let config = AgentConfig {
+            allowed_tools: Some(Vec::new()),
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(100);
+        let messages = agent.run_with_history(history, tx).await.unwrap();
+        assert_eq!(final_assistant_text(&messages).as_deref(), Some("It is backup-01.tar."));
+
+        let requests = llm_requests.lock().unwrap();
+        let sent: Vec<(String, String)> = requests[0]["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|m| m["role"] != "system")
+            .map(|m| {
+                (
+                    m["role"].as_str().unwrap_or_default().to_string(),
+                    m["content"].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                ("user".to_string(), "Where are my backups stored?".to_string()),
+                ("assistant".to_string(), "In ~/backups, rotated weekly.".to_string()),
+                ("user".to_string(), "Now list the oldest one".to_string()),
+            ]
+        );
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_agent_plan_saved_for_conversation() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("conv-1", "Cleanup", None).unwrap();
+        assert!(db.get_conversation_plan("conv-1").unwrap().is_none());
+
+        let llm_url = crate::test_support::spawn_mock_server(|_body| {
+            let reply = "<plan>\n1. Find log files older than 30 days\n2. Delete them\n</plan>\n\
+                         [STEP 1 START] Found 3 files. [STEP 1 DONE]\n[STEP 2 START] Deleting.";
+            let events = [
+                serde_json::json!({"choices": [{"delta": {"content": reply}}]}),
+                serde_json::json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+            ];
+            let mut sse: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+            sse.push_str("data: [DONE]\n\n");
+            ("text/event-stream", sse)
+        })
+        .await;
+
+        let config = AgentConfig {
+            allowed_tools: Some(Vec::new()),
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+        agent.run("Clean up old logs".to_string(), tx).await.unwrap();
+
+        let mut turn = 1;
+        while let Ok(event) = rx.try_recv() {
+            record_conversation_plan(&db, "conv-1", &mut turn, &event);
+        }
+
+        let plan = db.get_conversation_plan("conv-1").unwrap().expect("plan saved");
+        let steps: Vec<(i32, &str, &str)> = plan
+            .iter()
+            .map(|s| (s.step, s.description.as_str(), s.status.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (1, "Find log files older than 30 days", "completed"),
+                (2, "Delete them", "running"),
+            ]
+        );
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_cached_models_expire_after_ttl() {
+        let cache = std::sync::Mutex::new(std::collections::HashMap::new());
+        let now = std::time::Instant::now();
+        cache
+            .lock()
+            .unwrap()
+            .insert("openai|".to_string(), (vec!["gpt-4o".to_string()], now));
+        if let Some(stale) = now.checked_sub(MODEL_CACHE_TTL + std::time::Duration::from_secs(1)) {
+            cache
+                .lock()
+                .unwrap()
+                .insert("google|".to_string(), (vec!["gemini-2.5-pro".to_string()], stale));
+            assert_eq!(cached_models(&cache, "google|", MODEL_CACHE_TTL), None);
+        }
+
+        assert_eq!(cached_models(&cache, "openai|", MODEL_CACHE_TTL), Some(vec!["gpt-4o".to_string()]));
+        assert_eq!(cached_models(&cache, "openai|http://localhost:8080", MODEL_CACHE_TTL), None);
+    }
+
+    #[test]
+    fn test_model_cache_key_changes_with_api_key() {
+        let key = model_cache_key("openai", None, "sk-first");
+        assert_eq!(key, model_cache_key("openai", None, "sk-first"));
+        assert_ne!(key, model_cache_key("openai", None, "sk-second"));
+        assert_ne!(key, model_cache_key("openai", Some("http://localhost:8080"), "sk-first"));
+        assert!(!key.contains("sk-first"));
+    }
+
+    #[tokio::test]
+    async fn test_discover_models_uses_provider_key() {
+        let url = crate::test_support::spawn_mock_http_server(|head, _body| {
+            head.contains("Bearer gsk-groq").then(|| {
+                ("application/json", serde_json::json!({"data": [{"id": "llama-3.3-70b-versatile"}]}).to_string())
+            })
+        })
+        .await;
+        let mut settings = Settings {
+            provider: "anthropic".to_string(),
+            api_key: "sk-ant".to_string(),
+            ..Settings::default()
+        };
+        settings.provider_keys.insert("groq".to_string(), "gsk-groq".to_string());
+
+        let models = discover_models_for(&settings, "groq", Some(url)).await.unwrap();
+        assert_eq!(models, vec!["llama-3.3-70b-versatile"]);
+        let anthropic = discover_models_for(&settings, "anthropic", None).await.unwrap();
+        assert!(anthropic.iter().all(|m| m.starts_with("claude-")));
+    }
+
+    #[test]
+    fn test_task_from_template_injects_agent_config() {
+        let (db, db_path) = crate::test_support::temp_db();
+        let template = db
+            .save_task_template(&TaskTemplate {
+                id: "code-review".to_string(),
+                name: "Code review".to_string(),
+                description: "Review the latest changes".to_string(),
+                system_prompt: "Flag missing tests before style issues.".to_string(),
+                allowed_tools: vec!["read_file".to_string(), "grep".to_string()],
+                max_turns: Some(6),
+                created_at: 0,
+            })
+            .unwrap();
+
+        let task = task_from_template(&db, "code-review", "Review PR 12", Some("/tmp/repo")).unwrap();
+        assert_eq!(task.title, "Review PR 12");
+        assert_eq!(task.description, "Review the latest changes");
+        assert_eq!(task.project_path.as_deref(), Some("/tmp/repo"));
+        assert_eq!(task.template_id.as_deref(), Some("code-review"));
+        assert!(task_from_template(&db, "missing", "Nope", None).is_err());
+
+        let mut config = AgentConfig::default();
+        apply_task_template(&mut config, &template);
+        assert!(config
+            .system_prompt
+            .ends_with("## Task Template: Code review\nFlag missing tests before style issues."));
+        assert_eq!(config.allowed_tools, Some(vec!["read_file".to_string(), "grep".to_string()]));
+        assert_eq!(config.max_turns, 6);
+
+        // A template without a tool list leaves every tool available
+        let mut config = AgentConfig::default();
+        apply_task_template(
+            &mut config,
+            &TaskTemplate {
+                allowed_tools: Vec::new(),
+                max_turns: None,
+                ..template
+            },
+        );
+        assert_eq!(config.allowed_tools, None);
+        assert_eq!(config.max_turns, AgentConfig::default().max_turns);
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_get_task_returns_last_session_stats() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_task("task-1", "Docs", "List the docs", None).unwrap();
+        db.add_task_message("msg-1", "task-1", "user", "List the docs").unwrap();
+
+        // A tool call, then a text answer; each response reports its usage in a final chunk
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let llm_url = crate::test_support::spawn_mock_server(move |_body| {
+            let events = if counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                vec![
+                    serde_json::json!({"choices": [{"delta": {"tool_calls": [{
+                        "index": 0,
+                        "id": "call_1",
+                        "function": { "name": "glob", "arguments": "{\"pattern\":\"*.md\"}" }
+                    }]}}]}),
+                    serde_json::json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+                    serde_json::json!({"choices": [], "usage": {"prompt_tokens": 120, "completion_tokens": 15}}),
+                ]
+            } else {
+                vec![
+                    serde_json::json!({"choices": [{"delta": {"content": "Found the docs."}}]}),
+                    serde_json::json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}),
+                    serde_json::json!({"choices": [], "usage": {"prompt_tokens": 180, "completion_tokens": 6}}),
+                ]
+            };
+            let mut sse: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+            sse.push_str("data: [DONE]\n\n");
+            ("text/event-stream", sse)
+        })
+        .await;
+
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            AgentConfig::default(),
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+        agent.run("List the docs".to_string(), tx).await.unwrap();
+
+        let mut stats = AgentSessionStats::default();
+        while let Ok(event) = rx.try_recv() {
+            record_session_stats(&mut stats, &event);
+        }
+        stats.duration_ms = 42;
+        db.save_agent_session("task-1", "session-1", &stats).unwrap();
+        db.add_task_message("msg-2", "task-1", "assistant", "Found the docs.").unwrap();
+
+        let listed = db.list_tasks().unwrap();
+        assert_eq!(listed.len(), 1);
+
+        let with_stats = db.get_task_with_stats("task-1").unwrap().expect("task exists");
+        assert_eq!(with_stats.task.id, "task-1");
+        assert_eq!(with_stats.message_count, 2);
+        assert_eq!(
+            with_stats.last_session,
+            Some(AgentSessionStats {
+                total_turns: 2,
+                total_input_tokens: 300,
+                total_output_tokens: 21,
+                duration_ms: 42,
+            })
+        );
+        assert!(db.get_task_with_stats("missing").unwrap().is_none());
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_intermediate_tool_messages_restored_after_early_exit() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("conv-1", "Logs", None).unwrap();
+        db.add_message("msg-1", "conv-1", "user", "Summarize the error log").unwrap();
+
+        // Two tool turns are saved as they happen; the app exits before the final answer
+        let tool_call = |id: &str, name: &str, input: serde_json::Value| AgentMessage {
+            role: "assistant".to_string(),
+            content: AgentContent::Blocks(vec![ContentBlock::ToolUse {
+                id: id.to_string(),
+                name: name.to_string(),
+                input,
+                thought_signature: None,
+            }]),
+        };
+        let tool_results = |id: &str, text: &str| AgentMessage {
+            role: "user".to_string(),
+            content: AgentContent::ToolResults(vec![ToolResult::success(id.to_string(), text.to_string())]),
+        };
+        save_intermediate_message(&db, "conv-1", &tool_call("call_1", "glob", serde_json::json!({"pattern": "*.log"})));
+        save_intermediate_message(&db, "conv-1", &tool_results("call_1", "error.log"));
+        save_intermediate_message(&db, "conv-1", &tool_call("call_2", "read_file", serde_json::json!({"path": "error.log"})));
+        save_intermediate_message(&db, "conv-1", &tool_results("call_2", "disk full"));
+
+        let history = conversation_agent_history(&db, "conv-1", "Continue").unwrap();
+        assert_eq!(history.len(), 6);
+        let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user", "assistant", "user", "user"]);
+        match &history[3].content {
+            AgentContent::Blocks(blocks) => match &blocks[0] {
+                ContentBlock::ToolUse { id, name, input, .. } => {
+                    assert_eq!((id.as_str(), name.as_str()), ("call_2", "read_file"));
+                    assert_eq!(input["path"], "error.log");
+                }
+                other => panic!("expected a tool call, got {:?}", other),
+            },
+            other => panic!("expected blocks, got {:?}", other),
+        }
+        match &history[4].content {
+            AgentContent::ToolResults(results) => {
+                assert_eq!(results[0].tool_use_id, "call_2");
+                assert_eq!(results[0].content.to_text(), "disk full");
+            }
+            other => panic!("expected tool results, got {:?}", other),
+        }
+
+        // The chat view only shows the user's message
+        let visible: Vec<Message> = db
+            .get_messages("conv-1")
+            .unwrap()
+            .into_iter()
+            .filter(|m| !m.is_intermediate())
+            .collect();
+        assert_eq!(visible.len(), 1);
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_chat_tool_history_resumes_saved_agent_context() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("conv-1", "Logs", None).unwrap();
+
+        // First run: the user asks, the agent calls a tool and answers
+        db.add_message("u1", "conv-1", "user", "Which log is largest?").unwrap();
+        let mut first_run = chat_tool_history(&db, "conv-1", &db.get_messages("conv-1").unwrap());
+        assert_eq!(first_run.len(), 1);
+        first_run.push(AgentMessage {
+            role: "assistant".to_string(),
+            content: AgentContent::Blocks(vec![ContentBlock::ToolUse {
+                id: "call_1".to_string(),
+                name: "list_dir".to_string(),
+                input: serde_json::json!({"path": "logs"}),
+                thought_signature: Some("sig-1".to_string()),
+            }]),
+        });
+        first_run.push(AgentMessage {
+            role: "user".to_string(),
+            content: AgentContent::ToolResults(vec![ToolResult::success(
+                "call_1".to_string(),
+                "app.log 4 MB\nerror.log 9 MB".to_string(),
+            )]),
+        });
+        first_run.push(AgentMessage {
+            role: "assistant".to_string(),
+            content: AgentContent::Text("error.log (9 MB)".to_string()),
+        });
+        db.add_message("a1", "conv-1", "assistant", "error.log (9 MB)").unwrap();
+        save_chat_agent_context(&db, "conv-1", "a1", &first_run);
+
+        // Second run sees the first run's tool interaction before the new message
+        db.add_message("u2", "conv-1", "user", "Show its last lines").unwrap();
+        let second_run = chat_tool_history(&db, "conv-1", &db.get_messages("conv-1").unwrap());
+        let roles: Vec<&str> = second_run.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user", "assistant", "user"]);
+        match &second_run[1].content {
+            AgentContent::Blocks(blocks) => match &blocks[0] {
+                ContentBlock::ToolUse { id, thought_signature, .. } => {
+                    assert_eq!(id, "call_1");
+                    assert_eq!(thought_signature.as_deref(), Some("sig-1"));
+                }
+                other => panic!("expected a tool call, got {:?}", other),
+            },
+            other => panic!("expected blocks, got {:?}", other),
+        }
+        match &second_run[2].content {
+            AgentContent::ToolResults(results) => assert!(results[0].content.to_text().contains("error.log 9 MB")),
+            other => panic!("expected tool results, got {:?}", other),
+        }
+        assert!(matches!(&second_run[4].content, AgentContent::Text(t) if t == "Show its last lines"));
+
+        // Editing a message drops the saved context; history is rebuilt from stored messages
+        db.update_message_content("u1", "Which log is newest?").unwrap();
+        assert_eq!(db.get_agent_context("conv-1").unwrap(), None);
+        let rebuilt = chat_tool_history(&db, "conv-1", &db.get_messages("conv-1").unwrap());
+        assert_eq!(rebuilt.len(), 3);
+        assert!(matches!(&rebuilt[0].content, AgentContent::Text(t) if t == "Which log is newest?"));
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_connection_after_startup_uses_saved_api_key() {
+        let base_url = crate::test_support::spawn_mock_server(|_body| {
+            ("application/json", r#"{"content": [{"type": "text", "text": "Hi"}]}"#.to_string())
+        })
+        .await;
+        let (db, db_path) = crate::test_support::temp_db();
+        let settings = Settings {
+            provider: "anthropic".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            api_key: "sk-ant-saved".to_string(),
+            base_url,
+            ..Settings::default()
+        };
+        db.save_settings(&settings).unwrap();
+        assert!(claude_client_for(&settings).is_some());
+
+        // State as it was before save_settings has run in this session
+        let state = AppState {
+            db: Arc::new(db),
+            claude_client: Mutex::new(None),
+            mcp_manager: Arc::new(MCPManager::new()),
+            tool_registry: std::sync::RwLock::new(Arc::new(ToolRegistry::new())),
+            custom_tools_dir: None,
+            tool_metrics: Arc::new(std::sync::Mutex::new(ToolMetrics::new())),
+            startup_timing: std::sync::Mutex::new(Vec::new()),
+            model_cache: std::sync::Mutex::new(std::collections::HashMap::new()),
+            errors: ErrorReporter::new(),
+            run_queue: RunQueue::new(DEFAULT_MAX_CONCURRENT_AGENTS),
+        };
+
+        assert_eq!(check_saved_connection(&state).await.unwrap(), "success");
+        let client = state.claude_client.lock().await.clone().expect("client initialized");
+        assert!(client.matches("sk-ant-saved", &settings.base_url));
+
+        drop(state);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_preview_tool_calls_does_not_execute() {
+        let dir = std::env::temp_dir().join(format!("kuse-preview-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let llm_url = crate::test_support::spawn_mock_server(|_body| {
+            let events = [
+                serde_json::json!({"choices": [{"delta": {"tool_calls": [
+                    {
+                        "index": 0,
+                        "id": "call_1",
+                        "function": { "name": "write_file", "arguments": "{\"path\":\"out.txt\",\"content\":\"hi\"}" }
+                    },
+                    {
+                        "index": 1,
+                        "id": "call_2",
+                        "function": { "name": "read_file", "arguments": "{\"path\":\"notes.txt\"}" }
+                    }
+                ]}}]}),
+                serde_json::json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+            ];
+            let mut body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+            body.push_str("data: [DONE]\n\n");
+            ("text/event-stream", body)
+        })
+        .await;
+        let config = AgentConfig {
+            project_path: Some(dir.to_string_lossy().to_string()),
+            ..AgentConfig::default()
+        };
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            AgentConfig { max_turns: 1, ..config },
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            Arc::new(MCPManager::new()),
+            Some("ollama"),
+        )
+        .with_dry_run(true);
+
+        let messages = vec![AgentMessage {
+            role: "user".to_string(),
+            content: AgentContent::Text("Save a greeting".to_string()),
+        }];
+        let previews = preview_agent_tool_calls(&agent, messages).await.unwrap();
+
+        assert_eq!(
+            previews,
+            vec![
+                ToolCallPreview {
+                    tool: "write_file".to_string(),
+                    input: serde_json::json!({ "path": "out.txt", "content": "hi" }),
+                    estimated_risk: "write".to_string(),
+                },
+                ToolCallPreview {
+                    tool: "read_file".to_string(),
+                    input: serde_json::json!({ "path": "notes.txt" }),
+                    estimated_risk: "read_only".to_string(),
+                },
+            ]
+        );
+        assert!(!dir.join("out.txt").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_run_agent_uses_system_prompt_from_settings() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.save_settings(&Settings {
+            api_key: "sk-ant-test".to_string(),
+            system_prompt: Some("You are the finance team's assistant.".to_string()),
+            ..Settings::default()
+        })
+        .unwrap();
+        let state = AppState {
+            db: Arc::new(db),
+            claude_client: Mutex::new(None),
+            mcp_manager: Arc::new(MCPManager::new()),
+            tool_registry: std::sync::RwLock::new(Arc::new(ToolRegistry::new())),
+            custom_tools_dir: None,
+            tool_metrics: Arc::new(std::sync::Mutex::new(ToolMetrics::new())),
+            startup_timing: std::sync::Mutex::new(Vec::new()),
+            model_cache: std::sync::Mutex::new(std::collections::HashMap::new()),
+            errors: ErrorReporter::new(),
+            run_queue: RunQueue::new(DEFAULT_MAX_CONCURRENT_AGENTS),
+        };
+        let request = AgentRequest {
+            message: "Summarize Q3".to_string(),
+            project_path: Some(std::env::temp_dir().to_string_lossy().to_string()),
+            system_prompt: None,
+            max_turns: None,
+            conversation_id: None,
+        };
+
+        let mut settings = state.db.get_settings().unwrap();
+        let build = |config: AgentConfig| async move {
+            let messages = vec![AgentMessage {
+                role: "user".to_string(),
+                content: AgentContent::Text("Summarize Q3".to_string()),
+            }];
+            crate::agent::MessageBuilder::new(config, "claude-sonnet-4-5".to_string(), 1024, None)
+                .build_request(&messages)
+                .await
+                .system
+                .text
+        };
+
+        let system = build(agent_run_config(&state, &settings, &request).await).await;
+        assert!(system.starts_with("You are the finance team's assistant.\n\n## Role"), "{}", system);
+
+        settings.system_prompt_mode = Some("replace".to_string());
+        let system = build(agent_run_config(&state, &settings, &request).await).await;
+        assert_eq!(system, "You are the finance team's assistant.");
+
+        drop(state);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_max_agent_turns_setting_limits_run() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.save_settings(&Settings {
+            api_key: "sk-ant-test".to_string(),
+            max_agent_turns: Some(5),
+            max_agent_duration_secs: Some(120),
+            ..Settings::default()
+        })
+        .unwrap();
+        let state = AppState {
+            db: Arc::new(db),
+            claude_client: Mutex::new(None),
+            mcp_manager: Arc::new(MCPManager::new()),
+            tool_registry: std::sync::RwLock::new(Arc::new(ToolRegistry::new())),
+            custom_tools_dir: None,
+            tool_metrics: Arc::new(std::sync::Mutex::new(ToolMetrics::new())),
+            startup_timing: std::sync::Mutex::new(Vec::new()),
+            model_cache: std::sync::Mutex::new(std::collections::HashMap::new()),
+            errors: ErrorReporter::new(),
+            run_queue: RunQueue::new(DEFAULT_MAX_CONCURRENT_AGENTS),
+        };
+        let mut request = AgentRequest {
+            message: "Keep going".to_string(),
+            project_path: None,
+            system_prompt: Some("You are a test agent.".to_string()),
+            max_turns: None,
+            conversation_id: None,
+        };
+
+        let settings = state.db.get_settings().unwrap();
+        assert_eq!(settings.max_agent_turns, Some(5));
+        let config = agent_run_config(&state, &settings, &request).await;
+        assert_eq!(config.max_turns, 5);
+        assert_eq!(config.max_run_duration_secs, Some(120));
+
+        // The request's own limit wins, and the built-in default applies when neither is set
+        request.max_turns = Some(8);
+        assert_eq!(agent_run_config(&state, &settings, &request).await.max_turns, 8);
+        request.max_turns = None;
+        let unset = Settings { max_agent_turns: None, ..settings.clone() };
+        assert_eq!(agent_run_config(&state, &unset, &request).await.max_turns, DEFAULT_MAX_AGENT_TURNS);
+
+        // A model that calls a tool every turn is stopped after the configured 5 turns
+        let llm_requests = Arc::new(std::sync::Mutex::new(0usize));
+        let counted = llm_requests.clone();
+        let llm_url = crate::test_support::spawn_mock_server(move |_body| {
+            *counted.lock().unwrap() += 1;
+            let events = [
+                serde_json::json!({"choices": [{"delta": {"tool_calls": [{
+                    "index": 0,
+                    "id": "call_1",
+                    "function": { "name": "missing_tool", "arguments": "{}" }
+                }]}}]}),
+                serde_json::json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}),
+            ];
+            let mut body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+            body.push_str("data: [DONE]\n\n");
+            ("text/event-stream", body)
+        })
+        .await;
+        let agent = AgentLoop::new_with_provider(
+            String::new(),
+            llm_url,
+            config,
+            "llama3.3:latest".to_string(),
+            1024,
+            None,
+            state.mcp_manager.clone(),
+            Some("ollama"),
+        );
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(100);
+        agent.run("Keep going".to_string(), event_tx).await.unwrap();
+
+        assert_eq!(*llm_requests.lock().unwrap(), 5);
+        let mut errors = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AgentEvent::Error { message } = event {
+                errors.push(message);
+            }
+        }
+        assert_eq!(errors, vec!["Reached maximum turns (5)".to_string()]);
+
+        drop(state);
+        let _ = std::fs::remove_file(&db_path);
+    }
+}
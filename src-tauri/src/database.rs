@@ -1,7 +1,8 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,6 +13,8 @@ pub enum DbError {
     Io(#[from] std::io::Error),
     #[error("Lock error")]
     Lock,
+    #[error("Invalid checkpoint mode '{0}' (use PASSIVE, FULL or TRUNCATE)")]
+    InvalidCheckpointMode(String),
 }
 
 use std::collections::HashMap;
@@ -36,8 +39,46 @@ pub struct Settings {
     /// Optional OpenAI Project ID
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub openai_project: Option<String>,
+    /// Optional cheaper model used for generating conversation titles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_title_model: Option<String>,
+    /// Optional HTTP/HTTPS proxy for cloud provider requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// Comma-separated hosts that bypass the proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_no_proxy: Option<String>,
+    /// Request complete JSON responses instead of SSE, for endpoints or proxies that strip streams
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_streaming: Option<bool>,
+    /// Global system prompt for chat and agent runs, e.g. a persona
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// "prepend" (default) puts `system_prompt` before the built-in prompt, "replace" uses it instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_mode: Option<String>,
+    /// Turn limit for agent runs that don't set their own; `DEFAULT_MAX_AGENT_TURNS` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_agent_turns: Option<u32>,
+    /// Wall-clock limit for agent runs, in seconds; the agent's built-in limit when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_agent_duration_secs: Option<u64>,
+    /// Agent runs allowed at once, `DEFAULT_MAX_CONCURRENT_AGENTS` when unset; later runs queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_agents: Option<usize>,
+    /// Timeout for MCP tool calls on servers without their own, in seconds; read at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_tool_timeout_secs: Option<u64>,
+    /// Let the agent read files outside the mounted folder(s) by absolute path; off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_reads_outside_workspace: Option<bool>,
 }
 
+/// Agent turn limit used when neither the request nor the settings set one
+pub const DEFAULT_MAX_AGENT_TURNS: u32 = 30;
+/// Agent runs allowed at once when the settings don't say
+pub const DEFAULT_MAX_CONCURRENT_AGENTS: usize = 2;
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -50,11 +91,35 @@ impl Default for Settings {
             provider_keys: HashMap::new(),
             openai_organization: None,
             openai_project: None,
+            auto_title_model: None,
+            proxy_url: None,
+            proxy_no_proxy: None,
+            disable_streaming: None,
+            system_prompt: None,
+            system_prompt_mode: None,
+            max_agent_turns: None,
+            max_agent_duration_secs: None,
+            max_concurrent_agents: None,
+            mcp_tool_timeout_secs: None,
+            allow_reads_outside_workspace: None,
         }
     }
 }
 
 impl Settings {
+    /// `built_in` combined with the custom system prompt according to `system_prompt_mode`
+    pub fn apply_system_prompt(&self, built_in: &str) -> String {
+        let custom = match self.system_prompt.as_deref().map(str::trim) {
+            Some(custom) if !custom.is_empty() => custom,
+            _ => return built_in.to_string(),
+        };
+        if self.system_prompt_mode.as_deref() == Some("replace") || built_in.is_empty() {
+            custom.to_string()
+        } else {
+            format!("{}\n\n{}", custom, built_in)
+        }
+    }
+
     /// Automatically infer provider from model (if not set)
     pub fn get_provider(&self) -> String {
         if !self.provider.is_empty() {
@@ -73,6 +138,8 @@ impl Settings {
             "google".to_string()
         } else if model_lower.contains("minimax") {
             "minimax".to_string()
+        } else if model_lower.contains("sonar") && !model_lower.contains("/") {
+            "perplexity".to_string()
         } else if model_lower.starts_with("anthropic/") || model_lower.starts_with("openai/") || model_lower.starts_with("meta-llama/") || model_lower.starts_with("deepseek/") {
             "openrouter".to_string()
         } else if model_lower.contains(":") {
@@ -105,6 +172,67 @@ impl Settings {
         }
         false
     }
+
+    /// Check the settings are usable before they are persisted
+    pub fn validate(&self) -> Result<(), String> {
+        if self.model.trim().is_empty() {
+            return Err("Model must not be empty".to_string());
+        }
+
+        let base_url = self.base_url.trim();
+        match reqwest::Url::parse(base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(_) => {
+                return Err(format!(
+                    "Invalid base URL '{}': expected an http:// or https:// URL",
+                    base_url
+                ))
+            }
+            Err(e) => return Err(format!("Invalid base URL '{}': {}", base_url, e)),
+        }
+
+        if !(1..=200_000).contains(&self.max_tokens) {
+            return Err(format!(
+                "Max tokens must be between 1 and 200000 (got {})",
+                self.max_tokens
+            ));
+        }
+
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(format!(
+                "Temperature must be between 0.0 and 2.0 (got {})",
+                self.temperature
+            ));
+        }
+
+        if self.api_key.trim().is_empty() && !self.allows_empty_api_key() {
+            return Err(format!("An API key is required for provider '{}'", self.get_provider()));
+        }
+
+        if let Some(mode) = self.system_prompt_mode.as_deref() {
+            if !matches!(mode, "prepend" | "replace") {
+                return Err(format!(
+                    "System prompt mode must be 'prepend' or 'replace' (got '{}')",
+                    mode
+                ));
+            }
+        }
+
+        if self.max_agent_turns == Some(0) {
+            return Err("Max agent turns must be at least 1".to_string());
+        }
+        if self.max_agent_duration_secs == Some(0) {
+            return Err("Max agent duration must be at least 1 second".to_string());
+        }
+        if self.max_concurrent_agents == Some(0) {
+            return Err("Max concurrent agents must be at least 1".to_string());
+        }
+        if self.mcp_tool_timeout_secs == Some(0) {
+            return Err("MCP tool timeout must be at least 1 second".to_string());
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,6 +241,11 @@ pub struct Conversation {
     pub title: String,
     pub created_at: i64,
     pub updated_at: i64,
+    /// When the conversation was moved to the trash
+    pub deleted_at: Option<i64>,
+    /// Workspace the conversation belongs to, if any
+    #[serde(default)]
+    pub project_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -122,6 +255,19 @@ pub struct Message {
     pub role: String,
     pub content: String,
     pub timestamp: i64,
+    /// `None` for chat text; [`INTERMEDIATE_MESSAGE_TYPE`] for a tool call chain step
+    #[serde(default)]
+    pub message_type: Option<String>,
+}
+
+/// Tool calls and tool results saved while a chat-with-tools run is in progress.
+/// Their content is a JSON-serialized `AgentContent`.
+pub const INTERMEDIATE_MESSAGE_TYPE: &str = "intermediate";
+
+impl Message {
+    pub fn is_intermediate(&self) -> bool {
+        self.message_type.as_deref() == Some(INTERMEDIATE_MESSAGE_TYPE)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,6 +281,28 @@ pub struct Task {
     pub project_path: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Archived tasks are hidden from the task list but keep their messages
+    #[serde(default)]
+    pub archived: bool,
+    /// Template the task was created from, if any
+    #[serde(default)]
+    pub template_id: Option<String>,
+}
+
+/// Reusable setup for a kind of task, e.g. weekly report generation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Appended to the agent's system prompt for tasks created from the template
+    pub system_prompt: String,
+    /// Tools the agent may call; empty allows every tool
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    pub max_turns: Option<u32>,
+    #[serde(default)]
+    pub created_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,6 +312,94 @@ pub struct PlanStep {
     pub status: String, // "pending", "running", "completed", "failed"
 }
 
+/// Aggregate message statistics for a conversation or task
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationStats {
+    pub message_count: u32,
+    pub user_turns: u32,
+    pub assistant_turns: u32,
+    pub estimated_tokens: u32,
+    pub first_message_at: i64,
+    pub last_message_at: i64,
+    pub duration_secs: u64,
+}
+
+/// One page of conversations, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversationPage {
+    pub items: Vec<Conversation>,
+    /// Pass as `before_id` to fetch the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+    pub total_count: u32,
+}
+
+/// Outcome of a bulk conversation delete (or what it would trash, for a dry run)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BulkDeleteResult {
+    pub deleted_count: u32,
+    /// Requested ids with no conversation outside the trash
+    pub not_found: Vec<String>,
+    /// Messages moved to the trash along with the conversations
+    pub message_count: u32,
+}
+
+/// An agent event recorded while a task ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAgentEvent {
+    pub id: String,
+    pub task_id: String,
+    /// One id per `run_task_agent` invocation
+    pub session_id: String,
+    pub turn: u32,
+    pub event_type: String,
+    /// The event exactly as it was emitted to the frontend
+    pub event: serde_json::Value,
+    pub timestamp: i64,
+}
+
+/// Totals for one `run_task_agent` invocation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentSessionStats {
+    pub total_turns: u32,
+    pub total_input_tokens: u32,
+    pub total_output_tokens: u32,
+    pub duration_ms: u64,
+}
+
+/// A task along with its most recent agent run, returned by `get_task`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskWithStats {
+    pub task: Task,
+    pub last_session: Option<AgentSessionStats>,
+    pub message_count: u32,
+}
+
+/// A message matched by [`Database::global_search`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSearchResult {
+    pub kind: String, // "conversation", "task"
+    pub parent_id: String,
+    pub parent_title: String,
+    pub message_id: String,
+    pub role: String,
+    pub snippet: String,
+    pub timestamp: i64,
+}
+
+/// Rough token estimate: ~4 bytes of text per token
+pub fn estimate_message_tokens(content_bytes: u64) -> u32 {
+    content_bytes.div_ceil(4).min(u32::MAX as u64) as u32
+}
+
+/// Content a chat or task message had before one of its edits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEdit {
+    pub id: String,
+    pub message_id: String,
+    pub old_content: String,
+    pub edited_at: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskMessage {
     pub id: String,
@@ -153,27 +409,104 @@ pub struct TaskMessage {
     pub timestamp: i64,
 }
 
+/// Image attached to a task message; `data_base64` is filled on read whether the image
+/// was stored inline or in a file, unless the file has gone missing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAttachment {
+    pub id: String,
+    pub message_id: String,
+    pub name: String,
+    pub media_type: String,
+    pub data_base64: String,
+    /// The attachment's file could not be read; `data_base64` is empty
+    #[serde(default)]
+    pub missing: bool,
+}
+
+/// id, name, media_type, data_base64 and file_path of a stored attachment
+type AttachmentRow = (String, String, String, Option<String>, Option<String>);
+
+/// Attachments larger than this (base64 bytes) are stored as files next to the database
+const ATTACHMENT_INLINE_LIMIT: usize = 512 * 1024;
+
+/// Rows written between automatic PASSIVE WAL checkpoints
+const AUTO_CHECKPOINT_WRITES: u64 = 500;
+const CHECKPOINT_MODES: &[&str] = &["PASSIVE", "FULL", "TRUNCATE"];
+
+/// Result of `PRAGMA wal_checkpoint`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointStats {
+    /// Frames in the WAL file
+    pub pages_written: u32,
+    /// Frames copied back into the database file
+    pub frames_checkpointed: u32,
+}
+
 pub struct Database {
     pub(crate) conn: Mutex<Connection>,
+    attachments_dir: PathBuf,
+    /// Rows inserted, updated or deleted since the last checkpoint
+    write_count: Arc<AtomicU64>,
 }
 
 impl Database {
     pub fn new() -> Result<Self, DbError> {
         let db_path = Self::get_db_path()?;
+        Self::open(&db_path)
+    }
 
+    /// Open (or create) a database at the given path
+    pub fn open(db_path: &std::path::Path) -> Result<Self, DbError> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Connection::open(&db_path)?;
+        let conn = Connection::open(db_path)?;
+        let write_count = Arc::new(AtomicU64::new(0));
+        let counter = write_count.clone();
+        conn.update_hook(Some(move |_: rusqlite::hooks::Action, _: &str, _: &str, _: i64| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+
         let db = Self {
             conn: Mutex::new(conn),
+            attachments_dir: db_path
+                .parent()
+                .map(|p| p.join("attachments"))
+                .unwrap_or_else(|| PathBuf::from("attachments")),
+            write_count,
         };
         db.init_tables()?;
         Ok(db)
     }
 
+    /// Lock the connection, first running a PASSIVE checkpoint once
+    /// `AUTO_CHECKPOINT_WRITES` rows were written since the last one so the WAL can't grow unbounded
+    pub(crate) fn lock_conn(&self) -> Result<MutexGuard<'_, Connection>, DbError> {
+        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        if self.write_count.load(Ordering::Relaxed) >= AUTO_CHECKPOINT_WRITES {
+            self.write_count.store(0, Ordering::Relaxed);
+            if let Err(e) = wal_checkpoint(&conn, "PASSIVE") {
+                eprintln!("[database] Automatic WAL checkpoint failed: {}", e);
+            }
+        }
+        Ok(conn)
+    }
+
+    /// Copy WAL frames back into the database file. `mode` is PASSIVE, FULL or TRUNCATE;
+    /// TRUNCATE also empties the WAL file.
+    pub fn checkpoint(&self, mode: &str) -> Result<CheckpointStats, DbError> {
+        let mode = mode.trim().to_uppercase();
+        if !CHECKPOINT_MODES.contains(&mode.as_str()) {
+            return Err(DbError::InvalidCheckpointMode(mode));
+        }
+        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let stats = wal_checkpoint(&conn, &mode)?;
+        self.write_count.store(0, Ordering::Relaxed);
+        Ok(stats)
+    }
+
     fn get_db_path() -> Result<PathBuf, DbError> {
         let data_dir = dirs::data_dir()
             .ok_or_else(|| DbError::Io(std::io::Error::new(
@@ -184,7 +517,16 @@ impl Database {
     }
 
     fn init_tables(&self) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
+
+        // WAL lets readers proceed while a write is in flight; NORMAL sync is safe under WAL
+        // journal_mode returns the resulting mode as a row, so it needs the checked variant
+        let _journal_mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "cache_size", -64000)?;
+        // Enforce the ON DELETE CASCADE constraints declared below
+        conn.pragma_update(None, "foreign_keys", "ON")?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS settings (
@@ -232,7 +574,35 @@ impl Database {
                 current_step INTEGER NOT NULL DEFAULT 0,
                 project_path TEXT,
                 created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL
+                updated_at INTEGER NOT NULL,
+                archived INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Lightweight migration for databases created before archiving
+        add_column_if_missing(&conn, "tasks", "archived", "INTEGER NOT NULL DEFAULT 0")?;
+        // Validated JSON for tasks that asked for structured output
+        add_column_if_missing(&conn, "tasks", "task_output_json", "TEXT")?;
+        // Set once the user renames a conversation, so auto-titling leaves it alone
+        add_column_if_missing(&conn, "conversations", "custom_title", "INTEGER NOT NULL DEFAULT 0")?;
+        // Deleted conversations stay restorable until purged
+        add_column_if_missing(&conn, "conversations", "deleted_at", "INTEGER")?;
+        add_column_if_missing(&conn, "messages", "message_type", "TEXT")?;
+        // Full agent history (tool calls included) of the last tool-enabled chat run
+        add_column_if_missing(&conn, "conversations", "agent_context_json", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "template_id", "TEXT")?;
+        add_column_if_missing(&conn, "conversations", "project_path", "TEXT")?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_templates (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT NOT NULL,
+                system_prompt TEXT NOT NULL,
+                allowed_tools TEXT NOT NULL,
+                max_turns INTEGER,
+                created_at INTEGER NOT NULL
             )",
             [],
         )?;
@@ -255,12 +625,116 @@ impl Database {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS task_message_attachments (
+                id TEXT PRIMARY KEY,
+                message_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                media_type TEXT NOT NULL,
+                data_base64 TEXT,
+                file_path TEXT,
+                FOREIGN KEY (message_id) REFERENCES task_messages(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_task_message_attachments_message
+             ON task_message_attachments(message_id)",
+            [],
+        )?;
+
+        // Edit history for both chat and task messages, so no foreign key
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS message_edits (
+                id TEXT PRIMARY KEY,
+                message_id TEXT NOT NULL,
+                old_content TEXT NOT NULL,
+                edited_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_message_edits_message
+             ON message_edits(message_id, edited_at)",
+            [],
+        )?;
+
+        for table in ["messages", "task_messages"] {
+            conn.execute(
+                &format!(
+                    "CREATE TRIGGER IF NOT EXISTS {table}_edits_delete AFTER DELETE ON {table} BEGIN
+                        DELETE FROM message_edits WHERE message_id = old.id;
+                    END"
+                ),
+                [],
+            )?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS agent_events (
+                id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                turn INTEGER NOT NULL,
+                event_type TEXT NOT NULL,
+                event_json TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_agent_events_task
+             ON agent_events(task_id, session_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS agent_sessions (
+                task_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                total_turns INTEGER NOT NULL,
+                total_input_tokens INTEGER NOT NULL,
+                total_output_tokens INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (task_id, session_id),
+                FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS conversation_plans (
+                id TEXT PRIMARY KEY,
+                conversation_id TEXT NOT NULL,
+                turn INTEGER NOT NULL,
+                plan_json TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_conversation_plans_conversation
+             ON conversation_plans(conversation_id, created_at)",
+            [],
+        )?;
+
+        // Full-text indexes over message content, kept in sync by triggers
+        create_fts_index(&conn, "messages")?;
+        create_fts_index(&conn, "task_messages")?;
+
         Ok(())
     }
 
     // Settings methods
     pub fn get_settings(&self) -> Result<Settings, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let mut settings = Settings::default();
 
         let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
@@ -277,6 +751,17 @@ impl Database {
                 "max_tokens" => settings.max_tokens = value.parse().unwrap_or(4096),
                 "temperature" => settings.temperature = value.parse().unwrap_or(0.7),
                 "provider" => settings.provider = value,
+                "auto_title_model" => settings.auto_title_model = non_empty(value),
+                "proxy_url" => settings.proxy_url = non_empty(value),
+                "proxy_no_proxy" => settings.proxy_no_proxy = non_empty(value),
+                "system_prompt" => settings.system_prompt = non_empty(value),
+                "system_prompt_mode" => settings.system_prompt_mode = non_empty(value),
+                "disable_streaming" => settings.disable_streaming = value.parse().ok(),
+                "allow_reads_outside_workspace" => settings.allow_reads_outside_workspace = value.parse().ok(),
+                "max_agent_turns" => settings.max_agent_turns = value.parse().ok(),
+                "max_agent_duration_secs" => settings.max_agent_duration_secs = value.parse().ok(),
+                "max_concurrent_agents" => settings.max_concurrent_agents = value.parse().ok(),
+                "mcp_tool_timeout_secs" => settings.mcp_tool_timeout_secs = value.parse().ok(),
                 "provider_keys" => {
                     // Parse JSON to HashMap
                     if let Ok(keys) = serde_json::from_str::<HashMap<String, String>>(&value) {
@@ -303,57 +788,31 @@ impl Database {
     }
 
     pub fn save_settings(&self, settings: &Settings) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
-
-        // If provider is empty, infer automatically
-        let provider = if settings.provider.is_empty() {
-            settings.get_provider()
-        } else {
-            settings.provider.clone()
-        };
-
-        // Serialize provider_keys to JSON
-        let provider_keys_json = serde_json::to_string(&settings.provider_keys)
-            .unwrap_or_else(|_| "{}".to_string());
-
-        let pairs = [
-            ("api_key", settings.api_key.clone()),
-            ("model", settings.model.clone()),
-            ("base_url", settings.base_url.clone()),
-            ("max_tokens", settings.max_tokens.to_string()),
-            ("temperature", settings.temperature.to_string()),
-            ("provider", provider),
-            ("provider_keys", provider_keys_json),
-        ];
-
-        for (key, value) in pairs {
-            conn.execute(
-                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-                [key, &value],
-            )?;
-        }
-
-        Ok(())
+        let conn = self.lock_conn()?;
+        write_settings(&conn, settings)
     }
 
     // Conversation methods
     pub fn list_conversations(&self) -> Result<Vec<Conversation>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        self.list_conversations_by_deleted(false)
+    }
+
+    /// Conversations in the trash, most recently updated first
+    pub fn list_deleted_conversations(&self) -> Result<Vec<Conversation>, DbError> {
+        self.list_conversations_by_deleted(true)
+    }
+
+    fn list_conversations_by_deleted(&self, deleted: bool) -> Result<Vec<Conversation>, DbError> {
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, title, created_at, updated_at
+            "SELECT id, title, created_at, updated_at, deleted_at, project_path
              FROM conversations
+             WHERE (deleted_at IS NOT NULL) = ?1
              ORDER BY updated_at DESC"
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        })?;
+        let rows = stmt.query_map([deleted], conversation_from_row)?;
 
         let mut conversations = Vec::new();
         for row in rows {
@@ -363,13 +822,30 @@ impl Database {
         Ok(conversations)
     }
 
-    pub fn create_conversation(&self, id: &str, title: &str) -> Result<Conversation, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    /// A conversation by id, including one in the trash
+    pub fn get_conversation(&self, id: &str) -> Result<Option<Conversation>, DbError> {
+        let conn = self.lock_conn()?;
+        conn.query_row(
+            "SELECT id, title, created_at, updated_at, deleted_at, project_path FROM conversations WHERE id = ?1",
+            [id],
+            conversation_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn create_conversation(
+        &self,
+        id: &str,
+        title: &str,
+        project_path: Option<&str>,
+    ) -> Result<Conversation, DbError> {
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
-            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
-            [id, title, &now.to_string(), &now.to_string()],
+            "INSERT INTO conversations (id, title, created_at, updated_at, project_path) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, title, now, now, project_path],
         )?;
 
         Ok(Conversation {
@@ -377,11 +853,36 @@ impl Database {
             title: title.to_string(),
             created_at: now,
             updated_at: now,
+            deleted_at: None,
+            project_path: project_path.map(|p| p.to_string()),
         })
     }
 
+    /// Associate a conversation with a workspace
+    pub fn set_conversation_project_path(&self, id: &str, project_path: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE conversations SET project_path = ?1 WHERE id = ?2",
+            [project_path, id],
+        )?;
+        Ok(())
+    }
+
+    /// Conversations (not in the trash) associated with `project_path`, most recently updated first
+    pub fn list_conversations_for_project(&self, project_path: &str) -> Result<Vec<Conversation>, DbError> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, created_at, updated_at, deleted_at, project_path
+             FROM conversations
+             WHERE deleted_at IS NULL AND project_path = ?1
+             ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_map([project_path], conversation_from_row)?;
+        rows.collect::<Result<_, rusqlite::Error>>().map_err(Into::into)
+    }
+
     pub fn update_conversation_title(&self, id: &str, title: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
@@ -392,59 +893,269 @@ impl Database {
         Ok(())
     }
 
-    pub fn delete_conversation(&self, id: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    /// Rename a conversation on the user's behalf; auto-titling won't replace it afterwards
+    pub fn set_custom_conversation_title(&self, id: &str, title: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let now = chrono::Utc::now().timestamp_millis();
 
-        // Delete messages first (cascade)
-        conn.execute("DELETE FROM messages WHERE conversation_id = ?1", [id])?;
-        conn.execute("DELETE FROM conversations WHERE id = ?1", [id])?;
+        conn.execute(
+            "UPDATE conversations SET title = ?1, updated_at = ?2, custom_title = 1 WHERE id = ?3",
+            [title, &now.to_string(), id],
+        )?;
 
         Ok(())
     }
 
-    // Message methods
-    pub fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    /// Whether the user has set the conversation's title themselves
+    pub fn has_custom_title(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.lock_conn()?;
+        let custom: Option<bool> = conn
+            .query_row(
+                "SELECT custom_title FROM conversations WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(custom.unwrap_or(false))
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT id, conversation_id, role, content, timestamp
-             FROM messages
-             WHERE conversation_id = ?1
-             ORDER BY timestamp ASC"
+    /// List conversations a page at a time, continuing after the conversation `before_id`
+    pub fn list_conversations_paginated(
+        &self,
+        limit: u32,
+        before_id: Option<&str>,
+    ) -> Result<ConversationPage, DbError> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+
+        let total_count: u32 = tx.query_row(
+            "SELECT COUNT(*) FROM conversations WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
         )?;
 
-        let rows = stmt.query_map([conversation_id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                timestamp: row.get(4)?,
-            })
-        })?;
+        // Conversations updated in the same millisecond are ordered by id so pages never overlap.
+        // One extra row is fetched to tell whether another page follows.
+        let fetch = limit as i64 + 1;
+        let mut items: Vec<Conversation> = match before_id {
+            Some(before_id) => {
+                let mut stmt = tx.prepare(
+                    "SELECT id, title, created_at, updated_at, deleted_at, project_path
+                     FROM conversations
+                     WHERE deleted_at IS NULL
+                       AND (updated_at, id) < (SELECT updated_at, id FROM conversations WHERE id = ?1)
+                     ORDER BY updated_at DESC, id DESC
+                     LIMIT ?2",
+                )?;
+                let rows = stmt.query_map(rusqlite::params![before_id, fetch], conversation_from_row)?;
+                rows.collect::<Result<_, rusqlite::Error>>()?
+            }
+            None => {
+                let mut stmt = tx.prepare(
+                    "SELECT id, title, created_at, updated_at, deleted_at, project_path
+                     FROM conversations
+                     WHERE deleted_at IS NULL
+                     ORDER BY updated_at DESC, id DESC
+                     LIMIT ?1",
+                )?;
+                let rows = stmt.query_map([fetch], conversation_from_row)?;
+                rows.collect::<Result<_, rusqlite::Error>>()?
+            }
+        };
+        tx.commit()?;
 
-        let mut messages = Vec::new();
-        for row in rows {
-            messages.push(row?);
-        }
+        let next_cursor = if items.len() > limit as usize {
+            items.truncate(limit as usize);
+            items.last().map(|c| c.id.clone())
+        } else {
+            None
+        };
 
-        Ok(messages)
+        Ok(ConversationPage {
+            items,
+            next_cursor,
+            total_count,
+        })
     }
 
-    pub fn add_message(
-        &self,
-        id: &str,
-        conversation_id: &str,
-        role: &str,
-        content: &str,
-    ) -> Result<Message, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    /// Move a conversation to the trash; its messages are kept until it is purged
+    pub fn delete_conversation(&self, id: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            [id, conversation_id, role, content, &now.to_string()],
+            "UPDATE conversations SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            rusqlite::params![now, id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Take a conversation back out of the trash
+    pub fn restore_conversation(&self, id: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute("UPDATE conversations SET deleted_at = NULL WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Permanently delete a trashed conversation and its messages.
+    /// Returns false if the conversation isn't in the trash.
+    pub fn purge_conversation(&self, id: &str) -> Result<bool, DbError> {
+        let conn = self.lock_conn()?;
+        let purged = conn.execute(
+            "DELETE FROM conversations WHERE id = ?1 AND deleted_at IS NOT NULL",
+            [id],
+        )?;
+        Ok(purged > 0)
+    }
+
+    /// Permanently delete conversations that have been in the trash for more than `days` days
+    pub fn purge_conversations_older_than(&self, days: u32) -> Result<u32, DbError> {
+        let conn = self.lock_conn()?;
+        let cutoff = chrono::Utc::now().timestamp_millis() - i64::from(days) * 24 * 60 * 60 * 1000;
+        let purged = conn.execute(
+            "DELETE FROM conversations WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+            [cutoff],
+        )?;
+        Ok(purged as u32)
+    }
+
+    /// Move several conversations to the trash in one transaction, like `delete_conversation`.
+    /// With `dry_run` set nothing is trashed, only counted.
+    pub fn delete_conversations(&self, ids: &[String], dry_run: bool) -> Result<BulkDeleteResult, DbError> {
+        let mut ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let mut seen = std::collections::HashSet::new();
+        ids.retain(|id| seen.insert(*id));
+        if ids.is_empty() {
+            return Ok(BulkDeleteResult::default());
+        }
+
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        let placeholders = vec!["?"; ids.len()].join(", ");
+
+        let existing: std::collections::HashSet<String> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id FROM conversations WHERE id IN ({}) AND deleted_at IS NULL",
+                placeholders
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(&ids), |row| row.get(0))?;
+            rows.collect::<Result<_, rusqlite::Error>>()?
+        };
+
+        let message_count: u32 = tx.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM messages m JOIN conversations c ON c.id = m.conversation_id
+                 WHERE c.id IN ({}) AND c.deleted_at IS NULL",
+                placeholders
+            ),
+            rusqlite::params_from_iter(&ids),
+            |row| row.get(0),
+        )?;
+
+        let deleted_count = if dry_run {
+            existing.len() as u32
+        } else {
+            let now = chrono::Utc::now().timestamp_millis();
+            tx.execute(
+                &format!(
+                    "UPDATE conversations SET deleted_at = ?1 WHERE id IN ({}) AND deleted_at IS NULL",
+                    placeholders
+                ),
+                rusqlite::params_from_iter(
+                    std::iter::once(rusqlite::types::Value::from(now))
+                        .chain(ids.iter().map(|id| rusqlite::types::Value::from(id.to_string()))),
+                ),
+            )? as u32
+        };
+        tx.commit()?;
+
+        Ok(BulkDeleteResult {
+            deleted_count,
+            not_found: ids
+                .into_iter()
+                .filter(|id| !existing.contains(*id))
+                .map(str::to_string)
+                .collect(),
+            message_count,
+        })
+    }
+
+    /// Move every conversation to the trash; with `dry_run` set, only count them
+    pub fn delete_all_conversations(&self, dry_run: bool) -> Result<BulkDeleteResult, DbError> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+
+        let message_count: u32 = tx.query_row(
+            "SELECT COUNT(*) FROM messages m JOIN conversations c ON c.id = m.conversation_id
+             WHERE c.deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        let deleted_count = if dry_run {
+            tx.query_row("SELECT COUNT(*) FROM conversations WHERE deleted_at IS NULL", [], |row| row.get(0))?
+        } else {
+            tx.execute(
+                "UPDATE conversations SET deleted_at = ?1 WHERE deleted_at IS NULL",
+                [chrono::Utc::now().timestamp_millis()],
+            )? as u32
+        };
+        tx.commit()?;
+
+        Ok(BulkDeleteResult {
+            deleted_count,
+            not_found: Vec::new(),
+            message_count,
+        })
+    }
+
+    // Message methods
+    pub fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>, DbError> {
+        let conn = self.lock_conn()?;
+
+        // rowid keeps a tool call and its result in order when saved in the same millisecond
+        let mut stmt = conn.prepare(
+            "SELECT id, conversation_id, role, content, timestamp, message_type
+             FROM messages
+             WHERE conversation_id = ?1
+             ORDER BY timestamp ASC, rowid ASC"
+        )?;
+
+        let rows = stmt.query_map([conversation_id], message_from_row)?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(row?);
+        }
+
+        Ok(messages)
+    }
+
+    pub fn add_message(
+        &self,
+        id: &str,
+        conversation_id: &str,
+        role: &str,
+        content: &str,
+    ) -> Result<Message, DbError> {
+        self.add_message_with_type(id, conversation_id, role, content, None)
+    }
+
+    pub fn add_message_with_type(
+        &self,
+        id: &str,
+        conversation_id: &str,
+        role: &str,
+        content: &str,
+        message_type: Option<&str>,
+    ) -> Result<Message, DbError> {
+        let conn = self.lock_conn()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        conn.execute(
+            "INSERT INTO messages (id, conversation_id, role, content, timestamp, message_type)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![id, conversation_id, role, content, now, message_type],
         )?;
 
         // Update conversation's updated_at
@@ -459,48 +1170,148 @@ impl Database {
             role: role.to_string(),
             content: content.to_string(),
             timestamp: now,
+            message_type: message_type.map(str::to_string),
         })
     }
 
-    #[allow(dead_code)]
-    pub fn update_message_content(&self, id: &str, content: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    pub fn get_message(&self, id: &str) -> Result<Option<Message>, DbError> {
+        let conn = self.lock_conn()?;
 
-        conn.execute(
-            "UPDATE messages SET content = ?1 WHERE id = ?2",
-            [content, id],
+        let mut stmt = conn.prepare(
+            "SELECT id, conversation_id, role, content, timestamp, message_type
+             FROM messages
+             WHERE id = ?1"
         )?;
 
+        let mut rows = stmt.query([id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(message_from_row(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Replace a message's content, keeping the previous content in `message_edits`
+    pub fn update_message_content(&self, id: &str, content: &str) -> Result<(), DbError> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        edit_message_row(&tx, "messages", id, content)?;
+        // The saved agent history still holds the old content
+        tx.execute(
+            "UPDATE conversations SET agent_context_json = NULL
+             WHERE id = (SELECT conversation_id FROM messages WHERE id = ?1)",
+            [id],
+        )?;
+        tx.commit()?;
         Ok(())
     }
 
+    /// Previous contents of a message, oldest edit first
+    pub fn get_message_edit_history(&self, message_id: &str) -> Result<Vec<MessageEdit>, DbError> {
+        let conn = self.lock_conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, message_id, old_content, edited_at
+             FROM message_edits
+             WHERE message_id = ?1
+             ORDER BY edited_at ASC, rowid ASC"
+        )?;
+
+        let rows = stmt.query_map([message_id], |row| {
+            Ok(MessageEdit {
+                id: row.get(0)?,
+                message_id: row.get(1)?,
+                old_content: row.get(2)?,
+                edited_at: row.get(3)?,
+            })
+        })?;
+
+        let mut edits = Vec::new();
+        for row in rows {
+            edits.push(row?);
+        }
+
+        Ok(edits)
+    }
+
+    pub fn get_conversation_stats(&self, conversation_id: &str) -> Result<ConversationStats, DbError> {
+        let conn = self.lock_conn()?;
+        // Intermediate tool call steps are not chat turns
+        query_message_stats(&conn, "messages", "conversation_id = ?1 AND message_type IS NULL", conversation_id)
+    }
+
     // Task methods
+    /// Active (not archived) tasks, most recently updated first
     pub fn list_tasks(&self) -> Result<Vec<Task>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        self.list_tasks_by_archived(false)
+    }
+
+    pub fn list_archived_tasks(&self) -> Result<Vec<Task>, DbError> {
+        self.list_tasks_by_archived(true)
+    }
+
+    fn list_tasks_by_archived(&self, archived: bool) -> Result<Vec<Task>, DbError> {
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, title, description, status, plan, current_step, project_path, created_at, updated_at
+            "SELECT id, title, description, status, plan, current_step, project_path, created_at, updated_at, archived, template_id
              FROM tasks
+             WHERE archived = ?1
              ORDER BY updated_at DESC"
         )?;
 
-        let rows = stmt.query_map([], |row| {
-            let plan_json: Option<String> = row.get(4)?;
-            let plan: Option<Vec<PlanStep>> = plan_json
-                .and_then(|json| serde_json::from_str(&json).ok());
+        let rows = stmt.query_map([archived], task_from_row)?;
 
-            Ok(Task {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                status: row.get(3)?,
-                plan,
-                current_step: row.get(5)?,
-                project_path: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(row?);
+        }
+
+        Ok(tasks)
+    }
+
+    /// Unarchived tasks matching every given filter, most recently updated first.
+    /// `query` matches anywhere in the title or description, case-insensitively;
+    /// `created_after`/`created_before` are inclusive millisecond timestamps.
+    pub fn search_tasks(
+        &self,
+        query: Option<&str>,
+        status: Option<&str>,
+        created_after: Option<i64>,
+        created_before: Option<i64>,
+    ) -> Result<Vec<Task>, DbError> {
+        let mut sql = String::from(
+            "SELECT id, title, description, status, plan, current_step, project_path, created_at, updated_at, archived, template_id
+             FROM tasks
+             WHERE archived = 0",
+        );
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+        if let Some(query) = query.map(str::trim).filter(|q| !q.is_empty()) {
+            params.push(format!("%{}%", escape_like(query)).into());
+            sql.push_str(&format!(
+                " AND (title LIKE ?{0} ESCAPE '\\' OR description LIKE ?{0} ESCAPE '\\')",
+                params.len()
+            ));
+        }
+        if let Some(status) = status {
+            params.push(status.to_string().into());
+            sql.push_str(&format!(" AND status = ?{}", params.len()));
+        }
+        if let Some(created_after) = created_after {
+            params.push(created_after.into());
+            sql.push_str(&format!(" AND created_at >= ?{}", params.len()));
+        }
+        if let Some(created_before) = created_before {
+            params.push(created_before.into());
+            sql.push_str(&format!(" AND created_at <= ?{}", params.len()));
+        }
+        sql.push_str(" ORDER BY updated_at DESC");
+
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), task_from_row)?;
 
         let mut tasks = Vec::new();
         for row in rows {
@@ -511,44 +1322,80 @@ impl Database {
     }
 
     pub fn get_task(&self, id: &str) -> Result<Option<Task>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, title, description, status, plan, current_step, project_path, created_at, updated_at
+            "SELECT id, title, description, status, plan, current_step, project_path, created_at, updated_at, archived, template_id
              FROM tasks WHERE id = ?1"
         )?;
 
         let mut rows = stmt.query([id])?;
 
         if let Some(row) = rows.next()? {
-            let plan_json: Option<String> = row.get(4)?;
-            let plan: Option<Vec<PlanStep>> = plan_json
-                .and_then(|json| serde_json::from_str(&json).ok());
-
-            Ok(Some(Task {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                status: row.get(3)?,
-                plan,
-                current_step: row.get(5)?,
-                project_path: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            }))
+            Ok(Some(task_from_row(row)?))
         } else {
             Ok(None)
         }
     }
 
+    /// The task with its message count and the stats of its latest agent run
+    pub fn get_task_with_stats(&self, id: &str) -> Result<Option<TaskWithStats>, DbError> {
+        let Some(task) = self.get_task(id)? else {
+            return Ok(None);
+        };
+
+        let conn = self.lock_conn()?;
+        let message_count: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM task_messages WHERE task_id = ?1",
+            [id],
+            |row| row.get(0),
+        )?;
+        // rowid breaks ties between sessions saved in the same millisecond
+        let last_session = conn
+            .query_row(
+                "SELECT total_turns, total_input_tokens, total_output_tokens, duration_ms
+                 FROM agent_sessions
+                 WHERE task_id = ?1
+                 ORDER BY created_at DESC, rowid DESC
+                 LIMIT 1",
+                [id],
+                |row| {
+                    Ok(AgentSessionStats {
+                        total_turns: row.get(0)?,
+                        total_input_tokens: row.get(1)?,
+                        total_output_tokens: row.get(2)?,
+                        duration_ms: row.get::<_, i64>(3)? as u64,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(Some(TaskWithStats {
+            task,
+            last_session,
+            message_count,
+        }))
+    }
+
     pub fn create_task(&self, id: &str, title: &str, description: &str, project_path: Option<&str>) -> Result<Task, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        self.create_task_with_template(id, title, description, project_path, None)
+    }
+
+    pub fn create_task_with_template(
+        &self,
+        id: &str,
+        title: &str,
+        description: &str,
+        project_path: Option<&str>,
+        template_id: Option<&str>,
+    ) -> Result<Task, DbError> {
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
-            "INSERT INTO tasks (id, title, description, status, current_step, project_path, created_at, updated_at)
-             VALUES (?1, ?2, ?3, 'planning', 0, ?4, ?5, ?6)",
-            rusqlite::params![id, title, description, project_path, now, now],
+            "INSERT INTO tasks (id, title, description, status, current_step, project_path, created_at, updated_at, template_id)
+             VALUES (?1, ?2, ?3, 'planning', 0, ?4, ?5, ?6, ?7)",
+            rusqlite::params![id, title, description, project_path, now, now, template_id],
         )?;
 
         Ok(Task {
@@ -561,11 +1408,13 @@ impl Database {
             project_path: project_path.map(|s| s.to_string()),
             created_at: now,
             updated_at: now,
+            archived: false,
+            template_id: template_id.map(|s| s.to_string()),
         })
     }
 
     pub fn update_task_plan(&self, id: &str, plan: &[PlanStep]) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
         let plan_json = serde_json::to_string(plan).unwrap_or_default();
 
@@ -578,7 +1427,7 @@ impl Database {
     }
 
     pub fn update_task_step(&self, id: &str, current_step: i32, step_status: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         // Get current plan and update the step status
@@ -602,7 +1451,7 @@ impl Database {
     }
 
     pub fn update_task_status(&self, id: &str, status: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
@@ -613,17 +1462,144 @@ impl Database {
         Ok(())
     }
 
+    /// Store the validated structured output of a task
+    pub fn set_task_output_json(&self, id: &str, output_json: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE tasks SET task_output_json = ?1 WHERE id = ?2",
+            [output_json, id],
+        )?;
+        Ok(())
+    }
+
+    /// Forget the structured output of a task's previous run
+    pub fn clear_task_output_json(&self, id: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute("UPDATE tasks SET task_output_json = NULL WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn get_task_output_json(&self, id: &str) -> Result<Option<String>, DbError> {
+        let conn = self.lock_conn()?;
+        let output: Option<Option<String>> = conn
+            .query_row(
+                "SELECT task_output_json FROM tasks WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(output.flatten())
+    }
+
+    pub fn archive_task(&self, id: &str) -> Result<(), DbError> {
+        self.set_task_archived(id, true)
+    }
+
+    pub fn unarchive_task(&self, id: &str) -> Result<(), DbError> {
+        self.set_task_archived(id, false)
+    }
+
+    fn set_task_archived(&self, id: &str, archived: bool) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        conn.execute(
+            "UPDATE tasks SET archived = ?1, updated_at = ?2 WHERE id = ?3",
+            rusqlite::params![archived, now, id],
+        )?;
+
+        Ok(())
+    }
+
     pub fn delete_task(&self, id: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
-        // Delete messages first
-        conn.execute("DELETE FROM task_messages WHERE task_id = ?1", [id])?;
-        conn.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
+        let files = {
+            let conn = self.lock_conn()?;
+            let files = attachment_files(&conn, Some(id))?;
+            // Delete messages first; their attachment rows go with them via cascade
+            conn.execute("DELETE FROM task_messages WHERE task_id = ?1", [id])?;
+            conn.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
+            files
+        };
+        remove_attachment_files(&files);
+        Ok(())
+    }
+
+    // Task template methods
+    /// Insert or replace a template; `created_at` is set on first save
+    pub fn save_task_template(&self, template: &TaskTemplate) -> Result<TaskTemplate, DbError> {
+        let conn = self.lock_conn()?;
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT created_at FROM task_templates WHERE id = ?1",
+                [&template.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let created_at = existing.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let allowed_tools = serde_json::to_string(&template.allowed_tools).unwrap_or_else(|_| "[]".to_string());
+
+        conn.execute(
+            "INSERT OR REPLACE INTO task_templates
+             (id, name, description, system_prompt, allowed_tools, max_turns, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                template.id,
+                template.name,
+                template.description,
+                template.system_prompt,
+                allowed_tools,
+                template.max_turns,
+                created_at
+            ],
+        )?;
+
+        Ok(TaskTemplate {
+            created_at,
+            ..template.clone()
+        })
+    }
+
+    /// All templates, by name
+    pub fn list_task_templates(&self) -> Result<Vec<TaskTemplate>, DbError> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, system_prompt, allowed_tools, max_turns, created_at
+             FROM task_templates
+             ORDER BY name COLLATE NOCASE, created_at",
+        )?;
+
+        let rows = stmt.query_map([], task_template_from_row)?;
+
+        let mut templates = Vec::new();
+        for row in rows {
+            templates.push(row?);
+        }
+
+        Ok(templates)
+    }
+
+    pub fn get_task_template(&self, id: &str) -> Result<Option<TaskTemplate>, DbError> {
+        let conn = self.lock_conn()?;
+        conn.query_row(
+            "SELECT id, name, description, system_prompt, allowed_tools, max_turns, created_at
+             FROM task_templates WHERE id = ?1",
+            [id],
+            task_template_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    /// Tasks created from the template keep their `template_id` and fall back to default settings
+    pub fn delete_task_template(&self, id: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute("DELETE FROM task_templates WHERE id = ?1", [id])?;
         Ok(())
     }
 
     // Task message methods
     pub fn get_task_messages(&self, task_id: &str) -> Result<Vec<TaskMessage>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
             "SELECT id, task_id, role, content, timestamp
@@ -650,6 +1626,39 @@ impl Database {
         Ok(messages)
     }
 
+    pub fn get_task_message(&self, id: &str) -> Result<Option<TaskMessage>, DbError> {
+        let conn = self.lock_conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, role, content, timestamp
+             FROM task_messages
+             WHERE id = ?1"
+        )?;
+
+        let mut rows = stmt.query([id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(TaskMessage {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                role: row.get(2)?,
+                content: row.get(3)?,
+                timestamp: row.get(4)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Replace a task message's content, keeping the previous content in `message_edits`
+    pub fn update_task_message_content(&self, id: &str, content: &str) -> Result<(), DbError> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        edit_message_row(&tx, "task_messages", id, content)?;
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn add_task_message(
         &self,
         id: &str,
@@ -657,7 +1666,7 @@ impl Database {
         role: &str,
         content: &str,
     ) -> Result<TaskMessage, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
         let now = chrono::Utc::now().timestamp_millis();
 
         conn.execute(
@@ -681,15 +1690,1395 @@ impl Database {
         })
     }
 
-    #[allow(dead_code)]
-    pub fn update_task_message_content(&self, id: &str, content: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+    /// Store an image for a task message. Large images are written to a file
+    /// in the attachments directory and only referenced from the row.
+    pub fn add_task_message_attachment(
+        &self,
+        message_id: &str,
+        name: &str,
+        media_type: &str,
+        data: &str,
+    ) -> Result<TaskAttachment, DbError> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut file_path = None;
+        if data.len() > ATTACHMENT_INLINE_LIMIT {
+            // Data that isn't valid base64 stays inline rather than being lost
+            if let Ok(bytes) = general_purpose::STANDARD.decode(data) {
+                std::fs::create_dir_all(&self.attachments_dir)?;
+                let path = self.attachments_dir.join(&id);
+                std::fs::write(&path, bytes)?;
+                file_path = Some(path.to_string_lossy().to_string());
+            }
+        }
+        let inline_data = if file_path.is_none() { Some(data) } else { None };
 
+        let conn = self.lock_conn()?;
         conn.execute(
-            "UPDATE task_messages SET content = ?1 WHERE id = ?2",
-            [content, id],
+            "INSERT INTO task_message_attachments (id, message_id, name, media_type, data_base64, file_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![id, message_id, name, media_type, inline_data, file_path],
+        )?;
+
+        Ok(TaskAttachment {
+            id,
+            message_id: message_id.to_string(),
+            name: name.to_string(),
+            media_type: media_type.to_string(),
+            data_base64: data.to_string(),
+            missing: false,
+        })
+    }
+
+    pub fn get_task_message_attachments(&self, message_id: &str) -> Result<Vec<TaskAttachment>, DbError> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let rows: Vec<AttachmentRow> = {
+            let conn = self.lock_conn()?;
+            let mut stmt = conn.prepare(
+                "SELECT id, name, media_type, data_base64, file_path
+                 FROM task_message_attachments
+                 WHERE message_id = ?1
+                 ORDER BY rowid ASC",
+            )?;
+            let rows = stmt.query_map([message_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })?;
+            rows.collect::<Result<_, rusqlite::Error>>()?
+        };
+
+        let mut attachments = Vec::new();
+        for (id, name, media_type, data, file_path) in rows {
+            let mut missing = false;
+            let data_base64 = match (data, file_path) {
+                (Some(data), _) => data,
+                (None, Some(path)) => match std::fs::read(&path) {
+                    Ok(bytes) => general_purpose::STANDARD.encode(bytes),
+                    Err(e) => {
+                        // A lost file shouldn't keep the rest of the history from loading
+                        eprintln!("[database] Attachment {} unreadable at {}: {}", id, path, e);
+                        missing = true;
+                        String::new()
+                    }
+                },
+                (None, None) => String::new(),
+            };
+            attachments.push(TaskAttachment {
+                id,
+                message_id: message_id.to_string(),
+                name,
+                media_type,
+                data_base64,
+                missing,
+            });
+        }
+
+        Ok(attachments)
+    }
+
+    pub fn get_task_stats(&self, task_id: &str) -> Result<ConversationStats, DbError> {
+        let conn = self.lock_conn()?;
+        query_message_stats(&conn, "task_messages", "task_id = ?1", task_id)
+    }
+
+    // Conversation plan methods
+    /// Record the plan an agent laid out during `turn` of a conversation
+    pub fn save_conversation_plan(
+        &self,
+        conversation_id: &str,
+        turn: u32,
+        plan: &[PlanStep],
+    ) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let plan_json = serde_json::to_string(plan).unwrap_or_default();
+
+        conn.execute(
+            "INSERT INTO conversation_plans (id, conversation_id, turn, plan_json, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                uuid::Uuid::new_v4().to_string(),
+                conversation_id,
+                turn,
+                plan_json,
+                chrono::Utc::now().timestamp_millis()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// The most recent plan saved for a conversation
+    pub fn get_conversation_plan(&self, conversation_id: &str) -> Result<Option<Vec<PlanStep>>, DbError> {
+        let conn = self.lock_conn()?;
+        let plan_json: Option<String> = conn
+            .query_row(
+                "SELECT plan_json FROM conversation_plans
+                 WHERE conversation_id = ?1
+                 ORDER BY created_at DESC, rowid DESC
+                 LIMIT 1",
+                [conversation_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(plan_json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Set the status of one step in the conversation's most recent plan
+    pub fn update_conversation_plan_step(
+        &self,
+        conversation_id: &str,
+        step: i32,
+        status: &str,
+    ) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let latest: Option<(String, String)> = conn
+            .query_row(
+                "SELECT id, plan_json FROM conversation_plans
+                 WHERE conversation_id = ?1
+                 ORDER BY created_at DESC, rowid DESC
+                 LIMIT 1",
+                [conversation_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let Some((id, plan_json)) = latest else {
+            return Ok(());
+        };
+        let Ok(mut plan) = serde_json::from_str::<Vec<PlanStep>>(&plan_json) else {
+            return Ok(());
+        };
+        if let Some(plan_step) = plan.iter_mut().find(|s| s.step == step) {
+            plan_step.status = status.to_string();
+            conn.execute(
+                "UPDATE conversation_plans SET plan_json = ?1 WHERE id = ?2",
+                rusqlite::params![serde_json::to_string(&plan).unwrap_or_default(), id],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_agent_context(&self, conversation_id: &str, context_json: &str) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE conversations SET agent_context_json = ?1 WHERE id = ?2",
+            [context_json, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_agent_context(&self, conversation_id: &str) -> Result<Option<String>, DbError> {
+        let conn = self.lock_conn()?;
+        let context_json: Option<Option<String>> = conn
+            .query_row(
+                "SELECT agent_context_json FROM conversations WHERE id = ?1",
+                [conversation_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(context_json.flatten())
+    }
+
+    // Agent event methods
+    pub fn add_agent_event(
+        &self,
+        id: &str,
+        task_id: &str,
+        session_id: &str,
+        turn: u32,
+        event_type: &str,
+        event_json: &str,
+    ) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        conn.execute(
+            "INSERT INTO agent_events (id, task_id, session_id, turn, event_type, event_json, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![id, task_id, session_id, turn, event_type, event_json, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Events for a task (optionally one session) in the order they were recorded
+    pub fn get_agent_events(&self, task_id: &str, session_id: Option<&str>) -> Result<Vec<StoredAgentEvent>, DbError> {
+        let conn = self.lock_conn()?;
+
+        // rowid breaks ties between events recorded in the same millisecond
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, session_id, turn, event_type, event_json, timestamp
+             FROM agent_events
+             WHERE task_id = ?1 AND (?2 IS NULL OR session_id = ?2)
+             ORDER BY timestamp ASC, rowid ASC"
+        )?;
+
+        let rows = stmt.query_map(rusqlite::params![task_id, session_id], |row| {
+            let event_json: String = row.get(5)?;
+            Ok(StoredAgentEvent {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                session_id: row.get(2)?,
+                turn: row.get(3)?,
+                event_type: row.get(4)?,
+                event: serde_json::from_str(&event_json).unwrap_or(serde_json::Value::Null),
+                timestamp: row.get(6)?,
+            })
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            events.push(row?);
+        }
+
+        Ok(events)
+    }
+
+    /// Record the totals of an agent run, replacing any earlier save for the same session
+    pub fn save_agent_session(&self, task_id: &str, session_id: &str, stats: &AgentSessionStats) -> Result<(), DbError> {
+        let conn = self.lock_conn()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO agent_sessions
+             (task_id, session_id, total_turns, total_input_tokens, total_output_tokens, duration_ms, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                task_id,
+                session_id,
+                stats.total_turns,
+                stats.total_input_tokens,
+                stats.total_output_tokens,
+                stats.duration_ms as i64,
+                now
+            ],
         )?;
 
         Ok(())
     }
+
+    // Search methods
+    /// Full-text search over conversation and task messages, best matches first
+    pub fn global_search(&self, query: &str, limit: u32) -> Result<Vec<GlobalSearchResult>, DbError> {
+        let Some(match_query) = fts_match_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.lock_conn()?;
+
+        // bm25 rank is lower for better matches, so both sets sort together ascending
+        let mut stmt = conn.prepare(
+            "SELECT kind, parent_id, parent_title, message_id, role, snippet, timestamp FROM (
+                SELECT 'conversation' AS kind, m.conversation_id AS parent_id, c.title AS parent_title,
+                       m.id AS message_id, m.role AS role,
+                       snippet(messages_fts, 0, '[', ']', '...', 12) AS snippet,
+                       m.timestamp AS timestamp, messages_fts.rank AS rank
+                FROM messages_fts
+                JOIN messages m ON m.id = messages_fts.message_id
+                JOIN conversations c ON c.id = m.conversation_id
+                WHERE messages_fts MATCH ?1 AND c.deleted_at IS NULL AND m.message_type IS NULL
+                UNION ALL
+                SELECT 'task', tm.task_id, t.title, tm.id, tm.role,
+                       snippet(task_messages_fts, 0, '[', ']', '...', 12),
+                       tm.timestamp, task_messages_fts.rank
+                FROM task_messages_fts
+                JOIN task_messages tm ON tm.id = task_messages_fts.message_id
+                JOIN tasks t ON t.id = tm.task_id
+                WHERE task_messages_fts MATCH ?1
+             )
+             ORDER BY rank
+             LIMIT ?2"
+        )?;
+
+        let rows = stmt.query_map(rusqlite::params![match_query, limit], |row| {
+            Ok(GlobalSearchResult {
+                kind: row.get(0)?,
+                parent_id: row.get(1)?,
+                parent_title: row.get(2)?,
+                message_id: row.get(3)?,
+                role: row.get(4)?,
+                snippet: row.get(5)?,
+                timestamp: row.get(6)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+
+        Ok(results)
+    }
+}
+
+/// Create `{table}_fts` with sync triggers, indexing existing rows the first time
+/// (`table` is a trusted constant)
+fn create_fts_index(conn: &Connection, table: &str) -> Result<(), DbError> {
+    let fts_table = format!("{}_fts", table);
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [&fts_table],
+        |row| row.get(0),
+    )?;
+
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5(content, message_id UNINDEXED);
+
+         CREATE TRIGGER IF NOT EXISTS {fts}_insert AFTER INSERT ON {table} BEGIN
+             INSERT INTO {fts} (content, message_id) VALUES (new.content, new.id);
+         END;
+
+         CREATE TRIGGER IF NOT EXISTS {fts}_delete AFTER DELETE ON {table} BEGIN
+             DELETE FROM {fts} WHERE message_id = old.id;
+         END;
+
+         CREATE TRIGGER IF NOT EXISTS {fts}_update AFTER UPDATE OF content ON {table} BEGIN
+             DELETE FROM {fts} WHERE message_id = old.id;
+             INSERT INTO {fts} (content, message_id) VALUES (new.content, new.id);
+         END;",
+        fts = fts_table,
+        table = table
+    ))?;
+
+    if !exists {
+        conn.execute(
+            &format!("INSERT INTO {} (content, message_id) SELECT content, id FROM {}", fts_table, table),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Turn free text into an FTS5 query that matches all of its words.
+/// Each word is quoted so FTS syntax characters are searched literally.
+fn fts_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Files behind stored attachments, for one task or for all tasks
+pub(crate) fn attachment_files(conn: &Connection, task_id: Option<&str>) -> Result<Vec<String>, DbError> {
+    let mut stmt = conn.prepare(
+        "SELECT a.file_path FROM task_message_attachments a
+         JOIN task_messages m ON m.id = a.message_id
+         WHERE a.file_path IS NOT NULL AND (?1 IS NULL OR m.task_id = ?1)",
+    )?;
+    let files = stmt
+        .query_map([task_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(files)
+}
+
+/// Delete attachment files whose rows are gone; a file that can't be removed is only logged
+pub(crate) fn remove_attachment_files(files: &[String]) {
+    for file in files {
+        if let Err(e) = std::fs::remove_file(file) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("[database] Failed to remove attachment file {}: {}", file, e);
+            }
+        }
+    }
+}
+
+/// Optional settings are stored as an empty string when unset
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Store every settings field as a key/value row
+pub(crate) fn write_settings(conn: &Connection, settings: &Settings) -> Result<(), DbError> {
+    // If provider is empty, infer automatically
+    let provider = if settings.provider.is_empty() {
+        settings.get_provider()
+    } else {
+        settings.provider.clone()
+    };
+
+    // Serialize provider_keys to JSON
+    let provider_keys_json = serde_json::to_string(&settings.provider_keys)
+        .unwrap_or_else(|_| "{}".to_string());
+
+    let pairs = [
+        ("api_key", settings.api_key.clone()),
+        ("model", settings.model.clone()),
+        ("base_url", settings.base_url.clone()),
+        ("max_tokens", settings.max_tokens.to_string()),
+        ("temperature", settings.temperature.to_string()),
+        ("provider", provider),
+        ("provider_keys", provider_keys_json),
+        ("auto_title_model", settings.auto_title_model.clone().unwrap_or_default()),
+        ("proxy_url", settings.proxy_url.clone().unwrap_or_default()),
+        ("proxy_no_proxy", settings.proxy_no_proxy.clone().unwrap_or_default()),
+        ("disable_streaming", settings.disable_streaming.map(|v| v.to_string()).unwrap_or_default()),
+        (
+            "allow_reads_outside_workspace",
+            settings.allow_reads_outside_workspace.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+        ("system_prompt", settings.system_prompt.clone().unwrap_or_default()),
+        ("system_prompt_mode", settings.system_prompt_mode.clone().unwrap_or_default()),
+        ("max_agent_turns", settings.max_agent_turns.map(|v| v.to_string()).unwrap_or_default()),
+        (
+            "max_agent_duration_secs",
+            settings.max_agent_duration_secs.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+        (
+            "max_concurrent_agents",
+            settings.max_concurrent_agents.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+        (
+            "mcp_tool_timeout_secs",
+            settings.mcp_tool_timeout_secs.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+    ];
+
+    for (key, value) in pairs {
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            [key, &value],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Map a `conversations` row selected as `id, title, created_at, updated_at, deleted_at, project_path`
+fn conversation_from_row(row: &rusqlite::Row) -> rusqlite::Result<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        deleted_at: row.get(4)?,
+        project_path: row.get(5)?,
+    })
+}
+
+fn message_from_row(row: &rusqlite::Row) -> rusqlite::Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        timestamp: row.get(4)?,
+        message_type: row.get(5)?,
+    })
+}
+
+/// Escape `%`, `_` and `\` so they match literally in a `LIKE ... ESCAPE '\'` pattern
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Map a `tasks` row selected as
+/// `id, title, description, status, plan, current_step, project_path, created_at, updated_at, archived, template_id`
+fn task_from_row(row: &rusqlite::Row) -> rusqlite::Result<Task> {
+    let plan_json: Option<String> = row.get(4)?;
+    let plan: Option<Vec<PlanStep>> = plan_json
+        .and_then(|json| serde_json::from_str(&json).ok());
+
+    Ok(Task {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        status: row.get(3)?,
+        plan,
+        current_step: row.get(5)?,
+        project_path: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        archived: row.get(9)?,
+        template_id: row.get(10)?,
+    })
+}
+
+fn task_template_from_row(row: &rusqlite::Row) -> rusqlite::Result<TaskTemplate> {
+    let allowed_tools: String = row.get(4)?;
+
+    Ok(TaskTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        system_prompt: row.get(3)?,
+        allowed_tools: serde_json::from_str(&allowed_tools).unwrap_or_default(),
+        max_turns: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Add a column to an existing table, ignoring "duplicate column" errors
+pub(crate) fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), DbError> {
+    let sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
+    match conn.execute(&sql, []) {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            let msg = err.to_string().to_lowercase();
+            if msg.contains("duplicate column name") {
+                Ok(())
+            } else {
+                Err(err.into())
+            }
+        }
+    }
+}
+
+/// Record the current content of a message row in `message_edits`, then overwrite it.
+/// Does nothing if the row doesn't exist.
+fn edit_message_row(conn: &Connection, table: &str, id: &str, content: &str) -> Result<(), DbError> {
+    let old_content: Option<String> = conn
+        .query_row(
+            &format!("SELECT content FROM {} WHERE id = ?1", table),
+            [id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(old_content) = old_content else {
+        return Ok(());
+    };
+
+    conn.execute(
+        "INSERT INTO message_edits (id, message_id, old_content, edited_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![
+            uuid::Uuid::new_v4().to_string(),
+            id,
+            old_content,
+            chrono::Utc::now().timestamp_millis()
+        ],
+    )?;
+    conn.execute(
+        &format!("UPDATE {} SET content = ?1 WHERE id = ?2", table),
+        [content, id],
+    )?;
+
+    Ok(())
+}
+
+/// Aggregate message stats for the rows of `table` matching `condition`, which binds
+/// `owner_id` as `?1` (`table` and `condition` are trusted constants)
+fn query_message_stats(
+    conn: &Connection,
+    table: &str,
+    condition: &str,
+    owner_id: &str,
+) -> Result<ConversationStats, DbError> {
+    // LENGTH of the BLOB cast counts bytes rather than characters
+    let sql = format!(
+        "SELECT COUNT(*),
+                COALESCE(SUM(CASE WHEN role = 'user' THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN role = 'assistant' THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(LENGTH(CAST(content AS BLOB))), 0),
+                COALESCE(MIN(timestamp), 0),
+                COALESCE(MAX(timestamp), 0)
+         FROM {} WHERE {}",
+        table, condition
+    );
+
+    let (count, user_turns, assistant_turns, bytes, first, last) =
+        conn.query_row(&sql, [owner_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })?;
+
+    if count == 0 {
+        return Ok(ConversationStats::default());
+    }
+
+    Ok(ConversationStats {
+        message_count: count as u32,
+        user_turns: user_turns as u32,
+        assistant_turns: assistant_turns as u32,
+        estimated_tokens: estimate_message_tokens(bytes.max(0) as u64),
+        first_message_at: first,
+        last_message_at: last,
+        duration_secs: (last.saturating_sub(first).max(0) / 1000) as u64,
+    })
+}
+
+fn wal_checkpoint(conn: &Connection, mode: &str) -> rusqlite::Result<CheckpointStats> {
+    // Columns: busy flag, frames in the WAL, frames checkpointed (-1 when not in WAL mode)
+    conn.query_row(&format!("PRAGMA wal_checkpoint({})", mode), [], |row| {
+        let log_frames: i64 = row.get(1)?;
+        let checkpointed: i64 = row.get(2)?;
+        Ok(CheckpointStats {
+            pages_written: log_frames.max(0) as u32,
+            frames_checkpointed: checkpointed.max(0) as u32,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_db_path;
+
+    #[test]
+    fn test_truncate_checkpoint_empties_wal() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("conv-1", "Checkpoint", None).unwrap();
+        for i in 0..10 {
+            db.add_message(&format!("m{}", i), "conv-1", "user", "hello").unwrap();
+        }
+        assert!(db.write_count.load(Ordering::Relaxed) >= 10);
+
+        let stats = db.checkpoint("truncate").unwrap();
+        assert_eq!(stats.pages_written, stats.frames_checkpointed);
+        assert_eq!(db.write_count.load(Ordering::Relaxed), 0);
+
+        let wal_path = PathBuf::from(format!("{}-wal", path.display()));
+        let wal_len = std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+        assert_eq!(wal_len, 0);
+
+        assert!(matches!(db.checkpoint("RESTART"), Err(DbError::InvalidCheckpointMode(_))));
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&wal_path);
+        let _ = std::fs::remove_file(PathBuf::from(format!("{}-shm", path.display())));
+    }
+
+    #[test]
+    fn test_automatic_checkpoint_after_write_threshold() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("conv-1", "Busy", None).unwrap();
+        db.write_count.store(AUTO_CHECKPOINT_WRITES, Ordering::Relaxed);
+
+        db.get_messages("conv-1").unwrap();
+        assert_eq!(db.write_count.load(Ordering::Relaxed), 0);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_connection_pragmas_applied() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+
+        {
+            let conn = db.conn.lock().unwrap();
+            let journal_mode: String = conn
+                .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(journal_mode.to_lowercase(), "wal");
+
+            let synchronous: i64 = conn
+                .query_row("PRAGMA synchronous", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(synchronous, 1); // NORMAL
+
+            let cache_size: i64 = conn
+                .query_row("PRAGMA cache_size", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(cache_size, -64000);
+
+            let foreign_keys: i64 = conn
+                .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(foreign_keys, 1);
+        }
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stats_for_empty_conversation() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("empty", "Empty", None).unwrap();
+
+        assert_eq!(db.get_conversation_stats("empty").unwrap(), ConversationStats::default());
+        assert_eq!(db.get_task_stats("missing-task").unwrap(), ConversationStats::default());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_conversation_stats_duration() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("c1", "Stats", None).unwrap();
+
+        {
+            let conn = db.conn.lock().unwrap();
+            let rows = [
+                ("m1", "user", "12345678", 1_000_000i64),
+                ("m2", "assistant", "abcd", 1_030_500),
+                ("m3", "user", "xy", 1_095_000),
+            ];
+            for (id, role, content, ts) in rows {
+                conn.execute(
+                    "INSERT INTO messages (id, conversation_id, role, content, timestamp) VALUES (?1, 'c1', ?2, ?3, ?4)",
+                    rusqlite::params![id, role, content, ts],
+                )
+                .unwrap();
+            }
+        }
+
+        let stats = db.get_conversation_stats("c1").unwrap();
+        assert_eq!(stats.message_count, 3);
+        assert_eq!(stats.user_turns, 2);
+        assert_eq!(stats.assistant_turns, 1);
+        assert_eq!(stats.estimated_tokens, 4); // 14 bytes -> ceil(14 / 4)
+        assert_eq!(stats.first_message_at, 1_000_000);
+        assert_eq!(stats.last_message_at, 1_095_000);
+        assert_eq!(stats.duration_secs, 95);
+
+        // Intermediate tool call steps are left out of the stats
+        db.add_message_with_type("m4", "c1", "assistant", "{}", Some(INTERMEDIATE_MESSAGE_TYPE))
+            .unwrap();
+        assert_eq!(db.get_conversation_stats("c1").unwrap().message_count, 3);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Seed a conversation and a task whose messages match "kubernetes" with varying relevance
+    fn seed_search_fixture(db: &Database) {
+        db.create_conversation("conv-1", "Cluster chat", None).unwrap();
+        db.create_task("task-1", "Deploy task", "Roll out the release", None).unwrap();
+
+        let conn = db.conn.lock().unwrap();
+        let rows = [
+            ("messages", "conversation_id", "conv-1", "c1", "kubernetes kubernetes kubernetes kubernetes", 1i64),
+            ("task_messages", "task_id", "task-1", "t1", "kubernetes kubernetes deploy", 2),
+            ("messages", "conversation_id", "conv-1", "c2", "notes about the kubernetes rollout for the staging cluster today", 3),
+            ("task_messages", "task_id", "task-1", "t2", "a long status update that mentions kubernetes once among many other words", 4),
+            ("messages", "conversation_id", "conv-1", "c3", "lunch plans for friday", 5),
+            ("task_messages", "task_id", "task-1", "t3", "write the quarterly report", 6),
+        ];
+        for (table, owner_column, owner_id, id, content, ts) in rows {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {} (id, {}, role, content, timestamp) VALUES (?1, ?2, 'user', ?3, ?4)",
+                    table, owner_column
+                ),
+                rusqlite::params![id, owner_id, content, ts],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_global_search_interleaves_by_relevance() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        seed_search_fixture(&db);
+
+        let results = db.global_search("kubernetes", 50).unwrap();
+        let ids: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (r.kind.as_str(), r.message_id.as_str()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![("conversation", "c1"), ("task", "t1"), ("conversation", "c2"), ("task", "t2")]
+        );
+        assert_eq!(results[1].parent_id, "task-1");
+        assert_eq!(results[1].parent_title, "Deploy task");
+        assert_eq!(results[1].snippet, "[kubernetes] [kubernetes] deploy");
+
+        // The limit applies across both sources
+        let limited = db.global_search("kubernetes", 3).unwrap();
+        assert_eq!(limited.len(), 3);
+        assert_eq!(limited[2].message_id, "c2");
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_global_search_single_source_and_no_match() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        seed_search_fixture(&db);
+
+        let tasks_only = db.global_search("quarterly report", 50).unwrap();
+        assert_eq!(tasks_only.len(), 1);
+        assert_eq!(tasks_only[0].kind, "task");
+        assert_eq!(tasks_only[0].message_id, "t3");
+
+        let conversations_only = db.global_search("lunch", 50).unwrap();
+        assert_eq!(conversations_only.len(), 1);
+        assert_eq!(conversations_only[0].kind, "conversation");
+        assert_eq!(conversations_only[0].parent_title, "Cluster chat");
+
+        db.add_message_with_type("c4", "conv-1", "assistant", "{\"tool\": \"lunch\"}", Some(INTERMEDIATE_MESSAGE_TYPE))
+            .unwrap();
+        assert_eq!(db.global_search("lunch", 50).unwrap().len(), 1);
+
+        assert!(db.global_search("nonexistent", 50).unwrap().is_empty());
+        assert!(db.global_search("   ", 50).unwrap().is_empty());
+        // FTS operators in user input are matched literally instead of failing
+        assert!(db.global_search("\"unbalanced AND (", 50).unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_search_index_follows_updates_and_deletes() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        seed_search_fixture(&db);
+
+        db.update_message_content("c3", "dinner plans").unwrap();
+        assert!(db.global_search("lunch", 50).unwrap().is_empty());
+        assert_eq!(db.global_search("dinner", 50).unwrap().len(), 1);
+
+        db.delete_task("task-1").unwrap();
+        assert!(db.global_search("report", 50).unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_task_output_json_round_trip() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_task("task-1", "Inventory", "List stock", None).unwrap();
+
+        assert_eq!(db.get_task_output_json("task-1").unwrap(), None);
+        db.set_task_output_json("task-1", r#"{"items":[]}"#).unwrap();
+        assert_eq!(db.get_task_output_json("task-1").unwrap().as_deref(), Some(r#"{"items":[]}"#));
+        assert_eq!(db.get_task_output_json("missing").unwrap(), None);
+
+        db.clear_task_output_json("task-1").unwrap();
+        assert_eq!(db.get_task_output_json("task-1").unwrap(), None);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_message_edit_history_accumulates() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("conv-1", "Chat", None).unwrap();
+        db.add_message("m1", "conv-1", "user", "helo").unwrap();
+        db.create_task("task-1", "Task", "Work", None).unwrap();
+        db.add_task_message("t1", "task-1", "user", "frist").unwrap();
+
+        db.update_message_content("m1", "hello").unwrap();
+        db.update_message_content("m1", "hello there").unwrap();
+        db.update_task_message_content("t1", "first").unwrap();
+
+        assert_eq!(db.get_message("m1").unwrap().unwrap().content, "hello there");
+        assert_eq!(db.get_task_message("t1").unwrap().unwrap().content, "first");
+
+        let history: Vec<String> = db
+            .get_message_edit_history("m1")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.old_content)
+            .collect();
+        assert_eq!(history, vec!["helo", "hello"]);
+        assert_eq!(db.get_message_edit_history("t1").unwrap()[0].old_content, "frist");
+
+        // Editing a missing message records nothing
+        db.update_message_content("missing", "x").unwrap();
+        assert!(db.get_message("missing").unwrap().is_none());
+        assert!(db.get_message_edit_history("missing").unwrap().is_empty());
+
+        // History goes away with the message
+        db.delete_task("task-1").unwrap();
+        assert!(db.get_message_edit_history("t1").unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_task_template_crud_and_instantiation() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+
+        let template = TaskTemplate {
+            id: "weekly-report".to_string(),
+            name: "Weekly report".to_string(),
+            description: "Summarize the week's progress".to_string(),
+            system_prompt: "Write the report in Markdown with a Highlights section.".to_string(),
+            allowed_tools: vec!["read_file".to_string(), "docx_create".to_string()],
+            max_turns: Some(8),
+            created_at: 0,
+        };
+        let saved = db.save_task_template(&template).unwrap();
+        assert!(saved.created_at > 0);
+        assert_eq!(db.get_task_template("weekly-report").unwrap(), Some(saved.clone()));
+
+        // Re-saving keeps the original creation time
+        let renamed = TaskTemplate {
+            name: "Weekly status report".to_string(),
+            ..template.clone()
+        };
+        assert_eq!(db.save_task_template(&renamed).unwrap().created_at, saved.created_at);
+        let templates = db.list_task_templates().unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name, "Weekly status report");
+        assert_eq!(templates[0].allowed_tools, vec!["read_file", "docx_create"]);
+
+        let task = db
+            .create_task_with_template("task-1", "Week 42", &template.description, None, Some("weekly-report"))
+            .unwrap();
+        assert_eq!(task.template_id.as_deref(), Some("weekly-report"));
+        assert_eq!(db.get_task("task-1").unwrap().unwrap().template_id.as_deref(), Some("weekly-report"));
+        db.create_task("task-2", "Ad hoc", "No template", None).unwrap();
+        assert_eq!(db.get_task("task-2").unwrap().unwrap().template_id, None);
+
+        db.delete_task_template("weekly-report").unwrap();
+        assert_eq!(db.get_task_template("weekly-report").unwrap(), None);
+        assert!(db.list_task_templates().unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_search_tasks_filters() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        let tasks = [
+            ("t1", "Quarterly report", "Sales figures for Q3", "completed", 1_000),
+            ("t2", "Clean inbox", "Archive old newsletters", "planning", 2_000),
+            ("t3", "Budget review", "Compare the quarterly budget to actuals", "failed", 3_000),
+            ("t4", "Travel plans", "Book flights", "completed", 4_000),
+            ("t5", "100% done_list", "Track what is finished", "running", 5_000),
+        ];
+        for (id, title, description, status, created_at) in tasks {
+            db.create_task(id, title, description, None).unwrap();
+            db.update_task_status(id, status).unwrap();
+            db.lock_conn()
+                .unwrap()
+                .execute("UPDATE tasks SET created_at = ?1 WHERE id = ?2", rusqlite::params![created_at, id])
+                .unwrap();
+        }
+
+        let ids = |tasks: Vec<Task>| {
+            let mut ids: Vec<String> = tasks.into_iter().map(|t| t.id).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(db.search_tasks(None, None, None, None).unwrap()).len(), 5);
+        // Matches title (t1) and description (t3), case-insensitively
+        assert_eq!(ids(db.search_tasks(Some("QUARTERLY"), None, None, None).unwrap()), vec!["t1", "t3"]);
+        assert_eq!(ids(db.search_tasks(Some("100%"), None, None, None).unwrap()), vec!["t5"]);
+        assert!(db.search_tasks(Some("o_e"), None, None, None).unwrap().is_empty());
+        assert_eq!(ids(db.search_tasks(None, Some("completed"), None, None).unwrap()), vec!["t1", "t4"]);
+        assert_eq!(ids(db.search_tasks(None, None, Some(3_000), None).unwrap()), vec!["t3", "t4", "t5"]);
+        assert_eq!(ids(db.search_tasks(None, None, None, Some(2_000)).unwrap()), vec!["t1", "t2"]);
+        assert_eq!(
+            ids(db.search_tasks(Some("quarterly"), Some("completed"), Some(500), Some(2_500)).unwrap()),
+            vec!["t1"]
+        );
+        assert!(db.search_tasks(Some("quarterly"), Some("completed"), Some(2_000), None).unwrap().is_empty());
+
+        db.archive_task("t4").unwrap();
+        assert_eq!(ids(db.search_tasks(None, Some("completed"), None, None).unwrap()), vec!["t1"]);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_archived_tasks_hidden_and_restorable() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_task("active", "Active", "Still going", None).unwrap();
+        db.create_task("done", "Done", "Finished work", None).unwrap();
+        db.add_task_message("m1", "done", "user", "keep me").unwrap();
+
+        db.archive_task("done").unwrap();
+
+        let active: Vec<String> = db.list_tasks().unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(active, vec!["active".to_string()]);
+
+        let archived = db.list_archived_tasks().unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, "done");
+        assert!(archived[0].archived);
+        assert!(db.get_task("done").unwrap().unwrap().archived);
+        assert_eq!(db.get_task_messages("done").unwrap().len(), 1);
+
+        db.unarchive_task("done").unwrap();
+        assert_eq!(db.list_tasks().unwrap().len(), 2);
+        assert!(db.list_archived_tasks().unwrap().is_empty());
+        assert!(!db.get_task("done").unwrap().unwrap().archived);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore_conversation() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("c1", "Budget", None).unwrap();
+        db.create_conversation("c2", "Travel", None).unwrap();
+        db.add_message("m1", "c1", "user", "hi").unwrap();
+
+        db.delete_conversation("c1").unwrap();
+        let ids: Vec<String> = db.list_conversations().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec!["c2"]);
+        assert_eq!(db.list_conversations_paginated(10, None).unwrap().total_count, 1);
+        let deleted = db.list_deleted_conversations().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+        assert_eq!(db.get_messages("c1").unwrap().len(), 1);
+
+        // Only trashed conversations can be purged
+        assert!(!db.purge_conversation("c2").unwrap());
+
+        db.restore_conversation("c1").unwrap();
+        let restored = db.list_conversations().unwrap();
+        assert_eq!(restored.len(), 2);
+        assert!(restored.iter().all(|c| c.deleted_at.is_none()));
+        assert!(db.list_deleted_conversations().unwrap().is_empty());
+
+        db.delete_conversation("c1").unwrap();
+        assert!(db.purge_conversation("c1").unwrap());
+        assert!(db.list_deleted_conversations().unwrap().is_empty());
+        assert!(db.get_messages("c1").unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_list_conversations_for_project() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        let created = db.create_conversation("a", "Site copy", Some("/work/site")).unwrap();
+        assert_eq!(created.project_path.as_deref(), Some("/work/site"));
+        db.create_conversation("b", "Site deploy", Some("/work/site")).unwrap();
+        db.create_conversation("c", "Other repo", Some("/work/api")).unwrap();
+        db.create_conversation("d", "No project", None).unwrap();
+        db.create_conversation("e", "Later tied", None).unwrap();
+        db.set_conversation_project_path("e", "/work/site").unwrap();
+        db.delete_conversation("b").unwrap();
+
+        let mut ids: Vec<String> = db
+            .list_conversations_for_project("/work/site")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "e"]);
+        assert!(db.list_conversations_for_project("/work/none").unwrap().is_empty());
+        assert_eq!(db.get_conversation("d").unwrap().unwrap().project_path, None);
+        assert_eq!(
+            db.get_conversation("c").unwrap().unwrap().project_path.as_deref(),
+            Some("/work/api")
+        );
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_purge_conversations_older_than() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("old", "Old", None).unwrap();
+        db.create_conversation("recent", "Recent", None).unwrap();
+        db.add_message("m1", "old", "user", "hi").unwrap();
+        db.delete_conversation("old").unwrap();
+        db.delete_conversation("recent").unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            let ten_days_ago = chrono::Utc::now().timestamp_millis() - 10 * 24 * 60 * 60 * 1000;
+            conn.execute("UPDATE conversations SET deleted_at = ?1 WHERE id = 'old'", [ten_days_ago])
+                .unwrap();
+        }
+
+        assert_eq!(db.purge_conversations_older_than(30).unwrap(), 0);
+        assert_eq!(db.purge_conversations_older_than(7).unwrap(), 1);
+        let remaining: Vec<String> = db.list_deleted_conversations().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(remaining, vec!["recent"]);
+        assert!(db.get_messages("old").unwrap().is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_delete_conversations_dry_run_and_trash() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_conversation("c1", "One", None).unwrap();
+        db.create_conversation("c2", "Two", None).unwrap();
+        db.create_conversation("c3", "Three", None).unwrap();
+        db.add_message("m1", "c1", "user", "hi").unwrap();
+        db.add_message("m2", "c1", "assistant", "hello").unwrap();
+        db.add_message("m3", "c2", "user", "hey").unwrap();
+        db.add_message("m4", "c3", "user", "kept").unwrap();
+
+        let ids = vec!["c1".to_string(), "c2".to_string(), "missing".to_string()];
+
+        let preview = db.delete_conversations(&ids, true).unwrap();
+        assert_eq!(
+            preview,
+            BulkDeleteResult {
+                deleted_count: 2,
+                not_found: vec!["missing".to_string()],
+                message_count: 3,
+            }
+        );
+        assert_eq!(db.list_conversations().unwrap().len(), 3);
+        assert_eq!(db.get_messages("c1").unwrap().len(), 2);
+
+        let result = db.delete_conversations(&ids, false).unwrap();
+        assert_eq!(result, preview);
+        let active: Vec<String> = db.list_conversations().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(active, vec!["c3"]);
+        let trashed: std::collections::HashSet<String> =
+            db.list_deleted_conversations().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(trashed, ["c1".to_string(), "c2".to_string()].into_iter().collect());
+        // Messages stay until the trash is purged, and trashed ids aren't trashed again
+        assert_eq!(db.get_messages("c1").unwrap().len(), 2);
+        let again = db.delete_conversations(&ids, false).unwrap();
+        assert_eq!(again.deleted_count, 0);
+        assert_eq!(again.not_found.len(), 3);
+
+        let all = db.delete_all_conversations(true).unwrap();
+        assert_eq!((all.deleted_count, all.message_count), (1, 1));
+        assert_eq!(db.list_conversations().unwrap().len(), 1);
+
+        db.delete_all_conversations(false).unwrap();
+        assert!(db.list_conversations().unwrap().is_empty());
+        assert_eq!(db.list_deleted_conversations().unwrap().len(), 3);
+        assert_eq!(db.get_messages("c3").unwrap().len(), 1);
+
+        db.restore_conversation("c3").unwrap();
+        assert_eq!(db.list_conversations().unwrap()[0].id, "c3");
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        let valid = Settings {
+            api_key: "sk-ant-test".to_string(),
+            ..Settings::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let err = Settings { model: "  ".to_string(), ..valid.clone() }.validate().unwrap_err();
+        assert_eq!(err, "Model must not be empty");
+
+        let err = Settings { base_url: "not a url".to_string(), ..valid.clone() }.validate().unwrap_err();
+        assert!(err.starts_with("Invalid base URL 'not a url'"), "{}", err);
+
+        let err = Settings { base_url: "ftp://api.example.com".to_string(), ..valid.clone() }
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("expected an http:// or https:// URL"), "{}", err);
+
+        let err = Settings { max_tokens: 0, ..valid.clone() }.validate().unwrap_err();
+        assert!(err.starts_with("Max tokens must be between 1 and 200000"), "{}", err);
+        assert!(Settings { max_tokens: 200_001, ..valid.clone() }.validate().is_err());
+        assert!(Settings { max_tokens: 200_000, ..valid.clone() }.validate().is_ok());
+
+        let err = Settings { temperature: 2.5, ..valid.clone() }.validate().unwrap_err();
+        assert!(err.starts_with("Temperature must be between 0.0 and 2.0"), "{}", err);
+        assert!(Settings { temperature: -0.1, ..valid.clone() }.validate().is_err());
+        assert!(Settings { temperature: f32::NAN, ..valid.clone() }.validate().is_err());
+
+        let err = Settings { api_key: String::new(), ..valid.clone() }.validate().unwrap_err();
+        assert_eq!(err, "An API key is required for provider 'anthropic'");
+
+        let err = Settings { system_prompt_mode: Some("append".to_string()), ..valid.clone() }
+            .validate()
+            .unwrap_err();
+        assert!(err.starts_with("System prompt mode must be"), "{}", err);
+
+        let err = Settings { max_agent_turns: Some(0), ..valid.clone() }.validate().unwrap_err();
+        assert_eq!(err, "Max agent turns must be at least 1");
+        assert!(Settings { max_agent_duration_secs: Some(0), ..valid.clone() }.validate().is_err());
+        assert!(Settings { max_concurrent_agents: Some(0), ..valid.clone() }.validate().is_err());
+        assert!(Settings { mcp_tool_timeout_secs: Some(0), ..valid.clone() }.validate().is_err());
+        assert!(Settings { max_agent_turns: Some(5), max_agent_duration_secs: Some(60), ..valid.clone() }
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_system_prompt_settings_round_trip() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        let settings = Settings {
+            system_prompt: Some("Answer like a pirate.".to_string()),
+            system_prompt_mode: Some("replace".to_string()),
+            ..Settings::default()
+        };
+        db.save_settings(&settings).unwrap();
+
+        let loaded = db.get_settings().unwrap();
+        assert_eq!(loaded.system_prompt.as_deref(), Some("Answer like a pirate."));
+        assert_eq!(loaded.apply_system_prompt("Built-in prompt"), "Answer like a pirate.");
+
+        let prepend = Settings { system_prompt_mode: None, ..loaded.clone() };
+        assert_eq!(prepend.apply_system_prompt("Built-in prompt"), "Answer like a pirate.\n\nBuilt-in prompt");
+        assert_eq!(Settings::default().apply_system_prompt("Built-in prompt"), "Built-in prompt");
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_local_provider_settings_valid_without_api_key() {
+        let settings = Settings {
+            api_key: String::new(),
+            provider: "ollama".to_string(),
+            model: "llama3.3:latest".to_string(),
+            base_url: "http://localhost:11434".to_string(),
+            ..Settings::default()
+        };
+        assert!(settings.allows_empty_api_key());
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_task_message_attachments_inline_and_file() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_task("t1", "Review", "Look at screenshots", None).unwrap();
+        db.add_task_message("m1", "t1", "user", "see attached").unwrap();
+
+        let small = general_purpose::STANDARD.encode(b"tiny png");
+        let large = general_purpose::STANDARD.encode(vec![7u8; ATTACHMENT_INLINE_LIMIT]);
+        db.add_task_message_attachment("m1", "small.png", "image/png", &small).unwrap();
+        let stored = db.add_task_message_attachment("m1", "large.png", "image/png", &large).unwrap();
+
+        let file_path: Option<String> = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT file_path FROM task_message_attachments WHERE id = ?1",
+                [&stored.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let file_path = file_path.expect("large attachment stored as a file");
+
+        let attachments = db.get_task_message_attachments("m1").unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].name, "small.png");
+        assert_eq!(attachments[0].data_base64, small);
+        assert_eq!(attachments[1].data_base64, large);
+        assert!(!attachments[1].missing);
+        assert!(db.get_task_message_attachments("other").unwrap().is_empty());
+
+        // Deleting the task removes the attachment file too
+        db.delete_task("t1").unwrap();
+        assert!(!std::path::Path::new(&file_path).exists());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_missing_attachment_file_marked_missing() {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        db.create_task("t1", "Review", "Look at screenshots", None).unwrap();
+        db.add_task_message("m1", "t1", "user", "see attached").unwrap();
+        let large = general_purpose::STANDARD.encode(vec![7u8; ATTACHMENT_INLINE_LIMIT]);
+        db.add_task_message_attachment("m1", "large.png", "image/png", &large).unwrap();
+        for file in attachment_files(&db.conn.lock().unwrap(), Some("t1")).unwrap() {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        let attachments = db.get_task_message_attachments("m1").unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert!(attachments[0].missing);
+        assert!(attachments[0].data_base64.is_empty());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_list_conversations_paginated() {
+        let path = temp_db_path();
+        let db = Database::open(&path).unwrap();
+        for i in 0..25 {
+            db.create_conversation(&format!("c{:02}", i), &format!("Chat {}", i), None).unwrap();
+        }
+        // Spread timestamps out, leaving a few ties to exercise the id tiebreak
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute("UPDATE conversations SET updated_at = CAST(substr(id, 2) AS INTEGER) / 2", [])
+                .unwrap();
+        }
+
+        let expected: Vec<String> = db.list_conversations().unwrap().into_iter().map(|c| c.id).collect();
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut page_sizes = Vec::new();
+        loop {
+            let page = db.list_conversations_paginated(10, cursor.as_deref()).unwrap();
+            assert_eq!(page.total_count, 25);
+            page_sizes.push(page.items.len());
+            seen.extend(page.items.into_iter().map(|c| c.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(page_sizes, vec![10, 10, 5]);
+        assert_eq!(seen.len(), 25);
+        assert_eq!(seen[0], "c24");
+        assert_eq!(seen[24], "c00");
+        // Same set as the unpaginated list, newest first
+        let mut sorted_seen = seen.clone();
+        sorted_seen.sort();
+        let mut sorted_expected = expected.clone();
+        sorted_expected.sort();
+        assert_eq!(sorted_seen, sorted_expected);
+
+        // An exact multiple of the page size ends without a dangling cursor
+        let page = db.list_conversations_paginated(25, None).unwrap();
+        assert_eq!(page.items.len(), 25);
+        assert!(page.next_cursor.is_none());
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
 }
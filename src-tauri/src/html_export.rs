@@ -0,0 +1,166 @@
+//! Conversation export as a self-contained HTML page
+
+use crate::database::{Database, Message};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::path::Path;
+
+/// Syntax highlighting for `language-*` code blocks; the page still reads fine offline
+const PRISM_CSS: &str = "https://cdn.jsdelivr.net/npm/prismjs@1.29.0/themes/prism.min.css";
+const PRISM_JS: &str = "https://cdn.jsdelivr.net/npm/prismjs@1.29.0/prism.min.js";
+const PRISM_AUTOLOADER: &str = "https://cdn.jsdelivr.net/npm/prismjs@1.29.0/plugins/autoloader/prism-autoloader.min.js";
+
+/// URL schemes kept in exported links; anything else (`javascript:`, `data:`, `file:`,
+/// relative paths) is replaced with `#`
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; line-height: 1.6; color: #1f2328; background: #f6f8fa; margin: 0; }
+main { max-width: 820px; margin: 0 auto; padding: 32px 16px; }
+h1.title { font-size: 1.6em; margin: 0 0 24px; }
+.message { background: #fff; border: 1px solid #d0d7de; border-radius: 8px; padding: 12px 16px; margin-bottom: 16px; }
+.message.user { background: #eef6ff; border-color: #b6d4fe; }
+.message .role { font-size: 0.8em; font-weight: 600; text-transform: uppercase; color: #57606a; margin-bottom: 4px; }
+.message .content > :first-child { margin-top: 0; }
+.message .content > :last-child { margin-bottom: 0; }
+pre { background: #f6f8fa; border-radius: 6px; padding: 12px; overflow-x: auto; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 4px 8px; }
+blockquote { border-left: 4px solid #d0d7de; margin: 0; padding-left: 12px; color: #57606a; }
+"#;
+
+/// Write the conversation's visible messages to `output_path` as HTML
+pub fn export_conversation_html(db: &Database, conversation_id: &str, output_path: &Path) -> Result<(), String> {
+    let conversation = db
+        .get_conversation(conversation_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    let messages = db.get_messages(conversation_id).map_err(|e| e.to_string())?;
+
+    let html = render_conversation_html(&conversation.title, &messages);
+    std::fs::write(output_path, html).map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))
+}
+
+/// Full HTML document with one `<div class="message {role}">` per message.
+/// Tool call steps saved during chat-with-tools runs are left out.
+pub fn render_conversation_html(title: &str, messages: &[Message]) -> String {
+    let mut body = String::new();
+    for message in messages.iter().filter(|m| !m.is_intermediate()) {
+        let role = escape_html(&message.role);
+        body.push_str(&format!(
+            "<div class=\"message {}\">\n<div class=\"role\">{}</div>\n<div class=\"content\">\n{}</div>\n</div>\n",
+            role,
+            role,
+            markdown_to_html(&message.content)
+        ));
+    }
+
+    let title = escape_html(title);
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<link rel=\"stylesheet\" href=\"{PRISM_CSS}\">\n<style>{STYLE}</style>\n</head>\n<body>\n<main>\n<h1 class=\"title\">{title}</h1>\n{body}</main>\n<script src=\"{PRISM_JS}\"></script>\n<script src=\"{PRISM_AUTOLOADER}\"></script>\n</body>\n</html>\n"
+    )
+}
+
+/// Render Markdown, escaping raw HTML in the source instead of passing it through.
+/// Links keep only `ALLOWED_URL_SCHEMES`. Images become links to the image, so opening
+/// the export never fetches remote content. Fenced code blocks get a `language-{lang}`
+/// class, which Prism picks up.
+fn markdown_to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::Start(Tag::Link { link_type, dest_url, title, id })
+        | Event::Start(Tag::Image { link_type, dest_url, title, id }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+        other => other,
+    });
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, events);
+    rendered
+}
+
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let allowed = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| ALLOWED_URL_SCHEMES.iter().any(|s| scheme.eq_ignore_ascii_case(s)));
+    if allowed {
+        url
+    } else {
+        CowStr::Borrowed("#")
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_conversation_html() {
+        let (db, db_path) = crate::test_support::temp_db();
+        db.create_conversation("conv-1", "Fizz <buzz>", None).unwrap();
+        db.add_message("m1", "conv-1", "user", "How do I print **bold** text? <script>alert(1)</script>")
+            .unwrap();
+        db.add_message(
+            "m2",
+            "conv-1",
+            "assistant",
+            "Use *print*:\n\n```python\nif x < 3:\n    print(\"hi\")\n```\n",
+        )
+        .unwrap();
+
+        let output_path = std::env::temp_dir().join(format!("kuse-export-{}.html", uuid::Uuid::new_v4()));
+        export_conversation_html(&db, "conv-1", &output_path).unwrap();
+        let html = std::fs::read_to_string(&output_path).unwrap();
+
+        assert!(html.contains("<title>Fizz &lt;buzz&gt;</title>"), "{}", html);
+        assert!(html.contains("<div class=\"message user\">"));
+        assert!(html.contains("<div class=\"message assistant\">"));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<em>print</em>"));
+        assert!(html.contains("<pre><code class=\"language-python\">if x &lt; 3:"), "{}", html);
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!html.contains("<script>alert"));
+        assert!(html.contains("prism.min.js"));
+
+        assert!(export_conversation_html(&db, "missing", &output_path).is_err());
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+        let _ = std::fs::remove_file(&output_path);
+    }
+
+    #[test]
+    fn test_markdown_links_and_images_are_sanitized() {
+        let html = markdown_to_html(
+            "[site](https://example.com) [mail](mailto:a@example.com) [js](javascript:alert(1)) \
+             [data](data:text/html,hi) [local](../secret.txt)\n\n\
+             ![chart](https://tracker.example.com/pixel.png) ![inline](data:image/png;base64,AAAA)",
+        );
+
+        assert!(html.contains("<a href=\"https://example.com\">site</a>"), "{}", html);
+        assert!(html.contains("<a href=\"mailto:a@example.com\">mail</a>"));
+        assert!(html.contains("<a href=\"#\">js</a>"));
+        assert!(html.contains("<a href=\"#\">data</a>"));
+        assert!(html.contains("<a href=\"#\">local</a>"));
+        assert!(!html.contains("javascript:"));
+
+        // Images are never loaded, only linked
+        assert!(!html.contains("<img"));
+        assert!(html.contains("<a href=\"https://tracker.example.com/pixel.png\">chart</a>"));
+        assert!(html.contains("<a href=\"#\">inline</a>"));
+    }
+}
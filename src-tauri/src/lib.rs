@@ -1,36 +1,83 @@
 mod agent;
+mod app_errors;
+mod backup;
 mod claude;
 mod commands;
 mod database;
+mod html_export;
 mod llm_client;
 mod mcp;
+mod output_schema;
+mod project_context;
+mod run_queue;
 mod skills;
+mod startup;
+#[cfg(test)]
+mod test_support;
 mod tools;
 
 use commands::AppState;
 use mcp::MCPManager;
 use std::sync::Arc;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 use tokio::sync::Mutex;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let mut timer = startup::StartupTimer::new();
+
     // Initialize database
-    let db = database::Database::new().expect("Failed to initialize database");
+    let db = timer.time("database_init", || {
+        database::Database::new().expect("Failed to initialize database")
+    });
 
     // Initialize MCP tables
-    db.create_mcp_tables().expect("Failed to create MCP tables");
+    timer.time("mcp_tables", || {
+        db.create_mcp_tables().expect("Failed to create MCP tables")
+    });
 
     // Initialize MCP manager
-    let mcp_manager = Arc::new(MCPManager::new());
+    let mcp_tool_timeout_secs = db
+        .get_settings()
+        .ok()
+        .and_then(|settings| settings.mcp_tool_timeout_secs)
+        .unwrap_or(mcp::client::DEFAULT_TOOL_TIMEOUT_SECS);
+    let mcp_manager = Arc::new(MCPManager::new().with_default_tool_timeout_secs(mcp_tool_timeout_secs));
     let db_arc = Arc::new(db);
 
     // Auto-connect enabled MCP servers will be done in the tauri app setup
 
+    // Built-in tools plus the user's own *.tool.json scripts
+    let custom_tools_dir = tools::custom::default_custom_tools_dir();
+    if let Some(dir) = &custom_tools_dir {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let tool_registry = timer.time("tool_registry", || {
+        commands::build_tool_registry(custom_tools_dir.as_deref())
+    });
+
+    // Settings saved in an earlier session already carry the API key
+    let claude_client = db_arc
+        .get_settings()
+        .ok()
+        .and_then(|settings| commands::claude_client_for(&settings));
+    let max_concurrent_agents = db_arc
+        .get_settings()
+        .ok()
+        .and_then(|settings| settings.max_concurrent_agents)
+        .unwrap_or(database::DEFAULT_MAX_CONCURRENT_AGENTS);
+
     let app_state = Arc::new(AppState {
         db: db_arc,
-        claude_client: Mutex::new(None),
+        claude_client: Mutex::new(claude_client),
         mcp_manager,
+        tool_registry: std::sync::RwLock::new(Arc::new(tool_registry)),
+        custom_tools_dir,
+        tool_metrics: Arc::new(std::sync::Mutex::new(agent::ToolMetrics::new())),
+        startup_timing: std::sync::Mutex::new(Vec::new()),
+        model_cache: std::sync::Mutex::new(std::collections::HashMap::new()),
+        errors: app_errors::ErrorReporter::new(),
+        run_queue: run_queue::RunQueue::new(max_concurrent_agents),
     });
 
     tauri::Builder::default()
@@ -43,22 +90,63 @@ pub fn run() {
             commands::save_settings,
             commands::test_connection,
             commands::check_local_service_status,
+            commands::check_all_provider_connections,
+            commands::discover_provider_models,
             commands::list_conversations,
+            commands::list_conversations_paginated,
+            commands::list_conversations_for_project,
             commands::create_conversation,
             commands::update_conversation_title,
             commands::delete_conversation,
+            commands::list_deleted_conversations,
+            commands::restore_conversation,
+            commands::purge_conversation,
+            commands::purge_conversations_older_than,
+            commands::delete_conversations,
+            commands::delete_all_conversations,
             commands::get_messages,
+            commands::get_conversation_stats,
             commands::add_message,
+            commands::edit_message,
+            commands::edit_task_message,
+            commands::get_message_edit_history,
             commands::send_chat_message,
             commands::send_chat_with_tools,
             commands::run_agent,
+            commands::preview_tool_calls,
+            commands::get_agent_queue_depth,
+            commands::get_conversation_plan,
             commands::list_tasks,
             commands::get_task,
             commands::create_task,
+            commands::create_task_from_template,
+            commands::list_task_templates,
+            commands::save_task_template,
+            commands::delete_task_template,
             commands::delete_task,
+            commands::list_archived_tasks,
+            commands::search_tasks,
+            commands::archive_task,
+            commands::unarchive_task,
             commands::run_task_agent,
+            commands::get_task_output,
             commands::get_task_messages,
+            commands::get_task_stats,
+            commands::get_agent_events,
+            commands::replay_agent_events,
+            commands::global_search,
+            commands::get_tool_metrics,
+            commands::reset_tool_metrics,
+            commands::get_startup_timing,
+            commands::subscribe_to_errors,
+            commands::estimate_chat_tokens,
+            commands::export_all,
+            commands::export_conversation_html,
+            commands::checkpoint_database,
+            commands::import_all,
             commands::get_skills_list,
+            commands::list_available_tools,
+            commands::reload_custom_tools,
             commands::list_mcp_servers,
             commands::save_mcp_server,
             commands::test_mcp_server_config,
@@ -66,9 +154,15 @@ pub fn run() {
             commands::connect_mcp_server,
             commands::disconnect_mcp_server,
             commands::get_mcp_server_statuses,
+            commands::get_mcp_server_logs,
+            commands::get_mcp_connection_history,
             commands::execute_mcp_tool,
+            commands::set_mcp_tool_timeout,
+            commands::refresh_mcp_server_tools,
+            commands::list_mcp_prompts,
+            commands::get_mcp_prompt,
         ])
-        .setup(|app| {
+        .setup(move |app| {
             #[cfg(debug_assertions)]
             {
                 if let Some(window) = app.get_webview_window("main") {
@@ -76,31 +170,51 @@ pub fn run() {
                 }
             }
 
-            // Auto-connect enabled MCP servers
-            let app_state = app.state::<Arc<AppState>>();
-            let db = app_state.db.clone();
-            let mcp_manager = app_state.mcp_manager.clone();
-            let _app_handle = app.handle().clone();
+            // Auto-connect enabled MCP servers, retrying slow starters with backoff
+            let app_state = app.state::<Arc<AppState>>().inner().clone();
+            let app_handle = app.handle().clone();
+            app_state.mcp_manager.set_app_handle(app_handle.clone());
+            app_state.mcp_manager.set_error_reporter(app_state.errors.clone());
+            mcp::MCPManager::spawn_tool_refresh_task(app_state.mcp_manager.clone());
 
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(async move {
-                    if let Ok(servers) = db.get_mcp_servers() {
-                        for server in servers {
-                            if server.enabled {
-                                if let Err(e) = mcp_manager.connect_server(&server).await {
-                                    eprintln!("Failed to auto-connect MCP server '{}': {}", server.name, e);
-                                } else {
-                                    println!("Auto-connected MCP server: {}", server.name);
-                                }
-                            }
+            // Forward background errors to the frontend
+            let mut error_rx = app_state.errors.subscribe();
+            let error_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match error_rx.recv().await {
+                        Ok(error) => {
+                            let _ = error_handle.emit(app_errors::APP_ERROR_EVENT, &error);
                         }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
                     }
-                });
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                let summary =
+                    startup::connect_mcp_servers_timed(&app_state.db, &app_state.mcp_manager, &mut timer).await;
+                let _ = app_handle.emit("mcp-startup-complete", &summary);
+
+                let timing = timer.finish();
+                if let Ok(mut cached) = app_state.startup_timing.lock() {
+                    *cached = timing.events.clone();
+                }
+                let _ = app_handle.emit("app-startup-complete", &timing);
             });
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // The run loop callback is synchronous; block on the app's runtime, which owns
+                // the MCP child processes, until they have been stopped
+                let app_state = app.state::<Arc<AppState>>().inner().clone();
+                tauri::async_runtime::block_on(app_state.mcp_manager.shutdown_all());
+            }
+        });
 }
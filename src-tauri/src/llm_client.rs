@@ -1,6 +1,8 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
+use crate::database::Settings;
 use tokio::sync::mpsc;
 
 #[derive(Error, Debug)]
@@ -48,136 +50,263 @@ pub struct ProviderConfig {
     pub base_url: String,
     pub api_format: ApiFormat,
     pub auth_type: AuthType,
+    /// Request SSE streams; some proxies strip SSE and only return complete JSON
+    #[serde(default = "default_streaming")]
+    pub streaming: bool,
+    /// Provider-specific headers sent after the standard ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_headers: Option<HashMap<String, String>>,
+    /// Average tokens per byte of text for this provider's tokenizer
+    #[serde(default = "default_tokens_per_char")]
+    pub tokens_per_char: f32,
 }
 
-impl ProviderConfig {
-    /// Get preset configuration by provider ID
-    pub fn from_preset(provider_id: &str) -> Self {
-        match provider_id {
-            // Official APIs
-            "anthropic" => Self {
-                id: "anthropic".to_string(),
-                name: "Anthropic".to_string(),
-                base_url: "https://api.anthropic.com".to_string(),
-                api_format: ApiFormat::Anthropic,
-                auth_type: AuthType::ApiKey,
-            },
-            "openai" => Self {
-                id: "openai".to_string(),
-                name: "OpenAI".to_string(),
-                base_url: "https://api.openai.com".to_string(),
-                api_format: ApiFormat::OpenAI,
-                auth_type: AuthType::Bearer,
-            },
-            "google" => Self {
-                id: "google".to_string(),
-                name: "Google".to_string(),
-                base_url: "https://generativelanguage.googleapis.com".to_string(),
-                api_format: ApiFormat::Google,
-                auth_type: AuthType::QueryParam,
-            },
-            "minimax" => Self {
-                id: "minimax".to_string(),
-                name: "Minimax".to_string(),
-                base_url: "https://api.minimax.chat".to_string(),
-                api_format: ApiFormat::Minimax,
-                auth_type: AuthType::Bearer,
-            },
+fn default_streaming() -> bool {
+    true
+}
 
-            // Local inference services
-            "ollama" => Self {
-                id: "ollama".to_string(),
-                name: "Ollama".to_string(),
-                base_url: "http://localhost:11434".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
-            "lm-studio" => Self {
-                id: "lm-studio".to_string(),
-                name: "LM Studio".to_string(),
-                base_url: "http://localhost:1234".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
-            "localai" => Self {
-                id: "localai".to_string(),
-                name: "LocalAI".to_string(),
-                base_url: "http://localhost:8080".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
+/// Roughly 4 bytes of English text per token
+pub const DEFAULT_TOKENS_PER_CHAR: f32 = 0.25;
 
-            // Cloud GPU inference
-            "vllm" => Self {
-                id: "vllm".to_string(),
-                name: "vLLM".to_string(),
-                base_url: "http://localhost:8000".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
-            "tgi" => Self {
-                id: "tgi".to_string(),
-                name: "TGI".to_string(),
-                base_url: "http://localhost:8080".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
-            "sglang" => Self {
-                id: "sglang".to_string(),
-                name: "SGLang".to_string(),
-                base_url: "http://localhost:30000".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::None,
-            },
+fn default_tokens_per_char() -> f32 {
+    DEFAULT_TOKENS_PER_CHAR
+}
 
-            // API aggregation services
-            "openrouter" => Self {
-                id: "openrouter".to_string(),
-                name: "OpenRouter".to_string(),
-                base_url: "https://openrouter.ai/api/v1".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::Bearer,
-            },
-            "together" => Self {
-                id: "together".to_string(),
-                name: "Together AI".to_string(),
-                base_url: "https://api.together.xyz/v1".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::Bearer,
-            },
-            "groq" => Self {
-                id: "groq".to_string(),
-                name: "Groq".to_string(),
-                base_url: "https://api.groq.com/openai/v1".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::Bearer,
-            },
-            "deepseek" => Self {
-                id: "deepseek".to_string(),
-                name: "DeepSeek".to_string(),
-                base_url: "https://api.deepseek.com".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::Bearer,
-            },
-            "siliconflow" => Self {
-                id: "siliconflow".to_string(),
-                name: "SiliconFlow".to_string(),
-                base_url: "https://api.siliconflow.cn/v1".to_string(),
-                api_format: ApiFormat::OpenAICompatible,
-                auth_type: AuthType::Bearer,
-            },
+/// Context window sizes of known models, matched by longest model-name prefix
+const MODEL_CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("claude-opus-4", 200_000),
+    ("claude-sonnet-4", 200_000),
+    ("claude-3-7-sonnet", 200_000),
+    ("claude-3-5-sonnet", 200_000),
+    ("claude-3-5-haiku", 200_000),
+    ("claude-3-opus", 200_000),
+    ("claude-3-haiku", 200_000),
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("gemini-2.5-pro", 1_048_576),
+    ("gemini-2.5-flash", 1_048_576),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-1.5-flash", 1_048_576),
+    ("deepseek-chat", 64_000),
+    ("deepseek-reasoner", 64_000),
+];
+
+/// A built-in provider preset
+struct ProviderPreset {
+    id: &'static str,
+    name: &'static str,
+    base_url: &'static str,
+    api_format: ApiFormat,
+    auth_type: AuthType,
+    tokens_per_char: f32,
+    /// Lists its models at `/v1/models` (or the format's own listing endpoint)
+    discoverable: bool,
+}
 
+/// Every built-in provider preset, in the order they are shown and checked
+const PROVIDER_PRESETS: &[ProviderPreset] = &[
+    // Official APIs
+    ProviderPreset {
+        id: "anthropic",
+        name: "Anthropic",
+        base_url: "https://api.anthropic.com",
+        api_format: ApiFormat::Anthropic,
+        auth_type: AuthType::ApiKey,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "openai",
+        name: "OpenAI",
+        base_url: "https://api.openai.com",
+        api_format: ApiFormat::OpenAI,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: 0.22,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "google",
+        name: "Google",
+        base_url: "https://generativelanguage.googleapis.com",
+        api_format: ApiFormat::Google,
+        auth_type: AuthType::QueryParam,
+        tokens_per_char: 0.28,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "minimax",
+        name: "Minimax",
+        base_url: "https://api.minimax.chat",
+        api_format: ApiFormat::Minimax,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+
+    // Local inference services
+    ProviderPreset {
+        id: "ollama",
+        name: "Ollama",
+        base_url: "http://localhost:11434",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "lm-studio",
+        name: "LM Studio",
+        base_url: "http://localhost:1234",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "localai",
+        name: "LocalAI",
+        base_url: "http://localhost:8080",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+
+    // Cloud GPU inference
+    ProviderPreset {
+        id: "vllm",
+        name: "vLLM",
+        base_url: "http://localhost:8000",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "tgi",
+        name: "TGI",
+        base_url: "http://localhost:8080",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "sglang",
+        name: "SGLang",
+        base_url: "http://localhost:30000",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::None,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+
+    // API aggregation services
+    ProviderPreset {
+        id: "openrouter",
+        name: "OpenRouter",
+        base_url: "https://openrouter.ai/api/v1",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "together",
+        name: "Together AI",
+        base_url: "https://api.together.xyz/v1",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "groq",
+        name: "Groq",
+        base_url: "https://api.groq.com/openai/v1",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "deepseek",
+        name: "DeepSeek",
+        base_url: "https://api.deepseek.com",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "siliconflow",
+        name: "SiliconFlow",
+        base_url: "https://api.siliconflow.cn/v1",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        discoverable: true,
+    },
+    ProviderPreset {
+        id: "perplexity",
+        name: "Perplexity",
+        base_url: "https://api.perplexity.ai",
+        api_format: ApiFormat::OpenAICompatible,
+        auth_type: AuthType::Bearer,
+        tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
+        // No models endpoint
+        discoverable: false,
+    },
+];
+
+/// IDs of every built-in provider preset
+pub fn provider_preset_ids() -> Vec<&'static str> {
+    PROVIDER_PRESETS.iter().map(|preset| preset.id).collect()
+}
+
+impl ProviderConfig {
+    /// Get preset configuration by provider ID; unknown IDs get an OpenAI-compatible config
+    pub fn from_preset(provider_id: &str) -> Self {
+        match PROVIDER_PRESETS.iter().find(|preset| preset.id == provider_id) {
+            Some(preset) => Self {
+                id: preset.id.to_string(),
+                name: preset.name.to_string(),
+                base_url: preset.base_url.to_string(),
+                api_format: preset.api_format.clone(),
+                auth_type: preset.auth_type.clone(),
+                streaming: true,
+                extra_headers: None,
+                tokens_per_char: preset.tokens_per_char,
+            },
             // Default/Custom - assume OpenAI compatible
-            _ => Self {
+            None => Self {
                 id: provider_id.to_string(),
                 name: "Custom".to_string(),
                 base_url: "http://localhost:8000".to_string(),
                 api_format: ApiFormat::OpenAICompatible,
                 auth_type: AuthType::Bearer,
+                streaming: true,
+                extra_headers: None,
+                tokens_per_char: DEFAULT_TOKENS_PER_CHAR,
             },
         }
     }
 
+    /// Whether the provider can list its models; custom providers are assumed to
+    pub fn is_discoverable(&self) -> bool {
+        !PROVIDER_PRESETS
+            .iter()
+            .any(|preset| preset.id == self.id && !preset.discoverable)
+    }
+
     /// Infer provider from model name
     pub fn from_model(model: &str) -> Self {
         let model_lower = model.to_lowercase();
@@ -204,12 +333,24 @@ impl ProviderConfig {
             Self::from_preset("google")
         } else if model_lower.contains("minimax") {
             Self::from_preset("minimax")
+        } else if model_lower.contains("sonar") {
+            Self::from_preset("perplexity")
         } else {
             // Default to Anthropic
             Self::from_preset("anthropic")
         }
     }
 
+    /// Add headers that depend on the selected model.
+    /// SiliconFlow routes some model variants by the `SF-Model-Id` header.
+    pub fn apply_model_headers(&mut self, model: &str) {
+        if self.id == "siliconflow" && !model.is_empty() {
+            self.extra_headers
+                .get_or_insert_with(HashMap::new)
+                .insert("SF-Model-Id".to_string(), model.to_string());
+        }
+    }
+
     /// Get preset configuration with custom API format override
     fn from_preset_with_format(provider_id: &str, api_format: ApiFormat) -> Self {
         let mut config = Self::from_preset(provider_id);
@@ -225,6 +366,52 @@ pub struct Message {
     pub content: String,
 }
 
+/// A base64 image sent with the latest user message
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    pub media_type: String,
+    pub data: String,
+}
+
+/// The OpenAI organization and project from `settings`, if `provider_id` is OpenAI;
+/// other providers never get them
+pub fn openai_headers_for(settings: &Settings, provider_id: &str) -> (Option<String>, Option<String>) {
+    if provider_id == "openai" {
+        (settings.openai_organization.clone(), settings.openai_project.clone())
+    } else {
+        (None, None)
+    }
+}
+
+/// Build an HTTP client, routing all requests through `proxy_url` when set.
+/// `no_proxy` is a comma-separated list of hosts that bypass the proxy.
+pub fn build_http_client(proxy_url: Option<&str>, no_proxy: Option<&str>) -> Result<Client, reqwest::Error> {
+    let mut builder = Client::builder();
+
+    if let Some(proxy_url) = proxy_url.map(str::trim).filter(|u| !u.is_empty()) {
+        let mut proxy = reqwest::Proxy::all(proxy_url)?;
+        if let Some(no_proxy) = no_proxy.map(str::trim).filter(|n| !n.is_empty()) {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+        }
+        builder = builder.proxy(proxy);
+    }
+
+    builder.build()
+}
+
+/// HTTP client for requests to `provider_id`. Cloud providers go through the proxy in
+/// `settings`; local services are always reached directly.
+pub fn http_client_for(settings: &Settings, provider_id: &str) -> Client {
+    if ProviderConfig::from_preset(provider_id).auth_type == AuthType::None {
+        return Client::new();
+    }
+    // Proxy settings are validated when saved; fall back to a direct connection otherwise
+    build_http_client(settings.proxy_url.as_deref(), settings.proxy_no_proxy.as_deref()).unwrap_or_else(|e| {
+        eprintln!("[LLMClient] Ignoring invalid proxy settings: {}", e);
+        Client::new()
+    })
+}
+
 /// General LLM client
 pub struct LLMClient {
     client: Client,
@@ -233,13 +420,61 @@ pub struct LLMClient {
     provider_config: ProviderConfig,
     openai_organization: Option<String>,
     openai_project: Option<String>,
+    /// Images attached to the latest user message
+    images: Vec<InlineImage>,
 }
 
 impl LLMClient {
+    /// Approximate token count of `messages` using the provider's tokens-per-char ratio
+    pub fn estimate_tokens(&self, messages: &[Message]) -> u32 {
+        let chars: usize = messages.iter().map(|m| m.content.len()).sum();
+        (chars as f64 * self.provider_config.tokens_per_char as f64)
+            .ceil()
+            .min(u32::MAX as f64) as u32
+    }
+
+    /// Context window of a known model, or `None` if the model isn't listed
+    pub fn model_context_window(model: &str) -> Option<u32> {
+        let model = model.to_lowercase();
+        let model = model.rsplit('/').next().unwrap_or(&model);
+        MODEL_CONTEXT_WINDOWS
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, window)| *window)
+    }
+
     pub fn new(api_key: String, base_url: Option<String>, provider_id: Option<&str>, model: Option<&str>) -> Self {
-        Self::new_with_openai_headers(api_key, base_url, provider_id, model, None, None)
+        Self::new_with_openai_headers(api_key, base_url, provider_id, model, None, None, None, None)
     }
 
+    /// Client for the configured provider, model, headers and proxy in `settings`
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::from_settings_for_provider(settings, &settings.get_provider())
+    }
+
+    /// Like `from_settings`, with the provider given explicitly. The OpenAI organization and
+    /// project headers are only sent to OpenAI.
+    pub fn from_settings_for_provider(settings: &Settings, provider_id: &str) -> Self {
+        let (organization, project) = openai_headers_for(settings, provider_id);
+        let client = Self::new_with_openai_headers(
+            settings.api_key.clone(),
+            Some(settings.base_url.clone()),
+            Some(provider_id),
+            Some(&settings.model),
+            organization,
+            project,
+            settings.proxy_url.clone(),
+            settings.proxy_no_proxy.clone(),
+        );
+        if settings.disable_streaming == Some(true) {
+            client.force_non_streaming()
+        } else {
+            client
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_openai_headers(
         api_key: String,
         base_url: Option<String>,
@@ -247,6 +482,8 @@ impl LLMClient {
         model: Option<&str>,
         openai_organization: Option<String>,
         openai_project: Option<String>,
+        proxy_url: Option<String>,
+        proxy_no_proxy: Option<String>,
     ) -> Self {
         // Infer config from provider_id or model
         let mut config = if let Some(pid) = provider_id {
@@ -257,21 +494,44 @@ impl LLMClient {
             ProviderConfig::from_preset("anthropic")
         };
 
+        if let Some(m) = model {
+            config.apply_model_headers(m);
+        }
+
         // Override default with custom base_url if provided
         if let Some(url) = base_url {
             config.base_url = url;
         }
 
+        // Proxy settings are validated when saved; fall back to a direct connection otherwise
+        let client = build_http_client(proxy_url.as_deref(), proxy_no_proxy.as_deref()).unwrap_or_else(|e| {
+            eprintln!("[LLMClient] Ignoring invalid proxy settings: {}", e);
+            Client::new()
+        });
+
         Self {
-            client: Client::new(),
+            client,
             api_key,
             base_url: config.base_url.clone(),
             provider_config: config,
             openai_organization,
             openai_project,
+            images: Vec::new(),
         }
     }
 
+    /// Request complete JSON responses instead of SSE streams
+    pub fn force_non_streaming(mut self) -> Self {
+        self.provider_config.streaming = false;
+        self
+    }
+
+    /// Send `images` with the latest user message, in the provider's image format
+    pub fn with_images(mut self, images: Vec<InlineImage>) -> Self {
+        self.images = images;
+        self
+    }
+
     /// Get API format
     #[allow(dead_code)]
     pub fn api_format(&self) -> &ApiFormat {
@@ -285,7 +545,8 @@ impl LLMClient {
         match self.provider_config.api_format {
             ApiFormat::Anthropic => format!("{}/v1/messages", base),
             ApiFormat::OpenAI | ApiFormat::OpenAICompatible => {
-                if base.ends_with("/v1") {
+                // Perplexity serves chat completions without a version prefix
+                if base.ends_with("/v1") || self.provider_config.id == "perplexity" {
                     format!("{}/chat/completions", base)
                 } else {
                     format!("{}/v1/chat/completions", base)
@@ -345,6 +606,10 @@ impl LLMClient {
             }
         }
 
+        if let Some(extra) = &self.provider_config.extra_headers {
+            headers.extend(extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+
         headers
     }
 
@@ -356,12 +621,13 @@ impl LLMClient {
         max_tokens: u32,
         temperature: Option<f32>,
     ) -> Result<String, LLMError> {
+        self.check_images_supported()?;
         match self.provider_config.api_format {
             ApiFormat::Anthropic => self.send_anthropic(messages, model, max_tokens, temperature, false, None).await,
             ApiFormat::OpenAI | ApiFormat::OpenAICompatible => self.send_openai_compatible(messages, model, max_tokens, temperature, false, None).await,
             ApiFormat::OpenAIResponses => self.send_openai_responses(messages, model, max_tokens, temperature, false, None).await,
             ApiFormat::Google => self.send_google(messages, model, max_tokens, temperature, false, None).await,
-            _ => Err(LLMError::UnsupportedProvider(format!("{:?}", self.provider_config.api_format))),
+            ApiFormat::Minimax => self.send_minimax(messages, model, max_tokens, temperature, false, None).await,
         }
     }
 
@@ -374,15 +640,80 @@ impl LLMClient {
         temperature: Option<f32>,
         tx: mpsc::Sender<String>,
     ) -> Result<String, LLMError> {
+        self.check_images_supported()?;
+        // Without SSE the whole reply arrives at once, so it is sent as a single update
+        if !self.provider_config.streaming {
+            let text = self.send_message(messages, model, max_tokens, temperature).await?;
+            let _ = tx.send(text.clone()).await;
+            return Ok(text);
+        }
+
         match self.provider_config.api_format {
             ApiFormat::Anthropic => self.send_anthropic(messages, model, max_tokens, temperature, true, Some(tx)).await,
             ApiFormat::OpenAI | ApiFormat::OpenAICompatible => self.send_openai_compatible(messages, model, max_tokens, temperature, true, Some(tx)).await,
             ApiFormat::OpenAIResponses => self.send_openai_responses(messages, model, max_tokens, temperature, true, Some(tx)).await,
             ApiFormat::Google => self.send_google(messages, model, max_tokens, temperature, true, Some(tx)).await,
-            _ => Err(LLMError::UnsupportedProvider(format!("{:?}", self.provider_config.api_format))),
+            ApiFormat::Minimax => self.send_minimax(messages, model, max_tokens, temperature, true, Some(tx)).await,
         }
     }
 
+    fn check_images_supported(&self) -> Result<(), LLMError> {
+        if !self.images.is_empty() && self.provider_config.api_format == ApiFormat::Minimax {
+            return Err(LLMError::UnsupportedProvider(
+                "Minimax chat doesn't accept image attachments".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The attached images as content parts in the provider's format
+    fn image_parts(&self) -> Vec<serde_json::Value> {
+        self.images
+            .iter()
+            .map(|image| match self.provider_config.api_format {
+                ApiFormat::Anthropic => serde_json::json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": image.media_type, "data": image.data }
+                }),
+                ApiFormat::OpenAIResponses => serde_json::json!({
+                    "type": "input_image",
+                    "image_url": format!("data:{};base64,{}", image.media_type, image.data)
+                }),
+                ApiFormat::Google => serde_json::json!({
+                    "inline_data": { "mime_type": image.media_type, "data": image.data }
+                }),
+                ApiFormat::OpenAI | ApiFormat::OpenAICompatible | ApiFormat::Minimax => serde_json::json!({
+                    "type": "image_url",
+                    "image_url": { "url": format!("data:{};base64,{}", image.media_type, image.data) }
+                }),
+            })
+            .collect()
+    }
+
+    /// Messages as request JSON, with the attached images added to the latest user message
+    fn messages_with_images(&self, messages: &[Message]) -> Vec<serde_json::Value> {
+        let mut values: Vec<serde_json::Value> = messages
+            .iter()
+            .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+            .collect();
+        if self.images.is_empty() {
+            return values;
+        }
+
+        if let Some(last) = values.iter_mut().rev().find(|m| m["role"] == "user") {
+            let text = last["content"].as_str().unwrap_or_default().to_string();
+            let text_type = if self.provider_config.api_format == ApiFormat::OpenAIResponses {
+                "input_text"
+            } else {
+                "text"
+            };
+            let mut parts = vec![serde_json::json!({ "type": text_type, "text": text })];
+            parts.extend(self.image_parts());
+            last["content"] = serde_json::Value::Array(parts);
+        }
+        values
+    }
+
     /// Anthropic API call
     async fn send_anthropic(
         &self,
@@ -396,13 +727,18 @@ impl LLMClient {
         let url = self.get_api_endpoint();
         let headers = self.build_headers();
 
-        let payload = serde_json::json!({
+        // Anthropic takes the system prompt out of band
+        let (system, messages) = self.extract_instructions(messages);
+        let mut payload = serde_json::json!({
             "model": model,
             "max_tokens": max_tokens,
-            "messages": messages,
+            "messages": self.messages_with_images(&messages),
             "stream": stream,
             "temperature": temperature,
         });
+        if let Some(system) = system {
+            payload["system"] = serde_json::json!(system);
+        }
 
         let mut request = self.client.post(&url);
         for (key, value) in headers {
@@ -467,7 +803,7 @@ impl LLMClient {
         // Build payload based on model type
         let mut payload = serde_json::json!({
             "model": model,
-            "messages": messages,
+            "messages": self.messages_with_images(&messages),
             "stream": stream,
         });
 
@@ -515,7 +851,10 @@ impl LLMClient {
                 .and_then(|choice| choice["message"]["content"].as_str())
                 .unwrap_or("")
                 .to_string();
-            Ok(text)
+            Ok(match extract_citations(&data) {
+                Some(citations) => append_citations(text, &citations),
+                None => text,
+            })
         }
     }
 
@@ -570,6 +909,7 @@ impl LLMClient {
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
         let mut full_text = String::new();
+        let mut citations: Option<Vec<String>> = None;
 
         while let Some(chunk) = stream.next().await {
             let chunk = chunk?;
@@ -585,6 +925,119 @@ impl LLMClient {
                     }
 
                     if let Ok(event) = serde_json::from_str::<serde_json::Value>(data) {
+                        // Perplexity repeats the citation list on every chunk
+                        if let Some(found) = extract_citations(&event) {
+                            citations = Some(found);
+                        }
+
+                        if let Some(delta) = event["choices"]
+                            .as_array()
+                            .and_then(|arr| arr.first())
+                            .and_then(|choice| choice["delta"]["content"].as_str())
+                        {
+                            full_text.push_str(delta);
+                            let _ = tx.send(full_text.clone()).await;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(citations) = citations {
+            full_text = append_citations(full_text, &citations);
+            let _ = tx.send(full_text.clone()).await;
+        }
+
+        Ok(full_text)
+    }
+
+    /// Minimax chatcompletion_v2 API call
+    async fn send_minimax(
+        &self,
+        messages: Vec<Message>,
+        model: &str,
+        max_tokens: u32,
+        temperature: Option<f32>,
+        stream: bool,
+        tx: Option<mpsc::Sender<String>>,
+    ) -> Result<String, LLMError> {
+        let url = self.get_api_endpoint();
+        let headers = self.build_headers();
+
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": messages,
+            "max_tokens": max_tokens,
+            "stream": stream,
+        });
+        // Minimax only accepts temperatures in (0, 1]
+        if let Some(temp) = temperature {
+            payload["temperature"] = serde_json::json!(temp.clamp(0.01, 1.0));
+        }
+
+        let mut request = self.client.post(&url);
+        for (key, value) in headers {
+            request = request.header(&key, &value);
+        }
+
+        let response = request.json(&payload).send().await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LLMError::Api(error_text));
+        }
+
+        if stream {
+            self.handle_minimax_stream(response, tx.unwrap()).await
+        } else {
+            let data: serde_json::Value = response.json().await?;
+            if let Some(error) = minimax_error(&data) {
+                return Err(LLMError::Api(error));
+            }
+            let text = data["choices"]
+                .as_array()
+                .and_then(|arr| arr.first())
+                .and_then(|choice| choice["message"]["content"].as_str())
+                .unwrap_or("")
+                .to_string();
+            Ok(text)
+        }
+    }
+
+    /// Handle Minimax streaming response
+    ///
+    /// Chunks carry `delta.content`; the last one repeats the whole reply in `message`
+    /// and is skipped. Errors arrive as a chunk with a non-zero `base_resp.status_code`.
+    async fn handle_minimax_stream(
+        &self,
+        response: reqwest::Response,
+        tx: mpsc::Sender<String>,
+    ) -> Result<String, LLMError> {
+        use futures::StreamExt;
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+        let mut full_text = String::new();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line = buffer[..pos].to_string();
+                buffer = buffer[pos + 1..].to_string();
+
+                // Minimax does not always put a space after the colon
+                if let Some(data) = line.strip_prefix("data:").map(str::trim) {
+                    if data == "[DONE]" {
+                        continue;
+                    }
+
+                    if let Ok(event) = serde_json::from_str::<serde_json::Value>(data) {
+                        if let Some(error) = minimax_error(&event) {
+                            return Err(LLMError::Api(error));
+                        }
+
                         if let Some(delta) = event["choices"]
                             .as_array()
                             .and_then(|arr| arr.first())
@@ -620,9 +1073,7 @@ impl LLMClient {
         // Build request payload for Responses API
         let mut payload = serde_json::json!({
             "model": model,
-            "input": input_messages.iter().map(|m| {
-                serde_json::json!({"role": m.role, "content": m.content})
-            }).collect::<Vec<_>>(),
+            "input": self.messages_with_images(&input_messages),
             "max_output_tokens": max_tokens,
             "temperature": temperature.unwrap_or(1.0),
             "stream": stream
@@ -754,8 +1205,9 @@ impl LLMClient {
         };
 
         // Convert messages to Google format
-        // Google uses "contents" with "parts" structure
-        let contents: Vec<serde_json::Value> = messages
+        // Google uses "contents" with "parts" structure; the system prompt goes in systemInstruction
+        let (system, messages) = self.extract_instructions(messages);
+        let mut contents: Vec<serde_json::Value> = messages
             .iter()
             .map(|m| {
                 // Google uses "user" and "model" instead of "user" and "assistant"
@@ -766,14 +1218,22 @@ impl LLMClient {
                 })
             })
             .collect();
+        if let Some(last) = contents.iter_mut().rev().find(|c| c["role"] == "user") {
+            if let Some(parts) = last["parts"].as_array_mut() {
+                parts.extend(self.image_parts());
+            }
+        }
 
         // Build payload - Gemini 3 recommends NOT setting custom temperature (keep at default 1.0)
-        let payload = serde_json::json!({
+        let mut payload = serde_json::json!({
             "contents": contents,
             "generationConfig": {
                 "maxOutputTokens": max_tokens
             }
         });
+        if let Some(system) = system {
+            payload["systemInstruction"] = serde_json::json!({ "parts": [{ "text": system }] });
+        }
 
         // Use x-goog-api-key header for authentication (recommended for Gemini 3)
         let response = self.client
@@ -871,6 +1331,12 @@ impl LLMClient {
     pub async fn check_connection(&self) -> Result<bool, LLMError> {
         let base = self.base_url.trim_end_matches('/');
 
+        // Without a models endpoint, any answer from the API host counts as reachable
+        if !self.provider_config.is_discoverable() {
+            let response = self.client.get(base).timeout(std::time::Duration::from_secs(5)).send().await;
+            return Ok(response.is_ok());
+        }
+
         // Try OpenAI models endpoint
         let models_url = if base.ends_with("/v1") {
             format!("{}/models", base)
@@ -878,7 +1344,18 @@ impl LLMClient {
             format!("{}/v1/models", base)
         };
 
-        match self.client.get(&models_url).timeout(std::time::Duration::from_secs(5)).send().await {
+        let models_url = if self.provider_config.auth_type == AuthType::QueryParam && !self.api_key.is_empty() {
+            format!("{}?key={}", models_url, self.api_key)
+        } else {
+            models_url
+        };
+
+        // Cloud providers only list models for authenticated requests
+        let mut request = self.client.get(&models_url).timeout(std::time::Duration::from_secs(5));
+        for (key, value) in self.build_headers() {
+            request = request.header(key, value);
+        }
+        match request.send().await {
             Ok(resp) if resp.status().is_success() => return Ok(true),
             _ => {}
         }
@@ -894,27 +1371,33 @@ impl LLMClient {
     }
 
     /// Discover available models
-    #[allow(dead_code)]
     pub async fn discover_models(&self) -> Result<Vec<String>, LLMError> {
         let base = self.base_url.trim_end_matches('/');
 
-        // Try OpenAI models endpoint
+        match self.provider_config.api_format {
+            // Anthropic has no models endpoint
+            ApiFormat::Anthropic => return Ok(ANTHROPIC_MODELS.iter().map(|m| m.to_string()).collect()),
+            ApiFormat::Google => {
+                let url = format!("{}/v1beta/models?key={}", base, self.api_key);
+                let data = self.get_json(&url).await?;
+                return Ok(parse_google_models(&data));
+            }
+            _ => {}
+        }
+        if !self.provider_config.is_discoverable() {
+            return Ok(vec![]);
+        }
+
+        // OpenAI-style endpoint; OpenRouter's base URL already ends in /api/v1
         let models_url = if base.ends_with("/v1") {
             format!("{}/models", base)
         } else {
             format!("{}/v1/models", base)
         };
 
-        if let Ok(resp) = self.client.get(&models_url).send().await {
-            if resp.status().is_success() {
-                if let Ok(data) = resp.json::<serde_json::Value>().await {
-                    if let Some(models) = data["data"].as_array() {
-                        return Ok(models
-                            .iter()
-                            .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-                            .collect());
-                    }
-                }
+        if let Ok(data) = self.get_json(&models_url).await {
+            if let Some(models) = parse_openai_models(&data) {
+                return Ok(models);
             }
         }
 
@@ -935,12 +1418,138 @@ impl LLMClient {
 
         Ok(vec![])
     }
+
+    /// Authenticated GET returning the JSON body
+    async fn get_json(&self, url: &str) -> Result<serde_json::Value, LLMError> {
+        let mut request = self.client.get(url);
+        for (key, value) in self.build_headers() {
+            request = request.header(key, value);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LLMError::Api(format!("{}: {}", status, error_text)));
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+/// Models offered by Anthropic, newest first
+pub const ANTHROPIC_MODELS: &[&str] = &[
+    "claude-opus-4-5-20251101",
+    "claude-sonnet-4-5-20250929",
+    "claude-haiku-4-5-20251001",
+    "claude-opus-4-1-20250805",
+    "claude-sonnet-4-20250514",
+    "claude-3-5-haiku-20241022",
+];
+
+/// `data[].id` of an OpenAI-style (or OpenRouter) models response
+fn parse_openai_models(data: &serde_json::Value) -> Option<Vec<String>> {
+    let models = data["data"].as_array()?;
+    Some(
+        models
+            .iter()
+            .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
+            .collect(),
+    )
+}
+
+/// Gemini's `models[].name` without the `models/` prefix, skipping models that can't chat
+fn parse_google_models(data: &serde_json::Value) -> Vec<String> {
+    data["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|m| match m["supportedGenerationMethods"].as_array() {
+            Some(methods) => methods.iter().any(|v| v.as_str() == Some("generateContent")),
+            None => true,
+        })
+        .filter_map(|m| m["name"].as_str())
+        .map(|name| name.strip_prefix("models/").unwrap_or(name).to_string())
+        .collect()
+}
+
+/// Extract the `citations` array returned by search-backed providers (e.g. Perplexity)
+fn extract_citations(data: &serde_json::Value) -> Option<Vec<String>> {
+    let citations: Vec<String> = data["citations"]
+        .as_array()?
+        .iter()
+        .filter_map(|c| c.as_str().map(|s| s.to_string()))
+        .collect();
+
+    if citations.is_empty() {
+        None
+    } else {
+        Some(citations)
+    }
+}
+
+/// Minimax reports failures with HTTP 200 and a non-zero `base_resp.status_code`
+fn minimax_error(data: &serde_json::Value) -> Option<String> {
+    let status = data["base_resp"]["status_code"].as_i64().unwrap_or(0);
+    if status == 0 {
+        return None;
+    }
+    let message = data["base_resp"]["status_msg"].as_str().unwrap_or("unknown error");
+    Some(format!("Minimax error {}: {}", status, message))
+}
+
+/// Append a "Sources" block listing citations to the response text
+fn append_citations(mut text: String, citations: &[String]) -> String {
+    text.push_str("\n\nSources:");
+    for citation in citations {
+        text.push_str("\n- ");
+        text.push_str(citation);
+    }
+    text
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_estimate_tokens_close_to_tokenizer_counts() {
+        // 45 bytes and 10 tokens per sentence with both providers' tokenizers
+        let prompt = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        let messages = vec![
+            Message { role: "user".to_string(), content: prompt.clone() },
+            Message { role: "assistant".to_string(), content: prompt },
+        ];
+        let expected = 400.0;
+
+        for provider in ["anthropic", "openai"] {
+            let client = LLMClient::new(String::new(), None, Some(provider), None);
+            let estimate = client.estimate_tokens(&messages) as f64;
+            assert!(
+                (estimate - expected).abs() / expected <= 0.2,
+                "{} estimate {} is not within 20% of {}",
+                provider,
+                estimate,
+                expected
+            );
+        }
+
+        let gemini = LLMClient::new(String::new(), None, Some("google"), None);
+        let anthropic = LLMClient::new(String::new(), None, Some("anthropic"), None);
+        assert!(gemini.estimate_tokens(&messages) > anthropic.estimate_tokens(&messages));
+        assert_eq!(gemini.estimate_tokens(&[]), 0);
+    }
+
+    #[test]
+    fn test_model_context_window() {
+        assert_eq!(LLMClient::model_context_window("claude-sonnet-4-5-20250929"), Some(200_000));
+        assert_eq!(LLMClient::model_context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(LLMClient::model_context_window("gpt-4"), Some(8_192));
+        assert_eq!(LLMClient::model_context_window("openai/gpt-4o"), Some(128_000));
+        assert_eq!(LLMClient::model_context_window("llama3.3:latest"), None);
+        assert_eq!(LLMClient::model_context_window(""), None);
+    }
+
     #[test]
     fn test_provider_from_model() {
         // Claude model
@@ -978,4 +1587,334 @@ mod tests {
         assert_eq!(config.id, "openai");
         assert_eq!(config.api_format, ApiFormat::OpenAIResponses);
     }
+
+    #[test]
+    fn test_provider_presets() {
+        let ids = provider_preset_ids();
+        assert_eq!(ids.len(), 16);
+        assert_eq!(ids.first(), Some(&"anthropic"));
+        for id in &ids {
+            let config = ProviderConfig::from_preset(id);
+            assert_eq!(config.id, *id);
+            assert_ne!(config.name, "Custom", "{}", id);
+        }
+
+        let not_discoverable: Vec<&str> = ids
+            .iter()
+            .copied()
+            .filter(|id| !ProviderConfig::from_preset(id).is_discoverable())
+            .collect();
+        assert_eq!(not_discoverable, vec!["perplexity"]);
+        assert!(ProviderConfig::from_preset("my-gateway").is_discoverable());
+    }
+
+    #[test]
+    fn test_perplexity_provider() {
+        let config = ProviderConfig::from_model("llama-3.1-sonar-small-128k-online");
+        assert_eq!(config.id, "perplexity");
+        assert_eq!(config.base_url, "https://api.perplexity.ai");
+        assert_eq!(config.api_format, ApiFormat::OpenAICompatible);
+
+        let client = LLMClient::new("key".to_string(), None, Some("perplexity"), None);
+        assert_eq!(client.get_api_endpoint(), "https://api.perplexity.ai/chat/completions");
+    }
+
+    #[test]
+    fn test_citations_appended() {
+        let data = serde_json::json!({
+            "choices": [{"message": {"content": "Rust 1.0 shipped in 2015."}}],
+            "citations": ["https://blog.rust-lang.org/2015/05/15/Rust-1.0.html", "https://www.rust-lang.org"]
+        });
+
+        let citations = extract_citations(&data).unwrap();
+        let text = append_citations("Rust 1.0 shipped in 2015.".to_string(), &citations);
+        assert_eq!(
+            text,
+            "Rust 1.0 shipped in 2015.\n\nSources:\n- https://blog.rust-lang.org/2015/05/15/Rust-1.0.html\n- https://www.rust-lang.org"
+        );
+
+        let no_citations = serde_json::json!({"choices": []});
+        assert!(extract_citations(&no_citations).is_none());
+    }
+
+    #[test]
+    fn test_client_with_proxy_builds() {
+        assert!(build_http_client(Some("http://proxy.corp.example:8080"), Some("localhost,127.0.0.1")).is_ok());
+        assert!(build_http_client(None, None).is_ok());
+
+        let client = LLMClient::new_with_openai_headers(
+            "key".to_string(),
+            None,
+            Some("openai"),
+            None,
+            None,
+            None,
+            Some("http://proxy.corp.example:8080".to_string()),
+            Some(".internal.example".to_string()),
+        );
+        assert_eq!(client.get_api_endpoint(), "https://api.openai.com/v1/chat/completions");
+    }
+
+    #[tokio::test]
+    async fn test_openai_headers_sent_only_to_openai() {
+        let heads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = heads.clone();
+        let url = crate::test_support::spawn_mock_http_server(move |head, _body| {
+            recorded.lock().unwrap().push(head.to_lowercase());
+            Some(("application/json", r#"{"choices": [{"message": {"content": "ok"}}]}"#.to_string()))
+        })
+        .await;
+
+        let openai = Settings {
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            api_key: "sk-test".to_string(),
+            base_url: url.clone(),
+            openai_organization: Some("org-123".to_string()),
+            openai_project: Some("proj-456".to_string()),
+            ..Settings::default()
+        };
+        // Organization and project left over from an earlier OpenAI setup
+        let groq = Settings {
+            provider: "groq".to_string(),
+            model: "llama-3.3-70b-versatile".to_string(),
+            ..openai.clone()
+        };
+        let messages = vec![Message { role: "user".to_string(), content: "hi".to_string() }];
+        for settings in [&openai, &groq] {
+            let client = LLMClient::from_settings(settings);
+            assert_eq!(client.send_message(messages.clone(), &settings.model, 16, None).await.unwrap(), "ok");
+        }
+
+        let heads = heads.lock().unwrap();
+        assert_eq!(heads.len(), 2);
+        assert!(heads[0].contains("openai-organization: org-123\r\n"), "{}", heads[0]);
+        assert!(heads[0].contains("openai-project: proj-456\r\n"), "{}", heads[0]);
+        assert!(heads[1].contains("authorization: bearer sk-test\r\n"), "{}", heads[1]);
+        assert!(!heads[1].contains("openai-organization"), "{}", heads[1]);
+        assert!(!heads[1].contains("openai-project"), "{}", heads[1]);
+    }
+
+    #[tokio::test]
+    async fn test_discover_models_parses_each_provider_format() {
+        let url = crate::test_support::spawn_mock_http_server(|head, _body| {
+            let request_line = head.lines().next().unwrap_or("");
+            let reply = if request_line.starts_with("GET /v1/models ") {
+                assert!(head.contains("Bearer sk-openai"), "{}", head);
+                serde_json::json!({"object": "list", "data": [{"id": "gpt-4o"}, {"id": "gpt-4o-mini"}]})
+            } else if request_line.starts_with("GET /v1beta/models?key=gemini-key ") {
+                serde_json::json!({"models": [
+                    {"name": "models/gemini-2.5-pro", "supportedGenerationMethods": ["generateContent", "countTokens"]},
+                    {"name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"]},
+                    {"name": "models/gemini-2.0-flash"}
+                ]})
+            } else if request_line.starts_with("GET /api/v1/models ") {
+                serde_json::json!({"data": [
+                    {"id": "anthropic/claude-3.5-sonnet", "name": "Claude 3.5 Sonnet", "pricing": {"prompt": "0.000003"}},
+                    {"id": "meta-llama/llama-3.3-70b-instruct", "name": "Llama 3.3 70B"}
+                ]})
+            } else {
+                return None;
+            };
+            Some(("application/json", reply.to_string()))
+        })
+        .await;
+
+        let discover = |key: &str, base_url: String, provider: &str| {
+            LLMClient::new(key.to_string(), Some(base_url), Some(provider), None)
+        };
+
+        let openai = discover("sk-openai", url.clone(), "openai").discover_models().await.unwrap();
+        assert_eq!(openai, vec!["gpt-4o", "gpt-4o-mini"]);
+
+        let google = discover("gemini-key", url.clone(), "google").discover_models().await.unwrap();
+        assert_eq!(google, vec!["gemini-2.5-pro", "gemini-2.0-flash"]);
+
+        let openrouter = discover("sk-or", format!("{}/api/v1", url), "openrouter")
+            .discover_models()
+            .await
+            .unwrap();
+        assert_eq!(openrouter, vec!["anthropic/claude-3.5-sonnet", "meta-llama/llama-3.3-70b-instruct"]);
+
+        // Anthropic has no models endpoint, so the mock is never asked
+        let anthropic = discover("sk-ant", url.clone(), "anthropic").discover_models().await.unwrap();
+        assert_eq!(anthropic, ANTHROPIC_MODELS);
+
+        // A rejected key surfaces as an error instead of an empty list
+        assert!(discover("wrong-key", url, "google").discover_models().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disable_streaming_setting_sends_full_reply_once() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let url = crate::test_support::spawn_mock_server(move |body| {
+            recorded.lock().unwrap().push(body);
+            (
+                "application/json",
+                serde_json::json!({"choices": [{"message": {"role": "assistant", "content": "Complete reply"}}]})
+                    .to_string(),
+            )
+        })
+        .await;
+
+        let client = LLMClient::from_settings(&Settings {
+            provider: "ollama".to_string(),
+            model: "llama3.3:latest".to_string(),
+            base_url: url,
+            disable_streaming: Some(true),
+            ..Settings::default()
+        });
+        let (tx, mut rx) = mpsc::channel(10);
+        let text = client
+            .send_message_stream(
+                vec![Message { role: "user".to_string(), content: "hi".to_string() }],
+                "llama3.3:latest",
+                256,
+                None,
+                tx,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(text, "Complete reply");
+        assert_eq!(requests.lock().unwrap()[0]["stream"], serde_json::json!(false));
+        assert_eq!(rx.recv().await.as_deref(), Some("Complete reply"));
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_minimax_request_and_response_format() {
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let url = crate::test_support::spawn_mock_http_server(move |head, body| {
+            recorded.lock().unwrap().push((head.to_string(), body.clone()));
+            if !head.starts_with("POST /v1/text/chatcompletion_v2 ") {
+                return None;
+            }
+            if body["stream"] == serde_json::json!(true) {
+                let chunks = [
+                    serde_json::json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+                    serde_json::json!({"choices": [{"index": 0, "delta": {"content": "lo"}}]}),
+                    serde_json::json!({"choices": [{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "Hello"}}]}),
+                ];
+                let body: String = chunks.iter().map(|c| format!("data:{}\n\n", c)).collect();
+                Some(("text/event-stream", body))
+            } else {
+                Some((
+                    "application/json",
+                    serde_json::json!({
+                        "choices": [{"index": 0, "message": {"role": "assistant", "content": "Full reply"}}],
+                        "base_resp": {"status_code": 0, "status_msg": ""}
+                    })
+                    .to_string(),
+                ))
+            }
+        })
+        .await;
+
+        let client = LLMClient::new("mm-key".to_string(), Some(url), Some("minimax"), None);
+        let messages = vec![Message { role: "user".to_string(), content: "hi".to_string() }];
+
+        let text = client.send_message(messages.clone(), "MiniMax-Text-01", 256, Some(1.5)).await.unwrap();
+        assert_eq!(text, "Full reply");
+
+        let (tx, mut rx) = mpsc::channel(10);
+        let text = client
+            .send_message_stream(messages, "MiniMax-Text-01", 256, None, tx)
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello");
+        assert_eq!(rx.recv().await.as_deref(), Some("Hel"));
+        assert_eq!(rx.recv().await.as_deref(), Some("Hello"));
+        assert!(rx.recv().await.is_none());
+
+        let requests = requests.lock().unwrap();
+        let (head, body) = &requests[0];
+        assert!(head.to_lowercase().contains("authorization: bearer mm-key"), "{}", head);
+        assert_eq!(body["model"], "MiniMax-Text-01");
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["temperature"], 1.0);
+        assert_eq!(body["messages"], serde_json::json!([{ "role": "user", "content": "hi" }]));
+        assert_eq!(requests[1].1["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_images_sent_in_each_provider_format() {
+        let bodies = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+        let url = crate::test_support::spawn_mock_server(move |body| {
+            recorded.lock().unwrap().push(body);
+            (
+                "application/json",
+                serde_json::json!({
+                    "choices": [{"message": {"content": "A cat"}}],
+                    "candidates": [{"content": {"parts": [{"text": "A cat"}]}}]
+                })
+                .to_string(),
+            )
+        })
+        .await;
+        let image = InlineImage { media_type: "image/png".to_string(), data: "iVBORw0KGgo=".to_string() };
+        let messages = vec![
+            Message { role: "user".to_string(), content: "Hi".to_string() },
+            Message { role: "assistant".to_string(), content: "Hello".to_string() },
+            Message { role: "user".to_string(), content: "What is this?".to_string() },
+        ];
+
+        for provider in ["openai", "google"] {
+            let client = LLMClient::new("key".to_string(), Some(url.clone()), Some(provider), None)
+                .with_images(vec![image.clone()]);
+            let text = client.send_message(messages.clone(), "vision-model", 256, None).await.unwrap();
+            assert_eq!(text, "A cat");
+        }
+        let minimax = LLMClient::new("key".to_string(), Some(url), Some("minimax"), None)
+            .with_images(vec![image]);
+        let err = minimax.send_message(messages, "MiniMax-Text-01", 256, None).await.unwrap_err();
+        assert!(matches!(err, LLMError::UnsupportedProvider(_)), "{}", err);
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let openai = &bodies[0]["messages"];
+        assert_eq!(openai[0]["content"], "Hi");
+        assert_eq!(
+            openai[2]["content"],
+            serde_json::json!([
+                { "type": "text", "text": "What is this?" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } }
+            ])
+        );
+        let google = &bodies[1]["contents"];
+        assert_eq!(google[0]["parts"], serde_json::json!([{ "text": "Hi" }]));
+        assert_eq!(
+            google[2]["parts"],
+            serde_json::json!([
+                { "text": "What is this?" },
+                { "inline_data": { "mime_type": "image/png", "data": "iVBORw0KGgo=" } }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_minimax_error() {
+        let failed = serde_json::json!({"base_resp": {"status_code": 1004, "status_msg": "authorization failed"}});
+        assert_eq!(minimax_error(&failed).as_deref(), Some("Minimax error 1004: authorization failed"));
+        assert_eq!(minimax_error(&serde_json::json!({"base_resp": {"status_code": 0}})), None);
+        assert_eq!(minimax_error(&serde_json::json!({"choices": []})), None);
+    }
+
+    #[test]
+    fn test_siliconflow_model_header() {
+        let client = LLMClient::new("key".to_string(), None, Some("siliconflow"), Some("deepseek-ai/DeepSeek-R1"));
+        let headers = client.build_headers();
+        assert_eq!(headers.first().map(|(k, _)| k.as_str()), Some("Content-Type"));
+        assert_eq!(
+            headers.last(),
+            Some(&("SF-Model-Id".to_string(), "deepseek-ai/DeepSeek-R1".to_string()))
+        );
+
+        let client = LLMClient::new("key".to_string(), None, Some("deepseek"), Some("deepseek-reasoner"));
+        assert!(client.build_headers().iter().all(|(k, _)| k != "SF-Model-Id"));
+    }
 }
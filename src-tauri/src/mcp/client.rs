@@ -1,16 +1,29 @@
 use super::http_client::HttpMcpClient;
+use super::oauth::{self, OAuthToken, DEVICE_CODE_FLOW};
+use crate::app_errors::{AppError, ErrorReporter};
 use super::stdio_client::{ProtocolMode, StdioMcpClient};
 use super::types::*;
-use std::collections::HashMap;
+use super::ws_client::WebSocketMcpClient;
+use std::collections::{HashMap, VecDeque};
 use std::path::Path;
 use std::sync::Arc;
 use tokio::process::{Child, Command};
 use tokio::sync::RwLock;
 use tokio::time::{sleep, Duration, Instant};
 
+/// Retries at startup when a server doesn't set `startup_retry_attempts`
+const DEFAULT_STARTUP_RETRY_ATTEMPTS: u32 = 3;
+/// Delay before the first startup retry; doubles after each failure
+const STARTUP_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+/// How often the background task checks for servers whose tool list is due a refresh
+const TOOL_REFRESH_TICK: Duration = Duration::from_secs(5);
+/// Longest `shutdown_all` waits for servers and their processes to stop
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 enum MCPTransportClient {
     Http(HttpMcpClient),
     Stdio(StdioMcpClient),
+    WebSocket(WebSocketMcpClient),
 }
 
 pub struct MCPClient {
@@ -19,15 +32,58 @@ pub struct MCPClient {
     url: String,
 }
 
+/// Periodic tool list refresh of a server with `refresh_interval_secs` set
+struct ToolRefreshSchedule {
+    interval: Duration,
+    last_refresh: Instant,
+}
+
 struct ManagedProcess {
     child: Child,
     pid: Option<u32>,
 }
 
+/// Tool call timeout used when a server has no override
+pub const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 60;
+
+/// Stderr lines kept per stdio server
+pub const MAX_SERVER_LOG_LINES: usize = 50;
+
+/// Status changes kept in the connection history, across all servers
+pub const MAX_CONNECTION_HISTORY: usize = 200;
+
+/// Event emitted to the frontend whenever a server's connection status changes
+pub const STATUS_CHANGED_EVENT: &str = "mcp-status-changed";
+
+/// Receives every status change; the app forwards them to the frontend
+type StatusListener = Arc<dyn Fn(&MCPStatusChangedPayload) + Send + Sync>;
+
+/// Event asking the user to approve a device code sign-in
+pub const DEVICE_CODE_EVENT: &str = "mcp-oauth-device-code";
+
+type DeviceCodeListener = Arc<dyn Fn(&MCPDeviceCodePrompt) + Send + Sync>;
+
+/// An access token and the config it was obtained with, so it can be renewed on expiry
+struct OAuthSession {
+    config: MCPServerConfig,
+    token: OAuthToken,
+}
+
 pub struct MCPManager {
     clients: Arc<RwLock<HashMap<String, MCPClient>>>,
     server_status: Arc<RwLock<HashMap<String, MCPServerStatus>>>,
     managed_processes: Arc<RwLock<HashMap<String, ManagedProcess>>>,
+    default_tool_timeout_secs: u64,
+    status_listener: std::sync::RwLock<Option<StatusListener>>,
+    /// Recent stderr output of stdio servers, oldest first
+    server_logs: Arc<std::sync::RwLock<HashMap<String, VecDeque<String>>>>,
+    /// Recent status changes of all servers, oldest first
+    connection_history: Arc<std::sync::RwLock<VecDeque<MCPConnectionEvent>>>,
+    error_reporter: std::sync::RwLock<Option<ErrorReporter>>,
+    /// OAuth access tokens by server id; kept in memory only, never in the database
+    oauth_sessions: RwLock<HashMap<String, OAuthSession>>,
+    device_code_listener: std::sync::RwLock<Option<DeviceCodeListener>>,
+    tool_refresh: std::sync::RwLock<HashMap<String, ToolRefreshSchedule>>,
 }
 
 impl MCPManager {
@@ -36,9 +92,181 @@ impl MCPManager {
             clients: Arc::new(RwLock::new(HashMap::new())),
             server_status: Arc::new(RwLock::new(HashMap::new())),
             managed_processes: Arc::new(RwLock::new(HashMap::new())),
+            default_tool_timeout_secs: DEFAULT_TOOL_TIMEOUT_SECS,
+            status_listener: std::sync::RwLock::new(None),
+            server_logs: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            connection_history: Arc::new(std::sync::RwLock::new(VecDeque::new())),
+            error_reporter: std::sync::RwLock::new(None),
+            oauth_sessions: RwLock::new(HashMap::new()),
+            device_code_listener: std::sync::RwLock::new(None),
+            tool_refresh: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Send background connection failures to `reporter` instead of only logging them
+    pub fn set_error_reporter(&self, reporter: ErrorReporter) {
+        if let Ok(mut slot) = self.error_reporter.write() {
+            *slot = Some(reporter);
+        }
+    }
+
+    fn report_error(&self, error: AppError) {
+        let reporter = self.error_reporter.read().ok().and_then(|slot| slot.clone());
+        match reporter {
+            Some(reporter) => reporter.report(error),
+            None => eprintln!("[{}] {}: {}", error.component, error.code, error.message),
+        }
+    }
+
+    /// The last stderr lines of a stdio server, oldest first
+    pub fn get_server_logs(&self, server_id: &str) -> Vec<String> {
+        self.server_logs
+            .read()
+            .ok()
+            .and_then(|logs| logs.get(server_id).map(|lines| lines.iter().cloned().collect()))
+            .unwrap_or_default()
+    }
+
+    /// Recorded status changes, oldest first, optionally only those of one server
+    pub fn get_connection_history(&self, server_id: Option<&str>) -> Vec<MCPConnectionEvent> {
+        self.connection_history
+            .read()
+            .map(|history| {
+                history
+                    .iter()
+                    .filter(|event| server_id.is_none() || server_id == Some(event.server_id.as_str()))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Append a status change to the history; unchanged statuses are only kept when they carry an error
+    fn record_status_change(&self, server_id: &str, to: &ConnectionStatus, error: Option<&str>) {
+        let Ok(mut history) = self.connection_history.write() else {
+            return;
+        };
+        let to_status = format!("{:?}", to);
+        let from_status = history
+            .iter()
+            .rev()
+            .find(|event| event.server_id == server_id)
+            .map(|event| event.to_status.clone())
+            .unwrap_or_else(|| format!("{:?}", ConnectionStatus::Disconnected));
+        if from_status == to_status && error.is_none() {
+            return;
+        }
+        if history.len() >= MAX_CONNECTION_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(MCPConnectionEvent {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            server_id: server_id.to_string(),
+            from_status,
+            to_status,
+            error: error.map(|e| e.to_string()),
+        });
+    }
+
+    /// Logs each stderr line of a server and keeps the last `MAX_SERVER_LOG_LINES` of them
+    fn stderr_logger(&self, server_id: &str) -> impl Fn(String) + Send + 'static {
+        let server_logs = self.server_logs.clone();
+        let server_id = server_id.to_string();
+        move |line| {
+            eprintln!("[MCP:{}] {}", server_id, line);
+            if let Ok(mut logs) = server_logs.write() {
+                let lines = logs.entry(server_id.clone()).or_default();
+                if lines.len() >= MAX_SERVER_LOG_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        }
+    }
+
+    /// Emit `mcp-status-changed` on `app_handle` whenever a server connects, fails or disconnects,
+    /// and `mcp-oauth-device-code` when a server's device code sign-in needs the user
+    pub fn set_app_handle(&self, app_handle: tauri::AppHandle) {
+        use tauri::Emitter;
+        let device_code_handle = app_handle.clone();
+        self.set_status_listener(move |payload| {
+            let _ = app_handle.emit(STATUS_CHANGED_EVENT, payload);
+        });
+        self.set_device_code_listener(move |prompt| {
+            let _ = device_code_handle.emit(DEVICE_CODE_EVENT, prompt);
+        });
+    }
+
+    fn set_device_code_listener(&self, listener: impl Fn(&MCPDeviceCodePrompt) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.device_code_listener.write() {
+            *slot = Some(Arc::new(listener));
+        }
+    }
+
+    fn set_status_listener(&self, listener: impl Fn(&MCPStatusChangedPayload) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.status_listener.write() {
+            *slot = Some(Arc::new(listener));
+        }
+    }
+
+    /// Record the server's new status in the connection history and pass it to the listener
+    async fn notify_status_changed(&self, server_id: &str) {
+        let payload = {
+            let status_map = self.server_status.read().await;
+            let Some(status) = status_map.get(server_id) else {
+                return;
+            };
+            MCPStatusChangedPayload {
+                server_id: status.id.clone(),
+                name: status.name.clone(),
+                status: status.status.clone(),
+                error: status.last_error.clone(),
+            }
+        };
+        // Warnings on a connected server are not connection errors
+        let error = match payload.status {
+            ConnectionStatus::Error => payload.error.as_deref(),
+            _ => None,
+        };
+        self.record_status_change(server_id, &payload.status, error);
+
+        let listener = match self.status_listener.read() {
+            Ok(slot) => slot.clone(),
+            Err(_) => None,
+        };
+        if let Some(listener) = listener {
+            listener(&payload);
         }
     }
 
+    /// Set the timeout for tool calls on servers without their own override
+    pub fn with_default_tool_timeout_secs(mut self, secs: u64) -> Self {
+        self.default_tool_timeout_secs = secs;
+        self
+    }
+
+    /// Override the tool call timeout of a connected server until it reconnects
+    pub async fn set_tool_timeout(&self, server_id: &str, timeout_secs: u64) -> Result<(), String> {
+        if timeout_secs == 0 {
+            return Err("Tool timeout must be at least 1 second".to_string());
+        }
+        let mut status_map = self.server_status.write().await;
+        let status = status_map
+            .get_mut(server_id)
+            .ok_or_else(|| format!("Server {} not found", server_id))?;
+        status.tool_call_timeout_secs = Some(timeout_secs);
+        Ok(())
+    }
+
+    /// The server's own tool timeout if set, otherwise the manager default
+    pub async fn tool_timeout_secs(&self, server_id: &str) -> u64 {
+        let status_map = self.server_status.read().await;
+        status_map
+            .get(server_id)
+            .and_then(|s| s.tool_call_timeout_secs)
+            .unwrap_or(self.default_tool_timeout_secs)
+    }
+
     pub async fn connect_server(
         &self,
         config: &MCPServerConfig,
@@ -59,6 +287,7 @@ impl MCPManager {
                     transport: transport.clone(),
                     status: ConnectionStatus::Connecting,
                     tools: vec![],
+                    prompts: vec![],
                     last_error: None,
                     managed_process: false,
                     pid: None,
@@ -67,6 +296,7 @@ impl MCPManager {
                     } else {
                         Some(config.server_url.clone())
                     },
+                    tool_call_timeout_secs: None,
                 },
             );
         }
@@ -74,6 +304,7 @@ impl MCPManager {
         let connect_result = match transport.as_str() {
             "http" => self.connect_http_server(config).await,
             "stdio" => self.connect_stdio_server(config).await,
+            "websocket" => self.connect_ws_server(config).await,
             _ => Err(format!("Unsupported MCP transport: {}", transport).into()),
         };
 
@@ -83,22 +314,87 @@ impl MCPManager {
             return Err(msg.into());
         }
 
+        if let Ok(mut schedules) = self.tool_refresh.write() {
+            match config.refresh_interval_secs.filter(|secs| *secs > 0) {
+                Some(secs) => {
+                    schedules.insert(
+                        config.id.clone(),
+                        ToolRefreshSchedule {
+                            interval: Duration::from_secs(secs),
+                            last_refresh: Instant::now(),
+                        },
+                    );
+                }
+                None => {
+                    schedules.remove(&config.id);
+                }
+            }
+        }
+
         Ok(())
     }
 
+    /// Connect every enabled server, retrying each with exponential backoff.
+    /// Final statuses are left in `server_status`.
+    pub async fn connect_enabled_servers(&self, servers: &[MCPServerConfig]) -> MCPStartupSummary {
+        let mut summary = MCPStartupSummary::default();
+
+        for server in servers.iter().filter(|s| s.enabled) {
+            match self.connect_with_backoff(server, STARTUP_RETRY_BASE_DELAY).await {
+                Ok(()) => {
+                    println!("Auto-connected MCP server: {}", server.name);
+                    summary.connected += 1;
+                }
+                Err(e) => {
+                    self.report_error(AppError::new(
+                        "mcp",
+                        "mcp_connect_failed",
+                        format!("Failed to auto-connect MCP server '{}': {}", server.name, e),
+                        true,
+                    ));
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        summary
+    }
+
+    async fn connect_with_backoff(
+        &self,
+        config: &MCPServerConfig,
+        base_delay: Duration,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let retries = config
+            .startup_retry_attempts
+            .unwrap_or(DEFAULT_STARTUP_RETRY_ATTEMPTS);
+        let mut delay = base_delay;
+        let mut attempt = 0;
+
+        loop {
+            match self.connect_server(config).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    eprintln!(
+                        "MCP server '{}' failed to connect ({}), retry {}/{} in {:?}",
+                        config.name, e, attempt, retries, delay
+                    );
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     async fn connect_http_server(
         &self,
         config: &MCPServerConfig,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let managed = self.start_managed_process_if_needed(config).await?;
+        let (managed, env_warnings) = self.start_managed_process_if_needed(config).await?;
 
-        let oauth_token = if let (Some(client_id), Some(client_secret)) =
-            (&config.oauth_client_id, &config.oauth_client_secret)
-        {
-            Some(self.perform_oauth_flow(client_id, client_secret, &config.server_url).await?)
-        } else {
-            None
-        };
+        let oauth_token = self.oauth_token(config).await?;
 
         let endpoint = if config.server_url.trim().is_empty() {
             return Err("Server URL is required for HTTP transport".into());
@@ -117,6 +413,7 @@ impl MCPManager {
 
         let transport_client = MCPTransportClient::Http(http_client);
         let tools = self.discover_tools(&transport_client, &config.id).await?;
+        let prompts = self.discover_prompts(&transport_client, &config.id).await;
 
         let mcp_client = MCPClient {
             transport_client,
@@ -138,14 +435,18 @@ impl MCPManager {
                     transport: "http".to_string(),
                     status: ConnectionStatus::Connected,
                     tools,
-                    last_error: None,
+                    prompts,
+                    last_error: join_warnings(&env_warnings),
                     managed_process: managed.is_some(),
                     pid: managed,
                     endpoint: Some(endpoint),
+                    tool_call_timeout_secs: None,
                 },
             );
         }
 
+        self.notify_status_changed(&config.id).await;
+
         Ok(())
     }
 
@@ -171,6 +472,8 @@ impl MCPManager {
             }
         }
 
+        let (launch_env, env_warnings) = config.expanded_launch_env();
+
         let mut last_error: Option<String> = None;
         let mut selected_client: Option<StdioMcpClient> = None;
         let mut pid: Option<u32> = None;
@@ -179,8 +482,9 @@ impl MCPManager {
             let client = StdioMcpClient::new(
                 command,
                 &config.launch_args,
-                &config.launch_env,
+                &launch_env,
                 config.working_dir.as_deref(),
+                self.stderr_logger(&config.id),
             )
             .await?;
 
@@ -214,6 +518,7 @@ impl MCPManager {
 
         let transport_client = MCPTransportClient::Stdio(stdio_client);
         let tools = self.discover_tools(&transport_client, &config.id).await?;
+        let prompts = self.discover_prompts(&transport_client, &config.id).await;
 
         let endpoint = format!("stdio://{}", config.id);
         let mcp_client = MCPClient {
@@ -236,14 +541,74 @@ impl MCPManager {
                     transport: "stdio".to_string(),
                     status: ConnectionStatus::Connected,
                     tools,
-                    last_error: None,
+                    prompts,
+                    last_error: join_warnings(&env_warnings),
                     managed_process: true,
                     pid,
                     endpoint: Some(endpoint),
+                    tool_call_timeout_secs: None,
+                },
+            );
+        }
+
+        self.notify_status_changed(&config.id).await;
+
+        Ok(())
+    }
+
+    async fn connect_ws_server(
+        &self,
+        config: &MCPServerConfig,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let endpoint = config.server_url.trim().to_string();
+        if endpoint.is_empty() {
+            return Err("Server URL is required for WebSocket transport".into());
+        }
+
+        let ws_client = WebSocketMcpClient::new(&endpoint)
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+        if let Err(e) = ws_client.initialize(config.startup_timeout_ms).await {
+            ws_client.shutdown().await;
+            return Err(format!("Connection failed: {}", e).into());
+        }
+
+        let transport_client = MCPTransportClient::WebSocket(ws_client);
+        let tools = self.discover_tools(&transport_client, &config.id).await?;
+        let prompts = self.discover_prompts(&transport_client, &config.id).await;
+
+        let mcp_client = MCPClient {
+            transport_client,
+            url: endpoint.clone(),
+        };
+
+        {
+            let mut clients = self.clients.write().await;
+            clients.insert(config.id.clone(), mcp_client);
+        }
+
+        {
+            let mut status_map = self.server_status.write().await;
+            status_map.insert(
+                config.id.clone(),
+                MCPServerStatus {
+                    id: config.id.clone(),
+                    name: config.name.clone(),
+                    transport: "websocket".to_string(),
+                    status: ConnectionStatus::Connected,
+                    tools,
+                    prompts,
+                    last_error: None,
+                    managed_process: false,
+                    pid: None,
+                    endpoint: Some(endpoint),
+                    tool_call_timeout_secs: None,
                 },
             );
         }
 
+        self.notify_status_changed(&config.id).await;
+
         Ok(())
     }
 
@@ -254,26 +619,65 @@ impl MCPManager {
         };
 
         if let Some(client) = removed {
-            if let MCPTransportClient::Stdio(stdio_client) = client.transport_client {
-                stdio_client.shutdown().await;
+            match client.transport_client {
+                MCPTransportClient::Stdio(stdio_client) => stdio_client.shutdown().await,
+                MCPTransportClient::WebSocket(ws_client) => ws_client.shutdown().await,
+                MCPTransportClient::Http(_) => {}
             }
         }
 
         self.stop_managed_process(server_id).await;
 
+        if let Ok(mut logs) = self.server_logs.write() {
+            logs.remove(server_id);
+        }
+        if let Ok(mut schedules) = self.tool_refresh.write() {
+            schedules.remove(server_id);
+        }
+
         {
             let mut status_map = self.server_status.write().await;
             if let Some(status) = status_map.get_mut(server_id) {
                 status.status = ConnectionStatus::Disconnected;
                 status.tools.clear();
+                status.prompts.clear();
                 status.last_error = None;
                 status.managed_process = false;
                 status.pid = None;
             }
         }
+
+        self.notify_status_changed(server_id).await;
+    }
+
+    /// Disconnect every server and wait for stdio and managed child processes to exit,
+    /// giving up after `SHUTDOWN_TIMEOUT`. Called on app exit so no process is orphaned.
+    pub async fn shutdown_all(&self) {
+        let mut server_ids: Vec<String> = self.clients.read().await.keys().cloned().collect();
+        for server_id in self.managed_processes.read().await.keys() {
+            if !server_ids.contains(server_id) {
+                server_ids.push(server_id.clone());
+            }
+        }
+
+        let disconnect_all = futures::future::join_all(server_ids.iter().map(|id| self.disconnect_server(id)));
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, disconnect_all).await.is_err() {
+            eprintln!(
+                "[mcp] Shutdown timed out after {} seconds; some server processes may still be running",
+                SHUTDOWN_TIMEOUT.as_secs()
+            );
+        }
     }
 
     pub async fn execute_tool(&self, call: &MCPToolCall) -> MCPToolResult {
+        if let Err(e) = self.refresh_expired_oauth_token(&call.server_id).await {
+            return MCPToolResult {
+                success: false,
+                result: serde_json::Value::Null,
+                error: Some(format!("OAuth token refresh failed: {}", e)),
+            };
+        }
+
         let clients = self.clients.read().await;
 
         let Some(client) = clients.get(&call.server_id) else {
@@ -284,8 +688,10 @@ impl MCPManager {
             };
         };
 
+        let timeout_secs = self.tool_timeout_secs(&call.server_id).await;
+
         let call_result = tokio::time::timeout(
-            Duration::from_secs(60),
+            Duration::from_secs(timeout_secs),
             self.execute_transport_tool(client, &call.tool_name, call.parameters.clone()),
         )
         .await;
@@ -320,7 +726,7 @@ impl MCPManager {
             Err(_) => MCPToolResult {
                 success: false,
                 result: serde_json::Value::Null,
-                error: Some("Tool execution timed out after 60 seconds".to_string()),
+                error: Some(format!("Tool execution timed out after {} seconds", timeout_secs)),
             },
         }
     }
@@ -341,6 +747,32 @@ impl MCPManager {
         status_map.values().cloned().collect()
     }
 
+    /// Tools of the connected servers, grouped by server id, for the agent's system prompt.
+    /// Empty when no server is connected.
+    pub async fn format_tools_for_system_prompt(&self) -> String {
+        let status_map = self.server_status.read().await;
+        let mut servers: Vec<&MCPServerStatus> = status_map
+            .values()
+            .filter(|s| matches!(s.status, ConnectionStatus::Connected))
+            .collect();
+        if servers.is_empty() {
+            return String::new();
+        }
+        servers.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut info = String::from("\nMCP (Model Context Protocol) Tools:\n");
+        for server in servers {
+            info.push_str(&format!("Server '{}' is connected with tools:\n", server.id));
+            for tool in &server.tools {
+                info.push_str(&format!(
+                    "  - {}: {} (use format: {}:{})\n",
+                    tool.name, tool.description, server.id, tool.name
+                ));
+            }
+        }
+        info
+    }
+
     async fn execute_transport_tool(
         &self,
         client: &MCPClient,
@@ -350,6 +782,7 @@ impl MCPManager {
         match &client.transport_client {
             MCPTransportClient::Http(http) => http.call_tool(tool_name, Some(params)).await,
             MCPTransportClient::Stdio(stdio) => stdio.call_tool(tool_name, Some(params)).await,
+            MCPTransportClient::WebSocket(ws) => ws.call_tool(tool_name, Some(params)).await,
         }
     }
 
@@ -361,6 +794,7 @@ impl MCPManager {
         let tools_response = match client {
             MCPTransportClient::Http(http) => http.list_tools().await?,
             MCPTransportClient::Stdio(stdio) => stdio.list_tools().await?,
+            MCPTransportClient::WebSocket(ws) => ws.list_tools().await?,
         };
 
         let mut mcp_tools = Vec::new();
@@ -397,45 +831,185 @@ impl MCPManager {
         Ok(mcp_tools)
     }
 
-    async fn update_status_error(&self, server_id: &str, error: String) {
+    /// Fetch a connected server's tool list again, e.g. after the server added or changed tools
+    pub async fn refresh_server_tools(&self, server_id: &str) -> Result<(), String> {
+        let tools = {
+            let clients = self.clients.read().await;
+            let client = clients
+                .get(server_id)
+                .ok_or_else(|| format!("Server {} not connected", server_id))?;
+            self.discover_tools(&client.transport_client, server_id)
+                .await
+                .map_err(|e| format!("Failed to list tools: {}", e))?
+        };
+
+        {
+            let mut status_map = self.server_status.write().await;
+            if let Some(status) = status_map.get_mut(server_id) {
+                status.tools = tools;
+            }
+        }
+        if let Ok(mut schedules) = self.tool_refresh.write() {
+            if let Some(schedule) = schedules.get_mut(server_id) {
+                schedule.last_refresh = Instant::now();
+            }
+        }
+
+        self.notify_status_changed(server_id).await;
+        Ok(())
+    }
+
+    /// Refresh the tools of every server whose `refresh_interval_secs` has elapsed
+    pub async fn refresh_due_server_tools(&self) {
+        let due: Vec<String> = match self.tool_refresh.read() {
+            Ok(schedules) => schedules
+                .iter()
+                .filter(|(_, schedule)| schedule.last_refresh.elapsed() >= schedule.interval)
+                .map(|(server_id, _)| server_id.clone())
+                .collect(),
+            Err(_) => return,
+        };
+
+        for server_id in due {
+            if let Err(e) = self.refresh_server_tools(&server_id).await {
+                // Push the next attempt a full interval out rather than retrying every tick
+                if let Ok(mut schedules) = self.tool_refresh.write() {
+                    if let Some(schedule) = schedules.get_mut(&server_id) {
+                        schedule.last_refresh = Instant::now();
+                    }
+                }
+                self.report_error(AppError::new(
+                    "mcp",
+                    "mcp_tool_refresh_failed",
+                    format!("Refreshing tools of '{}' failed: {}", server_id, e),
+                    true,
+                ));
+            }
+        }
+    }
+
+    /// Run `refresh_due_server_tools` in the background for the life of the app
+    pub fn spawn_tool_refresh_task(manager: Arc<MCPManager>) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                sleep(TOOL_REFRESH_TICK).await;
+                manager.refresh_due_server_tools().await;
+            }
+        });
+    }
+
+    /// Prompts are optional in MCP; servers without the capability just have none
+    async fn discover_prompts(&self, client: &MCPTransportClient, server_id: &str) -> Vec<MCPPrompt> {
+        let response = match client {
+            MCPTransportClient::Http(http) => http.list_prompts().await,
+            MCPTransportClient::Stdio(stdio) => stdio.list_prompts().await,
+            MCPTransportClient::WebSocket(ws) => ws.list_prompts().await,
+        };
+
+        response
+            .ok()
+            .and_then(|r| parse_prompts(&r, server_id).ok())
+            .unwrap_or_default()
+    }
+
+    pub async fn list_prompts_for_server(&self, server_id: &str) -> Result<Vec<MCPPrompt>, String> {
+        let clients = self.clients.read().await;
+        let client = clients
+            .get(server_id)
+            .ok_or_else(|| format!("Server {} not connected", server_id))?;
+
+        let response = match &client.transport_client {
+            MCPTransportClient::Http(http) => http.list_prompts().await,
+            MCPTransportClient::Stdio(stdio) => stdio.list_prompts().await,
+            MCPTransportClient::WebSocket(ws) => ws.list_prompts().await,
+        }
+        .map_err(|e| format!("Failed to list prompts: {}", e))?;
+
+        let prompts = parse_prompts(&response, server_id)?;
+
         let mut status_map = self.server_status.write().await;
         if let Some(status) = status_map.get_mut(server_id) {
-            status.status = ConnectionStatus::Error;
-            status.last_error = Some(error);
-            status.tools.clear();
-            status.pid = None;
+            status.prompts = prompts.clone();
+        }
+
+        Ok(prompts)
+    }
+
+    /// Fetch a prompt with its arguments filled in; returns the JSON-RPC `result`
+    /// (`description` and `messages`)
+    pub async fn get_prompt(
+        &self,
+        server_id: &str,
+        name: &str,
+        args: HashMap<String, String>,
+    ) -> Result<serde_json::Value, String> {
+        let clients = self.clients.read().await;
+        let client = clients
+            .get(server_id)
+            .ok_or_else(|| format!("Server {} not connected", server_id))?;
+
+        let arguments = serde_json::to_value(args).unwrap_or_default();
+        let response = match &client.transport_client {
+            MCPTransportClient::Http(http) => http.get_prompt(name, arguments).await,
+            MCPTransportClient::Stdio(stdio) => stdio.get_prompt(name, arguments).await,
+            MCPTransportClient::WebSocket(ws) => ws.get_prompt(name, arguments).await,
+        }
+        .map_err(|e| format!("Failed to get prompt: {}", e))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(format!("Prompt error: {}", error));
+        }
+        response
+            .get("result")
+            .cloned()
+            .ok_or_else(|| "Invalid response format".to_string())
+    }
+
+    async fn update_status_error(&self, server_id: &str, error: String) {
+        {
+            let mut status_map = self.server_status.write().await;
+            if let Some(status) = status_map.get_mut(server_id) {
+                status.status = ConnectionStatus::Error;
+                status.last_error = Some(error);
+                status.tools.clear();
+                status.pid = None;
+            }
         }
+
+        self.notify_status_changed(server_id).await;
     }
 
     async fn start_managed_process_if_needed(
         &self,
         config: &MCPServerConfig,
-    ) -> Result<Option<u32>, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(Option<u32>, Vec<String>), Box<dyn std::error::Error + Send + Sync>> {
         let Some(command) = config
             .launch_command
             .as_ref()
             .map(|s| s.trim())
             .filter(|s| !s.is_empty())
         else {
-            return Ok(None);
+            return Ok((None, vec![]));
         };
 
         {
             let mut processes = self.managed_processes.write().await;
             if let Some(proc) = processes.get_mut(&config.id) {
                 if proc.child.try_wait()?.is_none() {
-                    return Ok(proc.pid);
+                    return Ok((proc.pid, vec![]));
                 }
                 processes.remove(&config.id);
             }
         }
 
+        let (launch_env, env_warnings) = config.expanded_launch_env();
+
         let mut cmd = Command::new(command);
         if !config.launch_args.is_empty() {
             cmd.args(&config.launch_args);
         }
-        if !config.launch_env.is_empty() {
-            cmd.envs(&config.launch_env);
+        if !launch_env.is_empty() {
+            cmd.envs(&launch_env);
         }
         if let Some(dir) = config
             .working_dir
@@ -465,7 +1039,7 @@ impl MCPManager {
 
         let mut processes = self.managed_processes.write().await;
         processes.insert(config.id.clone(), ManagedProcess { child, pid });
-        Ok(pid)
+        Ok((pid, env_warnings))
     }
 
     async fn stop_managed_process(&self, server_id: &str) {
@@ -508,61 +1082,100 @@ impl MCPManager {
         .into())
     }
 
-    async fn perform_oauth_flow(
+    /// Forget a server's OAuth token, so the next connect signs in again
+    pub async fn clear_oauth_session(&self, server_id: &str) {
+        self.oauth_sessions.write().await.remove(server_id);
+    }
+
+    /// Access token for an HTTP server's OAuth settings, if it has any. An unexpired token
+    /// from an earlier connect is reused, so device code sign-in isn't repeated on reconnect,
+    /// but only for the same server URL and client credentials.
+    async fn oauth_token(
         &self,
-        client_id: &str,
-        client_secret: &str,
-        server_url: &str,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let mut oauth_url = if server_url.ends_with("/mcp") {
-            server_url.trim_end_matches("/mcp").to_string()
-        } else {
-            server_url.to_string()
+        config: &MCPServerConfig,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(session) = self.oauth_sessions.read().await.get(&config.id) {
+            let same_client = session.config.server_url == config.server_url
+                && session.config.oauth_client_id == config.oauth_client_id
+                && session.config.oauth_client_secret == config.oauth_client_secret
+                && session.config.oauth_flow == config.oauth_flow;
+            if same_client && !session.token.is_expired() {
+                return Ok(Some(session.token.access_token.clone()));
+            }
+        }
+
+        let Some(token) = self.request_oauth_token(config).await? else {
+            return Ok(None);
         };
+        let access_token = token.access_token.clone();
+        self.oauth_sessions.write().await.insert(
+            config.id.clone(),
+            OAuthSession {
+                config: config.clone(),
+                token,
+            },
+        );
+        Ok(Some(access_token))
+    }
 
-        if !oauth_url.ends_with('/') {
-            oauth_url.push('/');
-        }
-        oauth_url.push_str("oauth/token");
-
-        let client = reqwest::Client::new();
-        let params = [
-            ("grant_type", "client_credentials"),
-            ("client_id", client_id),
-            ("client_secret", client_secret),
-        ];
-
-        let response = match tokio::time::timeout(
-            Duration::from_secs(15),
-            client
-                .post(&oauth_url)
-                .form(&params)
-                .header("Content-Type", "application/x-www-form-urlencoded")
-                .send(),
-        )
-        .await
-        {
-            Ok(Ok(response)) => response,
-            Ok(Err(e)) => {
-                return Err(format!("OAuth request failed: {}", e).into());
-            }
-            Err(_) => {
-                return Err("OAuth request timed out after 15 seconds".into());
-            }
+    async fn request_oauth_token(
+        &self,
+        config: &MCPServerConfig,
+    ) -> Result<Option<OAuthToken>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(client_id) = config.oauth_client_id.as_deref() else {
+            return Ok(None);
         };
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("OAuth request failed: {} - {}", status, error_text).into());
+        if config.oauth_flow.as_deref() == Some(DEVICE_CODE_FLOW) {
+            let listener = self.device_code_listener.read().ok().and_then(|slot| slot.clone());
+            let server_id = config.id.clone();
+            let token = oauth::perform_device_code_flow(client_id, &config.server_url, move |user_code, verification_url| {
+                let prompt = MCPDeviceCodePrompt {
+                    server_id,
+                    user_code,
+                    verification_url,
+                };
+                match listener {
+                    Some(listener) => listener(&prompt),
+                    None => eprintln!(
+                        "[mcp] Sign in to '{}' at {} with code {}",
+                        prompt.server_id, prompt.verification_url, prompt.user_code
+                    ),
+                }
+            })
+            .await?;
+            return Ok(Some(token));
         }
 
-        let oauth_response: serde_json::Value = response.json().await?;
-        if let Some(access_token) = oauth_response.get("access_token").and_then(|v| v.as_str()) {
-            Ok(format!("Bearer {}", access_token))
-        } else {
-            Err("No access_token in OAuth response".into())
+        match config.oauth_client_secret.as_deref() {
+            Some(client_secret) => Ok(Some(
+                oauth::request_client_credentials_token(client_id, client_secret, &config.server_url).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Re-run the OAuth flow for a connected server whose token expired and give the new
+    /// token to its client
+    async fn refresh_expired_oauth_token(
+        &self,
+        server_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let config = match self.oauth_sessions.read().await.get(server_id) {
+            Some(session) if session.token.is_expired() => session.config.clone(),
+            _ => return Ok(()),
+        };
+
+        let token = self.oauth_token(&config).await?;
+        let mut clients = self.clients.write().await;
+        if let Some(MCPClient {
+            transport_client: MCPTransportClient::Http(http_client),
+            ..
+        }) = clients.get_mut(server_id)
+        {
+            http_client.set_oauth_token(token);
         }
+        Ok(())
     }
 }
 
@@ -575,6 +1188,569 @@ impl Default for MCPManager {
 fn normalize_transport(transport: &str) -> String {
     match transport.trim().to_lowercase().as_str() {
         "stdio" => "stdio".to_string(),
+        "ws" | "websocket" => "websocket".to_string(),
         _ => "http".to_string(),
     }
 }
+
+/// Parse a `prompts/list` response
+fn parse_prompts(response: &serde_json::Value, server_id: &str) -> Result<Vec<MCPPrompt>, String> {
+    if let Some(error) = response.get("error") {
+        return Err(format!("Prompt listing error: {}", error));
+    }
+
+    let text = |value: &serde_json::Value, key: &str| {
+        value.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string()
+    };
+
+    let prompts = response
+        .get("result")
+        .and_then(|r| r.get("prompts"))
+        .and_then(|p| p.as_array())
+        .map(|prompts| {
+            prompts
+                .iter()
+                .map(|prompt| MCPPrompt {
+                    server_id: server_id.to_string(),
+                    name: text(prompt, "name"),
+                    description: text(prompt, "description"),
+                    arguments: prompt
+                        .get("arguments")
+                        .and_then(|a| a.as_array())
+                        .map(|args| {
+                            args.iter()
+                                .map(|arg| PromptArgument {
+                                    name: text(arg, "name"),
+                                    description: text(arg, "description"),
+                                    required: arg.get("required").and_then(|r| r.as_bool()).unwrap_or(false),
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(prompts)
+}
+
+/// Combine non-fatal connection warnings into a status message
+fn join_warnings(warnings: &[String]) -> Option<String> {
+    if warnings.is_empty() {
+        None
+    } else {
+        Some(format!("Warning: {}", warnings.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_mcp_response, spawn_mock_http_server, spawn_mock_server, spawn_mock_ws_server};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// MCP server whose first `failures` initialize requests return invalid JSON
+    async fn spawn_flaky_mcp_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let initialize_calls = Arc::new(AtomicUsize::new(0));
+        let calls = initialize_calls.clone();
+        let url = spawn_mock_server(move |body| {
+            if body["method"] == "initialize" && calls.fetch_add(1, Ordering::SeqCst) < failures {
+                return ("application/json", "starting up".to_string());
+            }
+            mock_mcp_response(body)
+        })
+        .await;
+        (url, initialize_calls)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_stderr_kept_and_cleared_on_disconnect() {
+        let manager = MCPManager::new();
+        let script = "i=1; while [ $i -le 60 ]; do echo \"line $i\" >&2; i=$((i+1)); done; sleep 5";
+        let client = StdioMcpClient::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            &HashMap::new(),
+            None,
+            manager.stderr_logger("noisy"),
+        )
+        .await
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.get_server_logs("noisy").last().map(String::as_str) != Some("line 60") {
+            assert!(Instant::now() < deadline, "stderr never arrived: {:?}", manager.get_server_logs("noisy"));
+            sleep(Duration::from_millis(20)).await;
+        }
+        let logs = manager.get_server_logs("noisy");
+        assert_eq!(logs.len(), MAX_SERVER_LOG_LINES);
+        assert_eq!(logs[0], "line 11");
+        assert!(manager.get_server_logs("other").is_empty());
+
+        client.shutdown().await;
+        manager.disconnect_server("noisy").await;
+        assert!(manager.get_server_logs("noisy").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shutdown_all_stops_child_processes() {
+        let manager = MCPManager::new();
+        let stdio = StdioMcpClient::new(
+            "sh",
+            &["-c".to_string(), "sleep 30".to_string()],
+            &HashMap::new(),
+            None,
+            manager.stderr_logger("stdio"),
+        )
+        .await
+        .unwrap();
+        manager.clients.write().await.insert(
+            "stdio".to_string(),
+            MCPClient {
+                transport_client: MCPTransportClient::Stdio(stdio),
+                url: String::new(),
+            },
+        );
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id().unwrap();
+        manager
+            .managed_processes
+            .write()
+            .await
+            .insert("managed".to_string(), ManagedProcess { child, pid: Some(pid) });
+
+        manager.shutdown_all().await;
+
+        assert!(manager.clients.read().await.is_empty());
+        assert!(manager.managed_processes.read().await.is_empty());
+        // The child was reaped, so its pid no longer refers to a running process
+        let alive = std::process::Command::new("kill").args(["-0", &pid.to_string()]).status().unwrap();
+        assert!(!alive.success());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_per_server_tool_timeout_overrides_default() {
+        // tools/call takes 1.5s, longer than the 1s default
+        let slow_server = || {
+            spawn_mock_server(|body| {
+                if body["method"] == "tools/call" {
+                    std::thread::sleep(Duration::from_millis(1500));
+                }
+                mock_mcp_response(body)
+            })
+        };
+        let manager = MCPManager::new().with_default_tool_timeout_secs(1);
+        for id in ["fast", "patient"] {
+            let config = MCPServerConfig::new(id.to_string(), id.to_string(), slow_server().await).with_enabled(true);
+            manager.connect_server(&config).await.unwrap();
+        }
+
+        manager.set_tool_timeout("patient", 5).await.unwrap();
+        assert_eq!(manager.tool_timeout_secs("patient").await, 5);
+        assert_eq!(manager.tool_timeout_secs("fast").await, 1);
+        assert!(manager.set_tool_timeout("missing", 5).await.is_err());
+
+        let call = |server_id: &str| MCPToolCall {
+            server_id: server_id.to_string(),
+            tool_name: "echo".to_string(),
+            parameters: serde_json::json!({ "text": "hi" }),
+        };
+        let timed_out = manager.execute_tool(&call("fast")).await;
+        assert!(!timed_out.success);
+        assert_eq!(timed_out.error.as_deref(), Some("Tool execution timed out after 1 seconds"));
+
+        let result = manager.execute_tool(&call("patient")).await;
+        assert!(result.success, "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn test_status_changes_notify_listener() {
+        let url = spawn_mock_server(mock_mcp_response).await;
+        let manager = MCPManager::new();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let captured = events.clone();
+        manager.set_status_listener(move |payload| captured.lock().unwrap().push(payload.clone()));
+
+        let config = MCPServerConfig::new("docs".to_string(), "Docs".to_string(), url).with_enabled(true);
+        manager.connect_server(&config).await.unwrap();
+        manager.disconnect_server("docs").await;
+
+        let bad = MCPServerConfig::new("bad".to_string(), "Bad".to_string(), String::new()).with_enabled(true);
+        assert!(manager.connect_server(&bad).await.is_err());
+
+        let payload = |server_id: &str, name: &str, status, error: Option<&str>| MCPStatusChangedPayload {
+            server_id: server_id.to_string(),
+            name: name.to_string(),
+            status,
+            error: error.map(str::to_string),
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                payload("docs", "Docs", ConnectionStatus::Connected, None),
+                payload("docs", "Docs", ConnectionStatus::Disconnected, None),
+                payload(
+                    "bad",
+                    "Bad",
+                    ConnectionStatus::Error,
+                    Some("Server URL is required for HTTP transport")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_history_records_transitions() {
+        let url = spawn_mock_server(mock_mcp_response).await;
+        let manager = MCPManager::new();
+
+        let config = MCPServerConfig::new("docs".to_string(), "Docs".to_string(), url).with_enabled(true);
+        manager.connect_server(&config).await.unwrap();
+        manager.update_status_error("docs", "connection reset".to_string()).await;
+        manager.disconnect_server("docs").await;
+
+        let other = MCPServerConfig::new("bad".to_string(), "Bad".to_string(), String::new()).with_enabled(true);
+        assert!(manager.connect_server(&other).await.is_err());
+
+        let history = manager.get_connection_history(Some("docs"));
+        let transitions: Vec<(&str, &str, Option<&str>)> = history
+            .iter()
+            .map(|e| (e.from_status.as_str(), e.to_status.as_str(), e.error.as_deref()))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                ("Disconnected", "Connected", None),
+                ("Connected", "Error", Some("connection reset")),
+                ("Error", "Disconnected", None),
+            ]
+        );
+        assert!(history.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let all = manager.get_connection_history(None);
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[3].server_id, "bad");
+        assert_eq!(all[3].to_status, "Error");
+    }
+
+    #[test]
+    fn test_connection_history_is_capped() {
+        let manager = MCPManager::new();
+        for i in 0..MAX_CONNECTION_HISTORY + 10 {
+            let status = if i % 2 == 0 { ConnectionStatus::Connected } else { ConnectionStatus::Error };
+            manager.record_status_change("flaky", &status, None);
+        }
+        let history = manager.get_connection_history(None);
+        assert_eq!(history.len(), MAX_CONNECTION_HISTORY);
+        assert_eq!(history.last().unwrap().to_status, "Error");
+    }
+
+    fn flaky_config(url: String) -> MCPServerConfig {
+        let mut config = MCPServerConfig::new("flaky".to_string(), "Flaky".to_string(), url).with_enabled(true);
+        // Make each connect_server call a single initialize attempt
+        config.startup_timeout_ms = Some(50);
+        config
+    }
+
+    #[tokio::test]
+    async fn test_startup_connect_retries_until_connected() {
+        let (url, initialize_calls) = spawn_flaky_mcp_server(2).await;
+        let manager = MCPManager::new();
+
+        manager
+            .connect_with_backoff(&flaky_config(url), Duration::from_millis(10))
+            .await
+            .unwrap();
+
+        assert_eq!(initialize_calls.load(Ordering::SeqCst), 3);
+        let statuses = manager.get_server_statuses().await;
+        assert_eq!(statuses.len(), 1);
+        assert!(matches!(statuses[0].status, ConnectionStatus::Connected));
+        assert_eq!(statuses[0].tools.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_startup_connect_gives_up_after_retries() {
+        let (url, initialize_calls) = spawn_flaky_mcp_server(usize::MAX).await;
+        let manager = MCPManager::new();
+        let mut config = flaky_config(url);
+        config.startup_retry_attempts = Some(1);
+
+        assert!(manager
+            .connect_with_backoff(&config, Duration::from_millis(10))
+            .await
+            .is_err());
+
+        assert_eq!(initialize_calls.load(Ordering::SeqCst), 2);
+        let statuses = manager.get_server_statuses().await;
+        assert!(matches!(statuses[0].status, ConnectionStatus::Error));
+        assert!(statuses[0].last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_connect_enabled_servers_summary() {
+        let (url, _) = spawn_flaky_mcp_server(0).await;
+        let manager = MCPManager::new();
+        let disabled = MCPServerConfig::new("off".to_string(), "Off".to_string(), "http://127.0.0.1:1".to_string());
+
+        let summary = manager
+            .connect_enabled_servers(&[flaky_config(url), disabled])
+            .await;
+
+        assert_eq!(summary, MCPStartupSummary { connected: 1, failed: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_failure_reported_to_error_channel() {
+        let (url, _) = spawn_flaky_mcp_server(usize::MAX).await;
+        let manager = MCPManager::new();
+        let reporter = ErrorReporter::new();
+        let mut rx = reporter.subscribe();
+        manager.set_error_reporter(reporter.clone());
+
+        let mut config = flaky_config(url);
+        config.startup_retry_attempts = Some(0);
+        let summary = manager.connect_enabled_servers(&[config]).await;
+        assert_eq!(summary, MCPStartupSummary { connected: 0, failed: 1 });
+
+        let sent = rx.recv().await.unwrap();
+        assert_eq!(sent.component, "mcp");
+        assert_eq!(sent.code, "mcp_connect_failed");
+        assert!(sent.message.contains("'Flaky'"), "{}", sent.message);
+        assert!(sent.recoverable);
+        assert_eq!(reporter.recent(), vec![sent]);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_transport_lists_and_calls_tools() {
+        let url = spawn_mock_ws_server(mock_mcp_response).await;
+        let manager = MCPManager::new();
+        let mut config = MCPServerConfig::new("ws".to_string(), "WS".to_string(), url).with_enabled(true);
+        config.transport = "ws".to_string();
+
+        manager.connect_server(&config).await.unwrap();
+
+        let statuses = manager.get_server_statuses().await;
+        assert_eq!(statuses[0].transport, "websocket");
+        assert!(matches!(statuses[0].status, ConnectionStatus::Connected));
+        let tools = manager.get_all_tools().await;
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "echo");
+
+        let result = manager
+            .execute_tool(&MCPToolCall {
+                server_id: "ws".to_string(),
+                tool_name: "echo".to_string(),
+                parameters: serde_json::json!({ "text": "over ws" }),
+            })
+            .await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.result["content"][0]["text"], "echo: over ws");
+
+        manager.disconnect_server("ws").await;
+        assert!(manager.get_all_tools().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_device_code_oauth_sends_bearer_token() {
+        let unauthorized = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = unauthorized.clone();
+        let url = spawn_mock_http_server(move |head, body| {
+            if head.starts_with("POST /oauth/device/code") {
+                let reply = r#"{"device_code": "dev-1", "user_code": "WDJB-MJHT", "verification_uri": "https://example.com/device", "interval": 0}"#;
+                return Some(("application/json", reply.to_string()));
+            }
+            if head.starts_with("POST /oauth/token") {
+                return Some(("application/json", r#"{"access_token": "tok-1", "expires_in": 3600}"#.to_string()));
+            }
+            if !head.to_lowercase().contains("authorization: bearer tok-1\r\n") {
+                counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+            Some(mock_mcp_response(body))
+        })
+        .await;
+
+        let manager = MCPManager::new();
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let captured = prompts.clone();
+        manager.set_device_code_listener(move |prompt| captured.lock().unwrap().push(prompt.clone()));
+
+        let mut config = MCPServerConfig::new("oauth".to_string(), "OAuth".to_string(), format!("{}/mcp", url))
+            .with_enabled(true);
+        config.oauth_client_id = Some("kuse".to_string());
+        config.oauth_flow = Some(DEVICE_CODE_FLOW.to_string());
+
+        manager.connect_server(&config).await.unwrap();
+        let result = manager
+            .execute_tool(&MCPToolCall {
+                server_id: "oauth".to_string(),
+                tool_name: "echo".to_string(),
+                parameters: serde_json::json!({ "text": "signed in" }),
+            })
+            .await;
+        assert!(result.success, "{:?}", result.error);
+
+        // Reconnecting reuses the cached token instead of prompting again
+        manager.disconnect_server("oauth").await;
+        manager.connect_server(&config).await.unwrap();
+
+        assert_eq!(
+            prompts.lock().unwrap().clone(),
+            vec![MCPDeviceCodePrompt {
+                server_id: "oauth".to_string(),
+                user_code: "WDJB-MJHT".to_string(),
+                verification_url: "https://example.com/device".to_string(),
+            }]
+        );
+
+        // A different client id, or a cleared session, signs in again
+        manager.disconnect_server("oauth").await;
+        config.oauth_client_id = Some("kuse-staging".to_string());
+        manager.connect_server(&config).await.unwrap();
+        assert_eq!(prompts.lock().unwrap().len(), 2);
+
+        manager.disconnect_server("oauth").await;
+        manager.clear_oauth_session("oauth").await;
+        manager.connect_server(&config).await.unwrap();
+        assert_eq!(prompts.lock().unwrap().len(), 3);
+        assert_eq!(unauthorized.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_server_tools_picks_up_changed_tool_list() {
+        let listings = Arc::new(AtomicUsize::new(0));
+        let counted = listings.clone();
+        let url = spawn_mock_server(move |body| {
+            if body["method"] != "tools/list" {
+                return mock_mcp_response(body);
+            }
+            let mut tools = vec![serde_json::json!({ "name": "echo", "description": "Echo", "inputSchema": { "type": "object" } })];
+            if counted.fetch_add(1, Ordering::SeqCst) > 0 {
+                tools.push(serde_json::json!({ "name": "reverse", "description": "Reverse text", "inputSchema": { "type": "object" } }));
+            }
+            let reply = serde_json::json!({ "jsonrpc": "2.0", "id": body["id"], "result": { "tools": tools } });
+            ("application/json", reply.to_string())
+        })
+        .await;
+        let manager = MCPManager::new();
+        let mut config = MCPServerConfig::new("mock".to_string(), "Mock".to_string(), url).with_enabled(true);
+        config.refresh_interval_secs = Some(60);
+        manager.connect_server(&config).await.unwrap();
+        assert_eq!(manager.get_all_tools().await.len(), 1);
+
+        manager.refresh_server_tools("mock").await.unwrap();
+        let mut names: Vec<String> = manager.get_all_tools().await.into_iter().map(|t| t.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["echo", "reverse"]);
+
+        // Not due yet, so the background refresh leaves the server alone
+        manager.refresh_due_server_tools().await;
+        assert_eq!(listings.load(Ordering::SeqCst), 2);
+        manager.tool_refresh.write().unwrap().get_mut("mock").unwrap().last_refresh =
+            Instant::now() - Duration::from_secs(61);
+        manager.refresh_due_server_tools().await;
+        assert_eq!(listings.load(Ordering::SeqCst), 3);
+
+        assert!(manager.refresh_server_tools("missing").await.is_err());
+        manager.disconnect_server("mock").await;
+        assert!(manager.tool_refresh.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_get_prompts() {
+        let url = spawn_mock_server(mock_mcp_response).await;
+        let manager = MCPManager::new();
+        let config = MCPServerConfig::new("mock".to_string(), "Mock".to_string(), url).with_enabled(true);
+        manager.connect_server(&config).await.unwrap();
+
+        // Prompts are discovered alongside tools on connect
+        let statuses = manager.get_server_statuses().await;
+        assert_eq!(statuses[0].prompts.len(), 1);
+
+        let prompts = manager.list_prompts_for_server("mock").await.unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].server_id, "mock");
+        assert_eq!(prompts[0].name, "summarize");
+        assert_eq!(
+            prompts[0].arguments,
+            vec![PromptArgument {
+                name: "topic".to_string(),
+                description: "What to summarize".to_string(),
+                required: true,
+            }]
+        );
+
+        let args = HashMap::from([("topic".to_string(), "release notes".to_string())]);
+        let result = manager.get_prompt("mock", "summarize", args).await.unwrap();
+        assert_eq!(result["messages"][0]["role"], "user");
+        assert_eq!(result["messages"][0]["content"]["text"], "Summarize release notes");
+
+        assert!(manager.list_prompts_for_server("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_format_tools_for_system_prompt_groups_by_server() {
+        let manager = MCPManager::new();
+        assert_eq!(manager.format_tools_for_system_prompt().await, "");
+
+        let server = |id: &str, status, tools: &[(&str, &str)]| MCPServerStatus {
+            id: id.to_string(),
+            name: id.to_string(),
+            transport: "http".to_string(),
+            status,
+            tools: tools
+                .iter()
+                .map(|(name, description)| MCPTool {
+                    server_id: id.to_string(),
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    input_schema: serde_json::json!({ "type": "object" }),
+                })
+                .collect(),
+            prompts: vec![],
+            last_error: None,
+            managed_process: false,
+            pid: None,
+            endpoint: None,
+            tool_call_timeout_secs: None,
+        };
+        {
+            let mut statuses = manager.server_status.write().await;
+            statuses.insert(
+                "github".to_string(),
+                server(
+                    "github",
+                    ConnectionStatus::Connected,
+                    &[("list_issues", "List issues"), ("create_issue", "Open an issue")],
+                ),
+            );
+            statuses.insert(
+                "files".to_string(),
+                server(
+                    "files",
+                    ConnectionStatus::Connected,
+                    &[("read", "Read a file"), ("write", "Write a file"), ("list", "List a directory")],
+                ),
+            );
+            statuses.insert(
+                "offline".to_string(),
+                server("offline", ConnectionStatus::Disconnected, &[("ping", "Ping")]),
+            );
+        }
+
+        assert_eq!(
+            manager.format_tools_for_system_prompt().await,
+            "\nMCP (Model Context Protocol) Tools:\n\
+             Server 'files' is connected with tools:\n\
+             \x20 - read: Read a file (use format: files:read)\n\
+             \x20 - write: Write a file (use format: files:write)\n\
+             \x20 - list: List a directory (use format: files:list)\n\
+             Server 'github' is connected with tools:\n\
+             \x20 - list_issues: List issues (use format: github:list_issues)\n\
+             \x20 - create_issue: Open an issue (use format: github:create_issue)\n"
+        );
+    }
+}
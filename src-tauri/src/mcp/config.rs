@@ -1,4 +1,6 @@
+use super::oauth::{CLIENT_CREDENTIALS_FLOW, DEVICE_CODE_FLOW};
 use super::types::MCPServerConfig;
+use std::collections::HashMap;
 
 impl MCPServerConfig {
     #[allow(dead_code)]
@@ -15,11 +17,14 @@ impl MCPServerConfig {
             server_url,
             launch_command: None,
             launch_args: vec![],
-            launch_env: std::collections::HashMap::new(),
+            launch_env: HashMap::new(),
             working_dir: None,
             startup_timeout_ms: None,
+            startup_retry_attempts: None,
+            refresh_interval_secs: None,
             oauth_client_id: None,
             oauth_client_secret: None,
+            oauth_flow: None,
             enabled: false,
             created_at: now.clone(),
             updated_at: now,
@@ -43,4 +48,264 @@ impl MCPServerConfig {
     pub fn update(&mut self) {
         self.updated_at = chrono::Utc::now().to_rfc3339();
     }
+
+    /// Check the config is usable before it is saved or connected.
+    ///
+    /// All problems are reported at once, joined by "; ".
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.id.is_empty() {
+            errors.push("id must not be empty".to_string());
+        } else if !self
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            errors.push("id may only contain letters, digits, '-' and '_'".to_string());
+        }
+
+        if self.name.trim().is_empty() {
+            errors.push("name must not be empty".to_string());
+        } else if self.name.chars().count() >= 64 {
+            errors.push("name must be shorter than 64 characters".to_string());
+        }
+
+        match self.transport.as_str() {
+            "http" => match reqwest::Url::parse(self.server_url.trim()) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => errors.push(format!(
+                    "server_url '{}' is not a valid http(s) URL",
+                    self.server_url
+                )),
+            },
+            "stdio" => {
+                if self
+                    .launch_command
+                    .as_deref()
+                    .map(str::trim)
+                    .unwrap_or("")
+                    .is_empty()
+                {
+                    errors.push("launch_command is required for stdio transport".to_string());
+                }
+            }
+            "websocket" | "ws" => match reqwest::Url::parse(self.server_url.trim()) {
+                Ok(url) if matches!(url.scheme(), "ws" | "wss") => {}
+                _ => errors.push(format!(
+                    "server_url '{}' is not a valid ws(s) URL",
+                    self.server_url
+                )),
+            },
+            other => errors.push(format!(
+                "transport '{}' is not supported (expected 'http', 'stdio' or 'websocket')",
+                other
+            )),
+        }
+
+        match self.oauth_flow.as_deref() {
+            None | Some(CLIENT_CREDENTIALS_FLOW) => {}
+            Some(DEVICE_CODE_FLOW) => {
+                if self.oauth_client_id.as_deref().map(str::trim).unwrap_or("").is_empty() {
+                    errors.push("oauth_client_id is required for the device_code OAuth flow".to_string());
+                }
+            }
+            Some(other) => errors.push(format!(
+                "oauth_flow '{}' is not supported (expected 'client_credentials' or 'device_code')",
+                other
+            )),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Expand `${VAR}` references and a leading `~` in every launch_env value.
+    ///
+    /// Returns the expanded environment together with warnings for undefined variables;
+    /// those references are left as-is.
+    pub fn expanded_launch_env(&self) -> (HashMap<String, String>, Vec<String>) {
+        let mut env = HashMap::new();
+        let mut warnings = Vec::new();
+
+        for (key, value) in &self.launch_env {
+            let (expanded, undefined) = expand_env_value_checked(value);
+            for var in undefined {
+                warnings.push(format!(
+                    "Environment variable '{}' referenced by launch_env '{}' is not defined",
+                    var, key
+                ));
+            }
+            env.insert(key.clone(), expanded);
+        }
+
+        (env, warnings)
+    }
+}
+
+/// Expand `${VAR_NAME}` references and a leading `~` in a launch_env value, also returning
+/// the names of any undefined variables, which are left unexpanded.
+fn expand_env_value_checked(value: &str) -> (String, Vec<String>) {
+    let mut undefined = Vec::new();
+    let mut result = String::with_capacity(value.len());
+
+    let mut rest = value;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        if let Some(home) = dirs::home_dir() {
+            result.push_str(&home.to_string_lossy());
+            rest = &rest[1..];
+        }
+    }
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            // Unterminated reference, keep the remainder literally
+            result.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+
+        let name = &after[..end];
+        match std::env::var(name) {
+            Ok(var) => result.push_str(&var),
+            _ => {
+                undefined.push(name.to_string());
+                result.push_str(&rest[start..start + 2 + end + 1]);
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+
+    (result, undefined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_home_variable() {
+        let home = std::env::var("HOME").expect("HOME should be set");
+        assert_eq!(expand_env_value_checked("${HOME}/.config").0, format!("{}/.config", home));
+    }
+
+    #[test]
+    fn test_expand_undefined_variable() {
+        let (expanded, undefined) =
+            expand_env_value_checked("prefix-${KUSE_COWORK_TEST_UNDEFINED_VAR}-suffix");
+        assert_eq!(expanded, "prefix-${KUSE_COWORK_TEST_UNDEFINED_VAR}-suffix");
+        assert_eq!(undefined, vec!["KUSE_COWORK_TEST_UNDEFINED_VAR".to_string()]);
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            expand_env_value_checked("~/mcp/data").0,
+            format!("{}/mcp/data", home.to_string_lossy())
+        );
+        assert_eq!(expand_env_value_checked("~").0, home.to_string_lossy());
+        // Tilde in the middle of a value is not expanded
+        assert_eq!(expand_env_value_checked("a~b").0, "a~b");
+    }
+
+    #[test]
+    fn test_expanded_launch_env_warnings() {
+        let mut config = MCPServerConfig::new(
+            "test".to_string(),
+            "Test".to_string(),
+            String::new(),
+        );
+        config
+            .launch_env
+            .insert("TOKEN".to_string(), "${KUSE_COWORK_TEST_MISSING}".to_string());
+
+        let (env, warnings) = config.expanded_launch_env();
+        assert_eq!(env.get("TOKEN").unwrap(), "${KUSE_COWORK_TEST_MISSING}");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("KUSE_COWORK_TEST_MISSING"));
+    }
+
+    fn stdio_config() -> MCPServerConfig {
+        let mut config = MCPServerConfig::new(
+            "local-fs".to_string(),
+            "Local FS".to_string(),
+            String::new(),
+        );
+        config.transport = "stdio".to_string();
+        config.launch_command = Some("npx".to_string());
+        config
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_configs() {
+        let http = MCPServerConfig::new(
+            "remote_1".to_string(),
+            "Remote".to_string(),
+            "https://mcp.example.com/mcp".to_string(),
+        );
+        assert_eq!(http.validate(), Ok(()));
+        assert_eq!(stdio_config().validate(), Ok(()));
+
+        let mut ws = MCPServerConfig::new("browser".to_string(), "Browser".to_string(), "ws://127.0.0.1:9222/mcp".to_string());
+        ws.transport = "websocket".to_string();
+        assert_eq!(ws.validate(), Ok(()));
+        ws.server_url = "http://127.0.0.1:9222".to_string();
+        assert!(ws.validate().unwrap_err().contains("not a valid ws(s) URL"));
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_fields() {
+        let mut config = stdio_config();
+        config.id = String::new();
+        assert!(config.validate().unwrap_err().contains("id must not be empty"));
+
+        let mut config = stdio_config();
+        config.id = "bad id/1".to_string();
+        assert!(config.validate().unwrap_err().contains("id may only contain"));
+
+        let mut config = stdio_config();
+        config.name = "  ".to_string();
+        assert!(config.validate().unwrap_err().contains("name must not be empty"));
+
+        let mut config = stdio_config();
+        config.name = "n".repeat(64);
+        assert!(config.validate().unwrap_err().contains("shorter than 64"));
+
+        let mut config = stdio_config();
+        config.transport = "websocket".to_string();
+        assert!(config.validate().unwrap_err().contains("transport 'websocket'"));
+
+        let mut config = stdio_config();
+        config.launch_command = Some(String::new());
+        assert!(config.validate().unwrap_err().contains("launch_command is required"));
+
+        let mut config = MCPServerConfig::new("remote".to_string(), "Remote".to_string(), "https://example.com/mcp".to_string());
+        config.oauth_flow = Some(DEVICE_CODE_FLOW.to_string());
+        assert!(config.validate().unwrap_err().contains("oauth_client_id is required"));
+        config.oauth_flow = Some("implicit".to_string());
+        assert!(config.validate().unwrap_err().contains("oauth_flow 'implicit'"));
+
+        for url in ["", "not a url", "ftp://example.com"] {
+            let config = MCPServerConfig::new("remote".to_string(), "Remote".to_string(), url.to_string());
+            assert!(config.validate().unwrap_err().contains("not a valid http(s) URL"));
+        }
+    }
+
+    #[test]
+    fn test_validate_joins_multiple_errors() {
+        let mut config = stdio_config();
+        config.id = String::new();
+        config.name = String::new();
+        config.launch_command = None;
+
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.split("; ").count(), 3);
+    }
 }
@@ -36,6 +36,11 @@ impl HttpMcpClient {
         }
     }
 
+    /// Use a new access token for later requests, e.g. after the old one expired
+    pub fn set_oauth_token(&mut self, oauth_token: Option<String>) {
+        self.oauth_token = oauth_token;
+    }
+
     fn next_message_id(&self) -> u64 {
         self.message_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     }
@@ -174,4 +179,49 @@ impl HttpMcpClient {
 
         Ok(response_body)
     }
-}
\ No newline at end of file
+
+    pub async fn list_prompts(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request("prompts/list", json!({})).await
+    }
+
+    pub async fn get_prompt(&self, name: &str, arguments: Value) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request(
+            "prompts/get",
+            json!({
+                "name": name,
+                "arguments": arguments
+            }),
+        )
+        .await
+    }
+
+    async fn send_request(&self, method: &str, params: Value) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let id = self.next_message_id();
+
+        let request_body = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        });
+
+        let mut request = self.client.post(&self.base_url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json");
+
+        // Add session ID if we have one
+        if let Some(ref session_id) = self.session_id {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+
+        // Add OAuth token if configured
+        if let Some(ref token) = self.oauth_token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let response = request.json(&request_body).send().await?;
+        let response_body: Value = response.json().await?;
+
+        Ok(response_body)
+    }
+}
@@ -1,9 +1,11 @@
 pub mod client;
 pub mod config;
 pub mod http_client;
+pub mod oauth;
 pub mod stdio_client;
 pub mod storage;
 pub mod types;
+pub mod ws_client;
 
 pub use client::MCPManager;
 pub use types::MCPServerConfig;
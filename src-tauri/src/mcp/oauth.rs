@@ -0,0 +1,228 @@
+//! OAuth for HTTP MCP servers: the client credentials grant and the device
+//! authorization grant (RFC 8628) for servers that need the user to sign in
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub const CLIENT_CREDENTIALS_FLOW: &str = "client_credentials";
+pub const DEVICE_CODE_FLOW: &str = "device_code";
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Longest wait for the user to approve a device code
+const DEVICE_CODE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Polling interval when the server doesn't send one
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+/// Tokens are treated as expired this long before `expires_in` runs out
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct OAuthToken {
+    pub access_token: String,
+    expires_at: Option<Instant>,
+}
+
+impl OAuthToken {
+    fn from_response(response: &serde_json::Value) -> Option<Self> {
+        let access_token = response.get("access_token")?.as_str()?.to_string();
+        let expires_at = response
+            .get("expires_in")
+            .and_then(|v| v.as_u64())
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        Some(Self { access_token, expires_at })
+    }
+
+    /// Whether the token has (nearly) expired; tokens sent without `expires_in` never do
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| Instant::now() + EXPIRY_MARGIN >= expires_at)
+    }
+}
+
+/// `{server}/oauth/{path}`, with a trailing `/mcp` dropped from the server URL
+fn oauth_url(server_url: &str, path: &str) -> String {
+    let base = server_url.trim_end_matches('/');
+    let base = base.strip_suffix("/mcp").unwrap_or(base);
+    format!("{}/oauth/{}", base, path)
+}
+
+/// POST a form and parse the JSON reply. Error replies are returned too, since OAuth
+/// reports `authorization_pending` and friends in a 400 body.
+async fn post_form(url: &str, params: &[(&str, &str)]) -> Result<(reqwest::StatusCode, serde_json::Value), BoxError> {
+    let request = reqwest::Client::new().post(url).form(params).send();
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, request).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(format!("OAuth request failed: {}", e).into()),
+        Err(_) => {
+            return Err(format!("OAuth request timed out after {} seconds", REQUEST_TIMEOUT.as_secs()).into())
+        }
+    };
+
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    let body = serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text));
+    Ok((status, body))
+}
+
+pub async fn request_client_credentials_token(
+    client_id: &str,
+    client_secret: &str,
+    server_url: &str,
+) -> Result<OAuthToken, BoxError> {
+    let params = [
+        ("grant_type", "client_credentials"),
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+    ];
+    let (status, body) = post_form(&oauth_url(server_url, "token"), &params).await?;
+    if !status.is_success() {
+        return Err(format!("OAuth request failed: {} - {}", status, body).into());
+    }
+    OAuthToken::from_response(&body).ok_or_else(|| "No access_token in OAuth response".into())
+}
+
+/// Sign in with a device code: request one, pass the user code and verification URL to
+/// `on_prompt` for the user to approve in a browser, then poll the token endpoint until
+/// they do (at most five minutes).
+pub async fn perform_device_code_flow(
+    client_id: &str,
+    server_url: &str,
+    on_prompt: impl FnOnce(String, String),
+) -> Result<OAuthToken, BoxError> {
+    let (status, body) = post_form(&oauth_url(server_url, "device/code"), &[("client_id", client_id)]).await?;
+    if !status.is_success() {
+        return Err(format!("Device authorization request failed: {} - {}", status, body).into());
+    }
+
+    let field = |key: &str| body.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let device_code = field("device_code").ok_or("No device_code in device authorization response")?;
+    let user_code = field("user_code").ok_or("No user_code in device authorization response")?;
+    // RFC 8628 names it verification_uri; some servers send verification_url
+    let verification_url = field("verification_uri")
+        .or_else(|| field("verification_url"))
+        .ok_or("No verification_uri in device authorization response")?;
+    let mut interval = Duration::from_secs(
+        body.get("interval")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+    );
+    let timeout = body
+        .get("expires_in")
+        .and_then(|v| v.as_u64())
+        .map(Duration::from_secs)
+        .map_or(DEVICE_CODE_TIMEOUT, |expires_in| expires_in.min(DEVICE_CODE_TIMEOUT));
+
+    on_prompt(user_code, verification_url);
+
+    let token_url = oauth_url(server_url, "token");
+    let params = [
+        ("grant_type", DEVICE_CODE_GRANT),
+        ("device_code", device_code.as_str()),
+        ("client_id", client_id),
+    ];
+    let deadline = Instant::now() + timeout;
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let (status, body) = post_form(&token_url, &params).await?;
+        if status.is_success() {
+            if let Some(token) = OAuthToken::from_response(&body) {
+                return Ok(token);
+            }
+        }
+        match body.get("error").and_then(|v| v.as_str()) {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += Duration::from_secs(5),
+            Some("access_denied") => return Err("Device code sign-in was denied".into()),
+            Some("expired_token") => return Err("Device code expired before it was approved".into()),
+            _ => return Err(format!("OAuth token request failed: {} - {}", status, body).into()),
+        }
+
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "Device code sign-in was not approved within {} seconds",
+                timeout.as_secs()
+            )
+            .into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_http_server;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_oauth_url() {
+        assert_eq!(oauth_url("https://mcp.example.com/mcp", "token"), "https://mcp.example.com/oauth/token");
+        assert_eq!(oauth_url("https://mcp.example.com/", "device/code"), "https://mcp.example.com/oauth/device/code");
+    }
+
+    #[tokio::test]
+    async fn test_device_code_flow_polls_until_approved() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counted = polls.clone();
+        let url = spawn_mock_http_server(move |head, _body| {
+            if head.starts_with("POST /oauth/device/code") {
+                let reply = r#"{"device_code": "dev-1", "user_code": "WDJB-MJHT", "verification_uri": "https://example.com/device", "expires_in": 600, "interval": 0}"#;
+                return Some(("application/json", reply.to_string()));
+            }
+            if head.starts_with("POST /oauth/token") {
+                let reply = if counted.fetch_add(1, Ordering::SeqCst) < 2 {
+                    r#"{"error": "authorization_pending"}"#
+                } else {
+                    r#"{"access_token": "tok-1", "token_type": "Bearer", "expires_in": 3600}"#
+                };
+                return Some(("application/json", reply.to_string()));
+            }
+            None
+        })
+        .await;
+
+        let prompted = Arc::new(Mutex::new(None));
+        let captured = prompted.clone();
+        let token = perform_device_code_flow("kuse", &format!("{}/mcp", url), move |user_code, verification_url| {
+            *captured.lock().unwrap() = Some((user_code, verification_url));
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(token.access_token, "tok-1");
+        assert!(!token.is_expired());
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            prompted.lock().unwrap().clone(),
+            Some(("WDJB-MJHT".to_string(), "https://example.com/device".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_code_flow_denied() {
+        let url = spawn_mock_http_server(|head, _body| {
+            let reply = if head.starts_with("POST /oauth/device/code") {
+                r#"{"device_code": "dev-1", "user_code": "ABCD", "verification_url": "https://example.com/device", "interval": 0}"#
+            } else {
+                r#"{"error": "access_denied"}"#
+            };
+            Some(("application/json", reply.to_string()))
+        })
+        .await;
+
+        let err = perform_device_code_flow("kuse", &url, |_, _| {}).await.unwrap_err();
+        assert_eq!(err.to_string(), "Device code sign-in was denied");
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let token = OAuthToken::from_response(&serde_json::json!({"access_token": "a", "expires_in": 10})).unwrap();
+        assert!(token.is_expired());
+        let token = OAuthToken::from_response(&serde_json::json!({"access_token": "a"})).unwrap();
+        assert!(!token.is_expired());
+        assert!(OAuthToken::from_response(&serde_json::json!({"error": "invalid_client"})).is_none());
+    }
+}
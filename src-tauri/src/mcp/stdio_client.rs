@@ -24,11 +24,13 @@ pub struct StdioMcpClient {
 }
 
 impl StdioMcpClient {
+    /// Spawn the server process; each line it writes to stderr is passed to `on_stderr_line`
     pub async fn new(
         command: &str,
         args: &[String],
         env: &std::collections::HashMap<String, String>,
         working_dir: Option<&str>,
+        on_stderr_line: impl Fn(String) + Send + 'static,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let mut cmd = Command::new(command);
         if !args.is_empty() {
@@ -45,7 +47,7 @@ impl StdioMcpClient {
 
         cmd.stdin(std::process::Stdio::piped());
         cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::null());
+        cmd.stderr(std::process::Stdio::piped());
 
         let mut child = cmd
             .spawn()
@@ -60,6 +62,16 @@ impl StdioMcpClient {
             .take()
             .ok_or("Failed to capture MCP process stdout")?;
 
+        // Ends on EOF, once the process exits
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    on_stderr_line(line);
+                }
+            });
+        }
+
         Ok(Self {
             inner: Mutex::new(StdioInner {
                 child,
@@ -133,6 +145,25 @@ impl StdioMcpClient {
         .await
     }
 
+    pub async fn list_prompts(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request("prompts/list", json!({})).await
+    }
+
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: Value,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request(
+            "prompts/get",
+            json!({
+                "name": name,
+                "arguments": arguments
+            }),
+        )
+        .await
+    }
+
     pub async fn shutdown(&self) {
         let mut inner = self.inner.lock().await;
         let _ = inner.child.kill().await;
@@ -1,11 +1,11 @@
 use super::types::MCPServerConfig;
-use crate::database::{Database, DbError};
-use rusqlite::params;
+use crate::database::{add_column_if_missing, Database, DbError};
+use rusqlite::{params, Connection};
 use std::collections::HashMap;
 
 impl Database {
     pub fn create_mcp_tables(&self) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS mcp_servers (
@@ -18,8 +18,11 @@ impl Database {
                 launch_env_json TEXT,
                 working_dir TEXT,
                 startup_timeout_ms INTEGER,
+                startup_retry_attempts INTEGER,
                 oauth_client_id TEXT,
                 oauth_client_secret TEXT,
+                oauth_flow TEXT,
+                refresh_interval_secs INTEGER,
                 enabled BOOLEAN NOT NULL DEFAULT 0,
                 created_at TIMESTAMP NOT NULL,
                 updated_at TIMESTAMP NOT NULL
@@ -34,42 +37,23 @@ impl Database {
         add_column_if_missing(&conn, "mcp_servers", "launch_env_json", "TEXT")?;
         add_column_if_missing(&conn, "mcp_servers", "working_dir", "TEXT")?;
         add_column_if_missing(&conn, "mcp_servers", "startup_timeout_ms", "INTEGER")?;
+        add_column_if_missing(&conn, "mcp_servers", "startup_retry_attempts", "INTEGER")?;
+        add_column_if_missing(&conn, "mcp_servers", "oauth_flow", "TEXT")?;
+        add_column_if_missing(&conn, "mcp_servers", "refresh_interval_secs", "INTEGER")?;
 
         Ok(())
     }
 
     pub fn save_mcp_server(&self, config: &MCPServerConfig) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
-
-        conn.execute(
-            "INSERT OR REPLACE INTO mcp_servers
-             (id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-            params![
-                config.id,
-                config.name,
-                config.transport,
-                config.server_url,
-                config.launch_command,
-                serde_json::to_string(&config.launch_args).unwrap_or_else(|_| "[]".to_string()),
-                serde_json::to_string(&config.launch_env).unwrap_or_else(|_| "{}".to_string()),
-                config.working_dir,
-                config.startup_timeout_ms,
-                config.oauth_client_id,
-                config.oauth_client_secret,
-                config.enabled,
-                config.created_at,
-                config.updated_at,
-            ],
-        )?;
-        Ok(())
+        let conn = self.lock_conn()?;
+        write_mcp_server(&conn, config)
     }
 
     pub fn get_mcp_servers(&self) -> Result<Vec<MCPServerConfig>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at
+            "SELECT id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at, startup_retry_attempts, oauth_flow, refresh_interval_secs
              FROM mcp_servers ORDER BY name"
         )?;
 
@@ -86,8 +70,11 @@ impl Database {
                 launch_env: parse_json_map(launch_env_json),
                 working_dir: row.get(7)?,
                 startup_timeout_ms: row.get(8)?,
+                startup_retry_attempts: row.get(14)?,
                 oauth_client_id: row.get(9)?,
                 oauth_client_secret: row.get(10)?,
+                oauth_flow: row.get(15)?,
+                refresh_interval_secs: row.get(16)?,
                 enabled: row.get(11)?,
                 created_at: row.get(12)?,
                 updated_at: row.get(13)?,
@@ -102,10 +89,10 @@ impl Database {
     }
 
     pub fn get_mcp_server(&self, id: &str) -> Result<Option<MCPServerConfig>, DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at
+            "SELECT id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at, startup_retry_attempts, oauth_flow, refresh_interval_secs
              FROM mcp_servers WHERE id = ?1"
         )?;
 
@@ -122,8 +109,11 @@ impl Database {
                 launch_env: parse_json_map(launch_env_json),
                 working_dir: row.get(7)?,
                 startup_timeout_ms: row.get(8)?,
+                startup_retry_attempts: row.get(14)?,
                 oauth_client_id: row.get(9)?,
                 oauth_client_secret: row.get(10)?,
+                oauth_flow: row.get(15)?,
+                refresh_interval_secs: row.get(16)?,
                 enabled: row.get(11)?,
                 created_at: row.get(12)?,
                 updated_at: row.get(13)?,
@@ -137,7 +127,7 @@ impl Database {
     }
 
     pub fn delete_mcp_server(&self, id: &str) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         conn.execute(
             "DELETE FROM mcp_servers WHERE id = ?1",
@@ -147,7 +137,7 @@ impl Database {
     }
 
     pub fn update_mcp_server_enabled(&self, id: &str, enabled: bool) -> Result<(), DbError> {
-        let conn = self.conn.lock().map_err(|_| DbError::Lock)?;
+        let conn = self.lock_conn()?;
 
         conn.execute(
             "UPDATE mcp_servers SET enabled = ?1, updated_at = ?2 WHERE id = ?3",
@@ -157,24 +147,33 @@ impl Database {
     }
 }
 
-fn add_column_if_missing(
-    conn: &rusqlite::Connection,
-    table: &str,
-    column: &str,
-    definition: &str,
-) -> Result<(), DbError> {
-    let sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
-    match conn.execute(&sql, []) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            let msg = err.to_string().to_lowercase();
-            if msg.contains("duplicate column name") {
-                Ok(())
-            } else {
-                Err(err.into())
-            }
-        }
-    }
+/// Insert or replace one server row
+pub(crate) fn write_mcp_server(conn: &Connection, config: &MCPServerConfig) -> Result<(), DbError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO mcp_servers
+         (id, name, transport, server_url, launch_command, launch_args_json, launch_env_json, working_dir, startup_timeout_ms, oauth_client_id, oauth_client_secret, enabled, created_at, updated_at, startup_retry_attempts, oauth_flow, refresh_interval_secs)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        params![
+            config.id,
+            config.name,
+            config.transport,
+            config.server_url,
+            config.launch_command,
+            serde_json::to_string(&config.launch_args).unwrap_or_else(|_| "[]".to_string()),
+            serde_json::to_string(&config.launch_env).unwrap_or_else(|_| "{}".to_string()),
+            config.working_dir,
+            config.startup_timeout_ms,
+            config.oauth_client_id,
+            config.oauth_client_secret,
+            config.enabled,
+            config.created_at,
+            config.updated_at,
+            config.startup_retry_attempts,
+            config.oauth_flow,
+            config.refresh_interval_secs,
+        ],
+    )?;
+    Ok(())
 }
 
 fn parse_json_vec(value: Option<String>) -> Vec<String> {
@@ -19,8 +19,17 @@ pub struct MCPServerConfig {
     pub working_dir: Option<String>,
     #[serde(default)]
     pub startup_timeout_ms: Option<u64>,
+    /// Connection retries at app startup (default 3)
+    #[serde(default)]
+    pub startup_retry_attempts: Option<u32>,
+    /// Re-fetch the server's tool list this often while connected; `None` fetches it only on connect
+    #[serde(default)]
+    pub refresh_interval_secs: Option<u64>,
     pub oauth_client_id: Option<String>,
     pub oauth_client_secret: Option<String>,
+    /// "client_credentials" (default) or "device_code" for servers that need the user to sign in
+    #[serde(default)]
+    pub oauth_flow: Option<String>,
     pub enabled: bool,
     pub created_at: String,
     pub updated_at: String,
@@ -34,6 +43,118 @@ pub struct MCPTool {
     pub input_schema: serde_json::Value,
 }
 
+impl MCPTool {
+    /// Function name exposed to the model (`mcp_{server_id}_{tool_name}`)
+    pub fn function_name(&self) -> String {
+        let safe_server_id = self.server_id.replace("-", "_").replace(":", "_");
+        let safe_tool_name = self.name.replace("-", "_").replace(":", "_");
+        format!("mcp_{}_{}", safe_server_id, safe_tool_name)
+    }
+}
+
+/// Schema keywords OpenAI function calling rejects
+const UNSUPPORTED_SCHEMA_KEYS: [&str; 3] = ["$schema", "$id", "$defs"];
+
+/// Copy of an MCP tool's input schema with `#/$defs/...` references inlined, without
+/// `$schema`, `$id` and `$defs` (at any depth) and with a top-level `"type": "object"`,
+/// as OpenAI-compatible providers require
+pub fn clean_input_schema(schema: &serde_json::Value) -> serde_json::Value {
+    let no_defs = serde_json::Map::new();
+    let defs = schema.get("$defs").and_then(|defs| defs.as_object()).unwrap_or(&no_defs);
+    let mut cleaned = strip_schema_keys(&inline_refs(schema, defs, &mut Vec::new()));
+    match cleaned.as_object_mut() {
+        Some(object) => {
+            object.insert("type".to_string(), serde_json::json!("object"));
+        }
+        None => cleaned = serde_json::json!({ "type": "object", "properties": {} }),
+    }
+    cleaned
+}
+
+/// Replace `{"$ref": "#/$defs/Name", ...}` with the definition merged with the sibling keys.
+/// A definition that refers back to itself is expanded once; the inner reference is dropped.
+fn inline_refs(
+    value: &serde_json::Value,
+    defs: &serde_json::Map<String, serde_json::Value>,
+    expanding: &mut Vec<String>,
+) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let Some(name) = object
+                .get("$ref")
+                .and_then(|reference| reference.as_str())
+                .and_then(|reference| reference.strip_prefix("#/$defs/"))
+            else {
+                return serde_json::Value::Object(
+                    object
+                        .iter()
+                        .map(|(key, value)| (key.clone(), inline_refs(value, defs, expanding)))
+                        .collect(),
+                );
+            };
+
+            let mut resolved = serde_json::Map::new();
+            if let Some(definition) = defs.get(name).filter(|_| !expanding.iter().any(|n| n == name)) {
+                expanding.push(name.to_string());
+                if let serde_json::Value::Object(inlined) = inline_refs(definition, defs, expanding) {
+                    resolved = inlined;
+                }
+                expanding.pop();
+            }
+            for (key, value) in object.iter().filter(|(key, _)| key.as_str() != "$ref") {
+                resolved.insert(key.clone(), inline_refs(value, defs, expanding));
+            }
+            serde_json::Value::Object(resolved)
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(|item| inline_refs(item, defs, expanding)).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+fn strip_schema_keys(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => serde_json::Value::Object(
+            object
+                .iter()
+                .filter(|(key, _)| !UNSUPPORTED_SCHEMA_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| {
+                    // Keys under "properties" are parameter names, not keywords, so keep them
+                    let value = match (key.as_str(), value) {
+                        ("properties", serde_json::Value::Object(properties)) => serde_json::Value::Object(
+                            properties
+                                .iter()
+                                .map(|(name, schema)| (name.clone(), strip_schema_keys(schema)))
+                                .collect(),
+                        ),
+                        _ => strip_schema_keys(value),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.iter().map(strip_schema_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Prompt template offered by a server's `prompts` capability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MCPPrompt {
+    pub server_id: String,
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPServerStatus {
     pub id: String,
@@ -41,13 +162,18 @@ pub struct MCPServerStatus {
     pub transport: String,
     pub status: ConnectionStatus,
     pub tools: Vec<MCPTool>,
+    #[serde(default)]
+    pub prompts: Vec<MCPPrompt>,
     pub last_error: Option<String>,
     pub managed_process: bool,
     pub pid: Option<u32>,
     pub endpoint: Option<String>,
+    /// Runtime override of the tool call timeout; not persisted
+    #[serde(default)]
+    pub tool_call_timeout_secs: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ConnectionStatus {
     Connected,
     Disconnected,
@@ -55,6 +181,41 @@ pub enum ConnectionStatus {
     Error,
 }
 
+/// Payload of the `mcp-status-changed` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MCPStatusChangedPayload {
+    pub server_id: String,
+    pub name: String,
+    pub status: ConnectionStatus,
+    pub error: Option<String>,
+}
+
+/// A recorded change of a server's connection status, for debugging flaky servers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MCPConnectionEvent {
+    /// Unix milliseconds
+    pub timestamp: i64,
+    pub server_id: String,
+    pub from_status: String,
+    pub to_status: String,
+    pub error: Option<String>,
+}
+
+/// Payload of the `mcp-oauth-device-code` event: the code the user enters at `verification_url`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MCPDeviceCodePrompt {
+    pub server_id: String,
+    pub user_code: String,
+    pub verification_url: String,
+}
+
+/// Payload of the `mcp-startup-complete` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MCPStartupSummary {
+    pub connected: u32,
+    pub failed: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPToolCall {
     pub server_id: String,
@@ -72,3 +233,94 @@ pub struct MCPToolResult {
 fn default_transport() -> String {
     "http".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_clean_input_schema_strips_schema_metadata() {
+        let tool = MCPTool {
+            server_id: "docs-server".to_string(),
+            name: "search".to_string(),
+            description: "Search the docs".to_string(),
+            input_schema: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "$id": "https://example.com/search.json",
+                "properties": {
+                    "query": { "type": "string" },
+                    "$id": { "type": "string", "description": "Document id" },
+                    "filter": {
+                        "$id": "#filter",
+                        "type": "object",
+                        "properties": { "tags": { "type": "array", "items": { "$schema": "x", "type": "string" } } },
+                        "$defs": { "tag": { "type": "string" } }
+                    }
+                },
+                "required": ["query"],
+                "$defs": { "filter": { "type": "object" } }
+            }),
+        };
+
+        assert_eq!(tool.function_name(), "mcp_docs_server_search");
+        assert_eq!(
+            clean_input_schema(&tool.input_schema),
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "$id": { "type": "string", "description": "Document id" },
+                    "filter": {
+                        "type": "object",
+                        "properties": { "tags": { "type": "array", "items": { "type": "string" } } }
+                    }
+                },
+                "required": ["query"]
+            })
+        );
+
+        assert_eq!(clean_input_schema(&json!(null)), json!({ "type": "object", "properties": {} }));
+    }
+
+    #[test]
+    fn test_clean_input_schema_inlines_refs() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "filter": { "$ref": "#/$defs/filter", "description": "Result filter" },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" } },
+                "missing": { "$ref": "#/$defs/nope" }
+            },
+            "$defs": {
+                "tag": { "type": "string" },
+                "filter": {
+                    "type": "object",
+                    "properties": {
+                        "tag": { "$ref": "#/$defs/tag" },
+                        "and": { "$ref": "#/$defs/filter" }
+                    }
+                }
+            }
+        });
+
+        assert_eq!(
+            clean_input_schema(&schema),
+            json!({
+                "type": "object",
+                "properties": {
+                    "filter": {
+                        "type": "object",
+                        "description": "Result filter",
+                        "properties": {
+                            "tag": { "type": "string" },
+                            "and": {}
+                        }
+                    },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "missing": {}
+                }
+            })
+        );
+    }
+}
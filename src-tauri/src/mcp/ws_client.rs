@@ -0,0 +1,155 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// JSON-RPC over a WebSocket, one JSON message per text frame
+pub struct WebSocketMcpClient {
+    stream: Mutex<WsStream>,
+    message_id: AtomicU64,
+}
+
+impl WebSocketMcpClient {
+    pub async fn new(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (stream, _) = connect_async(url).await?;
+
+        Ok(Self {
+            stream: Mutex::new(stream),
+            message_id: AtomicU64::new(1),
+        })
+    }
+
+    pub async fn initialize(
+        &self,
+        startup_timeout_ms: Option<u64>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let timeout_ms = startup_timeout_ms.unwrap_or(20_000);
+        let params = json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": {
+                "name": "kuse-cowork",
+                "title": "Kuse Cowork Desktop",
+                "version": "0.1.0"
+            }
+        });
+
+        let response = match timeout(
+            Duration::from_millis(timeout_ms),
+            self.send_request("initialize", params),
+        )
+        .await
+        {
+            Ok(res) => res?,
+            Err(_) => {
+                return Err(
+                    format!("Timed out after {} ms waiting for WebSocket MCP initialize", timeout_ms)
+                        .into(),
+                )
+            }
+        };
+
+        self.send_notification("notifications/initialized", json!({}))
+            .await?;
+        Ok(response)
+    }
+
+    pub async fn list_tools(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request("tools/list", json!({})).await
+    }
+
+    pub async fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Option<Value>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request(
+            "tools/call",
+            json!({
+                "name": tool_name,
+                "arguments": arguments.unwrap_or(json!({}))
+            }),
+        )
+        .await
+    }
+
+    pub async fn list_prompts(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request("prompts/list", json!({})).await
+    }
+
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: Value,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_request(
+            "prompts/get",
+            json!({
+                "name": name,
+                "arguments": arguments
+            }),
+        )
+        .await
+    }
+
+    pub async fn shutdown(&self) {
+        let mut stream = self.stream.lock().await;
+        let _ = stream.close(None).await;
+    }
+
+    async fn send_notification(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+        let mut stream = self.stream.lock().await;
+        stream.send(Message::Text(msg.to_string())).await?;
+        Ok(())
+    }
+
+    async fn send_request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let id = self.message_id.fetch_add(1, Ordering::SeqCst);
+        let req = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let mut stream = self.stream.lock().await;
+        stream.send(Message::Text(req.to_string())).await?;
+
+        // Skip server notifications and responses to other requests
+        loop {
+            let text = match stream.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(Message::Binary(bytes))) => String::from_utf8_lossy(&bytes).to_string(),
+                Some(Ok(Message::Close(_))) | None => return Err("MCP WebSocket closed".into()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            };
+
+            let Ok(message) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if message.get("id").and_then(|v| v.as_u64()) == Some(id) {
+                return Ok(message);
+            }
+        }
+    }
+}
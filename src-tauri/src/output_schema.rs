@@ -0,0 +1,144 @@
+//! Structured task output: asking the agent for JSON matching a schema and checking the result
+
+use crate::database::Settings;
+use crate::llm_client::{LLMClient, Message as LLMMessage};
+use serde_json::Value;
+
+/// Instruction appended to the system prompt when a task requests structured output
+pub fn schema_prompt(schema: &Value) -> String {
+    format!(
+        "\n\n## Output Format\nYour final response MUST be a valid JSON object matching this schema: {}",
+        schema
+    )
+}
+
+/// Check that `schema` is a usable JSON schema, before a run is spent on it
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    compile_schema(schema).map(|_| ())
+}
+
+fn compile_schema(schema: &Value) -> Result<jsonschema::Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| format!("Invalid output schema: {}", e))
+}
+
+/// Parse the JSON object in `text` and check it against `schema`.
+/// Errors describe every violation so they can be sent back to the model.
+pub fn validate_output(text: &str, schema: &Value) -> Result<Value, String> {
+    let validator = compile_schema(schema)?;
+
+    let json_text = extract_json(text).ok_or("Response does not contain a JSON object")?;
+    let output: Value =
+        serde_json::from_str(json_text).map_err(|e| format!("Response is not valid JSON: {}", e))?;
+
+    let errors: Vec<String> = validator.iter_errors(&output).map(|e| e.to_string()).collect();
+    if !errors.is_empty() {
+        return Err(format!("JSON does not match the schema: {}", errors.join("; ")));
+    }
+    Ok(output)
+}
+
+/// The outermost `{...}` in `text`, which also skips Markdown code fences and surrounding prose
+fn extract_json(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    (start < end).then(|| &text[start..=end])
+}
+
+/// Validate the agent's final response, asking the model once to fix it if it doesn't match
+pub async fn enforce_output_schema(settings: &Settings, text: &str, schema: &Value) -> Result<Value, String> {
+    let error = match validate_output(text, schema) {
+        Ok(output) => return Ok(output),
+        Err(e) => e,
+    };
+
+    let llm_client = LLMClient::from_settings(settings);
+    let messages = vec![LLMMessage {
+        role: "user".to_string(),
+        content: format!(
+            "Your previous response was supposed to be a JSON object matching this schema:\n{}\n\nValidation failed: {}\n\nPrevious response:\n{}\n\nReply with only the corrected JSON object.",
+            schema, error, text
+        ),
+    }];
+
+    let corrected = llm_client
+        .send_message(messages, &settings.model, settings.max_tokens, Some(settings.temperature))
+        .await
+        .map_err(|e| format!("{} (correction request failed: {})", error, e))?;
+    validate_output(&corrected, schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_server;
+    use serde_json::json;
+
+    fn inventory_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "count": { "type": "integer" }
+                        },
+                        "required": ["name", "count"]
+                    }
+                }
+            },
+            "required": ["items"]
+        })
+    }
+
+    #[test]
+    fn test_validate_output() {
+        let schema = inventory_schema();
+
+        let fenced = "Here is the inventory:\n```json\n{\"items\": [{\"name\": \"bolts\", \"count\": 40}]}\n```";
+        assert_eq!(
+            validate_output(fenced, &schema).unwrap(),
+            json!({ "items": [{ "name": "bolts", "count": 40 }] })
+        );
+
+        let err = validate_output(r#"{"items": [{"name": "bolts", "count": "forty"}]}"#, &schema).unwrap_err();
+        assert!(err.starts_with("JSON does not match the schema"), "{}", err);
+        assert!(err.contains("forty"), "{}", err);
+
+        assert_eq!(
+            validate_output("No inventory found.", &schema).unwrap_err(),
+            "Response does not contain a JSON object"
+        );
+    }
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&inventory_schema()).is_ok());
+        let err = check_schema(&json!({ "type": "no-such-type" })).unwrap_err();
+        assert!(err.starts_with("Invalid output schema"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_output_is_corrected_once() {
+        let base_url = spawn_mock_server(|_body| {
+            let reply = json!({
+                "choices": [{ "message": { "role": "assistant", "content": "{\"items\": []}" } }]
+            });
+            ("application/json", reply.to_string())
+        })
+        .await;
+        let settings = Settings {
+            provider: "ollama".to_string(),
+            model: "llama3.3:latest".to_string(),
+            base_url,
+            ..Settings::default()
+        };
+
+        let output = enforce_output_schema(&settings, "I could not find any items.", &inventory_schema())
+            .await
+            .unwrap();
+        assert_eq!(output, json!({ "items": [] }));
+    }
+}
@@ -0,0 +1,197 @@
+//! Project files read up front and added to a task's system prompt
+
+use crate::tools::path_utils;
+use std::path::{Path, PathBuf};
+
+/// Total bytes of file content added to the prompt
+const MAX_TOTAL_BYTES: usize = 20 * 1024;
+/// Bytes kept from any one file
+const MAX_FILE_BYTES: usize = 4 * 1024;
+const README_NAMES: &[&str] = &["README.md", "README.rst"];
+
+/// "## Project Context" section with the content of each file matched by `patterns`
+/// (paths or glob patterns), preceded by the project README when `auto_inject_readme` is set.
+/// Files outside the mounted folder(s) or that can't be read are skipped.
+pub fn project_context_prompt(
+    patterns: &[String],
+    auto_inject_readme: bool,
+    project_path: Option<&str>,
+) -> Option<String> {
+    let mut files = Vec::new();
+    if auto_inject_readme {
+        files.extend(find_readme(project_path));
+    }
+    for pattern in patterns {
+        for path in resolve_pattern(pattern, project_path) {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut sections = String::new();
+    let mut remaining = MAX_TOTAL_BYTES;
+    for path in files {
+        if remaining == 0 {
+            sections.push_str("\n[More files omitted: project context limit reached]\n");
+            break;
+        }
+        let content = match std::fs::read(&path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            Err(e) => {
+                eprintln!("[project_context] Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let content = truncate(content, MAX_FILE_BYTES.min(remaining));
+        remaining = remaining.saturating_sub(content.len());
+        sections.push_str(&format!(
+            "\n### {}\n```\n{}\n```\n",
+            display_name(&path, project_path),
+            content.trim_end()
+        ));
+    }
+
+    if sections.is_empty() {
+        return None;
+    }
+    Some(format!("\n\n## Project Context{}", sections.trim_end()))
+}
+
+fn find_readme(project_path: Option<&str>) -> Option<PathBuf> {
+    path_utils::parse_project_roots(project_path).first()?;
+    README_NAMES
+        .iter()
+        .filter_map(|name| path_utils::resolve_path_for_read(Path::new(name), project_path, false).ok())
+        .find(|path| path.is_file())
+}
+
+/// Files matched by a path or glob pattern, in name order
+fn resolve_pattern(pattern: &str, project_path: Option<&str>) -> Vec<PathBuf> {
+    let is_glob = pattern.contains(['*', '?', '[']);
+    if !is_glob {
+        return match path_utils::resolve_path_for_read(Path::new(pattern), project_path, false) {
+            Ok(path) if path.is_file() => vec![path],
+            Ok(path) => {
+                eprintln!("[project_context] Skipping {}: not a file", path.display());
+                Vec::new()
+            }
+            Err(e) => {
+                eprintln!("[project_context] Skipping {}: {}", pattern, e);
+                Vec::new()
+            }
+        };
+    }
+
+    let full_pattern = match path_utils::resolve_path(Path::new(pattern), project_path) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("[project_context] Skipping {}: {}", pattern, e);
+            return Vec::new();
+        }
+    };
+    let Ok(entries) = glob::glob(&full_pattern.to_string_lossy()) else {
+        eprintln!("[project_context] Skipping invalid glob pattern: {}", pattern);
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .filter(|path| path_utils::resolve_path_for_read(path, project_path, false).is_ok())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Path relative to the project root it is under, if any
+fn display_name(path: &Path, project_path: Option<&str>) -> String {
+    path_utils::parse_project_roots(project_path)
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn truncate(text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n[truncated: showing {} of {} bytes]",
+        &text[..end],
+        end,
+        text.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{AgentConfig, AgentContent, AgentMessage, MessageBuilder};
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_context_files_appear_in_request_system_prompt() {
+        let dir = std::env::temp_dir().join(format!("kuse-context-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("README.md"), "# Inventory service\nTracks stock levels.").unwrap();
+        fs::write(dir.join("src").join("main.rs"), "fn main() { serve(); }").unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "pub fn serve() {}").unwrap();
+        fs::write(dir.join("big.txt"), "x".repeat(MAX_FILE_BYTES + 100)).unwrap();
+        let outside = std::env::temp_dir().join(format!("kuse-outside-{}.txt", uuid::Uuid::new_v4()));
+        fs::write(&outside, "secret").unwrap();
+
+        let project_path = dir.to_string_lossy().to_string();
+        let patterns = vec![
+            "src/*.rs".to_string(),
+            "big.txt".to_string(),
+            outside.to_string_lossy().to_string(),
+            "missing.md".to_string(),
+        ];
+        let context = project_context_prompt(&patterns, true, Some(&project_path)).unwrap();
+
+        let mut config = AgentConfig::default();
+        config.system_prompt.push_str(&context);
+        let request = MessageBuilder::new(config, "claude-sonnet-4-5".to_string(), 1024, None)
+            .build_request(&[AgentMessage {
+                role: "user".to_string(),
+                content: AgentContent::Text("Add a health check".to_string()),
+            }])
+            .await;
+        let system = request.system.text;
+
+        assert!(system.contains("## Project Context\n### README.md\n```\n# Inventory service\nTracks stock levels.\n```"), "{}", system);
+        assert!(system.contains("### src/lib.rs\n```\npub fn serve() {}\n```"));
+        assert!(system.contains("### src/main.rs\n```\nfn main() { serve(); }\n```"));
+        assert!(system.find("src/lib.rs").unwrap() < system.find("src/main.rs").unwrap());
+        assert!(system.contains(&format!("[truncated: showing {} of {} bytes]", MAX_FILE_BYTES, MAX_FILE_BYTES + 100)));
+        assert!(!system.contains("secret"));
+
+        assert_eq!(project_context_prompt(&[], false, Some(&project_path)), None);
+
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_file(&outside);
+    }
+
+    #[test]
+    fn test_total_context_limited() {
+        let dir = std::env::temp_dir().join(format!("kuse-context-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..8 {
+            fs::write(dir.join(format!("notes{}.txt", i)), "y".repeat(MAX_FILE_BYTES)).unwrap();
+        }
+
+        let project_path = dir.to_string_lossy().to_string();
+        let context = project_context_prompt(&["*.txt".to_string()], false, Some(&project_path)).unwrap();
+        assert_eq!(context.matches("### notes").count(), MAX_TOTAL_BYTES / MAX_FILE_BYTES);
+        assert!(context.ends_with("[More files omitted: project context limit reached]"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,127 @@
+//! Limits how many agent runs execute at once; extra runs wait their turn in FIFO order
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub struct RunQueue {
+    semaphore: Arc<Semaphore>,
+    limit: Mutex<usize>,
+    waiting: AtomicUsize,
+}
+
+/// Takes a run off the waiting count when it starts or its caller gives up
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RunQueue {
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit: Mutex::new(limit),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a free run slot, held until the permit is dropped. When every slot is busy
+    /// `on_queued` gets the run's 1-based position in the queue before waiting.
+    pub async fn acquire(&self, on_queued: impl FnOnce(usize)) -> OwnedSemaphorePermit {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return permit;
+        }
+
+        let position = self.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+        let _waiting = WaitingGuard(&self.waiting);
+        on_queued(position);
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("run queue semaphore is never closed")
+    }
+
+    /// Runs waiting for a slot
+    pub fn depth(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Change how many runs may execute at once. Lowering the limit takes effect as
+    /// running agents finish; they are never interrupted.
+    pub fn set_limit(&self, limit: usize) {
+        let limit = limit.max(1);
+        let mut current = match self.limit.lock() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if limit > *current {
+            self.semaphore.add_permits(limit - *current);
+        } else if limit < *current {
+            let excess = (*current - limit) as u32;
+            let semaphore = self.semaphore.clone();
+            tokio::spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many_owned(excess).await {
+                    permits.forget();
+                }
+            });
+        }
+        *current = limit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_third_run_is_queued() {
+        let queue = Arc::new(RunQueue::new(2));
+        let first = queue.acquire(|_| panic!("first run should start immediately")).await;
+        let _second = queue.acquire(|_| panic!("second run should start immediately")).await;
+        assert_eq!(queue.depth(), 0);
+
+        let (queued_tx, queued_rx) = tokio::sync::oneshot::channel();
+        let third = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                let _permit = queue
+                    .acquire(|position| {
+                        let _ = queued_tx.send(position);
+                    })
+                    .await;
+            }
+        });
+
+        assert_eq!(queued_rx.await.unwrap(), 1);
+        assert_eq!(queue.depth(), 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!third.is_finished());
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), third).await.unwrap().unwrap();
+        assert_eq!(queue.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_set_limit() {
+        let queue = RunQueue::new(1);
+        let first = queue.acquire(|_| {}).await;
+
+        queue.set_limit(2);
+        let second = queue.acquire(|_| panic!("raised limit should free a slot")).await;
+
+        // Lowering waits for a running agent to finish
+        queue.set_limit(1);
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queue.semaphore.clone().try_acquire_owned().is_err());
+        drop(second);
+        let _third = queue.acquire(|_| panic!("a slot should be free")).await;
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,26 @@ use serde::{Deserialize, Serialize};
 pub struct SkillMetadata {
     pub name: String,
     pub description: String,
+    /// Input parameters declared in the frontmatter `parameters:` block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Vec<SkillParam>>,
+    /// Directory name of the skill a `*.skill.md` sub-skill belongs to; `None` for `SKILL.md`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_skill: Option<String>,
+}
+
+/// File name suffix of sub-skills inside a skill directory, e.g. `pdf/split.skill.md`
+const SUB_SKILL_SUFFIX: &str = ".skill.md";
+
+/// A single input parameter a skill accepts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillParam {
+    pub name: String,
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub description: String,
 }
 
 /// Get the skills directory path (app data directory only)
@@ -28,6 +49,13 @@ pub fn ensure_skills_directory() -> PathBuf {
     // Install default skills if directory is empty
     install_default_skills_if_needed(&skills_dir);
 
+    // Warn about parameter definitions that can't behave as declared
+    for metadata in read_skills(&skills_dir) {
+        for warning in check_parameter_definitions(&metadata) {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+
     skills_dir
 }
 
@@ -80,32 +108,144 @@ fn parse_skill_metadata(content: &str) -> Option<SkillMetadata> {
     let end_pos = content[3..].find("---")?;
     let yaml_content = &content[3..end_pos + 3];
 
-    // Simple YAML parsing for name and description
+    // Simple YAML parsing for name, description and the parameters block
     let mut name = None;
     let mut description = None;
+    let mut parameters: Option<Vec<SkillParam>> = None;
+    let mut in_parameters = false;
+
+    for raw_line in yaml_content.lines() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Indented lines belong to the parameters block until the next top-level key
+        let indented = raw_line.starts_with(' ') || raw_line.starts_with('\t');
+        if in_parameters && (indented || line.starts_with('-')) {
+            let params = parameters.get_or_insert_with(Vec::new);
+            let entry = match line.strip_prefix('-') {
+                Some(rest) => {
+                    params.push(SkillParam {
+                        name: String::new(),
+                        required: false,
+                        default: None,
+                        description: String::new(),
+                    });
+                    rest.trim()
+                }
+                None => line,
+            };
+            if let (Some(param), Some((key, value))) = (params.last_mut(), entry.split_once(':')) {
+                let value = unquote(value.trim());
+                match key.trim() {
+                    "name" => param.name = value,
+                    "required" => param.required = matches!(value.as_str(), "true" | "yes"),
+                    "default" => param.default = Some(value),
+                    "description" => param.description = value,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        in_parameters = false;
 
-    for line in yaml_content.lines() {
-        let line = line.trim();
         if let Some(stripped) = line.strip_prefix("name:") {
             name = Some(stripped.trim().to_string());
         } else if let Some(stripped) = line.strip_prefix("description:") {
             description = Some(stripped.trim().to_string());
+        } else if line.starts_with("parameters:") {
+            in_parameters = true;
         }
     }
 
+    if let Some(params) = parameters.as_mut() {
+        params.retain(|p| !p.name.is_empty());
+    }
+
     Some(SkillMetadata {
         name: name?,
         description: description?,
+        parameters,
+        parent_skill: None,
     })
 }
 
+/// Strip matching surrounding quotes from a YAML scalar
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+/// Check that required parameters don't declare a default
+fn check_parameter_definitions(metadata: &SkillMetadata) -> Vec<String> {
+    metadata
+        .parameters
+        .iter()
+        .flatten()
+        .filter(|p| p.required && p.default.is_some())
+        .map(|p| {
+            format!(
+                "skill '{}' parameter '{}' is required but has a default value",
+                metadata.name, p.name
+            )
+        })
+        .collect()
+}
+
+/// Check that all required parameters of a skill are provided. Nothing supplies skill
+/// parameters yet, so this is only built for tests until a caller exists.
+#[cfg(test)]
+pub fn validate_skill_parameters(skill_name: &str, params: HashMap<String, String>) -> Result<(), String> {
+    let skill = get_available_skills()
+        .into_iter()
+        .find(|s| s.name == skill_name)
+        .ok_or_else(|| format!("Skill '{}' not found", skill_name))?;
+
+    check_required_parameters(&skill, &params)
+}
+
+#[cfg(test)]
+fn check_required_parameters(skill: &SkillMetadata, params: &HashMap<String, String>) -> Result<(), String> {
+    let missing: Vec<&str> = skill
+        .parameters
+        .iter()
+        .flatten()
+        .filter(|p| p.required && !params.contains_key(&p.name))
+        .map(|p| p.name.as_str())
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Skill '{}' is missing required parameters: {}",
+            skill.name,
+            missing.join(", ")
+        ))
+    }
+}
+
 /// Get available skills by scanning the skills directory
 pub fn get_available_skills() -> Vec<SkillMetadata> {
     let skills_dir = ensure_skills_directory();
 
+    let mut skills = read_skills(&skills_dir);
+
+    // Sort skills by name for consistent ordering
+    skills.sort_by(|a, b| a.name.cmp(&b.name));
+    skills
+}
+
+/// Parse the SKILL.md and any `*.skill.md` sub-skills of every skill directory
+fn read_skills(skills_dir: &Path) -> Vec<SkillMetadata> {
     let mut skills = Vec::new();
 
-    if let Ok(entries) = fs::read_dir(&skills_dir) {
+    if let Ok(entries) = fs::read_dir(skills_dir) {
         for entry in entries.flatten() {
             let skill_dir = entry.path();
             if skill_dir.is_dir() {
@@ -117,15 +257,112 @@ pub fn get_available_skills() -> Vec<SkillMetadata> {
                         }
                     }
                 }
+
+                let parent = entry.file_name().to_string_lossy().to_string();
+                let mut sub_skill_files = Vec::new();
+                collect_sub_skill_files(&skill_dir, &mut HashSet::new(), &mut sub_skill_files);
+                sub_skill_files.sort();
+                for file in sub_skill_files {
+                    if let Some(mut metadata) = fs::read_to_string(&file).ok().and_then(|c| parse_skill_metadata(&c)) {
+                        metadata.parent_skill = Some(parent.clone());
+                        skills.push(metadata);
+                    }
+                }
             }
         }
     }
 
-    // Sort skills by name for consistent ordering
-    skills.sort_by(|a, b| a.name.cmp(&b.name));
     skills
 }
 
+/// `*.skill.md` files anywhere under `dir`. Each directory is visited once by its canonical
+/// path, so a symlink back up the tree doesn't recurse forever.
+fn collect_sub_skill_files(dir: &Path, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) {
+    let Ok(canonical) = fs::canonicalize(dir) else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_sub_skill_files(&path, visited, files);
+        } else if entry.file_name().to_string_lossy().ends_with(SUB_SKILL_SUFFIX) {
+            files.push(path);
+        }
+    }
+}
+
+/// Upper bound on skill text injected into one agent prompt
+pub const MAX_INJECTED_SKILL_CHARS: usize = 4000;
+
+/// Keywords that pull a skill into a task's prompt, keyed by skill name
+pub fn skill_injection_keywords() -> HashMap<String, Vec<String>> {
+    let keywords: [(&str, &[&str]); 4] = [
+        ("pdf", &["pdf", "portable document"]),
+        ("docx", &["docx", "word document"]),
+        ("xlsx", &["xlsx", "excel", "spreadsheet"]),
+        ("pptx", &["pptx", "powerpoint", "slide deck", "presentation"]),
+    ];
+
+    keywords
+        .iter()
+        .map(|(skill, words)| {
+            (
+                skill.to_string(),
+                words.iter().map(|w| w.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Names of skills whose keywords appear in the task description (case-insensitive), sorted
+pub fn match_skills_for_task(task_description: &str) -> Vec<String> {
+    let description = task_description.to_lowercase();
+    let mut matched: Vec<String> = skill_injection_keywords()
+        .into_iter()
+        .filter(|(_, words)| words.iter().any(|w| description.contains(&w.to_lowercase())))
+        .map(|(skill, _)| skill)
+        .collect();
+    matched.sort();
+    matched
+}
+
+/// `## Skills` prompt section for the skills matching a task, if any match
+pub fn skills_prompt_for_task(task_description: &str) -> Option<String> {
+    let skills = match_skills_for_task(task_description);
+    build_skills_section(&ensure_skills_directory(), &skills, MAX_INJECTED_SKILL_CHARS)
+}
+
+/// Concatenate each skill's SKILL.md under a `## Skills` heading, keeping the
+/// combined skill text within `max_chars`
+fn build_skills_section(skills_dir: &Path, skills: &[String], max_chars: usize) -> Option<String> {
+    let mut section = String::new();
+    let mut remaining = max_chars;
+
+    for skill in skills {
+        if remaining == 0 {
+            break;
+        }
+        let Ok(content) = fs::read_to_string(skills_dir.join(skill).join("SKILL.md")) else {
+            continue;
+        };
+        let content: String = content.trim().chars().take(remaining).collect();
+        remaining -= content.chars().count();
+        section.push_str(&format!("\n\n### {}\n{}", skill, content));
+    }
+
+    if section.is_empty() {
+        None
+    } else {
+        Some(format!("\n\n## Skills{}", section))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +385,89 @@ Some content here...
         assert_eq!(metadata.description, "Comprehensive PDF manipulation toolkit");
     }
 
+    const PARAMETERIZED_SKILL: &str = r#"---
+name: report
+description: Generate a report from a data file
+parameters:
+  - name: input_file
+    required: true
+    description: Path to the source data
+  - name: format
+    required: false
+    default: "pdf"
+    description: Output format
+license: MIT
+---
+
+# Report
+"#;
+
+    #[test]
+    fn test_parse_skill_parameters() {
+        let metadata = parse_skill_metadata(PARAMETERIZED_SKILL).unwrap();
+        assert_eq!(metadata.name, "report");
+        assert_eq!(metadata.description, "Generate a report from a data file");
+
+        let params = metadata.parameters.unwrap();
+        assert_eq!(
+            params,
+            vec![
+                SkillParam {
+                    name: "input_file".to_string(),
+                    required: true,
+                    default: None,
+                    description: "Path to the source data".to_string(),
+                },
+                SkillParam {
+                    name: "format".to_string(),
+                    required: false,
+                    default: Some("pdf".to_string()),
+                    description: "Output format".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skill_without_parameters() {
+        let metadata = parse_skill_metadata("---\nname: pdf\ndescription: PDF toolkit\n---\n").unwrap();
+        assert!(metadata.parameters.is_none());
+        assert!(check_parameter_definitions(&metadata).is_empty());
+    }
+
+    #[test]
+    fn test_required_parameter_with_default_warns() {
+        let content = PARAMETERIZED_SKILL.replace("required: true", "required: true\n    default: data.csv");
+        let metadata = parse_skill_metadata(&content).unwrap();
+
+        assert_eq!(
+            check_parameter_definitions(&metadata),
+            vec!["skill 'report' parameter 'input_file' is required but has a default value".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_check_required_parameters() {
+        let metadata = parse_skill_metadata(PARAMETERIZED_SKILL).unwrap();
+
+        // Optional parameters with defaults may be omitted
+        let mut params = HashMap::new();
+        params.insert("input_file".to_string(), "sales.csv".to_string());
+        assert!(check_required_parameters(&metadata, &params).is_ok());
+
+        let err = check_required_parameters(&metadata, &HashMap::new()).unwrap_err();
+        assert_eq!(err, "Skill 'report' is missing required parameters: input_file");
+    }
+
+    #[test]
+    fn test_validate_skill_parameters() {
+        // Bundled skills declare no parameters
+        assert!(validate_skill_parameters("pdf", HashMap::new()).is_ok());
+
+        let err = validate_skill_parameters("no-such-skill", HashMap::new()).unwrap_err();
+        assert_eq!(err, "Skill 'no-such-skill' not found");
+    }
+
     #[test]
     fn test_skills_directory_creation() {
         // This will create the skills directory and install default skills
@@ -178,4 +498,84 @@ Some content here...
         assert!(skill_names.contains(&"xlsx"));
         assert!(skill_names.contains(&"pptx"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sub_skills_read_alongside_skill_md() {
+        let dir = env::temp_dir().join(format!("kuse-skills-{}", uuid::Uuid::new_v4()));
+        install_skill(&dir, "pdf", "---\nname: pdf\ndescription: PDF toolkit\n---\n");
+        fs::write(
+            dir.join("pdf").join("split.skill.md"),
+            "---\nname: pdf-split\ndescription: Split a PDF into pages\n---\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("pdf").join("advanced")).unwrap();
+        fs::write(
+            dir.join("pdf").join("advanced").join("merge.skill.md"),
+            "---\nname: pdf-merge\ndescription: Merge PDFs\n---\n",
+        )
+        .unwrap();
+
+        let mut skills = read_skills(&dir);
+        skills.sort_by(|a, b| a.name.cmp(&b.name));
+        let found: Vec<(&str, Option<&str>)> = skills
+            .iter()
+            .map(|s| (s.name.as_str(), s.parent_skill.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("pdf", None), ("pdf-merge", Some("pdf")), ("pdf-split", Some("pdf"))]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sub_skill_scan_survives_symlink_loop() {
+        let dir = env::temp_dir().join(format!("kuse-skills-{}", uuid::Uuid::new_v4()));
+        install_skill(&dir, "pdf", "---\nname: pdf\ndescription: PDF toolkit\n---\n");
+        fs::write(
+            dir.join("pdf").join("split.skill.md"),
+            "---\nname: pdf-split\ndescription: Split a PDF into pages\n---\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(dir.join("pdf"), dir.join("pdf").join("again")).unwrap();
+
+        let mut names: Vec<String> = read_skills(&dir).into_iter().map(|s| s.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["pdf", "pdf-split"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_match_skills_for_task() {
+        assert_eq!(match_skills_for_task("Convert the Q3 numbers into a PDF"), vec!["pdf"]);
+        assert_eq!(
+            match_skills_for_task("Build an Excel model and a PowerPoint deck from it"),
+            vec!["pptx", "xlsx"]
+        );
+        assert!(match_skills_for_task("Refactor the parser").is_empty());
+    }
+
+    #[test]
+    fn test_build_skills_section_caps_content() {
+        let dir = env::temp_dir().join(format!("kuse-skills-{}", uuid::Uuid::new_v4()));
+        install_skill(&dir, "pdf", &"p".repeat(30));
+        install_skill(&dir, "xlsx", &"x".repeat(30));
+
+        let section = build_skills_section(&dir, &["pdf".to_string(), "xlsx".to_string()], 1000).unwrap();
+        assert!(section.starts_with("\n\n## Skills"));
+        assert!(section.contains(&format!("### pdf\n{}", "p".repeat(30))));
+        assert!(section.contains(&format!("### xlsx\n{}", "x".repeat(30))));
+
+        // The second skill only gets what is left of the cap
+        let capped = build_skills_section(&dir, &["pdf".to_string(), "xlsx".to_string()], 40).unwrap();
+        assert!(capped.contains(&format!("### pdf\n{}\n", "p".repeat(30))));
+        assert!(capped.ends_with(&format!("### xlsx\n{}", "x".repeat(10))));
+
+        assert!(build_skills_section(&dir, &["missing".to_string()], 1000).is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
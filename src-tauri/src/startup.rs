@@ -0,0 +1,138 @@
+//! Timing of the steps run while the app starts, to diagnose slow launches
+
+use crate::database::Database;
+use crate::mcp::{MCPManager, MCPStartupSummary};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// How long one startup step took
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartupEvent {
+    pub step: String,
+    pub duration_ms: u64,
+}
+
+/// Payload of the `app-startup-complete` event
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartupTiming {
+    pub events: Vec<StartupEvent>,
+    pub total_ms: u64,
+}
+
+/// Collects step timings from the moment it is created
+pub struct StartupTimer {
+    started: Instant,
+    events: Vec<StartupEvent>,
+}
+
+impl StartupTimer {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Run `f` and record how long it took under `step`
+    pub fn time<T>(&mut self, step: &str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.record(step, started);
+        result
+    }
+
+    /// Record a step that began at `started` and has just finished
+    pub fn record(&mut self, step: &str, started: Instant) {
+        self.events.push(StartupEvent {
+            step: step.to_string(),
+            duration_ms: elapsed_ms(started),
+        });
+    }
+
+    pub fn finish(self) -> StartupTiming {
+        StartupTiming {
+            total_ms: elapsed_ms(self.started),
+            events: self.events,
+        }
+    }
+}
+
+impl Default for StartupTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis().min(u64::MAX as u128) as u64
+}
+
+/// Load the saved MCP servers and auto-connect the enabled ones, timing each server separately
+pub async fn connect_mcp_servers_timed(
+    db: &Database,
+    mcp_manager: &MCPManager,
+    timer: &mut StartupTimer,
+) -> MCPStartupSummary {
+    let servers = timer.time("load_mcp_servers", || match db.get_mcp_servers() {
+        Ok(servers) => servers,
+        Err(e) => {
+            eprintln!("Failed to load MCP servers: {}", e);
+            Vec::new()
+        }
+    });
+
+    let mut summary = MCPStartupSummary::default();
+    for server in servers.iter().filter(|s| s.enabled) {
+        let started = Instant::now();
+        let result = mcp_manager
+            .connect_enabled_servers(std::slice::from_ref(server))
+            .await;
+        timer.record(&format!("mcp_connect:{}", server.id), started);
+
+        summary.connected += result.connected;
+        summary.failed += result.failed;
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::MCPServerConfig;
+    use crate::test_support::{mock_mcp_response, spawn_mock_server};
+
+    #[tokio::test]
+    async fn test_startup_steps_are_timed() {
+        let db_path = crate::test_support::temp_db_path();
+        let mut timer = StartupTimer::new();
+
+        let db = timer.time("database_init", || Database::open(&db_path).unwrap());
+        timer.time("mcp_tables", || db.create_mcp_tables().unwrap());
+
+        let url = spawn_mock_server(mock_mcp_response).await;
+        db.save_mcp_server(&MCPServerConfig::new("mock".to_string(), "Mock".to_string(), url).with_enabled(true))
+            .unwrap();
+        db.save_mcp_server(&MCPServerConfig::new(
+            "off".to_string(),
+            "Off".to_string(),
+            "http://127.0.0.1:1".to_string(),
+        ))
+        .unwrap();
+
+        let summary = connect_mcp_servers_timed(&db, &MCPManager::new(), &mut timer).await;
+        assert_eq!(summary, MCPStartupSummary { connected: 1, failed: 0 });
+
+        let timing = timer.finish();
+        let steps: Vec<&str> = timing.events.iter().map(|e| e.step.as_str()).collect();
+        assert_eq!(
+            steps,
+            vec!["database_init", "mcp_tables", "load_mcp_servers", "mcp_connect:mock"]
+        );
+        let step_total: u64 = timing.events.iter().map(|e| e.duration_ms).sum();
+        assert!(timing.total_ms >= step_total);
+
+        drop(db);
+        let _ = std::fs::remove_file(&db_path);
+    }
+}
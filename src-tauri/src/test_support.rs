@@ -0,0 +1,191 @@
+//! Helpers shared by unit tests across modules
+
+use crate::database::Database;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Path for a fresh database file in the temp directory
+pub fn temp_db_path() -> PathBuf {
+    std::env::temp_dir().join(format!("kuse-cowork-test-{}.db", uuid::Uuid::new_v4()))
+}
+
+/// Open a fresh database, returning it with its path so the test can remove the file
+pub fn temp_db() -> (Database, PathBuf) {
+    let path = temp_db_path();
+    let db = Database::open(&path).unwrap();
+    (db, path)
+}
+
+/// Serve JSON requests on a local port, answering each with `handler(body)`.
+/// The handler returns the response content type and body.
+pub async fn spawn_mock_server<F>(handler: F) -> String
+where
+    F: Fn(serde_json::Value) -> (&'static str, String) + Send + Sync + 'static,
+{
+    spawn_mock_http_server(move |_head, body| Some(handler(body))).await
+}
+
+/// Like `spawn_mock_server`, but the handler also gets the request line and headers,
+/// and returning `None` answers 404
+pub async fn spawn_mock_http_server<F>(handler: F) -> String
+where
+    F: Fn(&str, serde_json::Value) -> Option<(&'static str, String)> + Send + Sync + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let Some((head, body)) = read_http_request(&mut socket).await else {
+                    return;
+                };
+                let (status, content_type, reply) = match handler(&head, body) {
+                    Some((content_type, reply)) => ("200 OK", content_type, reply),
+                    None => ("404 Not Found", "text/plain", "not found".to_string()),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    reply.len(),
+                    reply
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Read one HTTP request and parse its body as JSON (`{}` if it isn't).
+/// Returns `None` if the client hangs up first.
+pub async fn read_json_request(socket: &mut tokio::net::TcpStream) -> Option<serde_json::Value> {
+    read_http_request(socket).await.map(|(_, body)| body)
+}
+
+/// Read one HTTP request, returning its request line and headers along with the JSON body
+pub async fn read_http_request(socket: &mut tokio::net::TcpStream) -> Option<(String, serde_json::Value)> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    let body_start = loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(header_end) = text.find("\r\n\r\n") {
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                })
+                .unwrap_or(0);
+            if request.len() >= header_end + 4 + content_length {
+                break header_end + 4;
+            }
+        }
+    };
+
+    let head = String::from_utf8_lossy(&request[..body_start]).to_string();
+    let body = serde_json::from_slice(&request[body_start..]).unwrap_or(json!({}));
+    Some((head, body))
+}
+
+/// Serve JSON-RPC over WebSocket on a local port, answering each request
+/// (messages with an `id`) with the body from `handler`
+pub async fn spawn_mock_ws_server<F>(handler: F) -> String
+where
+    F: Fn(serde_json::Value) -> (&'static str, String) + Send + Sync + 'static,
+{
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let Ok(mut ws) = tokio_tungstenite::accept_async(socket).await else {
+                    return;
+                };
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let body: serde_json::Value = serde_json::from_str(&text).unwrap_or(json!({}));
+                    if body.get("id").is_none() {
+                        continue;
+                    }
+                    let (_, reply) = handler(body);
+                    if ws.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+/// JSON-RPC handler for a minimal MCP server exposing a single `echo` tool
+/// and a single `summarize` prompt
+pub fn mock_mcp_response(body: serde_json::Value) -> (&'static str, String) {
+    let id = body.get("id").cloned().unwrap_or(serde_json::Value::Null);
+    let result = match body["method"].as_str().unwrap_or("") {
+        "initialize" => json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "mock", "version": "0.0.1" }
+        }),
+        "tools/list" => json!({
+            "tools": [{
+                "name": "echo",
+                "description": "Echo the input text",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": { "text": { "type": "string" } }
+                }
+            }]
+        }),
+        "tools/call" => json!({
+            "content": [{
+                "type": "text",
+                "text": format!("echo: {}", body["params"]["arguments"]["text"].as_str().unwrap_or(""))
+            }]
+        }),
+        "prompts/list" => json!({
+            "prompts": [{
+                "name": "summarize",
+                "description": "Summarize a topic",
+                "arguments": [{ "name": "topic", "description": "What to summarize", "required": true }]
+            }]
+        }),
+        "prompts/get" => json!({
+            "description": "Summarize a topic",
+            "messages": [{
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": format!("Summarize {}", body["params"]["arguments"]["topic"].as_str().unwrap_or(""))
+                }
+            }]
+        }),
+        _ => return ("application/json", "{}".to_string()),
+    };
+
+    (
+        "application/json",
+        json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+    )
+}
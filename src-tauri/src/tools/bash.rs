@@ -1,12 +1,14 @@
 use crate::agent::ToolDefinition;
+use crate::tools::{Tool, ToolFuture};
 use serde_json::json;
+use std::collections::HashMap;
 use std::process::{Command, Stdio};
 use std::time::Duration;
 
 pub fn definition() -> ToolDefinition {
     ToolDefinition {
         name: "bash".to_string(),
-        description: "Execute a shell command. Use for running builds, tests, git commands, etc. Commands run in a sandboxed environment with timeouts.".to_string(),
+        description: "Execute a shell command. Use for running builds, tests, git commands, etc. Commands run in a sandboxed environment with timeouts. Returns JSON with shell, exit_code, success, stdout and stderr (each capped at 5000 characters), plus combined for the merged output. The command does not inherit the app's environment: it only sees PATH, HOME, USER, LANG, TMPDIR, PROJECT_PATH (when a project is open) and the variables passed in env. HOME, USER, LANG and TMPDIR are kept because git, package managers and compilers misbehave without them, and PATH is a minimal system PATH followed by the user's own entries so tools installed in their home directory are found; API keys and every other variable are dropped. Pass untrusted data through env and reference it as \"$NAME\" instead of splicing it into the command; values containing null bytes are rejected.".to_string(),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -21,6 +23,11 @@ pub fn definition() -> ToolDefinition {
                 "timeout": {
                     "type": "integer",
                     "description": "Timeout in seconds (default: 60, max: 300)"
+                },
+                "env": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Environment variables for the command (optional)"
                 }
             },
             "required": ["command"]
@@ -42,6 +49,20 @@ const BLOCKED_PATTERNS: &[&str] = &[
     "curl | bash",
 ];
 
+/// stdout and stderr are each capped at this many characters
+const MAX_STREAM_CHARS: usize = 5000;
+
+/// PATH entries commands always get; the user's own PATH entries are appended after them
+#[cfg(not(target_os = "windows"))]
+const MINIMAL_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/opt/homebrew/bin";
+
+/// Non-secret variables passed through from the app's environment when set, on top of the
+/// minimal environment, since git, package managers and compilers misbehave without them
+#[cfg(not(target_os = "windows"))]
+const PASSTHROUGH_VARS: &[&str] = &["HOME", "USER", "LANG", "TMPDIR"];
+#[cfg(target_os = "windows")]
+const PASSTHROUGH_VARS: &[&str] = &["HOME", "USER", "LANG", "TMPDIR", "USERPROFILE", "TEMP", "PATHEXT", "ComSpec"];
+
 #[cfg(target_os = "windows")]
 const WINDOWS_BLOCKED_PATTERNS: &[&str] = &[
     "remove-item -recurse -force c:\\",
@@ -72,31 +93,13 @@ pub fn execute(
         .unwrap_or(60)
         .min(300);
 
-    let command_lower = command.to_lowercase();
+    let env = parse_env(input.get("env"))?;
 
-    // Security check
-    for pattern in BLOCKED_PATTERNS {
-        if command_lower.contains(&pattern.to_lowercase()) {
-            return Err(format!(
-                "Command blocked for safety: contains dangerous pattern '{}'",
-                pattern
-            ));
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    for pattern in WINDOWS_BLOCKED_PATTERNS {
-        if command_lower.contains(pattern) {
-            return Err(format!(
-                "Command blocked for safety: contains dangerous Windows pattern '{}'",
-                pattern
-            ));
-        }
-    }
+    check_command_safety(command)?;
 
     // Build command with OS-appropriate shell
     let mut cmd = build_shell_command(command);
-    let shell_name = shell_name();
+    set_controlled_env(&mut cmd, |name| std::env::var(name).ok(), project_path, &env);
 
     if let Some(dir) = cwd {
         cmd.current_dir(dir);
@@ -111,44 +114,147 @@ pub fn execute(
 
     let output = wait_with_timeout(child, Duration::from_secs(timeout_secs))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = cap_stream("stdout", &String::from_utf8_lossy(&output.stdout));
+    let stderr = cap_stream("stderr", &String::from_utf8_lossy(&output.stderr));
     let exit_code = output.status.code().unwrap_or(-1);
 
-    // Format output
-    let mut result = format!("[shell: {}]\n", shell_name);
-
-    if !stdout.is_empty() {
-        result.push_str(&stdout);
+    // Merged text in the original format, for consumers that read plain output
+    let mut combined = format!("[shell: {}]\n", shell_name());
+    combined.push_str(&stdout);
+    if !stderr.is_empty() {
+        combined.push_str("\n[stderr]\n");
+        combined.push_str(&stderr);
     }
+    combined.push_str(&format!("\n[exit code: {}]", exit_code));
 
-    if !stderr.is_empty() {
-        if !result.is_empty() {
-            result.push('\n');
+    let result = json!({
+        "shell": shell_name(),
+        "exit_code": exit_code,
+        "success": output.status.success(),
+        "stdout": stdout,
+        "stderr": stderr,
+        "combined": combined,
+    });
+
+    serde_json::to_string_pretty(&result)
+        .map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+/// Read the `env` input. Names must be non-empty without `=`; names and values must not
+/// contain null bytes, which would truncate them when handed to the OS.
+fn parse_env(env: Option<&serde_json::Value>) -> Result<HashMap<String, String>, String> {
+    let Some(env) = env.filter(|v| !v.is_null()) else {
+        return Ok(HashMap::new());
+    };
+    let env = env.as_object().ok_or("'env' must be an object of string values")?;
+
+    let mut vars = HashMap::new();
+    for (name, value) in env {
+        if name.is_empty() || name.contains('=') || name.contains('\0') {
+            return Err(format!("Invalid environment variable name '{}'", name.replace('\0', "\\0")));
         }
-        result.push_str("[stderr]\n");
-        result.push_str(&stderr);
+        let value = value
+            .as_str()
+            .ok_or_else(|| format!("Environment variable '{}' must be a string", name))?;
+        if value.contains('\0') {
+            return Err(format!("Environment variable '{}' contains a null byte", name));
+        }
+        vars.insert(name.clone(), value.to_string());
     }
+    Ok(vars)
+}
+
+/// Replace the inherited environment with `PASSTHROUGH_VARS`, a PATH of the minimal entries
+/// followed by the user's own, `PROJECT_PATH` and `env`. `source_env` reads the app's environment.
+pub(crate) fn set_controlled_env(
+    cmd: &mut Command,
+    source_env: impl Fn(&str) -> Option<String>,
+    project_path: Option<&str>,
+    env: &HashMap<String, String>,
+) {
+    cmd.env_clear();
 
-    result.push_str(&format!("\n[exit code: {}]", exit_code));
+    for name in PASSTHROUGH_VARS {
+        if let Some(value) = source_env(name) {
+            cmd.env(name, value);
+        }
+    }
 
-    // Truncate if too long
-    if result.len() > 50000 {
-        result = format!(
-            "{}...\n\n[Output truncated. Total length: {} chars]",
-            &result[..50000],
-            result.len()
+    #[cfg(target_os = "windows")]
+    {
+        // PowerShell can't start without SystemRoot
+        let system_root = source_env("SystemRoot").unwrap_or_else(|| "C:\\Windows".to_string());
+        let base_path = format!(
+            "{0}\\System32;{0};{0}\\System32\\WindowsPowerShell\\v1.0",
+            system_root
         );
+        cmd.env("PATH", extend_path(&base_path, source_env("PATH")));
+        cmd.env("SystemRoot", system_root);
+    }
+    #[cfg(not(target_os = "windows"))]
+    cmd.env("PATH", extend_path(MINIMAL_PATH, source_env("PATH")));
+
+    if let Some(project_path) = project_path {
+        cmd.env("PROJECT_PATH", project_path);
+    }
+    cmd.envs(env);
+}
+
+/// Append the entries of `user_path` that `base` doesn't already contain
+fn extend_path(base: &str, user_path: Option<String>) -> String {
+    let mut entries: Vec<std::path::PathBuf> = std::env::split_paths(base).collect();
+    for entry in user_path.iter().flat_map(std::env::split_paths) {
+        if !entry.as_os_str().is_empty() && !entries.contains(&entry) {
+            entries.push(entry);
+        }
     }
+    std::env::join_paths(entries)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| base.to_string())
+}
 
-    if result.is_empty() {
-        result = "[Command completed with no output]".to_string();
+/// Cap one output stream to `MAX_STREAM_CHARS`, noting how much was cut
+fn cap_stream(name: &str, text: &str) -> String {
+    let total = text.chars().count();
+    if total <= MAX_STREAM_CHARS {
+        return text.to_string();
     }
 
-    Ok(result)
+    let mut capped: String = text.chars().take(MAX_STREAM_CHARS).collect();
+    capped.push_str(&format!(
+        "\n[{} truncated: showing {} of {} chars]",
+        name, MAX_STREAM_CHARS, total
+    ));
+    capped
 }
 
-fn build_shell_command(command: &str) -> Command {
+/// Reject commands containing known-dangerous patterns
+pub(crate) fn check_command_safety(command: &str) -> Result<(), String> {
+    let command_lower = command.to_lowercase();
+
+    for pattern in BLOCKED_PATTERNS {
+        if command_lower.contains(&pattern.to_lowercase()) {
+            return Err(format!(
+                "Command blocked for safety: contains dangerous pattern '{}'",
+                pattern
+            ));
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    for pattern in WINDOWS_BLOCKED_PATTERNS {
+        if command_lower.contains(pattern) {
+            return Err(format!(
+                "Command blocked for safety: contains dangerous Windows pattern '{}'",
+                pattern
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn build_shell_command(command: &str) -> Command {
     #[cfg(target_os = "windows")]
     {
         let mut cmd = Command::new("powershell");
@@ -180,7 +286,7 @@ fn shell_name() -> &'static str {
     }
 }
 
-fn wait_with_timeout(
+pub(crate) fn wait_with_timeout(
     child: std::process::Child,
     timeout: Duration,
 ) -> Result<std::process::Output, String> {
@@ -208,3 +314,113 @@ fn wait_with_timeout(
         }
     }
 }
+
+pub struct BashTool;
+
+impl Tool for BashTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        // Commands can run for minutes; keep them off the async runtime so the agent's
+        // run timeout can still fire
+        let input = input.clone();
+        let project_path = project_path.map(str::to_string);
+        Box::pin(async move {
+            let result = tokio::task::spawn_blocking(move || execute(&input, project_path.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(format!("Command task failed: {}", e)));
+            crate::tools::text_result(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &str) -> serde_json::Value {
+        let output = execute(&json!({ "command": command }), None).unwrap();
+        serde_json::from_str(&output).unwrap()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_structured_output_separates_streams() {
+        let ok = run("echo hello");
+        assert_eq!(ok["exit_code"], 0);
+        assert_eq!(ok["success"], true);
+        assert_eq!(ok["stdout"], "hello\n");
+        assert_eq!(ok["stderr"], "");
+        assert_eq!(ok["shell"], "sh");
+        assert_eq!(ok["combined"], "[shell: sh]\nhello\n\n[exit code: 0]");
+
+        let failed = run("echo out; echo oops >&2; exit 3");
+        assert_eq!(failed["exit_code"], 3);
+        assert_eq!(failed["success"], false);
+        assert_eq!(failed["stdout"], "out\n");
+        assert_eq!(failed["stderr"], "oops\n");
+        let combined = failed["combined"].as_str().unwrap();
+        assert!(combined.contains("[stderr]\noops"));
+        assert!(combined.ends_with("[exit code: 3]"));
+
+        // Each stream is capped on its own
+        let long = run("head -c 20000 /dev/zero | tr '\\0' x; head -c 20000 /dev/zero | tr '\\0' y >&2");
+        let stdout = long["stdout"].as_str().unwrap();
+        let stderr = long["stderr"].as_str().unwrap();
+        assert!(stdout.starts_with(&"x".repeat(MAX_STREAM_CHARS)));
+        assert!(stdout.ends_with("[stdout truncated: showing 5000 of 20000 chars]"));
+        assert!(stderr.ends_with("[stderr truncated: showing 5000 of 20000 chars]"));
+    }
+
+    #[test]
+    fn test_env_value_with_null_byte_rejected() {
+        let err = execute(&json!({ "command": "echo hi", "env": { "NAME": "a\0b" } }), None).unwrap_err();
+        assert_eq!(err, "Environment variable 'NAME' contains a null byte");
+
+        let err = execute(&json!({ "command": "echo hi", "env": { "A=B": "x" } }), None).unwrap_err();
+        assert!(err.starts_with("Invalid environment variable name"), "{}", err);
+        let err = execute(&json!({ "command": "echo hi", "env": { "N": 1 } }), None).unwrap_err();
+        assert_eq!(err, "Environment variable 'N' must be a string");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_env_passed_with_only_baseline_parent_variables() {
+        let source: HashMap<&str, &str> = HashMap::from([
+            ("HOME", "/home/tester"),
+            ("LANG", "en_US.UTF-8"),
+            ("PATH", "/home/tester/.cargo/bin:/usr/bin"),
+            ("AWS_SECRET_ACCESS_KEY", "leaked"),
+        ]);
+        let env = HashMap::from([("GREETING".to_string(), "hello; rm -rf ~ $(whoami)".to_string())]);
+
+        let mut cmd = build_shell_command(
+            "printf '%s|%s|%s|%s|%s|%s' \"$GREETING\" \"$PROJECT_PATH\" \"$HOME\" \"$LANG\" \"$AWS_SECRET_ACCESS_KEY\" \"$PATH\"",
+        );
+        set_controlled_env(&mut cmd, |name| source.get(name).map(|v| v.to_string()), Some("/tmp"), &env);
+        let output = cmd.output().unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "hello; rm -rf ~ $(whoami)|/tmp|/home/tester|en_US.UTF-8||{}:/home/tester/.cargo/bin",
+                MINIMAL_PATH
+            )
+        );
+    }
+
+    #[test]
+    fn test_cap_stream() {
+        assert_eq!(cap_stream("stdout", "short"), "short");
+
+        let capped = cap_stream("stderr", &"é".repeat(MAX_STREAM_CHARS + 10));
+        assert!(capped.starts_with(&"é".repeat(MAX_STREAM_CHARS)));
+        assert!(capped.ends_with(&format!(
+            "[stderr truncated: showing {} of {} chars]",
+            MAX_STREAM_CHARS,
+            MAX_STREAM_CHARS + 10
+        )));
+    }
+}
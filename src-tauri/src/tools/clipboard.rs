@@ -0,0 +1,219 @@
+use crate::agent::ToolDefinition;
+use crate::tools::{Tool, ToolFuture};
+use serde_json::json;
+
+/// Maximum clipboard text returned by `clipboard_read`
+const MAX_READ_BYTES: usize = 50 * 1024;
+
+/// Where the clipboard tools read and write text
+pub trait ClipboardBackend {
+    fn write_text(&self, text: &str) -> Result<(), String>;
+
+    /// Current text, empty when the clipboard holds no text
+    fn read_text(&self) -> Result<String, String>;
+}
+
+/// The system clipboard, or an error on platforms without one
+pub struct SystemClipboard;
+
+impl ClipboardBackend for SystemClipboard {
+    fn write_text(&self, text: &str) -> Result<(), String> {
+        platform::write_text(text)
+    }
+
+    fn read_text(&self) -> Result<String, String> {
+        platform::read_text()
+    }
+}
+
+pub fn write_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "clipboard_write".to_string(),
+        description: "Copy text to the system clipboard, e.g. a drafted email or code snippet the user wants to paste elsewhere.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "text": {
+                    "type": "string",
+                    "description": "The text to place on the clipboard"
+                }
+            },
+            "required": ["text"]
+        }),
+    }
+}
+
+pub fn read_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "clipboard_read".to_string(),
+        description: "Read the current text content of the system clipboard (up to 50 KB).".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {}
+        }),
+    }
+}
+
+pub fn clipboard_write(input: &serde_json::Value, clipboard: &impl ClipboardBackend) -> Result<String, String> {
+    let text = input
+        .get("text")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'text' parameter")?;
+
+    clipboard.write_text(text)?;
+    Ok(format!("Copied {} characters to the clipboard", text.chars().count()))
+}
+
+pub fn clipboard_read(_input: &serde_json::Value, clipboard: &impl ClipboardBackend) -> Result<String, String> {
+    let text = clipboard.read_text()?;
+    if text.is_empty() {
+        return Ok("(clipboard is empty or does not contain text)".to_string());
+    }
+    Ok(cap_clipboard_text(text))
+}
+
+fn cap_clipboard_text(text: String) -> String {
+    if text.len() <= MAX_READ_BYTES {
+        return text;
+    }
+
+    let mut end = MAX_READ_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n[clipboard truncated: showing {} of {} bytes]",
+        &text[..end],
+        end,
+        text.len()
+    )
+}
+
+#[cfg(not(mobile))]
+use desktop as platform;
+#[cfg(mobile)]
+use unsupported as platform;
+
+#[cfg(not(mobile))]
+mod desktop {
+    use std::sync::Mutex;
+
+    // On Linux the clipboard is served by the process that set it, so the handle is kept open
+    static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+    fn with_clipboard<T>(
+        f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>,
+    ) -> Result<T, String> {
+        let mut guard = CLIPBOARD.lock().map_err(|_| "Clipboard lock poisoned".to_string())?;
+        if guard.is_none() {
+            *guard = Some(arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {}", e))?);
+        }
+        let clipboard = guard.as_mut().expect("clipboard initialized above");
+        f(clipboard).map_err(|e| format!("Clipboard error: {}", e))
+    }
+
+    pub fn write_text(text: &str) -> Result<(), String> {
+        with_clipboard(|clipboard| clipboard.set_text(text.to_string()))
+    }
+
+    pub fn read_text() -> Result<String, String> {
+        with_clipboard(|clipboard| match clipboard.get_text() {
+            Err(arboard::Error::ContentNotAvailable) => Ok(String::new()),
+            other => other,
+        })
+    }
+}
+
+#[cfg(any(mobile, test))]
+mod unsupported {
+    /// Error code returned where there is no system clipboard to use
+    pub const CLIPBOARD_UNSUPPORTED: &str = "clipboard_not_supported_on_this_platform";
+
+    pub fn write_text(_text: &str) -> Result<(), String> {
+        Err(CLIPBOARD_UNSUPPORTED.to_string())
+    }
+
+    pub fn read_text() -> Result<String, String> {
+        Err(CLIPBOARD_UNSUPPORTED.to_string())
+    }
+}
+
+pub struct ClipboardWriteTool<B: ClipboardBackend = SystemClipboard>(pub B);
+
+impl<B: ClipboardBackend + Sync> Tool for ClipboardWriteTool<B> {
+    fn definition(&self) -> ToolDefinition {
+        write_definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, _project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(clipboard_write(input, &self.0)) })
+    }
+}
+
+pub struct ClipboardReadTool<B: ClipboardBackend = SystemClipboard>(pub B);
+
+impl<B: ClipboardBackend + Sync> Tool for ClipboardReadTool<B> {
+    fn definition(&self) -> ToolDefinition {
+        read_definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, _project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(clipboard_read(input, &self.0)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory clipboard, so tests never touch the user's real one
+    #[derive(Clone, Default)]
+    struct MemoryClipboard(Arc<Mutex<String>>);
+
+    impl ClipboardBackend for MemoryClipboard {
+        fn write_text(&self, text: &str) -> Result<(), String> {
+            *self.0.lock().unwrap() = text.to_string();
+            Ok(())
+        }
+
+        fn read_text(&self) -> Result<String, String> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clipboard_round_trip() {
+        let clipboard = MemoryClipboard::default();
+        let write = ClipboardWriteTool(clipboard.clone());
+        let read = ClipboardReadTool(clipboard);
+
+        let empty = read.execute(&json!({}), None).await;
+        assert_eq!(empty.content.to_text(), "(clipboard is empty or does not contain text)");
+
+        let text = "Dear team,\n\nThe Q3 report is attached. — Ünïcødé ✓";
+        let written = write.execute(&json!({ "text": text }), None).await;
+        assert_eq!(written.content.to_text(), format!("Copied {} characters to the clipboard", text.chars().count()));
+        assert_eq!(read.execute(&json!({}), None).await.content.to_text(), text);
+    }
+
+    #[test]
+    fn test_unsupported_platform_error_code() {
+        use unsupported::CLIPBOARD_UNSUPPORTED;
+
+        assert_eq!(unsupported::write_text("hello").unwrap_err(), CLIPBOARD_UNSUPPORTED);
+        assert_eq!(unsupported::read_text().unwrap_err(), "clipboard_not_supported_on_this_platform");
+    }
+
+    #[test]
+    fn test_cap_clipboard_text() {
+        assert_eq!(cap_clipboard_text("short".to_string()), "short");
+
+        let capped = cap_clipboard_text("é".repeat(MAX_READ_BYTES));
+        assert!(capped.ends_with(&format!(
+            "[clipboard truncated: showing {} of {} bytes]",
+            MAX_READ_BYTES,
+            MAX_READ_BYTES * 2
+        )));
+    }
+}
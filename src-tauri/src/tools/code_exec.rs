@@ -0,0 +1,342 @@
+use crate::agent::ToolDefinition;
+use crate::tools::bash::{build_shell_command, check_command_safety, set_controlled_env, wait_with_timeout};
+use crate::tools::{Tool, ToolFuture};
+use serde_json::json;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Combined stdout + stderr limit, in bytes
+const MAX_OUTPUT_BYTES: usize = 10 * 1024;
+
+/// Interpreter calls that wipe the filesystem root or home directory without a shell
+const BLOCKED_CODE_PATTERNS: &[&str] = &[
+    "shutil.rmtree('/')",
+    "shutil.rmtree(\"/\")",
+    "shutil.rmtree(os.path.expanduser('~'))",
+    "shutil.rmtree(os.path.expanduser(\"~\"))",
+    "rmsync('/'",
+    "rmsync(\"/\"",
+    "rmsync(os.homedir()",
+    "fileutils.rm_rf('/')",
+    "fileutils.rm_rf(\"/\")",
+];
+
+pub fn definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "execute_code".to_string(),
+        description: "Execute a code snippet with a local interpreter and return its exit code, stdout and stderr. Supports python, node, bash and ruby. Output is capped at 10 KB. Snippets get the same restricted environment as the bash tool, not the app's own.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "language": {
+                    "type": "string",
+                    "enum": ["python", "node", "bash", "ruby"],
+                    "description": "Language of the snippet"
+                },
+                "code": {
+                    "type": "string",
+                    "description": "Source code to execute"
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "description": "Timeout in seconds (default: 30, max: 300)"
+                },
+                "stdin": {
+                    "type": "string",
+                    "description": "Text written to the program's standard input (optional)"
+                }
+            },
+            "required": ["language", "code"]
+        }),
+    }
+}
+
+pub fn execute(
+    input: &serde_json::Value,
+    project_path: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    let language = input
+        .get("language")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'language' parameter")?;
+
+    let code = input
+        .get("code")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'code' parameter")?;
+
+    let timeout_secs = input
+        .get("timeout_secs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(30)
+        .min(300);
+
+    let stdin = input.get("stdin").and_then(|v| v.as_str());
+
+    // Snippets in any language can shell out, so all of them get the bash tool's checks
+    check_code_safety(code)?;
+
+    let mut temp_file = None;
+    let mut cmd = match language {
+        "bash" => build_shell_command(code),
+        "python" | "node" | "ruby" => {
+            let (candidates, extension) = interpreter_candidates(language);
+
+            let interpreter = find_interpreter(candidates).ok_or_else(|| {
+                format!(
+                    "language_not_available: no {} interpreter found (tried {})",
+                    language,
+                    candidates.join(", ")
+                )
+            })?;
+
+            let path = std::env::temp_dir()
+                .join(format!("kuse-code-{}.{}", uuid::Uuid::new_v4(), extension));
+            std::fs::write(&path, code)
+                .map_err(|e| format!("Failed to write temp file: {}", e))?;
+
+            let mut cmd = Command::new(interpreter);
+            cmd.arg(&path);
+            temp_file = Some(TempFile(path));
+            cmd
+        }
+        other => {
+            return Err(format!(
+                "Unsupported language '{}'. Expected one of: python, node, bash, ruby",
+                other
+            ))
+        }
+    };
+
+    // Same environment as the bash tool, so snippets can't read the app's API keys
+    set_controlled_env(&mut cmd, |name| std::env::var(name).ok(), project_path, &Default::default());
+    if let Some(dir) = project_path {
+        cmd.current_dir(dir);
+    }
+
+    cmd.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()
+        .map_err(|e| format!("Failed to spawn {} process: {}", language, e))?;
+
+    // Write stdin on its own thread so a chatty child can't deadlock us
+    if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        let text = text.to_string();
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(text.as_bytes());
+        });
+    }
+
+    let output = wait_with_timeout(child, Duration::from_secs(timeout_secs));
+    drop(temp_file);
+    let output = output?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (stdout, stderr, truncated) = cap_output(&stdout, &stderr, MAX_OUTPUT_BYTES);
+
+    let mut result = json!({
+        "exit_code": output.status.code().unwrap_or(-1),
+        "stdout": stdout,
+        "stderr": stderr,
+    });
+    if truncated {
+        result["truncated"] = json!(true);
+    }
+
+    Ok(result)
+}
+
+/// Reject code containing a blocked shell command or a blocked interpreter call
+fn check_code_safety(code: &str) -> Result<(), String> {
+    check_command_safety(code)?;
+
+    let code_lower = code.to_lowercase();
+    match BLOCKED_CODE_PATTERNS.iter().find(|pattern| code_lower.contains(*pattern)) {
+        Some(pattern) => Err(format!(
+            "Code blocked for safety: contains dangerous pattern '{}'",
+            pattern
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Temp source file, removed when dropped
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Interpreter names to look for, and the source file extension, per language
+fn interpreter_candidates(language: &str) -> (&'static [&'static str], &'static str) {
+    match language {
+        "python" => (&["python3", "python"], "py"),
+        "node" => (&["node"], "js"),
+        _ => (&["ruby"], "rb"),
+    }
+}
+
+/// Return the first candidate executable found on PATH
+pub(crate) fn find_interpreter(candidates: &[&str]) -> Option<String> {
+    let locator = if cfg!(target_os = "windows") { "where" } else { "which" };
+
+    candidates.iter().find_map(|name| {
+        let output = Command::new(locator)
+            .arg(name)
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(|line| line.to_string())
+    })
+}
+
+/// Cap stdout + stderr to `limit` bytes total, giving stdout priority
+fn cap_output(stdout: &str, stderr: &str, limit: usize) -> (String, String, bool) {
+    let stdout_capped = truncate_at_char_boundary(stdout, limit);
+    let stderr_capped = truncate_at_char_boundary(stderr, limit - stdout_capped.len());
+    let truncated = stdout_capped.len() < stdout.len() || stderr_capped.len() < stderr.len();
+    (stdout_capped.to_string(), stderr_capped.to_string(), truncated)
+}
+
+fn truncate_at_char_boundary(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+pub struct CodeExecTool;
+
+impl Tool for CodeExecTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        // Snippets can run until the timeout; keep it off the async runtime
+        let input = input.clone();
+        let project_path = project_path.map(str::to_string);
+        Box::pin(async move {
+            let result = tokio::task::spawn_blocking(move || execute(&input, project_path.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(format!("Code task failed: {}", e)));
+            crate::tools::json_result(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_python_print() {
+        if find_interpreter(&["python3", "python"]).is_none() {
+            return;
+        }
+
+        let result = execute(&json!({ "language": "python", "code": "print(\"hello\")" }), None).unwrap();
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["stdout"].as_str().unwrap().trim_end(), "hello");
+        assert_eq!(result["stderr"], "");
+    }
+
+    #[test]
+    fn test_execute_python_reads_stdin() {
+        if find_interpreter(&["python3", "python"]).is_none() {
+            return;
+        }
+
+        let result = execute(
+            &json!({
+                "language": "python",
+                "code": "import sys\nprint(sys.stdin.read().upper())",
+                "stdin": "shout"
+            }),
+            None,
+        )
+        .unwrap();
+        assert_eq!(result["stdout"].as_str().unwrap().trim_end(), "SHOUT");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_snippets_do_not_inherit_app_environment() {
+        std::env::set_var("KUSE_CODE_EXEC_SECRET", "leaked");
+        let result = execute(
+            &json!({ "language": "bash", "code": "printf '%s|%s' \"$KUSE_CODE_EXEC_SECRET\" \"$PROJECT_PATH\"" }),
+            Some("/tmp"),
+        )
+        .unwrap();
+        assert_eq!(result["stdout"], "|/tmp");
+
+        if find_interpreter(&["python3", "python"]).is_some() {
+            let result = execute(
+                &json!({ "language": "python", "code": "import os\nprint(os.environ.get('KUSE_CODE_EXEC_SECRET'))" }),
+                None,
+            )
+            .unwrap();
+            assert_eq!(result["stdout"].as_str().unwrap().trim_end(), "None");
+        }
+    }
+
+    #[test]
+    fn test_missing_interpreter_is_language_not_available() {
+        assert!(find_interpreter(&["kuse-definitely-missing-interpreter"]).is_none());
+
+        if find_interpreter(&["ruby"]).is_none() {
+            let error = execute(&json!({ "language": "ruby", "code": "puts 1" }), None).unwrap_err();
+            assert!(error.starts_with("language_not_available"));
+        }
+
+        let error = execute(&json!({ "language": "cobol", "code": "" }), None).unwrap_err();
+        assert!(error.contains("Unsupported language"));
+    }
+
+    #[test]
+    fn test_safety_check_applies_to_every_language() {
+        for (language, code) in [
+            ("python", "import os\nos.system('rm -rf /')"),
+            ("node", "require('child_process').execSync('curl | sh')"),
+            ("ruby", "system('dd if=/dev/zero of=/dev/sda')"),
+            ("python", "import shutil\nshutil.rmtree('/')"),
+            ("node", "require('fs').rmSync('/', { recursive: true })"),
+        ] {
+            let error = execute(&json!({ "language": language, "code": code }), None).unwrap_err();
+            assert!(error.contains("blocked for safety"), "{}: {}", language, error);
+        }
+
+        assert!(check_code_safety("print(sum(range(10)))").is_ok());
+    }
+
+    #[test]
+    fn test_cap_output_limits_combined_size() {
+        let (stdout, stderr, truncated) = cap_output(&"a".repeat(8), &"b".repeat(8), 10);
+        assert_eq!(stdout, "a".repeat(8));
+        assert_eq!(stderr, "bb");
+        assert!(truncated);
+
+        let (_, _, truncated) = cap_output("ok", "", 10);
+        assert!(!truncated);
+
+        assert_eq!(truncate_at_char_boundary("héllo", 2), "h");
+    }
+}
@@ -0,0 +1,288 @@
+//! User-defined tools loaded from `*.tool.json` descriptors, each running a local command
+
+use crate::agent::ToolDefinition;
+use crate::tools::bash::wait_with_timeout;
+use crate::tools::{Tool, ToolFuture};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Combined stdout + stderr limit, in bytes
+const MAX_OUTPUT_BYTES: usize = 8 * 1024;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+const DESCRIPTOR_SUFFIX: &str = ".tool.json";
+
+/// Where custom tool descriptors are read from unless configured otherwise
+pub fn default_custom_tools_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("kuse-cowork").join("tools"))
+}
+
+/// Contents of a `*.tool.json` file: a tool definition plus how to run it
+#[derive(Debug, Deserialize)]
+struct CustomToolDescriptor {
+    #[serde(flatten)]
+    definition: ToolDefinition,
+    executor: CustomToolExecutor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum CustomToolExecutor {
+    Shell { command: String, args_template: String },
+}
+
+#[derive(Clone)]
+pub struct CustomTool {
+    definition: ToolDefinition,
+    executor: CustomToolExecutor,
+    /// Directory of the descriptor; relative commands are resolved against it
+    base_dir: PathBuf,
+}
+
+/// Load every `*.tool.json` in `dir`, in file name order.
+/// Descriptors that fail to parse or have an invalid name are skipped and reported in the
+/// returned errors.
+pub fn load_custom_tools(dir: &Path) -> (Vec<CustomTool>, Vec<String>) {
+    let mut tools = Vec::new();
+    let mut errors = Vec::new();
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (tools, errors);
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().ends_with(DESCRIPTOR_SUFFIX))
+                .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<CustomToolDescriptor>(&text).map_err(|e| e.to_string()))
+            .and_then(|descriptor| validate_tool_name(&descriptor.definition.name).map(|_| descriptor));
+        match parsed {
+            Ok(descriptor) => tools.push(CustomTool {
+                definition: descriptor.definition,
+                executor: descriptor.executor,
+                base_dir: dir.to_path_buf(),
+            }),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    (tools, errors)
+}
+
+/// Reject names that would be mistaken for MCP tools (`mcp_*` function names or the
+/// `server:tool` form) when the agent's tool calls are dispatched
+fn validate_tool_name(name: &str) -> Result<(), String> {
+    if name.starts_with("mcp_") || name.contains(':') {
+        return Err(format!(
+            "Invalid tool name '{}': names may not start with 'mcp_' or contain ':'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Substitute `{{input.field}}` placeholders with values from `input`.
+/// Strings are inserted as-is, other values as JSON; missing fields become empty.
+fn render_template(template: &str, input: &serde_json::Value) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{input.") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let field = rest[start + "{{input.".len()..start + len].trim();
+        match input.get(field) {
+            Some(serde_json::Value::String(s)) => rendered.push_str(s),
+            Some(serde_json::Value::Null) | None => {}
+            Some(other) => rendered.push_str(&other.to_string()),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Split the template on whitespace before rendering, so each value stays a single
+/// argument and is never interpreted by a shell. Arguments that render empty are dropped.
+fn render_args(template: &str, input: &serde_json::Value) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|token| render_template(token, input))
+        .filter(|arg| !arg.is_empty())
+        .collect()
+}
+
+impl CustomTool {
+    fn run(&self, input: &serde_json::Value, project_path: Option<&str>) -> Result<String, String> {
+        let CustomToolExecutor::Shell { command, args_template } = &self.executor;
+
+        let program = if Path::new(command).is_relative() && self.base_dir.join(command).exists() {
+            self.base_dir.join(command)
+        } else {
+            PathBuf::from(command)
+        };
+
+        let mut cmd = Command::new(&program);
+        cmd.args(render_args(args_template, input));
+        if let Some(dir) = crate::tools::path_utils::parse_project_roots(project_path).first() {
+            cmd.current_dir(dir);
+        }
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+        let output = wait_with_timeout(child, COMMAND_TIMEOUT)?;
+
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            text.push_str("\n[stderr]\n");
+            text.push_str(&stderr);
+        }
+        let text = cap_output(text);
+
+        if output.status.success() {
+            Ok(text)
+        } else {
+            Err(format!(
+                "{} exited with code {}\n{}",
+                self.definition.name,
+                output.status.code().unwrap_or(-1),
+                text
+            ))
+        }
+    }
+}
+
+fn cap_output(text: String) -> String {
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text;
+    }
+    let mut end = MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[output truncated: showing {} of {} bytes]", &text[..end], end, text.len())
+}
+
+impl Tool for CustomTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        // The command can run for a minute; keep it off the async runtime
+        let tool = self.clone();
+        let input = input.clone();
+        let project_path = project_path.map(str::to_string);
+        Box::pin(async move {
+            let result = tokio::task::spawn_blocking(move || tool.run(&input, project_path.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(format!("Command task failed: {}", e)));
+            crate::tools::text_result(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolRegistry;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn test_render_args() {
+        let input = json!({ "path": "my notes.txt; rm -rf ~", "limit": 5 });
+        assert_eq!(
+            render_args("--file={{input.path}} -n {{input.limit}} {{input.missing}}", &input),
+            vec!["--file=my notes.txt; rm -rf ~", "-n", "5"]
+        );
+    }
+
+    #[test]
+    fn test_custom_tool_names_cannot_look_like_mcp_tools() {
+        let dir = std::env::temp_dir().join(format!("kuse-custom-tools-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, name) in [("a", "mcp_github_list_issues"), ("b", "github:list_issues"), ("c", "list_issues")] {
+            let descriptor = json!({
+                "name": name,
+                "description": "List issues",
+                "input_schema": { "type": "object", "properties": {} },
+                "executor": { "type": "shell", "command": "true", "args_template": "" }
+            });
+            fs::write(dir.join(format!("{}.tool.json", file)), descriptor.to_string()).unwrap();
+        }
+
+        let (tools, errors) = load_custom_tools(&dir);
+        let names: Vec<&str> = tools.iter().map(|t| t.definition.name.as_str()).collect();
+        assert_eq!(names, vec!["list_issues"]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("Invalid tool name 'mcp_github_list_issues'"), "{:?}", errors);
+        assert!(errors[1].contains("Invalid tool name 'github:list_issues'"), "{:?}", errors);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_custom_tool_loaded_and_executed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("kuse-custom-tools-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("count_lines.sh");
+        fs::write(&script, "#!/bin/sh\nwc -l < \"$1\" | tr -d ' '\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(
+            dir.join("count_lines.tool.json"),
+            json!({
+                "name": "count_lines",
+                "description": "Count the lines in a file",
+                "input_schema": {
+                    "type": "object",
+                    "properties": { "path": { "type": "string" } },
+                    "required": ["path"]
+                },
+                "executor": { "type": "shell", "command": "count_lines.sh", "args_template": "{{input.path}}" }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        fs::write(dir.join("broken.tool.json"), "{ not json").unwrap();
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "one\ntwo\nthree\n").unwrap();
+
+        let mut registry = ToolRegistry::new();
+        let errors = registry.register_custom_tools(&dir);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("broken.tool.json"), "{:?}", errors);
+
+        // list_available_tools returns the registry's definitions
+        let names: Vec<String> = registry.definitions().into_iter().map(|d| d.name).collect();
+        assert!(names.contains(&"count_lines".to_string()), "{:?}", names);
+
+        let result = registry
+            .execute("count_lines", &json!({ "path": notes.to_string_lossy() }), None, false)
+            .await
+            .unwrap();
+        assert_eq!(result.content.to_text().trim(), "3");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::agent::{ToolDefinition, ToolResult, ToolUse};
+use crate::tools::bash::check_command_safety;
+use crate::tools::{Tool, ToolFuture};
 use bollard::container::{
     Config, CreateContainerOptions, RemoveContainerOptions, StartContainerOptions,
     WaitContainerOptions,
@@ -8,6 +10,16 @@ use bollard::models::HostConfig;
 use bollard::Docker;
 use futures::StreamExt;
 use serde_json::json;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+/// Output returned by `docker_container_logs` and `docker_exec` is capped at this many bytes
+const MAX_OUTPUT_BYTES: usize = 50 * 1024;
+const DEFAULT_LOG_TAIL: u32 = 100;
+const DEFAULT_FOLLOW_SECS: u64 = 10;
+const DEFAULT_EXEC_TIMEOUT_SECS: u64 = 30;
 
 /// Get Docker tool definitions
 pub fn get_docker_tools() -> Vec<ToolDefinition> {
@@ -62,9 +74,86 @@ pub fn get_docker_tools() -> Vec<ToolDefinition> {
                 "properties": {}
             }),
         },
+        ToolDefinition {
+            name: "docker_container_logs".to_string(),
+            description: "Read the logs of a container. With follow, streams new log lines for a few seconds. Returns {lines, truncated} as JSON; output is capped at 50 KB.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "container": {
+                        "type": "string",
+                        "description": "Container name or ID"
+                    },
+                    "tail": {
+                        "type": "integer",
+                        "description": "Number of lines to show from the end of the logs (default: 100)"
+                    },
+                    "follow": {
+                        "type": "boolean",
+                        "description": "Keep streaming new lines until timeout_secs elapses (default: false)"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "How long to follow the logs, in seconds (default: 10)"
+                    }
+                },
+                "required": ["container"]
+            }),
+        },
+        ToolDefinition {
+            name: "docker_exec".to_string(),
+            description: "Run a one-off shell command inside a running container".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "container": {
+                        "type": "string",
+                        "description": "Container name or ID"
+                    },
+                    "command": {
+                        "type": "string",
+                        "description": "Command to run with 'sh -c' inside the container"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Kill the command after this many seconds (default: 30)"
+                    }
+                },
+                "required": ["container", "command"]
+            }),
+        },
     ]
 }
 
+/// A single Docker tool (`docker_run`, `docker_list`, `docker_images`, `docker_container_logs`, `docker_exec`)
+pub struct DockerTool {
+    definition: ToolDefinition,
+}
+
+impl DockerTool {
+    pub fn new(definition: ToolDefinition) -> Self {
+        Self { definition }
+    }
+}
+
+impl Tool for DockerTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let tool_use = ToolUse {
+                id: String::new(),
+                name: self.definition.name.clone(),
+                input: input.clone(),
+                thought_signature: None,
+            };
+            execute_docker_tool(&tool_use, &project_path.map(|p| p.to_string()))
+        })
+    }
+}
+
 /// Execute a Docker tool (sync wrapper for non-async contexts)
 pub fn execute_docker_tool(tool_use: &ToolUse, project_path: &Option<String>) -> ToolResult {
     // Use a separate thread to avoid blocking the async runtime
@@ -93,6 +182,8 @@ async fn execute_docker_tool_inner(tool_use: &ToolUse, project_path: &Option<Str
         "docker_run" => docker_run(&docker, tool_use, project_path).await,
         "docker_list" => docker_list(&docker, tool_use).await,
         "docker_images" => docker_images(&docker, tool_use).await,
+        "docker_container_logs" => docker_container_logs(tool_use).await,
+        "docker_exec" => docker_exec(tool_use).await,
         _ => ToolResult::error(tool_use.id.clone(), format!("Unknown docker tool: {}", tool_use.name)),
     }
 }
@@ -289,6 +380,192 @@ async fn docker_images(docker: &Docker, tool_use: &ToolUse) -> ToolResult {
     }
 }
 
+/// Lines collected from a command, stopping once `MAX_OUTPUT_BYTES` is reached
+#[derive(Debug, Default)]
+struct CappedLines {
+    lines: Vec<String>,
+    bytes: usize,
+    truncated: bool,
+}
+
+impl CappedLines {
+    fn push(&mut self, line: String) {
+        if self.truncated || self.bytes + line.len() + 1 > MAX_OUTPUT_BYTES {
+            self.truncated = true;
+            return;
+        }
+        self.bytes += line.len() + 1;
+        self.lines.push(line);
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "lines": self.lines, "truncated": self.truncated })
+    }
+}
+
+fn container_arg(tool_use: &ToolUse) -> Result<&str, String> {
+    let container = tool_use
+        .input
+        .get("container")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .ok_or("Missing 'container' parameter")?;
+    if container.is_empty() || container.starts_with('-') {
+        return Err(format!("Invalid container name: '{}'", container));
+    }
+    Ok(container)
+}
+
+fn docker_logs_args(container: &str, tail: u32, follow: bool) -> Vec<String> {
+    let mut args = vec!["logs".to_string(), "--tail".to_string(), tail.to_string()];
+    if follow {
+        args.push("--follow".to_string());
+    }
+    args.push(container.to_string());
+    args
+}
+
+fn docker_exec_args(container: &str, command: &str) -> Vec<String> {
+    vec![
+        "exec".to_string(),
+        container.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        command.to_string(),
+    ]
+}
+
+fn docker_command(args: &[String]) -> Command {
+    let mut command = Command::new("docker");
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+/// Read stdout and stderr lines from `command` until it exits or `timeout` elapses,
+/// then kill it. Returns the collected lines and whether the timeout was hit.
+async fn collect_lines(mut command: Command, timeout: Duration) -> Result<(CappedLines, bool), String> {
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+    let mut stdout = BufReader::new(child.stdout.take().ok_or("Failed to capture stdout")?).lines();
+    let mut stderr = BufReader::new(child.stderr.take().ok_or("Failed to capture stderr")?).lines();
+
+    let mut output = CappedLines::default();
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+    let mut timed_out = false;
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    while stdout_open || stderr_open {
+        tokio::select! {
+            _ = &mut deadline => {
+                timed_out = true;
+                break;
+            }
+            line = stdout.next_line(), if stdout_open => match line {
+                Ok(Some(line)) => output.push(line),
+                _ => stdout_open = false,
+            },
+            line = stderr.next_line(), if stderr_open => match line {
+                Ok(Some(line)) => output.push(line),
+                _ => stderr_open = false,
+            },
+        }
+    }
+
+    if timed_out {
+        let _ = child.kill().await;
+    }
+    Ok((output, timed_out))
+}
+
+async fn docker_container_logs(tool_use: &ToolUse) -> ToolResult {
+    let container = match container_arg(tool_use) {
+        Ok(c) => c,
+        Err(e) => return ToolResult::error(tool_use.id.clone(), e),
+    };
+    let tail = tool_use.input.get("tail")
+        .and_then(|v| v.as_u64())
+        .map(|t| t.min(u32::MAX as u64) as u32)
+        .unwrap_or(DEFAULT_LOG_TAIL);
+    let follow = tool_use.input.get("follow")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let timeout_secs = tool_use.input.get("timeout_secs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_FOLLOW_SECS);
+
+    let command = docker_command(&docker_logs_args(container, tail, follow));
+    // Without follow the command exits on its own; the timeout only guards a stuck daemon
+    let timeout = if follow {
+        Duration::from_secs(timeout_secs)
+    } else {
+        Duration::from_secs(timeout_secs.max(DEFAULT_EXEC_TIMEOUT_SECS))
+    };
+
+    match collect_lines(command, timeout).await {
+        Ok((output, _)) => ToolResult::json(tool_use.id.clone(), output.to_json()),
+        Err(e) => ToolResult::error(tool_use.id.clone(), e),
+    }
+}
+
+async fn docker_exec(tool_use: &ToolUse) -> ToolResult {
+    let container = match container_arg(tool_use) {
+        Ok(c) => c,
+        Err(e) => return ToolResult::error(tool_use.id.clone(), e),
+    };
+    let command = match tool_use.input.get("command").and_then(|v| v.as_str()) {
+        Some(c) => c,
+        None => return ToolResult::error(tool_use.id.clone(), "Missing 'command' parameter".to_string()),
+    };
+    if let Err(e) = check_command_safety(command) {
+        return ToolResult::error(tool_use.id.clone(), e);
+    }
+    let timeout_secs = tool_use.input.get("timeout_secs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_EXEC_TIMEOUT_SECS);
+
+    let mut exec = docker_command(&docker_exec_args(container, command));
+    let output = match tokio::time::timeout(Duration::from_secs(timeout_secs), exec.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return ToolResult::error(tool_use.id.clone(), format!("Failed to run docker: {}", e)),
+        Err(_) => {
+            return ToolResult::error(
+                tool_use.id.clone(),
+                format!("Command timed out after {} seconds", timeout_secs),
+            )
+        }
+    };
+
+    let mut lines = CappedLines::default();
+    for text in [&output.stdout, &output.stderr] {
+        for line in String::from_utf8_lossy(text).lines() {
+            lines.push(line.to_string());
+        }
+    }
+    let mut text = lines.lines.join("\n");
+    if text.is_empty() {
+        text = "(no output)".to_string();
+    }
+    if lines.truncated {
+        text.push_str("\n[output truncated at 50 KB]");
+    }
+
+    let exit_code = output.status.code().unwrap_or(-1);
+    let result = format!("Exit code: {}\n\n{}", exit_code, text);
+    if output.status.success() {
+        ToolResult::success(tool_use.id.clone(), result)
+    } else {
+        ToolResult::error(tool_use.id.clone(), result)
+    }
+}
+
 async fn pull_image_if_needed(docker: &Docker, image: &str) -> Result<(), String> {
     // Check if image exists
     if docker.inspect_image(image).await.is_ok() {
@@ -319,3 +596,91 @@ async fn pull_image_if_needed(docker: &Docker, image: &str) -> Result<(), String
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_logs_and_exec_args() {
+        assert_eq!(docker_logs_args("web", 50, false), vec!["logs", "--tail", "50", "web"]);
+        assert_eq!(
+            docker_logs_args("web", 10, true),
+            vec!["logs", "--tail", "10", "--follow", "web"]
+        );
+        assert_eq!(
+            docker_exec_args("db", "psql -c 'select 1'"),
+            vec!["exec", "db", "sh", "-c", "psql -c 'select 1'"]
+        );
+    }
+
+    #[test]
+    fn test_container_arg_rejects_flags() {
+        let tool_use = |container: &str| ToolUse {
+            id: String::new(),
+            name: "docker_exec".to_string(),
+            input: json!({ "container": container, "command": "ls" }),
+            thought_signature: None,
+        };
+        assert_eq!(container_arg(&tool_use(" web ")).unwrap(), "web");
+        assert!(container_arg(&tool_use("--privileged")).is_err());
+        assert!(container_arg(&tool_use("")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_docker_exec_blocks_dangerous_commands() {
+        let result = docker_exec(&ToolUse {
+            id: "exec".to_string(),
+            name: "docker_exec".to_string(),
+            input: json!({ "container": "web", "command": "rm -rf /" }),
+            thought_signature: None,
+        })
+        .await;
+        assert_eq!(result.is_error, Some(true));
+        assert!(result.content.to_text().contains("blocked for safety"));
+    }
+
+    #[test]
+    fn test_capped_lines_truncates() {
+        let mut lines = CappedLines::default();
+        let line = "x".repeat(1023);
+        for _ in 0..60 {
+            lines.push(line.clone());
+        }
+        assert_eq!(lines.lines.len(), 50);
+        assert!(lines.truncated);
+        assert_eq!(lines.to_json()["truncated"], json!(true));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_collect_lines_stops_at_timeout() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "echo started >&2; while true; do echo tick; sleep 0.1; done"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let started = std::time::Instant::now();
+        let (output, timed_out) = collect_lines(command, Duration::from_millis(500)).await.unwrap();
+        assert!(timed_out);
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(output.lines.contains(&"started".to_string()));
+        assert!(output.lines.iter().any(|l| l == "tick"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_collect_lines_returns_when_command_exits() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "echo one; echo two"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let (output, timed_out) = collect_lines(command, Duration::from_secs(10)).await.unwrap();
+        assert!(!timed_out);
+        assert_eq!(output.lines, vec!["one", "two"]);
+        assert!(!output.truncated);
+    }
+}
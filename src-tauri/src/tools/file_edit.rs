@@ -1,5 +1,6 @@
 use crate::agent::ToolDefinition;
-use crate::tools::path_utils;
+use crate::tools::{path_utils, Tool, ToolFuture};
+use serde::Deserialize;
 use serde_json::json;
 use std::fs;
 use std::path::Path;
@@ -7,7 +8,7 @@ use std::path::Path;
 pub fn definition() -> ToolDefinition {
     ToolDefinition {
         name: "edit_file".to_string(),
-        description: "Make targeted edits to a file by replacing specific text. The old_string must match exactly (including whitespace and indentation). Use this for small, precise changes.".to_string(),
+        description: "Make targeted edits to a file by replacing specific text. The old_string must match exactly (including whitespace and indentation). Use this for small, precise changes. To make several changes to one file at once, pass them as 'edits' instead.".to_string(),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -26,9 +27,21 @@ pub fn definition() -> ToolDefinition {
                 "replace_all": {
                     "type": "boolean",
                     "description": "If true, replace all occurrences. Default is false (replace first only)"
+                },
+                "edits": {
+                    "type": "array",
+                    "description": "Several replacements matched against the original file and applied together, instead of old_string/new_string. If any old_string is missing or two edits overlap, nothing is changed.",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old_string": { "type": "string" },
+                            "new_string": { "type": "string" }
+                        },
+                        "required": ["old_string", "new_string"]
+                    }
                 }
             },
-            "required": ["path", "old_string", "new_string"]
+            "required": ["path"]
         }),
     }
 }
@@ -42,6 +55,12 @@ pub fn execute(
         .and_then(|v| v.as_str())
         .ok_or("Missing 'path' parameter")?;
 
+    if let Some(edits) = input.get("edits") {
+        let hunks: Vec<EditHunk> = serde_json::from_value(edits.clone())
+            .map_err(|e| format!("Invalid 'edits' parameter: {}", e))?;
+        return execute_hunks(path_str, &hunks, project_path);
+    }
+
     let old_string = input
         .get("old_string")
         .and_then(|v| v.as_str())
@@ -104,7 +123,180 @@ pub fn execute(
     ))
 }
 
+/// One replacement in a multi-edit call
+#[derive(Debug, Deserialize)]
+struct EditHunk {
+    old_string: String,
+    new_string: String,
+}
+
+fn execute_hunks(path_str: &str, hunks: &[EditHunk], project_path: Option<&str>) -> Result<String, String> {
+    if hunks.is_empty() {
+        return Err("'edits' must contain at least one edit".to_string());
+    }
+
+    let path = resolve_path(path_str, project_path)?;
+    if !path.exists() {
+        return Err(format!("File not found: {}", path_str));
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let new_content = apply_hunks(&content, hunks)
+        .map_err(|e| format!("{} in {}. No changes were made.", e, path_str))?;
+
+    write_atomically(&path, &new_content)?;
+
+    Ok(format!(
+        "Successfully applied {} edit(s) to {}",
+        hunks.len(),
+        path.display()
+    ))
+}
+
+/// Apply every hunk at its first occurrence in the original `content`
+fn apply_hunks(content: &str, hunks: &[EditHunk]) -> Result<String, String> {
+    let mut missing = Vec::new();
+    let mut spans = Vec::new();
+    for (i, hunk) in hunks.iter().enumerate() {
+        match content.find(&hunk.old_string).filter(|_| !hunk.old_string.is_empty()) {
+            Some(start) => spans.push((start, start + hunk.old_string.len(), i)),
+            None => missing.push(format!("#{} ({:?})", i + 1, preview(&hunk.old_string))),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Could not find the text for edit(s) {}", missing.join(", ")));
+    }
+
+    spans.sort();
+    for pair in spans.windows(2) {
+        let ((_, end, a), (start, _, b)) = (pair[0], pair[1]);
+        if start < end {
+            return Err(format!("Edits #{} and #{} overlap", a.min(b) + 1, a.max(b) + 1));
+        }
+    }
+
+    let mut result = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for (start, end, i) in spans {
+        result.push_str(&content[cursor..start]);
+        result.push_str(&hunks[i].new_string);
+        cursor = end;
+    }
+    result.push_str(&content[cursor..]);
+    Ok(result)
+}
+
+fn preview(text: &str) -> String {
+    if text.chars().count() > 40 {
+        format!("{}...", text.chars().take(40).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Write to a temporary file beside `path`, then rename it over the original
+fn write_atomically(path: &Path, content: &str) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+    fs::write(&tmp_path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&tmp_path, metadata.permissions());
+    }
+    fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        format!("Failed to write file: {}", e)
+    })
+}
+
 fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path::PathBuf, String> {
     let path = Path::new(path_str);
     path_utils::resolve_path_for_write(path, project_path)
 }
+
+pub struct EditFileTool;
+
+impl Tool for EditFileTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(content: &str) -> (std::path::PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("kuse-edit-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, content).unwrap();
+        (path, dir.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn test_multiple_edits_applied_against_original() {
+        let (path, project) = temp_file("name = \"app\"\nversion = \"1.0\"\ndebug = false\nport = 80\n");
+
+        // Listed out of file order; the first edit also grows the line before the others
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "edits": [
+                { "old_string": "port = 80", "new_string": "port = 8080" },
+                { "old_string": "name = \"app\"", "new_string": "name = \"kuse-app\"\nedition = \"2021\"" },
+                { "old_string": "debug = false", "new_string": "debug = true" }
+            ]
+        });
+
+        let output = execute(&input, Some(&project)).unwrap();
+        assert!(output.contains("applied 3 edit(s)"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "name = \"kuse-app\"\nedition = \"2021\"\nversion = \"1.0\"\ndebug = true\nport = 8080\n"
+        );
+
+        let _ = fs::remove_dir_all(&project);
+    }
+
+    #[test]
+    fn test_missing_edit_leaves_file_unchanged() {
+        let original = "alpha\nbeta\ngamma\n";
+        let (path, project) = temp_file(original);
+
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "edits": [
+                { "old_string": "alpha", "new_string": "ALPHA" },
+                { "old_string": "delta", "new_string": "DELTA" },
+                { "old_string": "gamma", "new_string": "GAMMA" }
+            ]
+        });
+
+        let err = execute(&input, Some(&project)).unwrap_err();
+        assert!(err.contains("Could not find the text for edit(s) #2 (\"delta\")"), "{}", err);
+        assert!(err.contains("No changes were made"), "{}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        let _ = fs::remove_dir_all(&project);
+    }
+
+    #[test]
+    fn test_overlapping_edits_rejected() {
+        let hunks = vec![
+            EditHunk { old_string: "beta gamma".to_string(), new_string: "x".to_string() },
+            EditHunk { old_string: "alpha beta".to_string(), new_string: "y".to_string() },
+        ];
+        assert_eq!(
+            apply_hunks("alpha beta gamma", &hunks).unwrap_err(),
+            "Edits #1 and #2 overlap"
+        );
+    }
+}
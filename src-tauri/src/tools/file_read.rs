@@ -1,5 +1,5 @@
 use crate::agent::ToolDefinition;
-use crate::tools::path_utils;
+use crate::tools::{path_utils, Tool, ToolFuture};
 use serde_json::json;
 use std::fs;
 
@@ -31,6 +31,7 @@ pub fn definition() -> ToolDefinition {
 pub fn execute(
     input: &serde_json::Value,
     project_path: Option<&str>,
+    allow_outside_workspace: bool,
 ) -> Result<String, String> {
     let path_str = input
         .get("path")
@@ -48,7 +49,7 @@ pub fn execute(
         .map(|v| v as usize);
 
     // Resolve path
-    let path = resolve_path(path_str, project_path)?;
+    let path = resolve_path(path_str, project_path, allow_outside_workspace)?;
 
     // Check if file exists
     if !path.exists() {
@@ -84,7 +85,11 @@ pub fn execute(
     Ok(result.join("\n"))
 }
 
-fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path::PathBuf, String> {
+fn resolve_path(
+    path_str: &str,
+    project_path: Option<&str>,
+    allow_outside_workspace: bool,
+) -> Result<std::path::PathBuf, String> {
     // Handle home directory expansion
     let expanded_path = if let Some(stripped) = path_str.strip_prefix("~/") {
         match dirs::home_dir() {
@@ -102,7 +107,30 @@ fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path:
 
     let path = expanded_path.as_path();
 
-    path_utils::resolve_path(path, project_path)
+    // Explicit paths to files outside the workspace may be read if the user allows it;
+    // writes stay confined
+    path_utils::resolve_path_for_read(path, project_path, allow_outside_workspace)
+}
+
+pub struct ReadFileTool;
+
+impl Tool for ReadFileTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        self.execute_with_outside_reads(input, project_path, false)
+    }
+
+    fn execute_with_outside_reads<'a>(
+        &'a self,
+        input: &'a serde_json::Value,
+        project_path: Option<&'a str>,
+        allow_outside_workspace: bool,
+    ) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path, allow_outside_workspace)) })
+    }
 }
 
 #[cfg(test)]
@@ -112,7 +140,7 @@ mod tests {
     #[test]
     fn test_resolve_path_home_expansion() {
         // Test home directory expansion
-        let result = resolve_path("~/.kuse-cowork/test", None);
+        let result = resolve_path("~/.kuse-cowork/test", None, false);
         assert!(result.is_ok());
 
         let path = result.unwrap();
@@ -123,7 +151,7 @@ mod tests {
 
     #[test]
     fn test_resolve_path_home_only() {
-        let result = resolve_path("~", None);
+        let result = resolve_path("~", None, false);
         assert!(result.is_ok());
 
         let path = result.unwrap();
@@ -132,7 +160,7 @@ mod tests {
 
     #[test]
     fn test_resolve_path_absolute() {
-        let result = resolve_path("/tmp/test", None);
+        let result = resolve_path("/tmp/test", None, false);
         assert!(result.is_ok());
 
         let path = result.unwrap();
@@ -0,0 +1,199 @@
+use crate::agent::ToolDefinition;
+use crate::tools::{path_utils, Tool, ToolFuture};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use serde_json::json;
+use std::path::Path;
+
+const DEFAULT_MAX_DEPTH: usize = 4;
+const MAX_LINES: usize = 200;
+
+pub fn definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "file_tree".to_string(),
+        description: "Show a compact, indented tree of a project with file sizes. Respects .gitignore and skips hidden files; useful for an overview at the start of a task.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "root_path": {
+                    "type": "string",
+                    "description": "Directory to show (defaults to project root)"
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "Maximum depth below the root (default: 4)"
+                },
+                "ignore_patterns": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Extra glob patterns to leave out, e.g. ['*.log', 'dist/']"
+                }
+            },
+            "required": []
+        }),
+    }
+}
+
+pub fn execute(
+    input: &serde_json::Value,
+    project_path: Option<&str>,
+) -> Result<String, String> {
+    let path_str = input
+        .get("root_path")
+        .and_then(|v| v.as_str())
+        .or(project_path)
+        .unwrap_or(".");
+
+    let max_depth = input
+        .get("max_depth")
+        .and_then(|v| v.as_u64())
+        .map(|d| d as usize)
+        .unwrap_or(DEFAULT_MAX_DEPTH);
+
+    let ignore_patterns: Vec<&str> = input
+        .get("ignore_patterns")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    let root = path_utils::resolve_path_for_read(Path::new(path_str), project_path, false)?;
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", path_str));
+    }
+
+    let mut overrides = OverrideBuilder::new(&root);
+    for pattern in &ignore_patterns {
+        overrides
+            .add(&format!("!{}", pattern.trim_end_matches('/')))
+            .map_err(|e| format!("Invalid ignore pattern '{}': {}", pattern, e))?;
+    }
+    let overrides = overrides
+        .build()
+        .map_err(|e| format!("Invalid ignore patterns: {}", e))?;
+
+    // .gitignore applies even when the folder is not a git checkout
+    let walker = WalkBuilder::new(&root)
+        .max_depth(Some(max_depth))
+        .require_git(false)
+        .overrides(overrides)
+        .sort_by_file_path(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.cmp(b)))
+        .build();
+
+    let root_name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| root.display().to_string());
+    let mut lines = vec![format!("{}/", root_name)];
+    let mut truncated = false;
+
+    for entry in walker.filter_map(|e| e.ok()) {
+        let depth = entry.depth();
+        if depth == 0 {
+            continue;
+        }
+        if lines.len() >= MAX_LINES {
+            truncated = true;
+            break;
+        }
+
+        let indent = "  ".repeat(depth);
+        let name = entry.file_name().to_string_lossy();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if is_dir {
+            lines.push(format!("{}{}/", indent, name));
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            lines.push(format!("{}{} ({})", indent, name, format_size(size)));
+        }
+    }
+
+    if truncated {
+        lines.push(format!(
+            "... (truncated at {} lines; narrow root_path or lower max_depth)",
+            MAX_LINES
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn format_size(size: u64) -> String {
+    if size < 1024 {
+        format!("{} B", size)
+    } else if size < 1024 * 1024 {
+        format!("{:.1} KB", size as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", size as f64 / (1024.0 * 1024.0))
+    }
+}
+
+pub struct FileTreeTool;
+
+impl Tool for FileTreeTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_file_tree_respects_gitignore_and_patterns() {
+        let dir = std::env::temp_dir().join(format!("kuse-tree-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join("build")).unwrap();
+        fs::write(dir.join(".gitignore"), "build/\n*.tmp\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(dir.join("notes.tmp"), "scratch").unwrap();
+        fs::write(dir.join("debug.log"), "log").unwrap();
+        fs::write(dir.join("build/out.bin"), "bin").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("src/nested/deep.rs"), "").unwrap();
+        let project = dir.to_string_lossy().to_string();
+
+        let tree = execute(&json!({ "ignore_patterns": ["*.log"] }), Some(&project)).unwrap();
+        let lines: Vec<&str> = tree.lines().collect();
+        assert!(lines[0].ends_with('/'));
+        assert!(lines.contains(&"  src/"), "{}", tree);
+        assert!(lines.contains(&"    main.rs (12 B)"), "{}", tree);
+        assert!(lines.contains(&"  Cargo.toml (9 B)"), "{}", tree);
+        for hidden in ["build", "out.bin", "notes.tmp", "debug.log", ".gitignore"] {
+            assert!(!tree.contains(hidden), "{} listed:\n{}", hidden, tree);
+        }
+
+        // Directories come before files at each level
+        let src = lines.iter().position(|l| *l == "  src/").unwrap();
+        let cargo = lines.iter().position(|l| *l == "  Cargo.toml (9 B)").unwrap();
+        assert!(src < cargo);
+
+        let shallow = execute(&json!({ "max_depth": 1 }), Some(&project)).unwrap();
+        assert!(shallow.contains("  src/"), "{}", shallow);
+        assert!(!shallow.contains("main.rs"), "{}", shallow);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_tree_caps_output() {
+        let dir = std::env::temp_dir().join(format!("kuse-tree-cap-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..250 {
+            fs::write(dir.join(format!("file{:03}.txt", i)), "").unwrap();
+        }
+        let project = dir.to_string_lossy().to_string();
+
+        let tree = execute(&json!({}), Some(&project)).unwrap();
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines.len(), MAX_LINES + 1);
+        assert!(lines.last().unwrap().starts_with("... (truncated at 200 lines"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
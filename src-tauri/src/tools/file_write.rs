@@ -1,5 +1,5 @@
 use crate::agent::ToolDefinition;
-use crate::tools::path_utils;
+use crate::tools::{path_utils, Tool, ToolFuture};
 use serde_json::json;
 use std::fs;
 use std::path::Path;
@@ -39,9 +39,23 @@ pub fn execute(
         .and_then(|v| v.as_str())
         .ok_or("Missing 'content' parameter")?;
 
+    // Reject paths that escape the mounted folder(s), including via `..`
+    if !path_utils::is_write_path_allowed(Path::new(path_str), project_path) {
+        return Err(write_error(
+            "outside_workspace",
+            Path::new(path_str),
+            &format!(
+                "Path is outside mounted folder(s). Allowed roots: {}",
+                project_path.unwrap_or_default()
+            ),
+        ));
+    }
+
     // Resolve path
     let path = resolve_path(path_str, project_path)?;
 
+    check_writable(&path)?;
+
     // Create parent directories if needed
     if let Some(parent) = path.parent() {
         if !parent.exists() {
@@ -62,7 +76,113 @@ pub fn execute(
     ))
 }
 
+/// Fail early with a `permission_denied` error instead of an opaque OS error from `fs::write`
+fn check_writable(path: &Path) -> Result<(), String> {
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.permissions().readonly() {
+            return Err(write_error("permission_denied", path, "File is read-only"));
+        }
+        return Ok(());
+    }
+
+    // New file: probe the nearest existing ancestor, since missing parents get created
+    let Some(dir) = path.ancestors().skip(1).find(|p| p.is_dir()) else {
+        return Ok(());
+    };
+    let probe = dir.join(format!(".kuse-write-probe-{}", uuid::Uuid::new_v4()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) => Err(write_error(
+            "permission_denied",
+            path,
+            &format!("Directory {} is not writable: {}", dir.display(), e),
+        )),
+    }
+}
+
+/// Structured error the agent can relay to the user as-is
+fn write_error(error: &str, path: &Path, reason: &str) -> String {
+    json!({
+        "error": error,
+        "path": path.display().to_string(),
+        "reason": reason,
+    })
+    .to_string()
+}
+
 fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path::PathBuf, String> {
     let path = Path::new(path_str);
     path_utils::resolve_path_for_write(path, project_path)
 }
+
+pub struct WriteFileTool;
+
+impl Tool for WriteFileTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("kuse-write-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_read_only_file_rejected_before_write() {
+        let root = temp_root();
+        let file = root.join("locked.txt");
+        fs::write(&file, "original").unwrap();
+        let mut permissions = fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&file, permissions).unwrap();
+
+        let input = json!({ "path": "locked.txt", "content": "changed" });
+        let err = execute(&input, Some(&root.to_string_lossy())).unwrap_err();
+        let error: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(error["error"], "permission_denied");
+        assert_eq!(error["path"], file.display().to_string());
+        assert_eq!(error["reason"], "File is read-only");
+
+        // The pre-check fired first, so the file is untouched
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original");
+
+        let mut permissions = fs::metadata(&file).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&file, permissions).unwrap();
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_write_outside_workspace_rejected() {
+        let root = temp_root();
+        let project_path = root.join("project");
+        fs::create_dir_all(&project_path).unwrap();
+        let project_path = project_path.to_string_lossy().to_string();
+
+        let input = json!({ "path": "../escaped.txt", "content": "nope" });
+        let err = execute(&input, Some(&project_path)).unwrap_err();
+        let error: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(error["error"], "outside_workspace");
+        assert!(!root.join("escaped.txt").exists());
+
+        let input = json!({ "path": "nested/ok.txt", "content": "fine" });
+        execute(&input, Some(&project_path)).unwrap();
+        assert!(root.join("project/nested/ok.txt").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,10 +1,13 @@
 use crate::agent::ToolDefinition;
+use crate::tools::{Tool, ToolFuture};
 use serde_json::json;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
 
 pub fn definition() -> ToolDefinition {
     ToolDefinition {
         name: "glob".to_string(),
-        description: "Find files matching a glob pattern. Returns a list of matching file paths. Use patterns like '**/*.rs' for recursive search or 'src/*.ts' for specific directories.".to_string(),
+        description: "Find files matching a glob pattern. Returns a list of matching file paths, or with include_metadata a JSON array of {path, size_bytes, modified_at, is_dir} sorted newest first. Use patterns like '**/*.rs' for recursive search or 'src/*.ts' for specific directories.".to_string(),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -19,6 +22,10 @@ pub fn definition() -> ToolDefinition {
                 "limit": {
                     "type": "integer",
                     "description": "Maximum number of results to return (default: 100)"
+                },
+                "include_metadata": {
+                    "type": "boolean",
+                    "description": "Return a JSON array with size and modification time (Unix ms) for the first `limit` matches, most recently modified first (default: false)"
                 }
             },
             "required": ["pattern"]
@@ -26,10 +33,11 @@ pub fn definition() -> ToolDefinition {
     }
 }
 
-pub fn execute(
-    input: &serde_json::Value,
+/// Matches for the input's pattern, with the pattern and result limit
+fn matching_paths<'a>(
+    input: &'a serde_json::Value,
     project_path: Option<&str>,
-) -> Result<String, String> {
+) -> Result<(&'a str, glob::Paths, usize), String> {
     let pattern = input
         .get("pattern")
         .and_then(|v| v.as_str())
@@ -57,6 +65,16 @@ pub fn execute(
     let entries = glob::glob(&full_pattern)
         .map_err(|e| format!("Invalid glob pattern: {}", e))?;
 
+    Ok((pattern, entries, limit))
+}
+
+/// Newline-separated list of matching paths
+pub fn execute(
+    input: &serde_json::Value,
+    project_path: Option<&str>,
+) -> Result<String, String> {
+    let (pattern, entries, limit) = matching_paths(input, project_path)?;
+
     let mut results: Vec<String> = Vec::new();
     let mut total_count = 0;
 
@@ -65,15 +83,7 @@ pub fn execute(
             Ok(path) => {
                 total_count += 1;
                 if results.len() < limit {
-                    // Make path relative to project if possible
-                    let display_path = if let Some(project) = project_path {
-                        path.strip_prefix(project)
-                            .map(|p| p.to_string_lossy().to_string())
-                            .unwrap_or_else(|_| path.to_string_lossy().to_string())
-                    } else {
-                        path.to_string_lossy().to_string()
-                    };
-                    results.push(display_path);
+                    results.push(display_path(&path, project_path));
                 }
             }
             Err(_) => {
@@ -98,3 +108,121 @@ pub fn execute(
 
     Ok(output)
 }
+
+/// Make a path relative to the project if possible
+fn display_path(path: &Path, project_path: Option<&str>) -> String {
+    if let Some(project) = project_path {
+        path.strip_prefix(project)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string_lossy().to_string())
+    } else {
+        path.to_string_lossy().to_string()
+    }
+}
+
+/// JSON array of the first `limit` matches with their size and mtime, newest first
+pub fn execute_with_metadata(
+    input: &serde_json::Value,
+    project_path: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    let (_, entries, limit) = matching_paths(input, project_path)?;
+
+    let mut results: Vec<(i64, serde_json::Value)> = entries
+        .filter_map(Result::ok)
+        .take(limit)
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(&path).ok()?;
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+            Some((
+                modified_at,
+                json!({
+                    "path": display_path(&path, project_path),
+                    "size_bytes": metadata.len(),
+                    "modified_at": modified_at,
+                    "is_dir": metadata.is_dir(),
+                }),
+            ))
+        })
+        .collect();
+
+    results.sort_by_key(|r| std::cmp::Reverse(r.0));
+    Ok(results.into_iter().map(|(_, v)| v).collect())
+}
+
+pub struct GlobTool;
+
+impl Tool for GlobTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move {
+            if input.get("include_metadata").and_then(|v| v.as_bool()).unwrap_or(false) {
+                crate::tools::json_result(execute_with_metadata(input, project_path))
+            } else {
+                crate::tools::text_result(execute(input, project_path))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_glob_include_metadata() {
+        let dir = std::env::temp_dir().join(format!("kuse-glob-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.to_string_lossy().to_string();
+
+        let now = SystemTime::now();
+        let files = [("old.txt", 10, 3600), ("new.txt", 25, 0), ("mid.txt", 0, 60)];
+        for (name, size, age_secs) in files {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            file.set_len(size).unwrap();
+            file.set_modified(now - Duration::from_secs(age_secs)).unwrap();
+        }
+
+        let output = execute_with_metadata(
+            &json!({ "pattern": "*.txt", "include_metadata": true }),
+            Some(&project),
+        )
+        .unwrap();
+        let results = output.as_array().unwrap();
+
+        let paths: Vec<&str> = results.iter().map(|r| r["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, vec!["new.txt", "mid.txt", "old.txt"]);
+        for (result, (_, size, age_secs)) in results.iter().zip([files[1], files[2], files[0]]) {
+            assert_eq!(result["size_bytes"].as_u64(), Some(size));
+            assert_eq!(result["is_dir"].as_bool(), Some(false));
+            let expected = (now - Duration::from_secs(age_secs))
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            assert!((result["modified_at"].as_i64().unwrap() - expected).abs() < 1000);
+        }
+
+        // The limit applies in match order, before any file is inspected
+        let limited = execute_with_metadata(
+            &json!({ "pattern": "*.txt", "include_metadata": true, "limit": 2 }),
+            Some(&project),
+        )
+        .unwrap();
+        let paths: Vec<&str> = limited.as_array().unwrap().iter().map(|r| r["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, vec!["new.txt", "mid.txt"]);
+
+        // Without the flag the plain path list is unchanged
+        let plain = execute(&json!({ "pattern": "new.txt" }), Some(&project)).unwrap();
+        assert_eq!(plain, "new.txt");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
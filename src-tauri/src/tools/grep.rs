@@ -1,7 +1,13 @@
 use crate::agent::ToolDefinition;
+use crate::tools::{path_utils, Tool, ToolFuture};
+use flate2::read::GzDecoder;
 use serde_json::json;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Decompressed bytes one search may read from `.gz` and `.zip` files
+const MAX_DECOMPRESSED_BYTES: u64 = 10 * 1024 * 1024;
 
 pub fn definition() -> ToolDefinition {
     ToolDefinition {
@@ -33,6 +39,10 @@ pub fn definition() -> ToolDefinition {
                 "limit": {
                     "type": "integer",
                     "description": "Maximum number of matches to return (default: 50)"
+                },
+                "search_compressed": {
+                    "type": "boolean",
+                    "description": "Also search inside .gz files and the text entries of .zip archives, reported as 'archive.zip::inner/file.txt' (default: false, 10 MB decompressed per search)"
                 }
             },
             "required": ["pattern"]
@@ -43,6 +53,7 @@ pub fn definition() -> ToolDefinition {
 pub fn execute(
     input: &serde_json::Value,
     project_path: Option<&str>,
+    allow_outside_workspace: bool,
 ) -> Result<String, String> {
     let pattern = input
         .get("pattern")
@@ -54,6 +65,10 @@ pub fn execute(
         .and_then(|v| v.as_str())
         .or(project_path)
         .unwrap_or(".");
+    let resolved_path = match input.get("path").and_then(|v| v.as_str()) {
+        Some(path) => path_utils::resolve_path_for_read(Path::new(path), project_path, allow_outside_workspace)?,
+        None => PathBuf::from(search_path),
+    };
 
     let file_glob = input
         .get("glob")
@@ -74,6 +89,12 @@ pub fn execute(
         .and_then(|v| v.as_u64())
         .unwrap_or(50) as usize;
 
+    let mut budget = input
+        .get("search_compressed")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+        .then_some(DecompressBudget { remaining: MAX_DECOMPRESSED_BYTES, exhausted: false });
+
     // Build regex
     let regex = if case_insensitive {
         regex::RegexBuilder::new(pattern)
@@ -83,20 +104,41 @@ pub fn execute(
         regex::Regex::new(pattern)
     }.map_err(|e| format!("Invalid regex pattern: {}", e))?;
 
-    let path = Path::new(search_path);
+    let path = resolved_path.as_path();
     let mut results: Vec<String> = Vec::new();
     let mut match_count = 0;
 
     if path.is_file() {
-        search_file(path, &regex, context, limit, &mut results, &mut match_count, project_path)?;
+        search_file(path, &regex, context, limit, &mut results, &mut match_count, project_path, budget.as_mut())?;
     } else if path.is_dir() {
-        search_directory(path, &regex, file_glob, context, limit, &mut results, &mut match_count, project_path)?;
+        search_directory(
+            path,
+            &regex,
+            file_glob,
+            context,
+            limit,
+            &mut results,
+            &mut match_count,
+            project_path,
+            budget.as_mut(),
+        )?;
     } else {
         return Err(format!("Path not found: {}", search_path));
     }
 
+    let budget_note = budget.filter(|b| b.exhausted).map(|_| {
+        format!(
+            "[Stopped decompressing after {} MB; some compressed files were not searched]",
+            MAX_DECOMPRESSED_BYTES / (1024 * 1024)
+        )
+    });
+
     if results.is_empty() {
-        return Ok(format!("No matches found for pattern: {}", pattern));
+        let mut output = format!("No matches found for pattern: {}", pattern);
+        if let Some(note) = budget_note {
+            output.push_str(&format!("\n\n{}", note));
+        }
+        return Ok(output);
     }
 
     let mut output = results.join("\n");
@@ -107,10 +149,50 @@ pub fn execute(
             limit, match_count
         ));
     }
+    if let Some(note) = budget_note {
+        output.push_str(&format!("\n\n{}", note));
+    }
 
     Ok(output)
 }
 
+/// Decompressed bytes left for the rest of a search
+struct DecompressBudget {
+    remaining: u64,
+    exhausted: bool,
+}
+
+impl DecompressBudget {
+    /// Read all of `reader`, or `None` once the budget runs out
+    fn read(&mut self, reader: impl Read) -> Option<Vec<u8>> {
+        if self.exhausted {
+            return None;
+        }
+        let mut data = Vec::new();
+        reader.take(self.remaining + 1).read_to_end(&mut data).ok()?;
+        if data.len() as u64 > self.remaining {
+            self.remaining = 0;
+            self.exhausted = true;
+            return None;
+        }
+        self.remaining -= data.len() as u64;
+        Some(data)
+    }
+}
+
+/// Decompressed content as text; `None` for binary (NUL bytes) or non-UTF-8 data
+fn decompressed_text(data: Vec<u8>) -> Option<String> {
+    if data.contains(&0) {
+        return None;
+    }
+    String::from_utf8(data).ok()
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn search_file(
     path: &Path,
     regex: &regex::Regex,
@@ -119,13 +201,8 @@ fn search_file(
     results: &mut Vec<String>,
     match_count: &mut usize,
     project_path: Option<&str>,
+    budget: Option<&mut DecompressBudget>,
 ) -> Result<(), String> {
-    let content = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(_) => return Ok(()), // Skip binary or unreadable files
-    };
-
-    let lines: Vec<&str> = content.lines().collect();
     let display_path = if let Some(project) = project_path {
         path.strip_prefix(project)
             .map(|p| p.to_string_lossy().to_string())
@@ -134,6 +211,82 @@ fn search_file(
         path.to_string_lossy().to_string()
     };
 
+    if let Some(budget) = budget {
+        if has_extension(path, "gz") {
+            let Ok(file) = fs::File::open(path) else {
+                return Ok(());
+            };
+            if let Some(content) = budget.read(GzDecoder::new(file)).and_then(decompressed_text) {
+                search_content(&content, &display_path, regex, context, limit, results, match_count);
+            }
+            return Ok(());
+        }
+        if has_extension(path, "zip") {
+            search_zip(path, &display_path, regex, context, limit, results, match_count, budget);
+            return Ok(());
+        }
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Ok(()), // Skip binary or unreadable files
+    };
+    search_content(&content, &display_path, regex, context, limit, results, match_count);
+
+    Ok(())
+}
+
+/// Search each text entry of a zip archive, shown as `archive.zip::inner/path`
+#[allow(clippy::too_many_arguments)]
+fn search_zip(
+    path: &Path,
+    display_path: &str,
+    regex: &regex::Regex,
+    context: usize,
+    limit: usize,
+    results: &mut Vec<String>,
+    match_count: &mut usize,
+    budget: &mut DecompressBudget,
+) {
+    // Skip unreadable or corrupt archives
+    let Ok(file) = fs::File::open(path) else {
+        return;
+    };
+    let Ok(mut archive) = zip::ZipArchive::new(file) else {
+        return;
+    };
+
+    for index in 0..archive.len() {
+        let Ok(entry) = archive.by_index(index) else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let entry_path = format!("{}::{}", display_path, entry.name());
+        let Some(data) = budget.read(entry) else {
+            if budget.exhausted {
+                return;
+            }
+            continue;
+        };
+        if let Some(content) = decompressed_text(data) {
+            search_content(&content, &entry_path, regex, context, limit, results, match_count);
+        }
+    }
+}
+
+fn search_content(
+    content: &str,
+    display_path: &str,
+    regex: &regex::Regex,
+    context: usize,
+    limit: usize,
+    results: &mut Vec<String>,
+    match_count: &mut usize,
+) {
+    let lines: Vec<&str> = content.lines().collect();
+
     for (i, line) in lines.iter().enumerate() {
         if regex.is_match(line) {
             *match_count += 1;
@@ -160,8 +313,6 @@ fn search_file(
             }
         }
     }
-
-    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -174,6 +325,7 @@ fn search_directory(
     results: &mut Vec<String>,
     match_count: &mut usize,
     project_path: Option<&str>,
+    mut budget: Option<&mut DecompressBudget>,
 ) -> Result<(), String> {
     let glob_pattern = file_glob.unwrap_or("**/*");
     let full_pattern = format!("{}/{}", path.to_string_lossy(), glob_pattern);
@@ -188,10 +340,88 @@ fn search_directory(
 
         if let Ok(file_path) = entry {
             if file_path.is_file() {
-                search_file(&file_path, regex, context, limit, results, match_count, project_path)?;
+                search_file(
+                    &file_path,
+                    regex,
+                    context,
+                    limit,
+                    results,
+                    match_count,
+                    project_path,
+                    budget.as_deref_mut(),
+                )?;
             }
         }
     }
 
     Ok(())
 }
+
+pub struct GrepTool;
+
+impl Tool for GrepTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        self.execute_with_outside_reads(input, project_path, false)
+    }
+
+    fn execute_with_outside_reads<'a>(
+        &'a self,
+        input: &'a serde_json::Value,
+        project_path: Option<&'a str>,
+        allow_outside_workspace: bool,
+    ) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path, allow_outside_workspace)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_search_compressed_files() {
+        let dir = std::env::temp_dir().join(format!("kuse-grep-gz-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut gz = GzEncoder::new(fs::File::create(dir.join("app.log.gz")).unwrap(), Compression::default());
+        gz.write_all(b"starting\nERROR disk full\nstopping\n").unwrap();
+        gz.finish().unwrap();
+
+        let mut archive = zip::ZipWriter::new(fs::File::create(dir.join("logs.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        archive.start_file("inner/old.log", options).unwrap();
+        archive.write_all(b"ok\nok\nERROR timeout\n").unwrap();
+        archive.start_file("inner/blob.bin", options).unwrap();
+        archive.write_all(b"ERROR\0binary").unwrap();
+        archive.finish().unwrap();
+
+        let project = dir.to_string_lossy().to_string();
+        let output = execute(&json!({ "pattern": "ERROR", "search_compressed": true }), Some(&project), false).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.contains(&"app.log.gz:2> ERROR disk full"), "{}", output);
+        assert!(lines.contains(&"logs.zip::inner/old.log:3> ERROR timeout"), "{}", output);
+        assert!(!output.contains("blob.bin"), "{}", output);
+
+        let plain = execute(&json!({ "pattern": "ERROR" }), Some(&project), false).unwrap();
+        assert!(!plain.contains("app.log.gz"), "{}", plain);
+        assert!(!plain.contains("::inner/"), "{}", plain);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_decompress_budget_stops_at_limit() {
+        let mut budget = DecompressBudget { remaining: 8, exhausted: false };
+        assert_eq!(budget.read(&b"12345"[..]).unwrap(), b"12345");
+        assert!(budget.read(&b"6789"[..]).is_none());
+        assert!(budget.exhausted);
+        assert!(budget.read(&b""[..]).is_none());
+    }
+}
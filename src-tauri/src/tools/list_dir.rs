@@ -1,5 +1,5 @@
 use crate::agent::ToolDefinition;
-use crate::tools::path_utils;
+use crate::tools::{path_utils, Tool, ToolFuture};
 use serde_json::json;
 use std::fs;
 use std::path::Path;
@@ -176,5 +176,17 @@ fn format_size(size: u64) -> String {
 
 fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path::PathBuf, String> {
     let path = Path::new(path_str);
-    path_utils::resolve_path(path, project_path)
+    path_utils::resolve_path_for_read(path, project_path, false)
+}
+
+pub struct ListDirTool;
+
+impl Tool for ListDirTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path)) })
+    }
 }
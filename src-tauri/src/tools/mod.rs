@@ -1,39 +1,238 @@
 pub mod bash;
+pub mod clipboard;
+pub mod code_exec;
+pub mod custom;
 pub mod docker;
 pub mod file_edit;
 pub mod file_read;
+pub mod file_tree;
 pub mod file_write;
 pub mod glob;
 pub mod grep;
 pub mod list_dir;
+pub mod ocr;
 pub mod path_utils;
 pub mod xlsx_create;
 
-use crate::agent::ToolDefinition;
-
-/// Get all available tool definitions
-pub fn get_all_tools() -> Vec<ToolDefinition> {
-    let mut tools = vec![
-        file_read::definition(),
-        file_write::definition(),
-        file_edit::definition(),
-        bash::definition(),
-        glob::definition(),
-        grep::definition(),
-        list_dir::definition(),
-        xlsx_create::definition(),
-    ];
-
-    // Add Docker tools
-    tools.extend(docker::get_docker_tools());
-
-    tools
+use crate::agent::{ToolDefinition, ToolResult};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Future returned by [`Tool::execute`]
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = ToolResult> + Send + 'a>>;
+
+/// A tool the agent can call.
+///
+/// Results are returned with an empty `tool_use_id`; the executor fills it in.
+pub trait Tool {
+    fn definition(&self) -> ToolDefinition;
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a>;
+
+    /// Like `execute`, with the user's choice of whether files outside the mounted folder(s)
+    /// may be read. Only tools that read a path named by the model override this.
+    fn execute_with_outside_reads<'a>(
+        &'a self,
+        input: &'a serde_json::Value,
+        project_path: Option<&'a str>,
+        _allow_outside_workspace: bool,
+    ) -> ToolFuture<'a> {
+        self.execute(input, project_path)
+    }
+}
+
+/// Convert a plain `Result<String, String>` tool output into a `ToolResult`
+pub fn text_result(result: Result<String, String>) -> ToolResult {
+    match result {
+        Ok(content) => ToolResult::success(String::new(), content),
+        Err(error) => ToolResult::error(String::new(), error),
+    }
+}
+
+/// Convert a structured tool output into a `ToolResult` with JSON content
+pub fn json_result(result: Result<serde_json::Value, String>) -> ToolResult {
+    match result {
+        Ok(value) => ToolResult::json(String::new(), value),
+        Err(error) => ToolResult::error(String::new(), error),
+    }
+}
+
+/// Registry of available tools, keyed by tool name
+pub struct ToolRegistry {
+    tools: HashMap<String, Box<dyn Tool + Send + Sync>>,
+    /// Registration order, so definitions are listed deterministically
+    order: Vec<String>,
+}
+
+impl ToolRegistry {
+    /// Create a registry with all built-in tools registered
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register(Box::new(file_read::ReadFileTool));
+        registry.register(Box::new(file_write::WriteFileTool));
+        registry.register(Box::new(file_edit::EditFileTool));
+        registry.register(Box::new(bash::BashTool));
+        registry.register(Box::new(glob::GlobTool));
+        registry.register(Box::new(grep::GrepTool));
+        registry.register(Box::new(list_dir::ListDirTool));
+        registry.register(Box::new(file_tree::FileTreeTool));
+        registry.register(Box::new(xlsx_create::CreateXlsxTool));
+        registry.register(Box::new(code_exec::CodeExecTool));
+        registry.register(Box::new(ocr::OcrTool));
+
+        // Mobile has no clipboard the agent can reach
+        #[cfg(not(mobile))]
+        {
+            registry.register(Box::new(clipboard::ClipboardWriteTool(clipboard::SystemClipboard)));
+            registry.register(Box::new(clipboard::ClipboardReadTool(clipboard::SystemClipboard)));
+        }
+
+        // Add Docker tools
+        for definition in docker::get_docker_tools() {
+            registry.register(Box::new(docker::DockerTool::new(definition)));
+        }
+
+        registry
+    }
+
+    /// Create a registry without any tools
+    pub fn empty() -> Self {
+        Self {
+            tools: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Register a tool, replacing any existing tool with the same name
+    pub fn register(&mut self, tool: Box<dyn Tool + Send + Sync>) {
+        let name = tool.definition().name;
+        if !self.tools.contains_key(&name) {
+            self.order.push(name.clone());
+        }
+        self.tools.insert(name, tool);
+    }
+
+    /// Register the custom tools described in `dir`. Tools named like an already
+    /// registered tool are skipped. Returns a message for each descriptor not loaded.
+    pub fn register_custom_tools(&mut self, dir: &std::path::Path) -> Vec<String> {
+        let (tools, mut errors) = custom::load_custom_tools(dir);
+        for tool in tools {
+            let name = tool.definition().name;
+            if self.tools.contains_key(&name) {
+                errors.push(format!("{}: a tool named '{}' already exists", dir.display(), name));
+                continue;
+            }
+            self.register(Box::new(tool));
+        }
+        errors
+    }
+
+    /// Get all available tool definitions
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.order
+            .iter()
+            .filter_map(|name| self.tools.get(name))
+            .map(|tool| tool.definition())
+            .collect()
+    }
+
+    /// Get tool definitions filtered by allowed list
+    pub fn definitions_for(&self, allowed: &[String]) -> Vec<ToolDefinition> {
+        self.definitions()
+            .into_iter()
+            .filter(|t| allowed.contains(&t.name))
+            .collect()
+    }
+
+    /// Execute a tool by name. Returns `None` if no such tool is registered.
+    pub async fn execute(
+        &self,
+        name: &str,
+        input: &serde_json::Value,
+        project_path: Option<&str>,
+        allow_outside_workspace: bool,
+    ) -> Option<ToolResult> {
+        let tool = self.tools.get(name)?;
+        Some(tool.execute_with_outside_reads(input, project_path, allow_outside_workspace).await)
+    }
 }
 
-/// Get tool definitions filtered by allowed list
-pub fn get_tools(allowed: &[String]) -> Vec<ToolDefinition> {
-    get_all_tools()
-        .into_iter()
-        .filter(|t| allowed.contains(&t.name))
-        .collect()
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ToolResultContent;
+    use serde_json::json;
+
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "echo".to_string(),
+                description: "Echo the input text".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": { "text": { "type": "string" } },
+                    "required": ["text"]
+                }),
+            }
+        }
+
+        fn execute<'a>(&'a self, input: &'a serde_json::Value, _project_path: Option<&'a str>) -> ToolFuture<'a> {
+            Box::pin(async move {
+                text_result(
+                    input
+                        .get("text")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string())
+                        .ok_or_else(|| "Missing 'text' parameter".to_string()),
+                )
+            })
+        }
+    }
+
+    #[test]
+    fn test_builtin_tools_registered() {
+        let registry = ToolRegistry::new();
+        let names: Vec<String> = registry.definitions().into_iter().map(|t| t.name).collect();
+        assert_eq!(names.first().map(String::as_str), Some("read_file"));
+        assert!(names.contains(&"create_xlsx_file".to_string()));
+        assert!(names.contains(&"file_tree".to_string()));
+        assert!(names.contains(&"extract_text_from_image".to_string()));
+        assert!(names.contains(&"docker_run".to_string()));
+        assert!(names.contains(&"docker_container_logs".to_string()));
+        assert!(names.contains(&"docker_exec".to_string()));
+
+        let allowed = vec!["bash".to_string(), "glob".to_string()];
+        let filtered: Vec<String> = registry.definitions_for(&allowed).into_iter().map(|t| t.name).collect();
+        assert_eq!(filtered, vec!["bash".to_string(), "glob".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_custom_tool_registration_and_execution() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool));
+
+        assert!(registry.definitions().iter().any(|t| t.name == "echo"));
+
+        let result = registry
+            .execute("echo", &json!({ "text": "hello" }), None, false)
+            .await
+            .unwrap();
+        assert_eq!(result.content, ToolResultContent::Text("hello".to_string()));
+        assert!(result.is_error.is_none());
+
+        let error = registry.execute("echo", &json!({}), None, false).await.unwrap();
+        assert_eq!(error.is_error, Some(true));
+
+        assert!(registry.execute("missing", &json!({}), None, false).await.is_none());
+    }
 }
@@ -0,0 +1,204 @@
+use crate::agent::ToolDefinition;
+use crate::tools::bash::wait_with_timeout;
+use crate::tools::code_exec::find_interpreter;
+use crate::tools::{path_utils, Tool, ToolFuture};
+use serde_json::json;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Extracted text limit, in bytes
+const MAX_OUTPUT_BYTES: usize = 50 * 1024;
+const OCR_TIMEOUT: Duration = Duration::from_secs(120);
+const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "tif", "tiff", "bmp"];
+const DEFAULT_LANGUAGE: &str = "eng";
+
+pub fn definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "extract_text_from_image".to_string(),
+        description: "Extract text from a screenshot or scanned document (JPEG, PNG, TIFF or BMP) using Tesseract OCR. Output is capped at 50 KB.".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "image_path": {
+                    "type": "string",
+                    "description": "Path to the image (relative to project root or absolute)"
+                },
+                "language": {
+                    "type": "string",
+                    "description": "Tesseract language code(s), e.g. \"eng\", \"deu\" or \"eng+fra\" (default: eng)"
+                }
+            },
+            "required": ["image_path"]
+        }),
+    }
+}
+
+pub fn execute(
+    input: &serde_json::Value,
+    project_path: Option<&str>,
+    allow_outside_workspace: bool,
+) -> Result<String, String> {
+    let image_path = input
+        .get("image_path")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'image_path' parameter")?;
+
+    let language = input
+        .get("language")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_LANGUAGE);
+
+    let path = path_utils::resolve_path_for_read(Path::new(image_path), project_path, allow_outside_workspace)?;
+    if !path.is_file() {
+        return Err(format!("File not found: {}", image_path));
+    }
+
+    extract_text(find_interpreter(&["tesseract"]).as_deref(), &path, language)
+}
+
+/// OCR `image` with the tesseract binary at `tesseract`, if one was found
+fn extract_text(tesseract: Option<&str>, image: &Path, language: &str) -> Result<String, String> {
+    check_supported_format(image)?;
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+') {
+        return Err(format!("Invalid language '{}', expected e.g. eng or eng+fra", language));
+    }
+
+    let tesseract = tesseract.ok_or(
+        "tesseract_not_installed: the tesseract binary was not found on PATH. Install it with \
+         `brew install tesseract` (macOS), `sudo apt install tesseract-ocr` (Debian/Ubuntu) or \
+         the installer from https://github.com/UB-Mannheim/tesseract/wiki (Windows), then try again.",
+    )?;
+
+    let child = tesseract_command(tesseract, image, language)
+        .spawn()
+        .map_err(|e| format!("Failed to run tesseract: {}", e))?;
+    let output = wait_with_timeout(child, OCR_TIMEOUT)?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed (exit code {}): {}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok("No text found in image".to_string());
+    }
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return Ok(text.to_string());
+    }
+
+    let mut end = MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Ok(format!(
+        "{}\n\n[Output truncated: showing {} of {} bytes]",
+        &text[..end],
+        end,
+        text.len()
+    ))
+}
+
+/// `tesseract {image} stdout -l {language}`
+fn tesseract_command(tesseract: &str, image: &Path, language: &str) -> Command {
+    let mut cmd = Command::new(tesseract);
+    cmd.arg(image)
+        .arg("stdout")
+        .arg("-l")
+        .arg(language)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+fn check_supported_format(image: &Path) -> Result<(), String> {
+    let extension = image
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    if SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unsupported image format '{}': expected JPEG, PNG, TIFF or BMP",
+            image.display()
+        ))
+    }
+}
+
+pub struct OcrTool;
+
+impl Tool for OcrTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        self.execute_with_outside_reads(input, project_path, false)
+    }
+
+    fn execute_with_outside_reads<'a>(
+        &'a self,
+        input: &'a serde_json::Value,
+        project_path: Option<&'a str>,
+        allow_outside_workspace: bool,
+    ) -> ToolFuture<'a> {
+        // Recognition can take a while on large scans; keep it off the async runtime
+        let input = input.clone();
+        let project_path = project_path.map(str::to_string);
+        Box::pin(async move {
+            let result = tokio::task::spawn_blocking(move || {
+                execute(&input, project_path.as_deref(), allow_outside_workspace)
+            })
+                .await
+                .unwrap_or_else(|e| Err(format!("OCR task failed: {}", e)));
+            crate::tools::text_result(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_tesseract_command_construction() {
+        let cmd = tesseract_command("/usr/bin/tesseract", Path::new("/scans/page 1.png"), "eng+deu");
+        assert_eq!(cmd.get_program(), OsStr::new("/usr/bin/tesseract"));
+        let args: Vec<&OsStr> = cmd.get_args().collect();
+        assert_eq!(args, vec!["/scans/page 1.png", "stdout", "-l", "eng+deu"]);
+    }
+
+    #[test]
+    fn test_missing_tesseract_reports_not_installed() {
+        let image = Path::new("/scans/receipt.PNG");
+        let error = extract_text(None, image, "eng").unwrap_err();
+        assert!(error.starts_with("tesseract_not_installed"), "{}", error);
+        assert!(error.contains("apt install tesseract-ocr"));
+
+        let error = extract_text(None, Path::new("/scans/receipt.gif"), "eng").unwrap_err();
+        assert!(error.starts_with("Unsupported image format"), "{}", error);
+        let error = extract_text(None, image, "eng; rm -rf /").unwrap_err();
+        assert!(error.starts_with("Invalid language"), "{}", error);
+    }
+
+    #[test]
+    fn test_missing_image_rejected() {
+        let dir = std::env::temp_dir().join(format!("kuse-ocr-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project_path = dir.to_string_lossy().to_string();
+
+        let error = execute(&json!({ "image_path": "missing.png" }), Some(&project_path), false).unwrap_err();
+        assert!(error.starts_with("File not found"), "{}", error);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -56,10 +56,127 @@ pub fn resolve_path(path: &Path, project_path: Option<&str>) -> Result<PathBuf,
     }
 }
 
+/// System files that are never read, even with `allow_outside_workspace`
+const BLOCKED_READ_PATHS: &[&str] = &[
+    "/etc/shadow",
+    "/etc/gshadow",
+    "/etc/sudoers",
+    "/etc/master.passwd",
+    "/private/etc/master.passwd",
+    "/private/etc/sudoers",
+    "/proc",
+    "/sys",
+    "/dev",
+    r"C:\Windows\System32",
+];
+
+/// Credential stores and browser profiles under the home directory that are never read
+/// from outside the mounted folder(s)
+const BLOCKED_HOME_DIRS: &[&str] = &[
+    ".ssh",
+    ".aws",
+    ".azure",
+    ".gnupg",
+    ".kube",
+    ".docker",
+    ".password-store",
+    ".config/gcloud",
+    ".config/gh",
+    ".mozilla",
+    ".config/google-chrome",
+    ".config/chromium",
+    ".config/BraveSoftware",
+    ".config/microsoft-edge",
+    "Library/Keychains",
+    "Library/Cookies",
+    "Library/Application Support/Google/Chrome",
+    "Library/Application Support/Firefox",
+    "Library/Application Support/BraveSoftware",
+    "Library/Application Support/Microsoft Edge",
+    "AppData/Local/Google/Chrome/User Data",
+    "AppData/Local/Microsoft/Edge/User Data",
+    "AppData/Local/BraveSoftware",
+    "AppData/Roaming/Mozilla/Firefox",
+];
+
+/// Like `resolve_path`, but also rejects paths that only leave the mounted folder(s)
+/// through a symlink. With `allow_outside_workspace`, a path outside the mounted folder(s)
+/// is accepted as long as it is an existing file and not a system or credential path; a path
+/// inside them that escapes through a symlink is still rejected.
+pub fn resolve_path_for_read(
+    path: &Path,
+    project_path: Option<&str>,
+    allow_outside_workspace: bool,
+) -> Result<PathBuf, String> {
+    let roots = parse_project_roots(project_path);
+    if allow_outside_workspace && path.is_absolute() && !roots.is_empty() && !is_within_roots(path, &roots) {
+        return resolve_outside_read_path(path);
+    }
+
+    let resolved = resolve_path(path, project_path)?;
+    match check_canonical_within_roots(&resolved, project_path, "Path") {
+        Ok(()) => Ok(resolved),
+        Err(_) if allow_outside_workspace && !is_within_roots(&normalize_path(&resolved), &roots) => {
+            resolve_outside_read_path(&resolved)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn resolve_outside_read_path(path: &Path) -> Result<PathBuf, String> {
+    let blocked = || format!("Reading system path is not allowed: {}", path.display());
+    if is_blocked_read_path(path) {
+        return Err(blocked());
+    }
+    let canonical = std::fs::canonicalize(path)
+        .map_err(|e| format!("Path not found: {} ({})", path.display(), e))?;
+    if is_blocked_read_path(&canonical) {
+        return Err(blocked());
+    }
+    if let Some(home) = dirs::home_dir() {
+        let canonical_home = std::fs::canonicalize(&home).unwrap_or_else(|_| home.clone());
+        if is_blocked_home_path(path, &home) || is_blocked_home_path(&canonical, &canonical_home) {
+            return Err(format!("Reading credential or browser data is not allowed: {}", path.display()));
+        }
+    }
+    if !canonical.is_file() {
+        return Err(format!(
+            "Path outside mounted folder(s) must be a file: {}",
+            path.display()
+        ));
+    }
+    Ok(canonical)
+}
+
+fn is_blocked_read_path(path: &Path) -> bool {
+    let path = normalize_path(path);
+    let lowered = path.to_string_lossy().to_lowercase();
+    BLOCKED_READ_PATHS.iter().any(|blocked| {
+        if blocked.contains('\\') {
+            // Windows paths are case-insensitive
+            let blocked = blocked.to_lowercase();
+            lowered == blocked || lowered.starts_with(&format!("{}\\", blocked))
+        } else {
+            path.starts_with(blocked)
+        }
+    })
+}
+
+fn is_blocked_home_path(path: &Path, home: &Path) -> bool {
+    let Ok(rest) = normalize_path(path).strip_prefix(home).map(Path::to_path_buf) else {
+        return false;
+    };
+    let rest = rest.to_string_lossy().replace('\\', "/").to_lowercase();
+    BLOCKED_HOME_DIRS.iter().any(|blocked| {
+        let blocked = blocked.to_lowercase();
+        rest == blocked || rest.starts_with(&format!("{}/", blocked))
+    })
+}
+
 pub fn resolve_path_for_write(path: &Path, project_path: Option<&str>) -> Result<PathBuf, String> {
     let roots = parse_project_roots(project_path);
 
-    if path.is_absolute() {
+    let resolved = if path.is_absolute() {
         if !roots.is_empty() && !is_within_roots(path, &roots) {
             return Err(format!(
                 "Write path is outside mounted folder(s): {}. Allowed roots: {}",
@@ -67,14 +184,105 @@ pub fn resolve_path_for_write(path: &Path, project_path: Option<&str>) -> Result
                 format_roots(&roots)
             ));
         }
-        return Ok(path.to_path_buf());
+        path.to_path_buf()
+    } else if let Some(root) = roots.first() {
+        root.join(path)
+    } else {
+        default_local_workspace_root()?.join(path)
+    };
+
+    check_canonical_within_roots(&resolved, project_path, "Write path")?;
+    Ok(resolved)
+}
+
+/// Re-check a resolved path against the mounted folder(s) after following symlinks.
+/// Roots that can't be canonicalized (e.g. not created yet) are skipped with a warning.
+fn check_canonical_within_roots(resolved: &Path, project_path: Option<&str>, kind: &str) -> Result<(), String> {
+    let roots = parse_project_roots(project_path);
+    if roots.is_empty() {
+        return Ok(());
     }
 
-    if let Some(root) = roots.first() {
-        Ok(root.join(path))
+    let canonical_roots: Vec<PathBuf> = roots
+        .iter()
+        .filter_map(|root| match std::fs::canonicalize(root) {
+            Ok(canonical) => Some(canonical),
+            Err(e) => {
+                eprintln!(
+                    "[path_utils] Warning: could not canonicalize workspace root {} ({}); skipping symlink check",
+                    root.display(),
+                    e
+                );
+                None
+            }
+        })
+        .collect();
+    if canonical_roots.is_empty() {
+        return Ok(());
+    }
+
+    let canonical = canonicalize_existing_prefix(resolved);
+    if !is_within_roots(&canonical, &canonical_roots) {
+        return Err(format!(
+            "{} is outside mounted folder(s) after resolving symlinks: {} -> {}. Allowed roots: {}",
+            kind,
+            resolved.display(),
+            canonical.display(),
+            format_roots(&roots)
+        ));
+    }
+    Ok(())
+}
+
+/// Canonicalize the longest existing ancestor of `path` and append the rest lexically,
+/// so targets that don't exist yet are still checked through their parent's symlinks
+fn canonicalize_existing_prefix(path: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = std::fs::canonicalize(ancestor) {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return normalize_path(&canonical.join(rest));
+        }
+    }
+    path
+}
+
+/// Whether a write target stays inside the mounted folder(s) once `.` and `..` are resolved.
+/// Always true when no folders are mounted.
+pub fn is_write_path_allowed(path: &Path, project_path: Option<&str>) -> bool {
+    let roots: Vec<PathBuf> = parse_project_roots(project_path)
+        .iter()
+        .map(|root| normalize_path(root))
+        .collect();
+    if roots.is_empty() {
+        return true;
+    }
+
+    let path = if path.is_absolute() {
+        normalize_path(path)
     } else {
-        default_local_workspace_root().map(|root| root.join(path))
+        normalize_path(&roots[0].join(path))
+    };
+    is_within_roots(&path, &roots)
+}
+
+/// Resolve `.` and `..` components lexically, without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
     }
+    normalized
 }
 
 fn is_within_roots(path: &Path, roots: &[PathBuf]) -> bool {
@@ -107,3 +315,126 @@ fn rewrite_outside_absolute_path(path: &Path, roots: &[PathBuf]) -> Option<PathB
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kuse-{}-{}", label, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_outside_workspace_rejected() {
+        let workspace = temp_dir("workspace");
+        let outside = temp_dir("outside");
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(&outside, workspace.join("escape")).unwrap();
+        let project_path = workspace.to_string_lossy().to_string();
+
+        let err = resolve_path_for_write(Path::new("escape/new.txt"), Some(&project_path)).unwrap_err();
+        assert!(err.starts_with("Write path is outside mounted folder(s) after resolving symlinks"), "{}", err);
+        let err = resolve_path_for_write(&workspace.join("escape/secret.txt"), Some(&project_path)).unwrap_err();
+        assert!(err.contains("after resolving symlinks"), "{}", err);
+        let err = resolve_path_for_read(Path::new("escape/secret.txt"), Some(&project_path), false).unwrap_err();
+        assert!(err.starts_with("Path is outside mounted folder(s) after resolving symlinks"), "{}", err);
+
+        fs::create_dir_all(workspace.join("reports")).unwrap();
+        assert_eq!(
+            resolve_path_for_write(Path::new("reports/q3.txt"), Some(&project_path)).unwrap(),
+            workspace.join("reports/q3.txt")
+        );
+        assert_eq!(
+            resolve_path_for_read(Path::new("reports/q3.txt"), Some(&project_path), false).unwrap(),
+            workspace.join("reports/q3.txt")
+        );
+
+        let _ = fs::remove_dir_all(&workspace);
+        let _ = fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn test_read_outside_workspace_when_allowed() {
+        let workspace = temp_dir("workspace");
+        let outside = temp_dir("outside");
+        let notes = outside.join("notes.txt");
+        fs::write(&notes, "notes").unwrap();
+        let project_path = workspace.to_string_lossy().to_string();
+
+        assert_eq!(
+            resolve_path_for_read(&notes, Some(&project_path), true).unwrap(),
+            fs::canonicalize(&notes).unwrap()
+        );
+        // Left off, the path is mapped into the workspace by file name instead
+        assert_eq!(
+            resolve_path_for_read(&notes, Some(&project_path), false).unwrap(),
+            workspace.join("notes.txt")
+        );
+
+        // Allowing outside reads doesn't let a path inside the workspace escape through a symlink
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, workspace.join("escape")).unwrap();
+            let err = resolve_path_for_read(Path::new("escape/notes.txt"), Some(&project_path), true).unwrap_err();
+            assert!(err.starts_with("Path is outside mounted folder(s) after resolving symlinks"), "{}", err);
+            let err = resolve_path_for_read(&workspace.join("escape/notes.txt"), Some(&project_path), true).unwrap_err();
+            assert!(err.contains("after resolving symlinks"), "{}", err);
+        }
+
+        let err = resolve_path_for_read(&outside, Some(&project_path), true).unwrap_err();
+        assert!(err.contains("must be a file"), "{}", err);
+        let err = resolve_path_for_read(&outside.join("missing.txt"), Some(&project_path), true).unwrap_err();
+        assert!(err.starts_with("Path not found"), "{}", err);
+
+        let _ = fs::remove_dir_all(&workspace);
+        let _ = fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn test_blocked_system_paths_rejected() {
+        assert!(is_blocked_read_path(Path::new("/etc/shadow")));
+        assert!(is_blocked_read_path(Path::new("/proc/1/environ")));
+        assert!(is_blocked_read_path(Path::new("/etc/../etc/sudoers")));
+        assert!(is_blocked_read_path(Path::new(r"c:\windows\system32\config\SAM")));
+        assert!(!is_blocked_read_path(Path::new("/etc/hosts")));
+        assert!(!is_blocked_read_path(Path::new("/devices/notes.txt")));
+
+        let home = Path::new("/home/ana");
+        assert!(is_blocked_home_path(Path::new("/home/ana/.ssh/id_ed25519"), home));
+        assert!(is_blocked_home_path(Path::new("/home/ana/.aws/credentials"), home));
+        assert!(is_blocked_home_path(Path::new("/home/ana/docs/../.gnupg/pubring.kbx"), home));
+        assert!(is_blocked_home_path(
+            Path::new("/home/ana/.config/google-chrome/Default/Login Data"),
+            home
+        ));
+        assert!(!is_blocked_home_path(Path::new("/home/ana/.sshrc"), home));
+        assert!(!is_blocked_home_path(Path::new("/home/ana/notes/.ssh.txt"), home));
+        assert!(!is_blocked_home_path(Path::new("/srv/.ssh/id_rsa"), home));
+
+        let workspace = temp_dir("workspace");
+        let project_path = workspace.to_string_lossy().to_string();
+        #[cfg(unix)]
+        {
+            let err = resolve_path_for_read(Path::new("/etc/shadow"), Some(&project_path), true).unwrap_err();
+            assert!(err.starts_with("Reading system path is not allowed"), "{}", err);
+            let err = resolve_path_for_read(Path::new("/proc/self/status"), Some(&project_path), true).unwrap_err();
+            assert!(err.starts_with("Reading system path is not allowed"), "{}", err);
+        }
+        let _ = fs::remove_dir_all(&workspace);
+    }
+
+    #[test]
+    fn test_missing_workspace_root_skips_symlink_check() {
+        let workspace = std::env::temp_dir().join(format!("kuse-missing-{}", uuid::Uuid::new_v4()));
+        let project_path = workspace.to_string_lossy().to_string();
+
+        assert_eq!(
+            resolve_path_for_write(Path::new("out.txt"), Some(&project_path)).unwrap(),
+            workspace.join("out.txt")
+        );
+    }
+}
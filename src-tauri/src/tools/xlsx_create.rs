@@ -1,8 +1,14 @@
 use crate::agent::ToolDefinition;
-use crate::tools::path_utils;
+use crate::tools::{path_utils, Tool, ToolFuture};
 use regex::Regex;
-use rust_xlsxwriter::Workbook;
+use rust_xlsxwriter::{
+    Chart, ChartType, Color, ConditionalFormatCell, ConditionalFormatCellRule, ConditionalFormatFormula,
+    Format, IntoConditionalFormatValue, Note, Table, TableColumn, TableStyle, Workbook, Worksheet,
+};
+use rust_xlsxwriter::utility::cell_range;
+use serde::Deserialize;
 use serde_json::json;
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::path::Path;
@@ -11,7 +17,7 @@ use zip::ZipArchive;
 pub fn definition() -> ToolDefinition {
     ToolDefinition {
         name: "create_xlsx_file".to_string(),
-        description: "Create simple or complex .xlsx workbooks in one call (multi-sheet, formulas, widths, freeze panes, filters, row heights).".to_string(),
+        description: "Create simple or complex .xlsx workbooks in one call (multi-sheet, formulas, named ranges, hyperlinks, widths, freeze panes, filters, tables, row heights, charts, conditional formats, cell notes).".to_string(),
         input_schema: json!({
             "type": "object",
             "properties": {
@@ -25,60 +31,18 @@ pub fn definition() -> ToolDefinition {
                     "properties": {
                         "sheets": {
                             "type": "array",
+                            "items": sheet_schema()
+                        },
+                        "named_ranges": {
+                            "type": "array",
+                            "description": "Workbook-level names usable in formulas, e.g. {\"name\": \"SalesData\", \"formula\": \"Sales!$A$2:$F$21\"} then =SUM(SalesData)",
                             "items": {
                                 "type": "object",
                                 "properties": {
-                                    "name": { "type": "string" },
-                                    "headers": { "type": "array", "items": { "type": "string" } },
-                                    "rows": {
-                                        "type": "array",
-                                        "items": {
-                                            "type": "array",
-                                            "items": {
-                                                "anyOf": [
-                                                    { "type": "string" },
-                                                    { "type": "number" },
-                                                    { "type": "boolean" },
-                                                    { "type": "null" },
-                                                    {
-                                                        "type": "object",
-                                                        "properties": {
-                                                            "value": {
-                                                                "anyOf": [
-                                                                    { "type": "string" },
-                                                                    { "type": "number" },
-                                                                    { "type": "boolean" },
-                                                                    { "type": "null" }
-                                                                ]
-                                                            },
-                                                            "formula": { "type": "string" }
-                                                        },
-                                                        "additionalProperties": true
-                                                    }
-                                                ]
-                                            }
-                                        }
-                                    },
-                                    "column_widths": { "type": "array", "items": { "type": "number" } },
-                                    "row_heights": { "type": "array", "items": { "type": "number" } },
-                                    "freeze_panes": {
-                                        "type": "object",
-                                        "properties": {
-                                            "row": { "type": "integer" },
-                                            "col": { "type": "integer" }
-                                        }
-                                    },
-                                    "autofilter": {
-                                        "type": "object",
-                                        "properties": {
-                                            "from_row": { "type": "integer" },
-                                            "from_col": { "type": "integer" },
-                                            "to_row": { "type": "integer" },
-                                            "to_col": { "type": "integer" }
-                                        }
-                                    }
+                                    "name": { "type": "string", "description": "Letters, digits and underscores, not starting with a digit" },
+                                    "formula": { "type": "string", "description": "Range the name refers to, e.g. Sales!$A$2:$F$21" }
                                 },
-                                "required": ["name", "rows"]
+                                "required": ["name", "formula"]
                             }
                         }
                     },
@@ -119,6 +83,248 @@ pub fn definition() -> ToolDefinition {
     }
 }
 
+/// Schema of one `workbook.sheets` entry, built in pieces to stay under the `json!` recursion limit
+fn sheet_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "headers": { "type": "array", "items": { "type": "string" } },
+            "rows": {
+                "type": "array",
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "anyOf": [
+                            { "type": "string" },
+                            { "type": "number" },
+                            { "type": "boolean" },
+                            { "type": "null" },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "value": {
+                                        "anyOf": [
+                                            { "type": "string" },
+                                            { "type": "number" },
+                                            { "type": "boolean" },
+                                            { "type": "null" }
+                                        ]
+                                    },
+                                    "formula": { "type": "string" },
+                                    "url": {
+                                        "type": "string",
+                                        "description": "Hyperlink target (http://, https://, mailto: or file://)"
+                                    },
+                                    "text": {
+                                        "type": "string",
+                                        "description": "Display text for a url cell (defaults to the url)"
+                                    },
+                                    "number_format": {
+                                        "type": "string",
+                                        "description": "Excel number format for a number or formula cell, e.g. \"$#,##0.00\", \"YYYY-MM-DD\" (value is a date serial number) or \"0%\""
+                                    }
+                                },
+                                "additionalProperties": true
+                            }
+                        ]
+                    }
+                }
+            },
+            "column_widths": { "type": "array", "items": { "type": "number" } },
+            "row_heights": { "type": "array", "items": { "type": "number" } },
+            "freeze_panes": {
+                "type": "object",
+                "properties": {
+                    "row": { "type": "integer" },
+                    "col": { "type": "integer" }
+                }
+            },
+            "autofilter": {
+                "type": "object",
+                "properties": {
+                    "from_row": { "type": "integer" },
+                    "from_col": { "type": "integer" },
+                    "to_row": { "type": "integer" },
+                    "to_col": { "type": "integer" }
+                }
+            },
+            "tables": {
+                "type": "array",
+                "description": "Excel tables over cell ranges; the first row of each range is the table header",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "range": {
+                            "type": "string",
+                            "description": "Cell range on this sheet including the header row, e.g. \"A1:D20\""
+                        },
+                        "style": {
+                            "type": "string",
+                            "description": "Built-in table style, e.g. \"TableStyleMedium9\" (Light1-21, Medium1-28, Dark1-11 or None)"
+                        },
+                        "total_row": {
+                            "type": "boolean",
+                            "description": "Use the last row of the range as a total row"
+                        }
+                    },
+                    "required": ["range"]
+                }
+            },
+            "notes": {
+                "type": "array",
+                "description": "Cell notes (comments), e.g. for audit remarks; row and col are 0-based sheet positions including the header row",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "row": { "type": "integer" },
+                        "col": { "type": "integer" },
+                        "author": { "type": "string", "description": "Defaults to Kuse Cowork" },
+                        "text": { "type": "string" }
+                    },
+                    "required": ["row", "col", "text"]
+                }
+            },
+            "charts": chart_schema(),
+            "conditional_formats": conditional_format_schema(),
+            "merged_cells": merged_cells_schema(),
+            "print_settings": print_settings_schema()
+        },
+        "required": ["name", "rows"]
+    })
+}
+
+fn chart_schema() -> serde_json::Value {
+    json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "chart_type": {
+                    "type": "string",
+                    "enum": ["bar", "column", "line", "pie"]
+                },
+                "title": { "type": "string" },
+                "data_range": {
+                    "type": "string",
+                    "description": "Series values range, e.g. \"Sheet1!$B$2:$B$10\""
+                },
+                "position": {
+                    "type": "object",
+                    "properties": {
+                        "row": { "type": "integer" },
+                        "col": { "type": "integer" }
+                    },
+                    "required": ["row", "col"]
+                },
+                "size": {
+                    "type": "object",
+                    "properties": {
+                        "width": { "type": "integer" },
+                        "height": { "type": "integer" }
+                    }
+                }
+            },
+            "required": ["chart_type", "data_range", "position"]
+        }
+    })
+}
+
+fn conditional_format_schema() -> serde_json::Value {
+    json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "range": {
+                    "type": "string",
+                    "description": "Cell range on this sheet, e.g. \"B2:F20\""
+                },
+                "rule": {
+                    "type": "object",
+                    "properties": {
+                        "type": { "type": "string", "enum": ["cell", "formula"] },
+                        "operator": {
+                            "type": "string",
+                            "enum": [
+                                "equal_to", "not_equal_to",
+                                "greater_than", "greater_than_or_equal_to",
+                                "less_than", "less_than_or_equal_to",
+                                "between", "not_between"
+                            ]
+                        },
+                        "value": {
+                            "description": "Comparison value for cell rules; [min, max] for between/not_between"
+                        },
+                        "formula": {
+                            "type": "string",
+                            "description": "Formula for formula rules, relative to the top-left cell, e.g. \"=$C2<0\""
+                        }
+                    },
+                    "required": ["type"]
+                },
+                "format": {
+                    "type": "object",
+                    "properties": {
+                        "bg_color": { "type": "string", "description": "Hex color, e.g. \"#FFC7CE\"" },
+                        "font_color": { "type": "string", "description": "Hex color, e.g. \"#9C0006\"" },
+                        "bold": { "type": "boolean" }
+                    }
+                }
+            },
+            "required": ["range", "rule", "format"]
+        }
+    })
+}
+
+fn merged_cells_schema() -> serde_json::Value {
+    json!({
+        "type": "array",
+        "description": "Cell ranges to merge (zero-based, inclusive), e.g. a header spanning several columns",
+        "items": {
+            "type": "object",
+            "properties": {
+                "from_row": { "type": "integer" },
+                "from_col": { "type": "integer" },
+                "to_row": { "type": "integer" },
+                "to_col": { "type": "integer" },
+                "value": {
+                    "description": "Value shown in the merged cell",
+                    "anyOf": [
+                        { "type": "string" },
+                        { "type": "number" },
+                        { "type": "boolean" },
+                        { "type": "null" }
+                    ]
+                }
+            },
+            "required": ["from_row", "from_col", "to_row", "to_col"]
+        }
+    })
+}
+
+fn print_settings_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "description": "Page setup used when the sheet is printed or exported to PDF",
+        "properties": {
+            "orientation": { "type": "string", "enum": ["portrait", "landscape"] },
+            "paper_size": {
+                "type": "string",
+                "enum": ["letter", "legal", "tabloid", "a3", "a4", "a5"]
+            },
+            "fit_to_page": { "type": "boolean", "description": "Shrink the sheet to one printed page" },
+            "scale": { "type": "integer", "description": "Print scale percentage (10-400)" },
+            "margin_left": { "type": "number", "description": "Inches" },
+            "margin_right": { "type": "number", "description": "Inches" },
+            "margin_top": { "type": "number", "description": "Inches" },
+            "margin_bottom": { "type": "number", "description": "Inches" },
+            "header": { "type": "string", "description": "Page header, e.g. \"&CQuarterly Report\"" },
+            "footer": { "type": "string", "description": "Page footer, e.g. \"&CPage &P of &N\"" }
+        }
+    })
+}
+
 pub fn execute(input: &serde_json::Value, project_path: Option<&str>) -> Result<String, String> {
     let strict = input.get("strict").and_then(|v| v.as_bool()).unwrap_or(true);
     let path_str = input
@@ -139,8 +345,9 @@ pub fn execute(input: &serde_json::Value, project_path: Option<&str>) -> Result<
 
     let mut workbook = Workbook::new();
     let used_complex_payload = input.get("workbook").is_some();
+    let mut warnings = Vec::new();
     if let Some(workbook_payload) = input.get("workbook") {
-        write_complex_workbook(&mut workbook, workbook_payload)?;
+        warnings = write_complex_workbook(&mut workbook, workbook_payload)?;
     } else {
         write_simple_sheet(&mut workbook, input)?;
     }
@@ -153,10 +360,11 @@ pub fn execute(input: &serde_json::Value, project_path: Option<&str>) -> Result<
         verify_written_workbook(&path, input, used_complex_payload)?;
     }
 
-    Ok(format!(
-        "Successfully created XLSX file at {} (verified)",
-        path.display()
-    ))
+    let mut message = format!("Successfully created XLSX file at {} (verified)", path.display());
+    for warning in &warnings {
+        message.push_str(&format!("\nWarning: {}", warning));
+    }
+    Ok(message)
 }
 
 fn resolve_path(path_str: &str, project_path: Option<&str>) -> Result<std::path::PathBuf, String> {
@@ -190,6 +398,7 @@ fn write_simple_sheet(workbook: &mut Workbook, input: &serde_json::Value) -> Res
         .set_name(sheet_name)
         .map_err(|e| format!("Invalid sheet name: {}", e))?;
 
+    let mut formats = FormatRegistry::default();
     let mut row_index: u32 = 0;
 
     if !headers.is_empty() {
@@ -207,7 +416,7 @@ fn write_simple_sheet(workbook: &mut Workbook, input: &serde_json::Value) -> Res
             .ok_or_else(|| format!("rows[{}] must be an array", ri))?;
 
         for (ci, cell) in cells.iter().enumerate() {
-            write_cell(worksheet, row_index, ci as u16, cell)?;
+            write_cell(worksheet, &mut formats, row_index, ci as u16, cell)?;
         }
         row_index += 1;
     }
@@ -290,8 +499,44 @@ fn count_xml_rows(sheet_xml: &str) -> usize {
     sheet_xml.matches("<row ").count() + sheet_xml.matches("<row>").count()
 }
 
-fn count_xml_formulas(sheet_xml: &str) -> usize {
-    sheet_xml.matches("<f>").count() + sheet_xml.matches("<f ").count()
+/// Count formula cells in a worksheet's XML.
+///
+/// Every formula cell normally carries its own `<f>` element, including cells with a string
+/// result (`t="str"`). A shared formula is the exception: the first cell holds
+/// `<f t="shared" ref="A2:A10" si="0">B2*2</f>` and the rest of the range only
+/// `<f t="shared" si="0"/>`, so the `ref` range is counted once and the references skipped.
+/// Array formulas (`t="array"`) also carry a `ref` but are a single formula.
+fn count_xml_formulas_accurate(sheet_xml: &str) -> usize {
+    let mut count = 0;
+    for (start, _) in sheet_xml.match_indices("<f") {
+        let rest = &sheet_xml[start + 2..];
+        if !rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            continue;
+        }
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        if xml_attr(tag, "t") != Some("shared") {
+            count += 1;
+            continue;
+        }
+        // Shared formula references without `ref` are covered by their range's first cell
+        if let Some(range) = xml_attr(tag, "ref") {
+            count += match parse_cell_range(range) {
+                Ok((first_row, first_col, last_row, last_col)) => {
+                    (last_row - first_row + 1) as usize * (last_col - first_col + 1) as usize
+                }
+                Err(_) => 1,
+            };
+        }
+    }
+    count
+}
+
+/// Value of a double-quoted attribute in the inside of an XML tag
+fn xml_attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!(" {}=\"", name);
+    let start = tag.find(&needle)? + needle.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
 }
 
 fn verify_simple_payload<R: Read + std::io::Seek>(
@@ -309,11 +554,12 @@ fn verify_simple_payload<R: Read + std::io::Seek>(
         .ok_or_else(|| format!("Workbook verification failed: sheet '{}' not found", expected_name))?;
     let sheet_xml = read_zip_entry_string(archive, target)?;
 
-    let rows_len = input
+    let rows = input
         .get("rows")
         .and_then(|v| v.as_array())
-        .map(|a| a.len())
-        .unwrap_or(0);
+        .map(|a| a.as_slice())
+        .unwrap_or(&[]);
+    let rows_len = rows.len();
     let has_headers = input
         .get("headers")
         .and_then(|v| v.as_array())
@@ -327,6 +573,13 @@ fn verify_simple_payload<R: Read + std::io::Seek>(
             expected_rows, expected_name, actual_rows
         ));
     }
+    if has_url_cells(rows) && !sheet_xml.contains("<hyperlinks>") {
+        return Err(format!(
+            "Workbook verification failed: url cells were written to '{}' but no hyperlinks were found",
+            expected_name
+        ));
+    }
+    verify_number_formats(&sheet_xml, rows, expected_name)?;
     Ok(())
 }
 
@@ -349,6 +602,54 @@ fn count_expected_formulas(rows: &[serde_json::Value]) -> usize {
     count
 }
 
+fn has_url_cells(rows: &[serde_json::Value]) -> bool {
+    rows.iter()
+        .filter_map(|row| row.as_array())
+        .flatten()
+        .any(|cell| {
+            cell.as_object()
+                .and_then(|o| o.get("url"))
+                .and_then(|u| u.as_str())
+                .is_some()
+        })
+}
+
+/// Cells given a `number_format` that `write_cell` applies (numbers and formulas)
+fn count_expected_number_formats(rows: &[serde_json::Value]) -> usize {
+    rows.iter()
+        .filter_map(|row| row.as_array())
+        .flatten()
+        .filter_map(|cell| cell.as_object())
+        .filter(|obj| cell_number_format(obj).is_some())
+        .filter(|obj| obj.contains_key("formula") || obj.get("value").is_some_and(|v| v.is_number()))
+        .count()
+}
+
+/// Cells whose `s` (style index) attribute points at a style other than the default
+fn count_xml_styled_cells(sheet_xml: &str) -> usize {
+    sheet_xml
+        .match_indices("<c ")
+        .filter(|(start, _)| {
+            let tag = &sheet_xml[*start..];
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            tag.split_whitespace()
+                .any(|attr| attr.starts_with("s=\"") && attr.trim_end_matches('/') != "s=\"0\"")
+        })
+        .count()
+}
+
+fn verify_number_formats(sheet_xml: &str, rows: &[serde_json::Value], sheet_name: &str) -> Result<(), String> {
+    let expected = count_expected_number_formats(rows);
+    let actual = count_xml_styled_cells(sheet_xml);
+    if actual < expected {
+        return Err(format!(
+            "Workbook verification failed for '{}': expected at least {} cell(s) with a number format, found {}",
+            sheet_name, expected, actual
+        ));
+    }
+    Ok(())
+}
+
 fn verify_complex_payload<R: Read + std::io::Seek>(
     archive: &mut ZipArchive<R>,
     input: &serde_json::Value,
@@ -393,7 +694,7 @@ fn verify_complex_payload<R: Read + std::io::Seek>(
 
         let expected_formulas = count_expected_formulas(rows);
         if expected_formulas > 0 {
-            let actual_formulas = count_xml_formulas(&sheet_xml);
+            let actual_formulas = count_xml_formulas_accurate(&sheet_xml);
             if actual_formulas < expected_formulas {
                 return Err(format!(
                     "Workbook verification failed for '{}': expected at least {} formula cell(s), found {}",
@@ -402,6 +703,15 @@ fn verify_complex_payload<R: Read + std::io::Seek>(
             }
         }
 
+        if has_url_cells(rows) && !sheet_xml.contains("<hyperlinks>") {
+            return Err(format!(
+                "Workbook verification failed for '{}': url cells were written but no hyperlinks were found",
+                name
+            ));
+        }
+
+        verify_number_formats(&sheet_xml, rows, name)?;
+
         if expected.get("freeze_panes").is_some() && !sheet_xml.contains("<pane") {
             return Err(format!(
                 "Workbook verification failed for '{}': freeze panes were requested but not found",
@@ -415,12 +725,96 @@ fn verify_complex_payload<R: Read + std::io::Seek>(
                 name
             ));
         }
+
+        if expected.get("print_settings").is_some() && !sheet_xml.contains("<pageSetup") {
+            return Err(format!(
+                "Workbook verification failed for '{}': print settings were requested but not found",
+                name
+            ));
+        }
+
+        let tables_requested = expected
+            .get("tables")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false);
+        if tables_requested && !sheet_xml.contains("<tableParts") {
+            return Err(format!(
+                "Workbook verification failed for '{}': tables were requested but not found",
+                name
+            ));
+        }
+
+        let notes_requested = expected
+            .get("notes")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false);
+        if notes_requested
+            && !sheet_xml.contains("<legacyDrawing")
+            && !archive.file_names().any(|n| n.starts_with("xl/drawings/vmlDrawing") && n.ends_with(".vml"))
+        {
+            return Err(format!(
+                "Workbook verification failed for '{}': notes were requested but not found",
+                name
+            ));
+        }
+
+        let conditional_formats_requested = expected
+            .get("conditional_formats")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false);
+        if conditional_formats_requested && !sheet_xml.contains("<conditionalFormatting") {
+            return Err(format!(
+                "Workbook verification failed for '{}': conditional formats were requested but not found",
+                name
+            ));
+        }
+
+        let merges_requested = expected
+            .get("merged_cells")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false);
+        if merges_requested && !sheet_xml.contains("<mergeCell") {
+            return Err(format!(
+                "Workbook verification failed for '{}': merged cells were requested but not found",
+                name
+            ));
+        }
+    }
+
+    let charts_requested = expected_sheets.iter().any(|sheet| {
+        sheet
+            .get("charts")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false)
+    });
+    if charts_requested && !archive.file_names().any(|n| n.starts_with("xl/charts/")) {
+        return Err("Workbook verification failed: charts were requested but xl/charts/ is missing".to_string());
+    }
+
+    let names_requested = input
+        .get("workbook")
+        .and_then(|w| w.get("named_ranges"))
+        .and_then(|v| v.as_array())
+        .map(|a| !a.is_empty())
+        .unwrap_or(false);
+    if names_requested {
+        let workbook_xml = read_zip_entry_string(archive, "xl/workbook.xml")?;
+        if !workbook_xml.contains("<definedName") {
+            return Err("Workbook verification failed: named ranges were requested but not found".to_string());
+        }
     }
 
     Ok(())
 }
 
-fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value) -> Result<(), String> {
+/// Write every sheet of a `workbook` payload. Returns warnings about requests that were
+/// written anyway but look like mistakes.
+fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value) -> Result<Vec<String>, String> {
     let sheets = payload
         .get("sheets")
         .and_then(|v| v.as_array())
@@ -430,6 +824,9 @@ fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value)
         return Err("workbook.sheets cannot be empty".to_string());
     }
 
+    let mut formats = FormatRegistry::default();
+    let mut warnings = Vec::new();
+
     for (si, sheet) in sheets.iter().enumerate() {
         let name = sheet
             .get("name")
@@ -484,6 +881,13 @@ fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value)
                 .map_err(|e| format!("Failed setting freeze panes on '{}': {}", name, e))?;
         }
 
+        if let Some(settings) = sheet.get("print_settings") {
+            let settings: PrintSettings = serde_json::from_value(settings.clone())
+                .map_err(|e| format!("workbook.sheets[{}].print_settings: {}", si, e))?;
+            apply_print_settings(worksheet, &settings)
+                .map_err(|e| format!("workbook.sheets[{}].print_settings: {}", si, e))?;
+        }
+
         let mut row_index: u32 = 0;
         if !headers.is_empty() {
             for (col_index, value) in headers.iter().enumerate() {
@@ -499,11 +903,32 @@ fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value)
                 .as_array()
                 .ok_or_else(|| format!("workbook.sheets[{}].rows[{}] must be an array", si, ri))?;
             for (ci, cell) in cells.iter().enumerate() {
-                write_cell(worksheet, row_index, ci as u16, cell)?;
+                write_cell(worksheet, &mut formats, row_index, ci as u16, cell)?;
             }
             row_index += 1;
         }
 
+        if let Some(merges) = sheet.get("merged_cells") {
+            let merges = parse_merged_cells(merges)
+                .map_err(|e| format!("workbook.sheets[{}].merged_cells: {}", si, e))?;
+            for merge in &merges {
+                worksheet
+                    .merge_range(
+                        merge.from_row,
+                        merge.from_col,
+                        merge.to_row,
+                        merge.to_col,
+                        &merge.value_or_empty(),
+                        &Format::new(),
+                    )
+                    .map_err(|e| format!("Failed merging {} on '{}': {}", merge.range(), name, e))?;
+                // merge_range writes text; numbers and booleans keep their type in the top-left cell
+                if let Some(value) = merge.value.as_ref().filter(|v| !v.is_string() && !v.is_null()) {
+                    write_cell(worksheet, &mut formats, merge.from_row, merge.from_col, value)?;
+                }
+            }
+        }
+
         if let Some(filter) = sheet.get("autofilter").and_then(|v| v.as_object()) {
             let from_row = filter.get("from_row").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
             let from_col = filter.get("from_col").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
@@ -519,32 +944,662 @@ fn write_complex_workbook(workbook: &mut Workbook, payload: &serde_json::Value)
                 .autofilter(from_row, from_col, to_row, to_col)
                 .map_err(|e| format!("Failed setting autofilter on '{}': {}", name, e))?;
         }
+
+        if let Some(tables) = sheet.get("tables") {
+            let tables: Vec<ExcelTable> = serde_json::from_value(tables.clone())
+                .map_err(|e| format!("workbook.sheets[{}].tables: invalid table: {}", si, e))?;
+            for (ti, spec) in tables.iter().enumerate() {
+                add_table(worksheet, spec, &headers)
+                    .map_err(|e| format!("workbook.sheets[{}].tables[{}]: {}", si, ti, e))?;
+            }
+        }
+
+        if let Some(notes) = sheet.get("notes") {
+            let notes: Vec<CellNote> = serde_json::from_value(notes.clone())
+                .map_err(|e| format!("workbook.sheets[{}].notes: invalid note: {}", si, e))?;
+            let written_cols = rows
+                .iter()
+                .filter_map(|row| row.as_array())
+                .map(|cells| cells.len())
+                .chain(std::iter::once(headers.len()))
+                .max()
+                .unwrap_or(0) as u16;
+            for (ni, note) in notes.iter().enumerate() {
+                if note.row >= row_index || note.col >= written_cols {
+                    warnings.push(format!(
+                        "workbook.sheets[{}].notes[{}] at row {}, col {} is outside the written data of '{}'",
+                        si, ni, note.row, note.col, name
+                    ));
+                }
+                add_note(worksheet, note)
+                    .map_err(|e| format!("workbook.sheets[{}].notes[{}]: {}", si, ni, e))?;
+            }
+        }
+
+        if let Some(formats) = sheet.get("conditional_formats").and_then(|v| v.as_array()) {
+            for (fi, spec) in formats.iter().enumerate() {
+                add_conditional_format(worksheet, spec)
+                    .map_err(|e| format!("workbook.sheets[{}].conditional_formats[{}]: {}", si, fi, e))?;
+            }
+        }
+
+        if let Some(charts) = sheet.get("charts").and_then(|v| v.as_array()) {
+            for (ci, spec) in charts.iter().enumerate() {
+                let chart = build_chart(spec)
+                    .map_err(|e| format!("workbook.sheets[{}].charts[{}]: {}", si, ci, e))?;
+                let position = spec.get("position").and_then(|v| v.as_object());
+                let row = position
+                    .and_then(|p| p.get("row"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0) as u32;
+                let col = position
+                    .and_then(|p| p.get("col"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0) as u16;
+                worksheet
+                    .insert_chart(row, col, &chart)
+                    .map_err(|e| format!("Failed inserting chart on '{}': {}", name, e))?;
+            }
+        }
+    }
+
+    // Names may refer to any sheet, so they are defined once every sheet exists
+    if let Some(names) = payload.get("named_ranges") {
+        let names: Vec<NamedRange> = serde_json::from_value(names.clone())
+            .map_err(|e| format!("workbook.named_ranges: invalid named range: {}", e))?;
+        for (ni, named) in names.iter().enumerate() {
+            if !is_valid_range_name(&named.name) {
+                return Err(format!(
+                    "workbook.named_ranges[{}]: invalid name '{}' (use letters, digits and underscores, not starting with a digit)",
+                    ni, named.name
+                ));
+            }
+            let formula = format!("={}", named.formula.trim().trim_start_matches('='));
+            workbook
+                .define_name(named.name.as_str(), &formula)
+                .map_err(|e| format!("Failed defining name '{}': {}", named.name, e))?;
+        }
+    }
+
+    Ok(warnings)
+}
+
+fn build_chart(spec: &serde_json::Value) -> Result<Chart, String> {
+    let chart_type = match spec.get("chart_type").and_then(|v| v.as_str()).unwrap_or("") {
+        "bar" => ChartType::Bar,
+        "column" => ChartType::Column,
+        "line" => ChartType::Line,
+        "pie" => ChartType::Pie,
+        other => {
+            return Err(format!(
+                "unsupported chart_type '{}' (expected bar, column, line or pie)",
+                other
+            ))
+        }
+    };
+
+    let data_range = spec
+        .get("data_range")
+        .and_then(|v| v.as_str())
+        .ok_or("data_range is required")?;
+    let (sheet_name, first_row, first_col, last_row, last_col) = parse_chart_range(data_range)?;
+
+    let mut chart = Chart::new(chart_type);
+    chart
+        .add_series()
+        .set_values((sheet_name.as_str(), first_row, first_col, last_row, last_col));
+
+    if let Some(title) = spec.get("title").and_then(|v| v.as_str()) {
+        chart.title().set_name(title);
+    }
+
+    if let Some(size) = spec.get("size").and_then(|v| v.as_object()) {
+        if let Some(width) = size.get("width").and_then(|v| v.as_u64()) {
+            chart.set_width(width as u32);
+        }
+        if let Some(height) = size.get("height").and_then(|v| v.as_u64()) {
+            chart.set_height(height as u32);
+        }
+    }
+
+    Ok(chart)
+}
+
+/// Parse a range like `Sheet1!$A$1:$A$10` (or `'My Sheet'!B2:B5`) into
+/// `(sheet, first_row, first_col, last_row, last_col)` with zero-based indices.
+fn parse_chart_range(range: &str) -> Result<(String, u32, u16, u32, u16), String> {
+    let (sheet, cells) = range
+        .rsplit_once('!')
+        .ok_or_else(|| format!("data_range '{}' must include a sheet name, e.g. Sheet1!$A$1:$A$10", range))?;
+    let sheet = sheet.trim().trim_matches('\'').to_string();
+    if sheet.is_empty() {
+        return Err(format!("data_range '{}' has an empty sheet name", range));
     }
 
+    let (start, end) = cells.split_once(':').unwrap_or((cells, cells));
+    let (first_row, first_col) = parse_cell_ref(start)
+        .ok_or_else(|| format!("Invalid cell reference '{}' in data_range '{}'", start, range))?;
+    let (last_row, last_col) = parse_cell_ref(end)
+        .ok_or_else(|| format!("Invalid cell reference '{}' in data_range '{}'", end, range))?;
+
+    Ok((sheet, first_row, first_col, last_row, last_col))
+}
+
+fn add_conditional_format(
+    worksheet: &mut rust_xlsxwriter::Worksheet,
+    spec: &serde_json::Value,
+) -> Result<(), String> {
+    let range = spec
+        .get("range")
+        .and_then(|v| v.as_str())
+        .ok_or("range is required")?;
+    let (first_row, first_col, last_row, last_col) = parse_cell_range(range)?;
+
+    let rule = spec.get("rule").ok_or("rule is required")?;
+    let format = build_conditional_format_style(spec.get("format"))?;
+
+    let result = match rule.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+        "cell" => {
+            let operator = rule
+                .get("operator")
+                .and_then(|v| v.as_str())
+                .ok_or("rule.operator is required for cell rules")?;
+            let value = rule.get("value").ok_or("rule.value is required for cell rules")?;
+
+            // Numbers compare numerically; strings are passed through (cell refs or quoted text)
+            if is_numeric_rule_value(value) {
+                let conditional_format = ConditionalFormatCell::new()
+                    .set_rule(cell_rule(operator, value, |v| v.as_f64())?)
+                    .set_format(format);
+                worksheet.add_conditional_format(first_row, first_col, last_row, last_col, &conditional_format)
+            } else {
+                let conditional_format = ConditionalFormatCell::new()
+                    .set_rule(cell_rule(operator, value, |v| v.as_str().map(|s| s.to_string()))?)
+                    .set_format(format);
+                worksheet.add_conditional_format(first_row, first_col, last_row, last_col, &conditional_format)
+            }
+        }
+        "formula" => {
+            let formula = rule
+                .get("formula")
+                .and_then(|v| v.as_str())
+                .ok_or("rule.formula is required for formula rules")?;
+            let formula = if formula.starts_with('=') {
+                formula.to_string()
+            } else {
+                format!("={}", formula)
+            };
+            let conditional_format = ConditionalFormatFormula::new()
+                .set_rule(formula.as_str())
+                .set_format(format);
+            worksheet.add_conditional_format(first_row, first_col, last_row, last_col, &conditional_format)
+        }
+        other => {
+            return Err(format!(
+                "unsupported rule.type '{}' (expected cell or formula)",
+                other
+            ))
+        }
+    };
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Failed adding conditional format for '{}': {}", range, e))
+}
+
+fn is_numeric_rule_value(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Array(items) => items.iter().all(|v| v.is_number()),
+        other => other.is_number(),
+    }
+}
+
+/// Build a cell rule from an operator name; `between`/`not_between` take a `[min, max]` value
+fn cell_rule<T: IntoConditionalFormatValue>(
+    operator: &str,
+    value: &serde_json::Value,
+    convert: impl Fn(&serde_json::Value) -> Option<T>,
+) -> Result<ConditionalFormatCellRule<T>, String> {
+    let single = || convert(value).ok_or_else(|| format!("rule.value must be a number or string for '{}'", operator));
+    let pair = || -> Result<(T, T), String> {
+        let bounds = value
+            .as_array()
+            .filter(|a| a.len() == 2)
+            .ok_or_else(|| format!("rule.value must be [min, max] for '{}'", operator))?;
+        let min = convert(&bounds[0]).ok_or("rule.value min is invalid")?;
+        let max = convert(&bounds[1]).ok_or("rule.value max is invalid")?;
+        Ok((min, max))
+    };
+
+    Ok(match operator {
+        "equal_to" => ConditionalFormatCellRule::EqualTo(single()?),
+        "not_equal_to" => ConditionalFormatCellRule::NotEqualTo(single()?),
+        "greater_than" => ConditionalFormatCellRule::GreaterThan(single()?),
+        "greater_than_or_equal_to" => ConditionalFormatCellRule::GreaterThanOrEqualTo(single()?),
+        "less_than" => ConditionalFormatCellRule::LessThan(single()?),
+        "less_than_or_equal_to" => ConditionalFormatCellRule::LessThanOrEqualTo(single()?),
+        "between" => {
+            let (min, max) = pair()?;
+            ConditionalFormatCellRule::Between(min, max)
+        }
+        "not_between" => {
+            let (min, max) = pair()?;
+            ConditionalFormatCellRule::NotBetween(min, max)
+        }
+        other => return Err(format!("unsupported rule.operator '{}'", other)),
+    })
+}
+
+fn build_conditional_format_style(spec: Option<&serde_json::Value>) -> Result<Format, String> {
+    let mut format = Format::new();
+    let Some(spec) = spec else {
+        return Ok(format);
+    };
+
+    if let Some(bg_color) = spec.get("bg_color").and_then(|v| v.as_str()) {
+        format = format.set_background_color(parse_hex_color(bg_color)?);
+    }
+    if let Some(font_color) = spec.get("font_color").and_then(|v| v.as_str()) {
+        format = format.set_font_color(parse_hex_color(font_color)?);
+    }
+    if spec.get("bold").and_then(|v| v.as_bool()).unwrap_or(false) {
+        format = format.set_bold();
+    }
+
+    Ok(format)
+}
+
+/// A workbook-level name from `named_ranges`
+#[derive(Debug, Deserialize)]
+struct NamedRange {
+    name: String,
+    formula: String,
+}
+
+/// Excel names: letters, digits and underscores, not starting with a digit
+fn is_valid_range_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Page setup from a sheet's `print_settings`
+#[derive(Debug, Default, Deserialize)]
+struct PrintSettings {
+    #[serde(default)]
+    orientation: PageOrientation,
+    paper_size: Option<String>,
+    fit_to_page: Option<bool>,
+    scale: Option<u32>,
+    margin_left: Option<f64>,
+    margin_right: Option<f64>,
+    margin_top: Option<f64>,
+    margin_bottom: Option<f64>,
+    header: Option<String>,
+    footer: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PageOrientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+/// Excel's numeric paper size code for a paper name
+fn paper_size_code(name: &str) -> Option<u8> {
+    match name.trim().to_lowercase().as_str() {
+        "letter" => Some(1),
+        "tabloid" => Some(3),
+        "legal" => Some(5),
+        "a3" => Some(8),
+        "a4" => Some(9),
+        "a5" => Some(11),
+        _ => None,
+    }
+}
+
+fn apply_print_settings(worksheet: &mut Worksheet, settings: &PrintSettings) -> Result<(), String> {
+    match settings.orientation {
+        PageOrientation::Portrait => worksheet.set_portrait(),
+        PageOrientation::Landscape => worksheet.set_landscape(),
+    };
+
+    if let Some(paper) = &settings.paper_size {
+        let code = paper_size_code(paper).ok_or_else(|| {
+            format!("unknown paper_size '{}' (use letter, legal, tabloid, a3, a4 or a5)", paper)
+        })?;
+        worksheet.set_paper_size(code);
+    }
+
+    if settings.fit_to_page.unwrap_or(false) {
+        worksheet.set_print_fit_to_pages(1, 1);
+    } else if let Some(scale) = settings.scale {
+        if !(10..=400).contains(&scale) {
+            return Err(format!("scale must be between 10 and 400, got {}", scale));
+        }
+        worksheet.set_print_scale(scale as u16);
+    }
+
+    let margins = [
+        settings.margin_left,
+        settings.margin_right,
+        settings.margin_top,
+        settings.margin_bottom,
+    ];
+    if margins.iter().flatten().any(|m| *m < 0.0) {
+        return Err("margins cannot be negative".to_string());
+    }
+    if margins.iter().any(Option::is_some) {
+        // Negative values keep Excel's default for that margin
+        let [left, right, top, bottom] = margins.map(|m| m.unwrap_or(-1.0));
+        worksheet.set_margins(left, right, top, bottom, -1.0, -1.0);
+    }
+
+    if let Some(header) = &settings.header {
+        worksheet.set_header(header);
+    }
+    if let Some(footer) = &settings.footer {
+        worksheet.set_footer(footer);
+    }
+
+    Ok(())
+}
+
+/// An Excel table from a sheet's `tables` list
+#[derive(Debug, Deserialize)]
+struct ExcelTable {
+    range: String,
+    style: Option<String>,
+    total_row: Option<bool>,
+}
+
+const LIGHT_TABLE_STYLES: [TableStyle; 21] = [
+    TableStyle::Light1, TableStyle::Light2, TableStyle::Light3, TableStyle::Light4,
+    TableStyle::Light5, TableStyle::Light6, TableStyle::Light7, TableStyle::Light8,
+    TableStyle::Light9, TableStyle::Light10, TableStyle::Light11, TableStyle::Light12,
+    TableStyle::Light13, TableStyle::Light14, TableStyle::Light15, TableStyle::Light16,
+    TableStyle::Light17, TableStyle::Light18, TableStyle::Light19, TableStyle::Light20,
+    TableStyle::Light21,
+];
+
+const MEDIUM_TABLE_STYLES: [TableStyle; 28] = [
+    TableStyle::Medium1, TableStyle::Medium2, TableStyle::Medium3, TableStyle::Medium4,
+    TableStyle::Medium5, TableStyle::Medium6, TableStyle::Medium7, TableStyle::Medium8,
+    TableStyle::Medium9, TableStyle::Medium10, TableStyle::Medium11, TableStyle::Medium12,
+    TableStyle::Medium13, TableStyle::Medium14, TableStyle::Medium15, TableStyle::Medium16,
+    TableStyle::Medium17, TableStyle::Medium18, TableStyle::Medium19, TableStyle::Medium20,
+    TableStyle::Medium21, TableStyle::Medium22, TableStyle::Medium23, TableStyle::Medium24,
+    TableStyle::Medium25, TableStyle::Medium26, TableStyle::Medium27, TableStyle::Medium28,
+];
+
+const DARK_TABLE_STYLES: [TableStyle; 11] = [
+    TableStyle::Dark1, TableStyle::Dark2, TableStyle::Dark3, TableStyle::Dark4,
+    TableStyle::Dark5, TableStyle::Dark6, TableStyle::Dark7, TableStyle::Dark8,
+    TableStyle::Dark9, TableStyle::Dark10, TableStyle::Dark11,
+];
+
+/// Built-in style for a name like `TableStyleMedium9` (the `TableStyle` prefix is optional)
+fn table_style(name: &str) -> Option<TableStyle> {
+    let name = name.trim();
+    let name = name.strip_prefix("TableStyle").unwrap_or(name);
+    if name == "None" {
+        return Some(TableStyle::None);
+    }
+
+    let split = name.find(|c: char| c.is_ascii_digit())?;
+    let (family, number) = name.split_at(split);
+    let styles: &[TableStyle] = match family {
+        "Light" => &LIGHT_TABLE_STYLES,
+        "Medium" => &MEDIUM_TABLE_STYLES,
+        "Dark" => &DARK_TABLE_STYLES,
+        _ => return None,
+    };
+    let index: usize = number.parse().ok()?;
+    styles.get(index.checked_sub(1)?).cloned()
+}
+
+/// Add a table over `spec.range`. A table writes its own header row, so when it starts
+/// on the sheet's header row those names are reused instead of Excel's "Column1", ...
+fn add_table(worksheet: &mut Worksheet, spec: &ExcelTable, headers: &[String]) -> Result<(), String> {
+    let (first_row, first_col, last_row, last_col) = parse_cell_range(&spec.range)?;
+    let total_row = spec.total_row.unwrap_or(false);
+    let min_rows = if total_row { 3 } else { 2 };
+    if last_row - first_row + 1 < min_rows {
+        return Err(format!(
+            "range {} needs at least {} rows (header, data{})",
+            spec.range,
+            min_rows,
+            if total_row { ", total" } else { "" }
+        ));
+    }
+
+    let mut table = Table::new().set_total_row(total_row);
+    if let Some(name) = &spec.style {
+        let style = table_style(name).ok_or_else(|| {
+            format!("unknown table style '{}' (use e.g. TableStyleLight1-21, TableStyleMedium1-28 or TableStyleDark1-11)", name)
+        })?;
+        table = table.set_style(style);
+    }
+    if first_row == 0 && !headers.is_empty() {
+        let columns: Vec<TableColumn> = (first_col..=last_col)
+            .map(|col| match headers.get(col as usize) {
+                Some(header) => TableColumn::new().set_header(header),
+                None => TableColumn::new(),
+            })
+            .collect();
+        table = table.set_columns(&columns);
+    }
+
+    worksheet
+        .add_table(first_row, first_col, last_row, last_col, &table)
+        .map_err(|e| format!("failed adding table {}: {}", spec.range, e))?;
     Ok(())
 }
 
+/// A note (comment) from a sheet's `notes` list
+#[derive(Debug, Deserialize)]
+struct CellNote {
+    row: u32,
+    col: u16,
+    author: Option<String>,
+    text: String,
+}
+
+const DEFAULT_NOTE_AUTHOR: &str = "Kuse Cowork";
+
+fn add_note(worksheet: &mut Worksheet, note: &CellNote) -> Result<(), String> {
+    if note.text.trim().is_empty() {
+        return Err("note text must not be empty".to_string());
+    }
+    let author = note.author.as_deref().filter(|a| !a.trim().is_empty()).unwrap_or(DEFAULT_NOTE_AUTHOR);
+    worksheet
+        .insert_note(note.row, note.col, &Note::new(&note.text).set_author(author))
+        .map_err(|e| format!("failed adding note at row {}, col {}: {}", note.row, note.col, e))?;
+    Ok(())
+}
+
+/// A merged cell range from a sheet's `merged_cells` list
+#[derive(Debug, Deserialize)]
+struct MergedCell {
+    from_row: u32,
+    from_col: u16,
+    to_row: u32,
+    to_col: u16,
+    #[serde(default)]
+    value: Option<serde_json::Value>,
+}
+
+impl MergedCell {
+    fn value_or_empty(&self) -> String {
+        match &self.value {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        }
+    }
+
+    fn range(&self) -> String {
+        cell_range(self.from_row, self.from_col, self.to_row, self.to_col)
+    }
+
+    fn overlaps(&self, other: &MergedCell) -> bool {
+        self.from_row <= other.to_row
+            && other.from_row <= self.to_row
+            && self.from_col <= other.to_col
+            && other.from_col <= self.to_col
+    }
+}
+
+/// Parse and validate merge ranges: each must span more than one cell and none may overlap
+fn parse_merged_cells(value: &serde_json::Value) -> Result<Vec<MergedCell>, String> {
+    let merges: Vec<MergedCell> = serde_json::from_value(value.clone())
+        .map_err(|e| format!("invalid merged cell: {}", e))?;
+
+    for merge in &merges {
+        if merge.from_row > merge.to_row || merge.from_col > merge.to_col {
+            return Err(format!("range {} must go from top-left to bottom-right", merge.range()));
+        }
+        if merge.from_row == merge.to_row && merge.from_col == merge.to_col {
+            return Err(format!("range {} is a single cell", merge.range()));
+        }
+    }
+
+    let conflicts: Vec<String> = merges
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            merges[i + 1..]
+                .iter()
+                .filter(move |b| a.overlaps(b))
+                .map(move |b| format!("{} and {}", a.range(), b.range()))
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(format!("overlapping merge ranges: {}", conflicts.join(", ")));
+    }
+
+    Ok(merges)
+}
+
+/// Parse `#RRGGBB` (leading `#` optional)
+fn parse_hex_color(color: &str) -> Result<Color, String> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Invalid color '{}', expected #RRGGBB", color));
+    }
+    u32::from_str_radix(hex, 16)
+        .map(Color::RGB)
+        .map_err(|_| format!("Invalid color '{}', expected #RRGGBB", color))
+}
+
+/// Parse a range like `A1:F20` (or a single cell) into
+/// `(first_row, first_col, last_row, last_col)` with zero-based indices.
+fn parse_cell_range(range: &str) -> Result<(u32, u16, u32, u16), String> {
+    let (start, end) = range.split_once(':').unwrap_or((range, range));
+    let (first_row, first_col) = parse_cell_ref(start)
+        .ok_or_else(|| format!("Invalid range '{}', expected e.g. A1:F20", range))?;
+    let (last_row, last_col) = parse_cell_ref(end)
+        .ok_or_else(|| format!("Invalid range '{}', expected e.g. A1:F20", range))?;
+
+    Ok((
+        first_row.min(last_row),
+        first_col.min(last_col),
+        first_row.max(last_row),
+        first_col.max(last_col),
+    ))
+}
+
+/// Parse an A1-style reference (dollar signs allowed) into zero-based `(row, col)`
+fn parse_cell_ref(cell: &str) -> Option<(u32, u16)> {
+    let cell = cell.trim().replace('$', "");
+    let split = cell.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let mut col: u32 = 0;
+    for c in letters.to_ascii_uppercase().chars() {
+        col = col * 26 + (c as u32 - 'A' as u32 + 1);
+    }
+    let row: u32 = digits.parse().ok()?;
+    if row == 0 || col == 0 || col > u16::MAX as u32 {
+        return None;
+    }
+
+    Some((row - 1, (col - 1) as u16))
+}
+
+/// Cell formats built so far in a workbook, keyed by their spec so each is created once
+#[derive(Default)]
+struct FormatRegistry {
+    number_formats: HashMap<String, Format>,
+}
+
+impl FormatRegistry {
+    fn number_format(&mut self, num_format: &str) -> &Format {
+        self.number_formats
+            .entry(num_format.to_string())
+            .or_insert_with(|| Format::new().set_num_format(num_format))
+    }
+}
+
+fn cell_number_format(obj: &serde_json::Map<String, serde_json::Value>) -> Option<&str> {
+    obj.get("number_format")
+        .and_then(|v| v.as_str())
+        .filter(|f| !f.trim().is_empty())
+}
+
 fn write_cell(
     worksheet: &mut rust_xlsxwriter::Worksheet,
+    formats: &mut FormatRegistry,
     row: u32,
     col: u16,
     cell: &serde_json::Value,
 ) -> Result<(), String> {
     if let Some(obj) = cell.as_object() {
+        let number_format = cell_number_format(obj);
+        if let Some(url) = obj.get("url").and_then(|v| v.as_str()) {
+            validate_url(url)?;
+            let written = match obj.get("text").and_then(|v| v.as_str()) {
+                Some(text) => worksheet.write_url_with_text(row, col, url, text),
+                None => worksheet.write_url(row, col, url),
+            };
+            return written
+                .map(|_| ())
+                .map_err(|e| format!("Failed writing url cell: {}", e));
+        }
         if let Some(formula) = obj.get("formula").and_then(|v| v.as_str()) {
             let fx = if formula.starts_with('=') {
                 formula.to_string()
             } else {
                 format!("={}", formula)
             };
-            worksheet
-                .write_formula(row, col, fx.as_str())
+            let written = match number_format {
+                Some(num_format) => {
+                    worksheet.write_formula_with_format(row, col, fx.as_str(), formats.number_format(num_format))
+                }
+                None => worksheet.write_formula(row, col, fx.as_str()),
+            };
+            written
                 .map(|_| ())
                 .map_err(|e| format!("Failed writing formula cell: {}", e))?;
             return Ok(());
         }
         if let Some(value) = obj.get("value") {
-            return write_cell(worksheet, row, col, value);
+            if let (Some(num_format), Some(num)) = (number_format, value.as_f64()) {
+                return worksheet
+                    .write_number_with_format(row, col, num, formats.number_format(num_format))
+                    .map(|_| ())
+                    .map_err(|e| format!("Failed writing number cell: {}", e));
+            }
+            return write_cell(worksheet, formats, row, col, value);
         }
     }
 
@@ -577,3 +1632,521 @@ fn write_cell(
             .map_err(|e| format!("Failed writing JSON cell: {}", e)),
     }
 }
+
+fn validate_url(url: &str) -> Result<(), String> {
+    const SCHEMES: [&str; 4] = ["http://", "https://", "mailto:", "file://"];
+    if SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid url '{}': must start with http://, https://, mailto: or file://",
+            url
+        ))
+    }
+}
+
+pub struct CreateXlsxTool;
+
+impl Tool for CreateXlsxTool {
+    fn definition(&self) -> ToolDefinition {
+        definition()
+    }
+
+    fn execute<'a>(&'a self, input: &'a serde_json::Value, project_path: Option<&'a str>) -> ToolFuture<'a> {
+        Box::pin(async move { crate::tools::text_result(execute(input, project_path)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chart_range() {
+        assert_eq!(
+            parse_chart_range("Sheet1!$A$1:$A$10").unwrap(),
+            ("Sheet1".to_string(), 0, 0, 9, 0)
+        );
+        assert_eq!(
+            parse_chart_range("'Q1 Sales'!B2:AA5").unwrap(),
+            ("Q1 Sales".to_string(), 1, 1, 4, 26)
+        );
+        assert!(parse_chart_range("$A$1:$A$10").is_err());
+        assert!(parse_chart_range("Sheet1!1A:2B").is_err());
+    }
+
+    #[test]
+    fn test_column_chart_written() {
+        let path = std::env::temp_dir().join(format!("kuse-chart-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Sales",
+                    "headers": ["Region", "Revenue"],
+                    "rows": [["North", 120], ["South", 95], ["West", 143]],
+                    "charts": [{
+                        "chart_type": "column",
+                        "title": "Revenue by Region",
+                        "data_range": "Sales!$B$2:$B$4",
+                        "position": { "row": 1, "col": 3 }
+                    }]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let chart_xml = read_zip_entry_string(&mut archive, "xl/charts/chart1.xml").unwrap();
+        assert!(chart_xml.contains("<c:barChart>"));
+        assert!(chart_xml.contains(r#"<c:barDir val="col"/>"#));
+        assert!(chart_xml.contains("Sales!$B$2:$B$4"));
+        assert!(chart_xml.contains("Revenue by Region"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_count_xml_formulas_accurate_expands_shared_ranges() {
+        let sheet_xml = concat!(
+            r#"<sheetData><row r="1"><c r="A1"><f>SUM(B1:B3)</f><v>6</v></c>"#,
+            r#"<c r="B1" t="str"><f>UPPER("a")</f><v>A</v></c></row>"#,
+            r#"<row r="2"><c r="C2"><f t="shared" ref="C2:C11" si="0">A2*2</f><v>0</v></c></row>"#,
+            r#"<row r="3"><c r="C3"><f t="shared" si="0"/><v>0</v></c></row>"#,
+            r#"<row r="4"><c r="C4"><f t="shared" si="0"/><v>0</v></c></row>"#,
+            r#"<row r="12"><c r="D12"><f t="array" ref="D12:D14">A2:A4*2</f></c></row>"#,
+            r#"</sheetData><conditionalFormatting sqref="A1"><cfRule type="expression">"#,
+            r#"<formula>$A1&gt;0</formula></cfRule></conditionalFormatting>"#,
+        );
+
+        // 2 plain formulas, 10 cells of the shared range and 1 array formula
+        assert_eq!(count_xml_formulas_accurate(sheet_xml), 13);
+        assert_eq!(count_xml_formulas_accurate("<sheetData/>"), 0);
+        assert_eq!(
+            count_xml_formulas_accurate(r#"<c r="E1"><f t="shared" ref="E1:F2" si="1">1</f></c>"#),
+            4
+        );
+    }
+
+    #[test]
+    fn test_parse_cell_range() {
+        assert_eq!(parse_cell_range("A1:F20").unwrap(), (0, 0, 19, 5));
+        assert_eq!(parse_cell_range("$C$5").unwrap(), (4, 2, 4, 2));
+        assert_eq!(parse_cell_range("F20:A1").unwrap(), (0, 0, 19, 5));
+        assert!(parse_cell_range("Sheet1!A1:B2").is_err());
+    }
+
+    #[test]
+    fn test_conditional_format_written() {
+        let path = std::env::temp_dir().join(format!("kuse-cond-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "P&L",
+                    "headers": ["Month", "Net"],
+                    "rows": [["Jan", 1200], ["Feb", -340], ["Mar", 560]],
+                    "conditional_formats": [
+                        {
+                            "range": "B2:B4",
+                            "rule": { "type": "cell", "operator": "less_than", "value": 0 },
+                            "format": { "bg_color": "#FFC7CE", "font_color": "#9C0006", "bold": true }
+                        },
+                        {
+                            "range": "A2:B4",
+                            "rule": { "type": "formula", "formula": "$B2>1000" },
+                            "format": { "bg_color": "#C6EFCE" }
+                        }
+                    ]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(sheet_xml.contains(r#"<conditionalFormatting sqref="B2:B4">"#));
+        assert!(sheet_xml.contains(r#"type="cellIs""#));
+        assert!(sheet_xml.contains(r#"operator="lessThan""#));
+        assert!(sheet_xml.contains(r#"<conditionalFormatting sqref="A2:B4">"#));
+        assert!(sheet_xml.contains("$B2&gt;1000"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_conditional_format_rejects_bad_rule() {
+        let path = std::env::temp_dir().join(format!("kuse-cond-bad-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Data",
+                    "rows": [[1], [2]],
+                    "conditional_formats": [{
+                        "range": "A1:A2",
+                        "rule": { "type": "cell", "operator": "between", "value": 5 },
+                        "format": { "bg_color": "#FFC7CE" }
+                    }]
+                }]
+            }
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("conditional_formats[0]"), "{}", err);
+        assert!(err.contains("[min, max]"), "{}", err);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_table_with_style_and_total_row_written() {
+        let path = std::env::temp_dir().join(format!("kuse-table-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Orders",
+                    "headers": ["Product", "Units"],
+                    "rows": [["Pens", 40], ["Paper", 12], ["Ink", 7]],
+                    "tables": [{ "range": "A1:B5", "style": "TableStyleMedium9", "total_row": true }]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(sheet_xml.contains(r#"<tableParts count="1">"#), "{}", sheet_xml);
+        let table_xml = read_zip_entry_string(&mut archive, "xl/tables/table1.xml").unwrap();
+        assert!(table_xml.contains(r#"ref="A1:B5""#), "{}", table_xml);
+        assert!(table_xml.contains(r#"totalsRowCount="1""#));
+        assert!(table_xml.contains(r#"name="TableStyleMedium9""#));
+        assert!(table_xml.contains(r#"name="Product""#));
+        assert!(table_xml.contains(r#"name="Units""#));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_cell_notes_written() {
+        let path = std::env::temp_dir().join(format!("kuse-notes-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Audit",
+                    "headers": ["Account", "Balance"],
+                    "rows": [["Cash", 1200], ["Receivables", 560]],
+                    "notes": [
+                        { "row": 1, "col": 1, "author": "J. Auditor", "text": "Agreed to bank statement" },
+                        { "row": 2, "col": 1, "text": "Aging report pending" }
+                    ]
+                }]
+            }
+        });
+
+        let message = execute(&input, None).unwrap();
+        assert!(!message.contains("Warning"), "{}", message);
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        assert!(archive.file_names().any(|n| n == "xl/drawings/vmlDrawing1.vml"));
+        let comments_xml = read_zip_entry_string(&mut archive, "xl/comments1.xml").unwrap();
+        assert!(comments_xml.contains("Agreed to bank statement"), "{}", comments_xml);
+        assert!(comments_xml.contains("<author>J. Auditor</author>"), "{}", comments_xml);
+        assert!(comments_xml.contains(&format!("<author>{}</author>", DEFAULT_NOTE_AUTHOR)));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_note_outside_data_warns() {
+        let path = std::env::temp_dir().join(format!("kuse-notes-warn-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Data",
+                    "rows": [[1, 2]],
+                    "notes": [{ "row": 5, "col": 0, "text": "Fill in later" }]
+                }]
+            }
+        });
+
+        let message = execute(&input, None).unwrap();
+        assert!(message.contains("Warning: workbook.sheets[0].notes[0] at row 5, col 0 is outside"), "{}", message);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_table_style_names() {
+        assert!(matches!(table_style("TableStyleMedium9"), Some(TableStyle::Medium9)));
+        assert!(matches!(table_style("Light21"), Some(TableStyle::Light21)));
+        assert!(matches!(table_style("TableStyleDark11"), Some(TableStyle::Dark11)));
+        assert!(matches!(table_style("TableStyleNone"), Some(TableStyle::None)));
+        assert!(table_style("TableStyleMedium29").is_none());
+        assert!(table_style("TableStyleLight0").is_none());
+        assert!(table_style("Fancy3").is_none());
+    }
+
+    #[test]
+    fn test_table_rejects_unknown_style() {
+        let path = std::env::temp_dir().join(format!("kuse-table-bad-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Data",
+                    "headers": ["A"],
+                    "rows": [[1], [2]],
+                    "tables": [{ "range": "A1:A3", "style": "TableStyleMedium99" }]
+                }]
+            }
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("tables[0]"), "{}", err);
+        assert!(err.contains("TableStyleMedium99"), "{}", err);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_merged_header_written() {
+        let path = std::env::temp_dir().join(format!("kuse-merge-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Quarterly",
+                    "rows": [[null, null], ["Jan", "Feb"], [100, 120]],
+                    "merged_cells": [
+                        { "from_row": 0, "from_col": 0, "to_row": 0, "to_col": 1, "value": "Q1 2025" }
+                    ]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(sheet_xml.contains(r#"<mergeCell ref="A1:B1"/>"#), "{}", sheet_xml);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_overlapping_merges_rejected() {
+        let path = std::env::temp_dir().join(format!("kuse-merge-bad-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Data",
+                    "rows": [[1, 2, 3]],
+                    "merged_cells": [
+                        { "from_row": 0, "from_col": 0, "to_row": 0, "to_col": 1 },
+                        { "from_row": 0, "from_col": 1, "to_row": 1, "to_col": 2 }
+                    ]
+                }]
+            }
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("merged_cells"), "{}", err);
+        assert!(err.contains("overlapping merge ranges: A1:B1 and B1:C2"), "{}", err);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_named_range_used_in_formula() {
+        let path = std::env::temp_dir().join(format!("kuse-names-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [
+                    { "name": "Sales", "headers": ["Amount"], "rows": [[100], [250], [75]] },
+                    { "name": "Summary", "rows": [["Total", { "formula": "=SUM(SalesData)" }]] }
+                ],
+                "named_ranges": [{ "name": "SalesData", "formula": "Sales!$A$2:$A$4" }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let workbook_xml = read_zip_entry_string(&mut archive, "xl/workbook.xml").unwrap();
+        assert!(
+            workbook_xml.contains(r#"<definedName name="SalesData">Sales!$A$2:$A$4</definedName>"#),
+            "{}",
+            workbook_xml
+        );
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet2.xml").unwrap();
+        assert!(sheet_xml.contains("<f>SUM(SalesData)</f>"), "{}", sheet_xml);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_invalid_range_name_rejected() {
+        assert!(is_valid_range_name("SalesData"));
+        assert!(is_valid_range_name("_q1_totals"));
+        assert!(!is_valid_range_name("2024Sales"));
+        assert!(!is_valid_range_name("Sales Data"));
+        assert!(!is_valid_range_name(""));
+
+        let path = std::env::temp_dir().join(format!("kuse-names-bad-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{ "name": "Sales", "rows": [[1]] }],
+                "named_ranges": [{ "name": "1st", "formula": "Sales!$A$1" }]
+            }
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("workbook.named_ranges[0]: invalid name '1st'"), "{}", err);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_landscape_a4_print_settings_written() {
+        let path = std::env::temp_dir().join(format!("kuse-print-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Report",
+                    "headers": ["Region", "Revenue"],
+                    "rows": [["North", 1200], ["South", 950]],
+                    "print_settings": {
+                        "orientation": "landscape",
+                        "paper_size": "A4",
+                        "fit_to_page": true,
+                        "margin_left": 0.5,
+                        "footer": "&CPage &P of &N"
+                    }
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(sheet_xml.contains("<pageSetup"), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"paperSize="9""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"orientation="landscape""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<pageMargins left="0.5""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains("<oddFooter>&amp;CPage &amp;P of &amp;N</oddFooter>"), "{}", sheet_xml);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_print_settings_reject_unknown_paper_size() {
+        let input = json!({
+            "path": std::env::temp_dir().join("kuse-print-bad.xlsx").to_string_lossy(),
+            "workbook": {
+                "sheets": [{ "name": "Report", "rows": [[1]], "print_settings": { "paper_size": "B5" } }]
+            }
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("workbook.sheets[0].print_settings: unknown paper_size 'B5'"), "{}", err);
+    }
+
+    #[test]
+    fn test_url_cell_written_as_hyperlink() {
+        let path = std::env::temp_dir().join(format!("kuse-url-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Links",
+                    "rows": [[{ "url": "https://example.com/report", "text": "Full report" }, 42]]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(sheet_xml.contains("<hyperlinks>"), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<hyperlink ref="A1""#), "{}", sheet_xml);
+        let rels = read_zip_entry_string(&mut archive, "xl/worksheets/_rels/sheet1.xml.rels").unwrap();
+        assert!(rels.contains("https://example.com/report"), "{}", rels);
+        let strings = read_zip_entry_string(&mut archive, "xl/sharedStrings.xml").unwrap();
+        assert!(strings.contains("Full report"), "{}", strings);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_url_cell_rejects_unknown_scheme() {
+        let path = std::env::temp_dir().join(format!("kuse-url-bad-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "rows": [[{ "url": "javascript:alert(1)" }]]
+        });
+
+        let err = execute(&input, None).unwrap_err();
+        assert!(err.contains("Invalid url"), "{}", err);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_number_formats_written_as_cell_styles() {
+        let path = std::env::temp_dir().join(format!("kuse-numfmt-{}.xlsx", uuid::Uuid::new_v4()));
+        let input = json!({
+            "path": path.to_string_lossy(),
+            "workbook": {
+                "sheets": [{
+                    "name": "Budget",
+                    "headers": ["Due", "Amount", "Share", "Total"],
+                    "rows": [[
+                        { "value": 45292, "number_format": "YYYY-MM-DD" },
+                        { "value": 1234.5, "number_format": "$#,##0.00" },
+                        { "value": 0.25, "number_format": "0%" },
+                        { "formula": "B2*2", "number_format": "$#,##0.00" }
+                    ]]
+                }]
+            }
+        });
+
+        execute(&input, None).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let sheet_xml = read_zip_entry_string(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert_eq!(count_xml_styled_cells(&sheet_xml), 4, "{}", sheet_xml);
+        // Header cells keep the default style; the two currency cells share one
+        assert!(!sheet_xml.contains(r#"<c r="A1" s="#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<c r="A2" s="1""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<c r="B2" s="2""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<c r="C2" s="3""#), "{}", sheet_xml);
+        assert!(sheet_xml.contains(r#"<c r="D2" s="2""#), "{}", sheet_xml);
+        let styles = read_zip_entry_string(&mut archive, "xl/styles.xml").unwrap();
+        assert!(styles.contains("YYYY-MM-DD"), "{}", styles);
+        assert!(styles.contains("$#,##0.00"), "{}", styles);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -120,7 +120,7 @@ const App: Component = () => {
       // Refresh task to get final state
       const updated = await getTask(task.id);
       if (updated) {
-        setActiveTask(updated);
+        setActiveTask(updated.task);
       }
       // Reload messages to show saved conversation
       const messages = await getTaskMessages(task.id);
@@ -274,7 +274,7 @@ const App: Component = () => {
       // Refresh task to get final state
       const updated = await getTask(task.id);
       if (updated) {
-        setActiveTask(updated);
+        setActiveTask(updated.task);
       }
       // Reload messages to show saved conversation
       const messages = await getTaskMessages(task.id);
@@ -293,7 +293,8 @@ const App: Component = () => {
 
   // Delete a task
   const handleDeleteTask = async (taskId: string) => {
-    await deleteTask(taskId);
+    // Deleting from the sidebar is an explicit user action
+    await deleteTask(taskId, true);
     // If we deleted the active task, clear it
     if (activeTask()?.id === taskId) {
       setActiveTask(null);
@@ -1,7 +1,7 @@
 import { Component, For, Show, createSignal } from "solid-js";
 import { useChat } from "../stores/chat";
 import { useSettings } from "../stores/settings";
-import { sendChatMessage, sendChatWithTools, ChatEvent, isTauri } from "../lib/tauri-api";
+import { sendChatMessage, sendChatWithTools, ChatEvent, ChatImageAttachment, isTauri } from "../lib/tauri-api";
 import "./Chat.css";
 
 interface ToolExecution {
@@ -32,6 +32,7 @@ const Chat: Component = () => {
   const [projectPath, setProjectPath] = createSignal("");
   const [toolExecutions, setToolExecutions] = createSignal<ToolExecution[]>([]);
   const [showProjectInput, setShowProjectInput] = createSignal(false);
+  const [pastedImages, setPastedImages] = createSignal<ChatImageAttachment[]>([]);
   let messagesEnd: HTMLDivElement | undefined;
 
   const scrollToBottom = () => {
@@ -89,6 +90,48 @@ const Chat: Component = () => {
     }
   };
 
+  const toBase64 = (file: File): Promise<string> => new Promise((resolve, reject) => {
+    const reader = new FileReader();
+    reader.onload = () => {
+      const result = String(reader.result || "");
+      const comma = result.indexOf(",");
+      if (comma === -1) {
+        reject(new Error("Invalid data URL"));
+        return;
+      }
+      resolve(result.slice(comma + 1));
+    };
+    reader.onerror = () => reject(reader.error || new Error("Failed to read file"));
+    reader.readAsDataURL(file);
+  });
+
+  const handlePaste = (e: ClipboardEvent) => {
+    const items = e.clipboardData?.items;
+    if (!items || items.length === 0) return;
+
+    const imageFiles: File[] = [];
+    for (let i = 0; i < items.length; i += 1) {
+      const item = items[i];
+      if (item.kind === "file" && item.type.startsWith("image/")) {
+        const file = item.getAsFile();
+        if (file) imageFiles.push(file);
+      }
+    }
+
+    if (imageFiles.length === 0) return;
+    e.preventDefault();
+
+    void Promise.all(imageFiles.map(async (file) => ({
+      name: file.name || `pasted-image-${Date.now()}.png`,
+      media_type: file.type || "image/png",
+      data: await toBase64(file),
+    }))).then((newImages) => {
+      setPastedImages((prev) => [...prev, ...newImages]);
+    }).catch((err) => {
+      console.error("Failed to process pasted image:", err);
+    });
+  };
+
   const handleSubmit = async (e: Event) => {
     e.preventDefault();
     const text = input().trim();
@@ -101,7 +144,9 @@ const Chat: Component = () => {
       convId = conv.id;
     }
 
+    const images = pastedImages();
     setInput("");
+    setPastedImages([]);
     setToolExecutions([]); // Reset tool executions
     addLocalMessage("user", text);
     addLocalMessage("assistant", "");
@@ -110,7 +155,8 @@ const Chat: Component = () => {
 
     try {
       // Use enhanced chat with tools if enabled and in Tauri
-      if (enableTools() && isTauri()) {
+      // Pasted images go through simple chat, which sends them as content blocks
+      if (enableTools() && isTauri() && images.length === 0) {
         await sendChatWithTools(
           {
             conversation_id: convId,
@@ -125,7 +171,7 @@ const Chat: Component = () => {
         await sendChatMessage(convId, text, (streamedText) => {
           updateLastMessage(streamedText);
           scrollToBottom();
-        });
+        }, images);
       }
       // Refresh conversations to get updated title
       await refreshConversations();
@@ -246,10 +292,29 @@ const Chat: Component = () => {
           </Show>
         </div>
 
+        <Show when={pastedImages().length > 0}>
+          <div class="pasted-images">
+            <For each={pastedImages()}>
+              {(img, index) => (
+                <span class="pasted-image-chip">
+                  {img.name}
+                  <button
+                    type="button"
+                    onClick={() => setPastedImages((prev) => prev.filter((_, i) => i !== index()))}
+                  >
+                    ×
+                  </button>
+                </span>
+              )}
+            </For>
+          </div>
+        </Show>
+
         <form class="input-form" onSubmit={handleSubmit}>
           <textarea
             value={input()}
             onInput={(e) => setInput(e.currentTarget.value)}
+            onPaste={handlePaste}
             onKeyDown={(e) => {
               if (e.key === "Enter" && !e.shiftKey) {
                 e.preventDefault();
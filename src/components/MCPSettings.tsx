@@ -18,7 +18,7 @@ interface MCPSettingsProps {
 
 type MCPPreset = {
   label: string;
-  transport: "http" | "stdio";
+  transport: "http" | "stdio" | "websocket";
   serverUrl?: string;
   launchCommand?: string;
   launchArgs?: string;
@@ -84,7 +84,7 @@ const MCPSettings: Component<MCPSettingsProps> = (props) => {
   // Form state
   const [formData, setFormData] = createSignal({
     name: "",
-    transport: "http" as "http" | "stdio",
+    transport: "http" as "http" | "stdio" | "websocket",
     serverUrl: "",
     launchCommand: "",
     launchArgs: "",
@@ -130,7 +130,7 @@ const MCPSettings: Component<MCPSettingsProps> = (props) => {
   const resetForm = () => {
     setFormData({
       name: "",
-      transport: "http" as "http" | "stdio",
+      transport: "http" as "http" | "stdio" | "websocket",
       serverUrl: "",
       launchCommand: "",
       launchArgs: "",
@@ -375,10 +375,11 @@ const MCPSettings: Component<MCPSettingsProps> = (props) => {
               <label>Transport</label>
               <select
                 value={formData().transport}
-                onInput={(e) => setFormData(prev => ({ ...prev, transport: e.currentTarget.value as "http" | "stdio" }))}
+                onInput={(e) => setFormData(prev => ({ ...prev, transport: e.currentTarget.value as "http" | "stdio" | "websocket" }))}
               >
                 <option value="http">HTTP</option>
                 <option value="stdio">stdio (local process)</option>
+                <option value="websocket">WebSocket</option>
               </select>
             </div>
 
@@ -388,12 +389,20 @@ const MCPSettings: Component<MCPSettingsProps> = (props) => {
                 type="url"
                 value={formData().serverUrl}
                 onInput={(e) => setFormData(prev => ({ ...prev, serverUrl: e.currentTarget.value }))}
-                placeholder={formData().transport === "http" ? "https://your-mcp-server.com" : "http://127.0.0.1:8787"}
+                placeholder={
+                  formData().transport === "http"
+                    ? "https://your-mcp-server.com"
+                    : formData().transport === "websocket"
+                      ? "ws://127.0.0.1:9222/mcp"
+                      : "http://127.0.0.1:8787"
+                }
               />
               <small class="hint">
                 {formData().transport === "http"
                   ? "Endpoint used for MCP HTTP transport."
-                  : "Optional HTTP endpoint when your stdio-launched server also exposes HTTP (otherwise keep empty)."}
+                  : formData().transport === "websocket"
+                    ? "ws:// or wss:// endpoint used for MCP WebSocket transport."
+                    : "Optional HTTP endpoint when your stdio-launched server also exposes HTTP (otherwise keep empty)."}
               </small>
             </div>
 
@@ -74,7 +74,7 @@ const ModelSelector: Component<ModelSelectorProps> = (props) => {
     },
     aggregator: {
       name: "Aggregation Services",
-      providers: ["openrouter", "together", "groq", "siliconflow"]
+      providers: ["openrouter", "together", "groq", "siliconflow", "perplexity"]
     }
   };
 
@@ -445,8 +445,8 @@ class OpenAICompatibleProvider implements AIProvider {
     // Normalize API endpoint
     const url = baseUrl.replace(/\/$/, ""); // Remove trailing slash
 
-    // If already contains /v1, use directly
-    if (url.endsWith("/v1")) {
+    // If already contains /v1, use directly (Perplexity has no version prefix)
+    if (url.endsWith("/v1") || url.includes("api.perplexity.ai")) {
       return `${url}/chat/completions`;
     }
 
@@ -768,7 +768,7 @@ const providers: Record<string, AIProvider> = {
 const openaiCompatibleProviders = [
   "ollama", "localai",
   "vllm", "tgi", "sglang",
-  "openrouter", "together", "groq", "deepseek", "siliconflow",
+  "openrouter", "together", "groq", "deepseek", "siliconflow", "perplexity",
   "custom"
 ];
 
@@ -3,15 +3,18 @@ import { invoke } from "@tauri-apps/api/core";
 export interface MCPServerConfig {
   id: string;
   name: string;
-  transport: "http" | "stdio";
+  transport: "http" | "stdio" | "websocket";
   server_url: string;
   launch_command?: string;
   launch_args: string[];
   launch_env: Record<string, string>;
   working_dir?: string;
   startup_timeout_ms?: number;
+  startup_retry_attempts?: number;
+  refresh_interval_secs?: number;  // Re-fetch the tool list this often while connected
   oauth_client_id?: string;
   oauth_client_secret?: string;
+  oauth_flow?: "client_credentials" | "device_code";
   enabled: boolean;
   created_at: string;
   updated_at: string;
@@ -24,16 +27,66 @@ export interface MCPTool {
   input_schema: any;
 }
 
+export interface PromptArgument {
+  name: string;
+  description: string;
+  required: boolean;
+}
+
+export interface MCPPrompt {
+  server_id: string;
+  name: string;
+  description: string;
+  arguments: PromptArgument[];
+}
+
+// Result of prompts/get: the filled-in prompt messages
+export interface MCPPromptResult {
+  description?: string;
+  messages: { role: string; content: any }[];
+}
+
+// Payload of the "mcp-startup-complete" event
+export interface MCPStartupSummary {
+  connected: number;
+  failed: number;
+}
+
+// Payload of the "mcp-oauth-device-code" event: show user_code and open verification_url
+export interface MCPDeviceCodePrompt {
+  server_id: string;
+  user_code: string;
+  verification_url: string;
+}
+
+// Payload of the "mcp-status-changed" event
+export interface MCPStatusChangedPayload {
+  server_id: string;
+  name: string;
+  status: MCPServerStatus["status"];
+  error?: string;
+}
+
+export interface MCPConnectionEvent {
+  timestamp: number;
+  server_id: string;
+  from_status: MCPServerStatus["status"];
+  to_status: MCPServerStatus["status"];
+  error?: string | null;
+}
+
 export interface MCPServerStatus {
   id: string;
   name: string;
   transport: string;
   status: "Connected" | "Disconnected" | "Connecting" | "Error";
   tools: MCPTool[];
+  prompts: MCPPrompt[];
   last_error?: string;
   managed_process: boolean;
   pid?: number;
   endpoint?: string;
+  tool_call_timeout_secs?: number;
 }
 
 export interface MCPToolCall {
@@ -76,6 +129,35 @@ export async function getMCPServerStatuses(): Promise<MCPServerStatus[]> {
   return invoke("get_mcp_server_statuses");
 }
 
+// Last 50 stderr lines of a stdio server, cleared on disconnect
+export async function getMCPServerLogs(serverId: string): Promise<string[]> {
+  return invoke("get_mcp_server_logs", { serverId });
+}
+
+export async function getMCPConnectionHistory(serverId?: string): Promise<MCPConnectionEvent[]> {
+  return invoke("get_mcp_connection_history", { serverId: serverId ?? null });
+}
+
+export async function setMCPToolTimeout(serverId: string, timeoutSecs: number): Promise<void> {
+  return invoke("set_mcp_tool_timeout", { serverId, timeoutSecs });
+}
+
 export async function executeMCPTool(call: MCPToolCall): Promise<MCPToolResult> {
   return invoke("execute_mcp_tool", { call });
 }
+
+export async function refreshMCPServerTools(serverId: string): Promise<void> {
+  return invoke("refresh_mcp_server_tools", { serverId });
+}
+
+export async function listMCPPrompts(serverId: string): Promise<MCPPrompt[]> {
+  return invoke("list_mcp_prompts", { serverId });
+}
+
+export async function getMCPPrompt(
+  serverId: string,
+  name: string,
+  args: Record<string, string> = {}
+): Promise<MCPPromptResult> {
+  return invoke("get_mcp_prompt", { serverId, name, args });
+}
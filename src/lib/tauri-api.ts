@@ -12,6 +12,17 @@ export interface Settings {
   provider_keys: Record<string, string>;  // Provider-specific API keys
   openai_organization?: string;  // Optional OpenAI Organization ID
   openai_project?: string;  // Optional OpenAI Project ID
+  auto_title_model?: string;  // Optional cheaper model for conversation titles
+  proxy_url?: string;  // Optional HTTP/HTTPS proxy for cloud providers
+  proxy_no_proxy?: string;  // Comma-separated hosts that bypass the proxy
+  disable_streaming?: boolean;  // Request complete responses for endpoints that strip SSE
+  system_prompt?: string;  // Global system prompt for chat and agent runs
+  system_prompt_mode?: "prepend" | "replace";  // How system_prompt combines with the built-in prompt
+  max_agent_turns?: number;  // Default turn limit for agent runs (30 when unset)
+  max_agent_duration_secs?: number;  // Default wall-clock limit for agent runs
+  max_concurrent_agents?: number;  // Agent runs allowed at once (2 when unset); later runs queue
+  mcp_tool_timeout_secs?: number;  // Default MCP tool call timeout (60 when unset); applied on restart
+  allow_reads_outside_workspace?: boolean;  // Let the agent read files outside the mounted folders (off when unset)
 }
 
 export interface Conversation {
@@ -19,6 +30,8 @@ export interface Conversation {
   title: string;
   created_at: number;
   updated_at: number;
+  deleted_at?: number;
+  project_path?: string | null;
 }
 
 export interface Message {
@@ -27,6 +40,7 @@ export interface Message {
   role: "user" | "assistant";
   content: string;
   timestamp: number;
+  message_type?: string | null;
 }
 
 interface StreamPayload {
@@ -40,15 +54,40 @@ export interface AgentRequest {
   project_path?: string;
   system_prompt?: string;
   max_turns?: number;
+  conversation_id?: string;
 }
 
 export type AgentEvent =
   | { type: "text"; content: string }
+  | { type: "thinking_content"; content: string }
+  | { type: "thinking"; content: string; signature: string | null }
   | { type: "plan"; steps: PlanStepInfo[] }
   | { type: "step_start"; step: number }
   | { type: "step_done"; step: number }
-  | { type: "tool_start"; tool: string; input: Record<string, unknown> }
-  | { type: "tool_end"; tool: string; result: string; success: boolean }
+  | {
+      type: "tool_start";
+      tool: string;
+      input: Record<string, unknown>;
+      tool_type: "native" | "mcp";
+      server_id?: string | null;
+    }
+  | {
+      type: "tool_end";
+      tool: string;
+      result: string;
+      success: boolean;
+      tool_type: "native" | "mcp";
+      duration_ms: number;
+    }
+  | { type: "tool_result_truncated"; tool: string; original_chars: number; truncated_to: number }
+  | {
+      type: "usage";
+      input_tokens: number;
+      output_tokens: number;
+      cache_creation_input_tokens?: number;
+      cache_read_input_tokens?: number;
+    }
+  | { type: "queued"; position: number }
   | { type: "turn_complete"; turn: number }
   | { type: "done"; total_turns: number }
   | { type: "error"; message: string };
@@ -69,6 +108,31 @@ export interface Task {
   project_path: string | null;
   created_at: number;
   updated_at: number;
+  archived: boolean;
+  template_id?: string | null;
+}
+
+export interface TaskTemplate {
+  id: string;
+  name: string;
+  description: string;
+  system_prompt: string;
+  allowed_tools: string[];
+  max_turns: number | null;
+  created_at: number;
+}
+
+export interface AgentSessionStats {
+  total_turns: number;
+  total_input_tokens: number;
+  total_output_tokens: number;
+  duration_ms: number;
+}
+
+export interface TaskWithStats {
+  task: Task;
+  last_session: AgentSessionStats | null;
+  message_count: number;
 }
 
 export interface PlanStep {
@@ -88,6 +152,10 @@ export interface TaskAgentRequest {
     data: string; // base64 payload (without data URL prefix)
   }>;
   max_turns?: number;
+  output_schema?: Record<string, unknown>;
+  // File paths or glob patterns added to the system prompt (20 KB total, 4 KB per file)
+  inject_context_files?: string[];
+  auto_inject_readme?: boolean;
 }
 
 export interface TaskMessage {
@@ -98,9 +166,18 @@ export interface TaskMessage {
   timestamp: number;
 }
 
+export interface SkillParam {
+  name: string;
+  required: boolean;
+  default?: string;
+  description: string;
+}
+
 export interface SkillMetadata {
   name: string;
   description: string;
+  parameters?: SkillParam[];
+  parent_skill?: string;
 }
 
 export interface LocalModelInfo {
@@ -116,19 +193,29 @@ export interface LocalServiceStatus {
   error?: string;
 }
 
+export interface ProviderHealthStatus {
+  provider_id: string;
+  name: string;
+  reachable: boolean;
+  latency_ms?: number;
+  error?: string;
+}
+
 // Enhanced chat with tools
 export interface EnhancedChatRequest {
   conversation_id: string;
   content: string;
   project_path?: string;
   enable_tools: boolean;
+  max_context_messages?: number;
 }
 
 export type ChatEvent =
   | { type: "text"; content: string }
   | { type: "tool_start"; tool: string; input: Record<string, unknown> }
   | { type: "tool_end"; tool: string; result: string; success: boolean }
-  | { type: "done"; final_text: string };
+  | { type: "done"; final_text: string }
+  | { type: "context_truncated"; removed: number };
 
 // Check if running in Tauri (Tauri 2.x uses __TAURI_INTERNALS__)
 export function isTauri(): boolean {
@@ -215,20 +302,53 @@ export async function listConversations(): Promise<Conversation[]> {
   return invoke<Conversation[]>("list_conversations");
 }
 
-export async function createConversation(title: string): Promise<Conversation> {
+export interface ConversationPage {
+  items: Conversation[];
+  next_cursor: string | null;
+  total_count: number;
+}
+
+export async function listConversationsPaginated(
+  limit: number,
+  beforeId?: string
+): Promise<ConversationPage> {
+  if (!isTauri()) {
+    const conversations = await listConversations();
+    const start = beforeId ? conversations.findIndex((c) => c.id === beforeId) + 1 : 0;
+    const items = start > 0 || !beforeId ? conversations.slice(start, start + limit) : [];
+    const hasMore = start + limit < conversations.length && items.length === limit;
+    return {
+      items,
+      next_cursor: hasMore ? items[items.length - 1].id : null,
+      total_count: conversations.length,
+    };
+  }
+  return invoke<ConversationPage>("list_conversations_paginated", { limit, beforeId: beforeId ?? null });
+}
+
+export async function createConversation(title: string, projectPath?: string): Promise<Conversation> {
   if (!isTauri()) {
     const conv: Conversation = {
       id: crypto.randomUUID(),
       title,
       created_at: Date.now(),
       updated_at: Date.now(),
+      project_path: projectPath ?? null,
     };
     const conversations = await listConversations();
     conversations.unshift(conv);
     localStorage.setItem("kuse-cowork-conversations", JSON.stringify(conversations));
     return conv;
   }
-  return invoke<Conversation>("create_conversation", { title });
+  return invoke<Conversation>("create_conversation", { title, projectPath: projectPath ?? null });
+}
+
+export async function listConversationsForProject(projectPath: string): Promise<Conversation[]> {
+  if (!isTauri()) {
+    const conversations = await listConversations();
+    return conversations.filter((c) => c.project_path === projectPath);
+  }
+  return invoke<Conversation[]>("list_conversations_for_project", { projectPath });
 }
 
 export async function updateConversationTitle(
@@ -259,6 +379,65 @@ export async function deleteConversation(id: string): Promise<void> {
   return invoke("delete_conversation", { id });
 }
 
+export async function listDeletedConversations(): Promise<Conversation[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<Conversation[]>("list_deleted_conversations");
+}
+
+export async function restoreConversation(id: string): Promise<void> {
+  return invoke("restore_conversation", { id });
+}
+
+export async function purgeConversation(id: string): Promise<void> {
+  return invoke("purge_conversation", { id });
+}
+
+export async function purgeConversationsOlderThan(days: number): Promise<number> {
+  return invoke<number>("purge_conversations_older_than", { days });
+}
+
+export interface BulkDeleteResult {
+  deleted_count: number;
+  not_found: string[];
+  message_count: number;
+}
+
+export async function deleteConversations(
+  ids: string[],
+  dryRun = false
+): Promise<BulkDeleteResult> {
+  if (!isTauri()) {
+    const conversations = await listConversations();
+    const existing = new Set(conversations.map((c) => c.id));
+    const targets = ids.filter((id) => existing.has(id));
+    const messageCount = targets.reduce((count, id) => {
+      const stored = localStorage.getItem(`kuse-cowork-messages-${id}`);
+      return count + (stored ? JSON.parse(stored).length : 0);
+    }, 0);
+    if (!dryRun) {
+      for (const id of targets) {
+        await deleteConversation(id);
+      }
+    }
+    return {
+      deleted_count: targets.length,
+      not_found: ids.filter((id) => !existing.has(id)),
+      message_count: messageCount,
+    };
+  }
+  return invoke<BulkDeleteResult>("delete_conversations", { ids, dryRun });
+}
+
+export async function deleteAllConversations(dryRun = false): Promise<BulkDeleteResult> {
+  if (!isTauri()) {
+    const conversations = await listConversations();
+    return deleteConversations(conversations.map((c) => c.id), dryRun);
+  }
+  return invoke<BulkDeleteResult>("delete_all_conversations", { dryRun });
+}
+
 // Messages API
 export async function getMessages(conversationId: string): Promise<Message[]> {
   if (!isTauri()) {
@@ -275,11 +454,41 @@ function saveMessagesLocal(conversationId: string, messages: Message[]) {
   );
 }
 
+// Content a message had before one of its edits
+export interface MessageEdit {
+  id: string;
+  message_id: string;
+  old_content: string;
+  edited_at: number;
+}
+
+export async function editMessage(id: string, content: string): Promise<Message> {
+  return invoke<Message>("edit_message", { id, content });
+}
+
+export async function editTaskMessage(id: string, content: string): Promise<TaskMessage> {
+  return invoke<TaskMessage>("edit_task_message", { id, content });
+}
+
+export async function getMessageEditHistory(messageId: string): Promise<MessageEdit[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<MessageEdit[]>("get_message_edit_history", { messageId });
+}
+
 // Chat API with streaming
+export interface ChatImageAttachment {
+  name?: string;
+  media_type: string;
+  data: string; // base64 payload (without data URL prefix)
+}
+
 export async function sendChatMessage(
   conversationId: string,
   content: string,
-  onStream: (text: string) => void
+  onStream: (text: string) => void,
+  images?: ChatImageAttachment[]
 ): Promise<string> {
   if (!isTauri()) {
     // Web fallback - direct API call
@@ -343,6 +552,7 @@ export async function sendChatMessage(
     const response = await invoke<string>("send_chat_message", {
       conversationId,
       content,
+      images: images && images.length > 0 ? images : null,
     });
 
     return response;
@@ -381,6 +591,38 @@ export async function runAgent(
   }
 }
 
+export async function getAgentQueueDepth(): Promise<number> {
+  if (!isTauri()) {
+    return 0;
+  }
+  return invoke<number>("get_agent_queue_depth");
+}
+
+export interface PreviewMessage {
+  role: "user" | "assistant";
+  content: string;
+}
+
+export interface ToolCallPreview {
+  tool: string;
+  input: Record<string, unknown>;
+  estimated_risk: "read_only" | "write" | "exec";
+}
+
+export async function previewToolCalls(messages: PreviewMessage[]): Promise<ToolCallPreview[]> {
+  if (!isTauri()) {
+    throw new Error("Agent mode requires the desktop app");
+  }
+  return invoke<ToolCallPreview[]>("preview_tool_calls", { messages });
+}
+
+export async function getConversationPlan(conversationId: string): Promise<PlanStep[] | null> {
+  if (!isTauri()) {
+    return null;
+  }
+  return invoke<PlanStep[] | null>("get_conversation_plan", { conversationId });
+}
+
 // Enhanced Chat API with tool support
 export async function sendChatWithTools(
   request: EnhancedChatRequest,
@@ -418,12 +660,20 @@ export async function listTasks(): Promise<Task[]> {
   return invoke<Task[]>("list_tasks");
 }
 
-export async function getTask(id: string): Promise<Task | null> {
+export async function getTask(id: string): Promise<TaskWithStats | null> {
   if (!isTauri()) {
     const tasks = await listTasks();
-    return tasks.find((t) => t.id === id) || null;
+    const task = tasks.find((t) => t.id === id);
+    return task ? { task, last_session: null, message_count: 0 } : null;
   }
-  return invoke<Task | null>("get_task", { id });
+  return invoke<TaskWithStats | null>("get_task", { id });
+}
+
+export async function getTaskOutput(taskId: string): Promise<Record<string, unknown> | null> {
+  if (!isTauri()) {
+    return null;
+  }
+  return invoke<Record<string, unknown> | null>("get_task_output", { taskId });
 }
 
 export async function createTask(
@@ -442,6 +692,7 @@ export async function createTask(
       project_path: projectPath || null,
       created_at: Date.now(),
       updated_at: Date.now(),
+      archived: false,
     };
     const tasks = await listTasks();
     tasks.unshift(task);
@@ -451,14 +702,73 @@ export async function createTask(
   return invoke<Task>("create_task", { title, description, projectPath });
 }
 
-export async function deleteTask(id: string): Promise<void> {
+export async function createTaskFromTemplate(
+  templateId: string,
+  title: string,
+  projectPath?: string
+): Promise<Task> {
+  return invoke<Task>("create_task_from_template", { templateId, title, projectPath });
+}
+
+export async function listTaskTemplates(): Promise<TaskTemplate[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<TaskTemplate[]>("list_task_templates");
+}
+
+// Pass an empty id to create a new template
+export async function saveTaskTemplate(template: TaskTemplate): Promise<TaskTemplate> {
+  return invoke<TaskTemplate>("save_task_template", { template });
+}
+
+export async function deleteTaskTemplate(id: string): Promise<void> {
+  return invoke("delete_task_template", { id });
+}
+
+// Only archived tasks can be deleted unless force is set
+export async function deleteTask(id: string, force = false): Promise<void> {
   if (!isTauri()) {
     const tasks = await listTasks();
     const filtered = tasks.filter((t) => t.id !== id);
     localStorage.setItem("kuse-cowork-tasks", JSON.stringify(filtered));
     return;
   }
-  return invoke("delete_task", { id });
+  return invoke("delete_task", { id, force });
+}
+
+export async function listArchivedTasks(): Promise<Task[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<Task[]>("list_archived_tasks");
+}
+
+// Dates are millisecond timestamps; omitted filters match everything
+export async function searchTasks(filters: {
+  query?: string;
+  status?: string;
+  createdAfter?: number;
+  createdBefore?: number;
+}): Promise<Task[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<Task[]>("search_tasks", filters);
+}
+
+export async function archiveTask(id: string): Promise<void> {
+  if (!isTauri()) {
+    return;
+  }
+  return invoke("archive_task", { id });
+}
+
+export async function unarchiveTask(id: string): Promise<void> {
+  if (!isTauri()) {
+    return;
+  }
+  return invoke("unarchive_task", { id });
 }
 
 export async function runTaskAgent(
@@ -494,6 +804,200 @@ export async function getTaskMessages(taskId: string): Promise<TaskMessage[]> {
   return invoke<TaskMessage[]>("get_task_messages", { taskId });
 }
 
+export interface StoredAgentEvent {
+  id: string;
+  task_id: string;
+  session_id: string;
+  turn: number;
+  event_type: string;
+  event: AgentEvent;
+  timestamp: number;
+}
+
+export async function getAgentEvents(taskId: string, sessionId?: string): Promise<StoredAgentEvent[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<StoredAgentEvent[]>("get_agent_events", { taskId, sessionId });
+}
+
+// Replayed events are emitted as "agent-event-replay"; resolves with the number emitted
+export async function replayAgentEvents(taskId: string): Promise<number> {
+  if (!isTauri()) {
+    return 0;
+  }
+  return invoke<number>("replay_agent_events", { taskId });
+}
+
+export interface ConversationStats {
+  message_count: number;
+  user_turns: number;
+  assistant_turns: number;
+  estimated_tokens: number;
+  first_message_at: number;
+  last_message_at: number;
+  duration_secs: number;
+}
+
+const EMPTY_STATS: ConversationStats = {
+  message_count: 0,
+  user_turns: 0,
+  assistant_turns: 0,
+  estimated_tokens: 0,
+  first_message_at: 0,
+  last_message_at: 0,
+  duration_secs: 0,
+};
+
+export async function getConversationStats(conversationId: string): Promise<ConversationStats> {
+  if (!isTauri()) {
+    return EMPTY_STATS;
+  }
+  return invoke<ConversationStats>("get_conversation_stats", { conversationId });
+}
+
+export async function getTaskStats(taskId: string): Promise<ConversationStats> {
+  if (!isTauri()) {
+    return EMPTY_STATS;
+  }
+  return invoke<ConversationStats>("get_task_stats", { taskId });
+}
+
+// Search API
+export interface GlobalSearchResult {
+  kind: "conversation" | "task";
+  parent_id: string;
+  parent_title: string;
+  message_id: string;
+  role: string;
+  snippet: string;
+  timestamp: number;
+}
+
+export async function globalSearch(query: string, limit?: number): Promise<GlobalSearchResult[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<GlobalSearchResult[]>("global_search", { query, limit });
+}
+
+// Tool metrics API
+export interface ToolStats {
+  call_count: number;
+  total_duration_ms: number;
+  error_count: number;
+  last_called_at: number | null;
+}
+
+export async function getToolMetrics(): Promise<Record<string, ToolStats>> {
+  if (!isTauri()) {
+    return {};
+  }
+  return invoke<Record<string, ToolStats>>("get_tool_metrics");
+}
+
+export async function resetToolMetrics(): Promise<void> {
+  if (!isTauri()) {
+    return;
+  }
+  return invoke("reset_tool_metrics");
+}
+
+// Startup timing API
+export interface StartupEvent {
+  step: string;
+  duration_ms: number;
+}
+
+// Payload of the "app-startup-complete" event
+export interface StartupTiming {
+  events: StartupEvent[];
+  total_ms: number;
+}
+
+export async function getStartupTiming(): Promise<StartupEvent[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<StartupEvent[]>("get_startup_timing");
+}
+
+// Payload of the "app-error" event, sent when background work fails
+export interface AppError {
+  code: string;
+  message: string;
+  component: string;
+  recoverable: boolean;
+}
+
+// Last 100 background errors, oldest first
+export async function subscribeToErrors(): Promise<AppError[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<AppError[]>("subscribe_to_errors");
+}
+
+// Approximate token count of chat messages for a provider's tokenizer
+export async function estimateChatTokens(
+  messages: { role: string; content: string }[],
+  providerId: string
+): Promise<number> {
+  if (!isTauri()) {
+    const bytes = messages.reduce((sum, m) => sum + new TextEncoder().encode(m.content).length, 0);
+    return Math.ceil(bytes / 4);
+  }
+  return invoke<number>("estimate_chat_tokens", { messages, providerId });
+}
+
+// Backup API
+export interface ImportSummary {
+  conversations: number;
+  messages: number;
+  tasks: number;
+  task_messages: number;
+  mcp_servers: number;
+  settings: boolean;
+  skipped: number;
+}
+
+export async function exportAll(): Promise<string> {
+  if (!isTauri()) {
+    throw new Error("Export requires the desktop app");
+  }
+  return invoke<string>("export_all");
+}
+
+// Returns the path written
+export async function exportConversationHtml(conversationId: string, outputPath: string): Promise<string> {
+  if (!isTauri()) {
+    throw new Error("Export requires the desktop app");
+  }
+  return invoke<string>("export_conversation_html", { conversationId, outputPath });
+}
+
+export async function importAll(
+  json: string,
+  mergeMode: "replace" | "merge"
+): Promise<ImportSummary> {
+  if (!isTauri()) {
+    throw new Error("Import requires the desktop app");
+  }
+  return invoke<ImportSummary>("import_all", { json, mergeMode });
+}
+
+export interface CheckpointStats {
+  pages_written: number;
+  frames_checkpointed: number;
+}
+
+// TRUNCATE also shrinks the WAL file back to zero bytes
+export async function checkpointDatabase(
+  mode: "PASSIVE" | "FULL" | "TRUNCATE"
+): Promise<CheckpointStats> {
+  return invoke<CheckpointStats>("checkpoint_database", { mode });
+}
+
 // File/Folder picker API
 export async function openFolderDialog(): Promise<string | null> {
   if (!isTauri()) {
@@ -531,6 +1035,25 @@ export async function getSkillsList(): Promise<SkillMetadata[]> {
   return invoke<SkillMetadata[]>("get_skills_list");
 }
 
+// Tools API
+export interface ToolDefinition {
+  name: string;
+  description: string;
+  input_schema: Record<string, unknown>;
+}
+
+export async function listAvailableTools(): Promise<ToolDefinition[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<ToolDefinition[]>("list_available_tools");
+}
+
+// Re-read *.tool.json descriptors; returns the custom tool names now available
+export async function reloadCustomTools(): Promise<string[]> {
+  return invoke<string[]>("reload_custom_tools");
+}
+
 export async function openImageFilesDialog(): Promise<string[]> {
   if (!isTauri()) {
     return [];
@@ -558,3 +1081,18 @@ export async function checkLocalServiceStatus(baseUrl: string): Promise<LocalSer
   }
   return invoke<LocalServiceStatus>("check_local_service_status", { baseUrl });
 }
+
+export async function checkAllProviderConnections(): Promise<ProviderHealthStatus[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<ProviderHealthStatus[]>("check_all_provider_connections");
+}
+
+// Cached for five minutes per provider and base URL
+export async function discoverProviderModels(providerId: string, baseUrl?: string): Promise<string[]> {
+  if (!isTauri()) {
+    return [];
+  }
+  return invoke<string[]>("discover_provider_models", { providerId, baseUrl });
+}
@@ -14,6 +14,9 @@ export interface Settings {
   providerKeys: Record<string, string>;  // Provider-specific API keys
   openaiOrganization?: string;  // Optional OpenAI Organization ID
   openaiProject?: string;  // Optional OpenAI Project ID
+  autoTitleModel?: string;  // Optional cheaper model for conversation titles
+  proxyUrl?: string;  // Optional HTTP/HTTPS proxy for cloud providers
+  proxyNoProxy?: string;  // Comma-separated hosts that bypass the proxy
 }
 
 // Provider configuration type
@@ -148,6 +151,14 @@ export const PROVIDER_PRESETS: Record<string, ProviderConfig> = {
     authType: "bearer",
     description: "Cloud inference service",
   },
+  perplexity: {
+    id: "perplexity",
+    name: "Perplexity",
+    baseUrl: "https://api.perplexity.ai",
+    apiFormat: "openai-compatible",
+    authType: "bearer",
+    description: "Search-grounded answers with citations",
+  },
 
   // Custom
   custom: {
@@ -213,6 +224,10 @@ export const AVAILABLE_MODELS = [
   { id: "Qwen/Qwen2.5-72B-Instruct", name: "Qwen 2.5 72B", description: "via SiliconFlow", provider: "siliconflow", baseUrl: "https://api.siliconflow.cn/v1" },
   { id: "deepseek-ai/DeepSeek-V3", name: "DeepSeek V3", description: "via SiliconFlow", provider: "siliconflow", baseUrl: "https://api.siliconflow.cn/v1" },
 
+  // ========== Perplexity ==========
+  { id: "llama-3.1-sonar-small-128k-online", name: "Sonar Small Online", description: "Perplexity (with citations)", provider: "perplexity", baseUrl: "https://api.perplexity.ai" },
+  { id: "llama-3.1-sonar-large-128k-online", name: "Sonar Large Online", description: "Perplexity (with citations)", provider: "perplexity", baseUrl: "https://api.perplexity.ai" },
+
   // ========== Custom ==========
   { id: "custom-model", name: "Custom Model", description: "Enter your model ID", provider: "custom", baseUrl: "http://localhost:8000" },
 ];
@@ -336,6 +351,9 @@ function fromApiSettings(api: ApiSettings): Settings {
     providerKeys,
     openaiOrganization: api.openai_organization,
     openaiProject: api.openai_project,
+    autoTitleModel: api.auto_title_model,
+    proxyUrl: api.proxy_url,
+    proxyNoProxy: api.proxy_no_proxy,
   };
 }
 
@@ -356,6 +374,9 @@ function toApiSettings(settings: Settings): ApiSettings {
     provider_keys: providerKeys,
     openai_organization: settings.openaiOrganization,
     openai_project: settings.openaiProject,
+    auto_title_model: settings.autoTitleModel,
+    proxy_url: settings.proxyUrl,
+    proxy_no_proxy: settings.proxyNoProxy,
   };
 }
 